@@ -0,0 +1,1953 @@
+use std::sync::{Arc, Mutex};
+
+use chrono::{NaiveDate, NaiveDateTime};
+use clap::{Parser, Subcommand};
+use qum_core::api::{self, ApiConfig};
+use qum_core::calculation::advance::{load_advance_payment_proposal, DEFAULT_SAFETY_MARGIN};
+use qum_core::calculation::costs::load_cost_breakdowns;
+use qum_core::calculation::intervals::load_bucket_consumption;
+use qum_core::calculation::reconciliation::load_reconciliation;
+use qum_core::db::connection::{database_file, establish_connection, run_migrations};
+use qum_core::export::{csv_export, xlsx_export, ExportData, ExportKind, ExportLocale};
+use qum_core::import::bank_statement::{self, BankCsvOptions, MatchStatus};
+use qum_core::import::e_invoice;
+use qum_core::import::green_button::{self, GreenButtonImportOptions};
+use qum_core::import::meter_readings_csv::{self, CsvColumn, CsvImportOptions};
+use qum_core::import::mscons::{self, MsconsImportOptions};
+use qum_core::import::spot_prices::{self, PriceUnit, SpotPriceImportOptions};
+use qum_core::models::adjustment::CreateAdjustmentParams;
+use qum_core::models::fees::{
+    default_brake_end, default_brake_start, CreateFeeParams, PriceBlock, PriceBrake,
+    DEFAULT_CONTINGENT,
+};
+use qum_core::models::interval::{Resolution, DEFAULT_RAW_RETENTION_DAYS};
+use qum_core::models::invoice::CreateInvoiceParams;
+use qum_core::models::meter::{CreateMeterParams, MeterKind, DEFAULT_METER_ID};
+use qum_core::models::payment::{CreatePaymentParams, CreatePaymentRuleParams};
+use qum_core::models::price_component::{ComponentKind, CreatePriceComponentParams};
+use qum_core::models::tariff::{parse_weekdays, CreateTariffRuleParams, Holiday};
+use qum_core::models::vat::CreateVatRateParams;
+use qum_core::models::{format_datetime, parse_date};
+use qum_core::report::{pdf_report, AnnualReport};
+use qum_core::repository::{
+    create_meter_reading_for_date, AdjustmentRepository, FeeRepository, IntervalRepository,
+    InvoiceRepository, MeterReadingRepository, MeterRepository, PaymentRepository,
+    PriceComponentRepository, SpotPriceRepository, SqliteRepository, TariffRepository,
+    VatRepository,
+};
+use qum_core::smart_meter::dsmr::{self, DsmrIngest, DsmrOptions, DsmrRegister, TelegramReader};
+use qum_core::smart_meter::mqtt::{self, MqttConfig, MqttSubscription};
+use qum_core::smart_meter::sml::{self, FrameReader, SmlIngest, SmlOptions, SmlRegister};
+
+/// Command line interface for the qum database.
+#[derive(Parser)]
+#[command(name = "qum-cli", version)]
+struct Cli {
+    /// Database file, defaults to the one of the desktop app (~/.qum/qum.db)
+    #[arg(long, global = true)]
+    database: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Manage meters and registers, every meter has its own readings, interval data and fees
+    #[command(subcommand)]
+    Meters(MetersCommand),
+    /// Manage fees
+    #[command(subcommand)]
+    Fees(FeesCommand),
+    /// Manage the split of fee prices into grid fees, levies and taxes
+    #[command(subcommand)]
+    Components(ComponentsCommand),
+    /// Manage one-off charges and credits of fee periods
+    #[command(subcommand)]
+    Adjustments(AdjustmentsCommand),
+    /// Manage the advance payments actually debited
+    #[command(subcommand)]
+    Payments(PaymentsCommand),
+    /// Manage the invoices of the supplier and compare them with the computed costs
+    #[command(subcommand)]
+    Invoices(InvoicesCommand),
+    /// Manage the VAT rates per date range
+    #[command(subcommand)]
+    Vat(VatCommand),
+    /// Manage the time-of-use windows of fees
+    #[command(subcommand)]
+    Tariffs(TariffsCommand),
+    /// Manage the holidays of time-of-use tariffs
+    #[command(subcommand)]
+    Holidays(HolidaysCommand),
+    /// Manage meter readings
+    #[command(subcommand)]
+    Readings(ReadingsCommand),
+    /// Show or prune the interval data of smart meters
+    #[command(subcommand)]
+    Intervals(IntervalsCommand),
+    /// Import or show the hourly prices of dynamic tariffs
+    #[command(subcommand)]
+    SpotPrices(SpotPricesCommand),
+    /// Import meter readings from a CSV file
+    Import(ImportArgs),
+    /// Import a Green Button (ESPI XML) file
+    GreenButton(GreenButtonArgs),
+    /// Import an EDIFACT MSCONS message of the metering point operator
+    Mscons(MsconsArgs),
+    /// Record the direct debits of the supplier from a bank statement (CSV or CAMT.053 XML)
+    BankStatement(BankStatementArgs),
+    /// Store an XRechnung (CII or UBL XML) or ZUGFeRD (PDF) invoice, the fee of the billing
+    /// period is created from it if there is none
+    EInvoice(EInvoiceArgs),
+    /// Store readings of a DSMR smart meter (P1 port) or of recorded telegrams
+    Dsmr(DsmrArgs),
+    /// Store readings of an SML meter (IR head) or of a captured binary file
+    Sml(SmlArgs),
+    /// Store readings published over MQTT until the process is stopped
+    Mqtt(MqttArgs),
+    /// Export readings, consumption, costs or an annual report
+    #[command(subcommand)]
+    Export(ExportCommand),
+    /// Print the cost summary of every fee period
+    Costs,
+    /// Propose the monthly advance payment of a fee period from the consumption of the
+    /// year before
+    Advance {
+        fee: i32,
+        /// Percent added to the expected costs
+        #[arg(long, default_value_t = DEFAULT_SAFETY_MARGIN)]
+        safety_margin: f32,
+        /// Expected kWh of the period instead of the consumption of the year before
+        #[arg(long)]
+        consumption: Option<f32>,
+    },
+    /// Run the REST/JSON api until the process is stopped
+    Serve {
+        #[arg(long, default_value = api::DEFAULT_ADDRESS)]
+        address: String,
+        /// Token clients have to send as `Authorization: Bearer <token>`, defaults to `QUM_API_TOKEN`
+        #[arg(long, env = "QUM_API_TOKEN")]
+        token: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum MetersCommand {
+    List,
+    Add {
+        name: String,
+        /// consumption, feedIn or gas
+        #[arg(long, value_parser = MeterKind::parse, default_value = "consumption")]
+        kind: MeterKind,
+        /// Id at the grid operator, e.g. the MeLo/MaLo of MSCONS messages or the usage
+        /// point of Green Button files
+        #[arg(long)]
+        external_id: Option<String>,
+    },
+    Delete {
+        id: i32,
+    },
+}
+
+#[derive(Subcommand)]
+enum FeesCommand {
+    List,
+    Add {
+        /// Meter the fee prices
+        #[arg(long, default_value_t = DEFAULT_METER_ID)]
+        meter: i32,
+        #[arg(long)]
+        base_fee: f32,
+        #[arg(long)]
+        price_per_unit: f32,
+        #[arg(long)]
+        monthly_discount: f32,
+        /// Dynamic tariff: surcharge per kWh on top of the hourly spot price
+        #[arg(long)]
+        spot_surcharge: Option<f32>,
+        /// Block of a tiered tariff as `<kWh per year>:<price per unit>`, e.g. 1000:0.35,
+        /// consumption above the last block is charged at the price per unit
+        #[arg(long = "block", value_parser = parse_block)]
+        blocks: Vec<PriceBlock>,
+        /// The prices exclude VAT
+        #[arg(long)]
+        net_prices: bool,
+        /// Energy price brake as `<forecast kWh per year>:<cap price>[:<contingent %>]`,
+        /// e.g. 3500:0.40, the contingent defaults to 80 %
+        #[arg(long, value_parser = parse_price_brake)]
+        price_brake: Option<PriceBrake>,
+        /// First day of the price brake (YYYY-MM-DD), defaults to 2023-01-01
+        #[arg(long, value_parser = parse_date, requires = "price_brake")]
+        price_brake_start: Option<NaiveDate>,
+        /// Last day of the price brake (YYYY-MM-DD), defaults to 2023-12-31
+        #[arg(long, value_parser = parse_date, requires = "price_brake")]
+        price_brake_end: Option<NaiveDate>,
+        /// First day of the period (YYYY-MM-DD)
+        #[arg(long, value_parser = parse_day)]
+        start: NaiveDateTime,
+        /// Last day of the period (YYYY-MM-DD)
+        #[arg(long, value_parser = parse_day)]
+        end: NaiveDateTime,
+    },
+    Delete {
+        id: i32,
+    },
+}
+
+#[derive(Subcommand)]
+enum ComponentsCommand {
+    List {
+        #[arg(long)]
+        fee: i32,
+    },
+    /// Add a net price component, the energy costs are the rest of the fee prices
+    Add {
+        #[arg(long)]
+        fee: i32,
+        /// gridFee, meteringPoint, concessionLevy, surcharge, electricityTax or vat
+        #[arg(long, value_parser = ComponentKind::parse)]
+        kind: ComponentKind,
+        /// Defaults to the kind
+        #[arg(long, default_value = "")]
+        name: String,
+        /// Per month
+        #[arg(long, default_value_t = 0.0)]
+        base_fee: f32,
+        /// Per kWh
+        #[arg(long, default_value_t = 0.0)]
+        price_per_unit: f32,
+        /// VAT rate in percent
+        #[arg(long, default_value_t = 0.0)]
+        rate: f32,
+    },
+    Delete {
+        id: i32,
+    },
+}
+
+#[derive(Subcommand)]
+enum TariffsCommand {
+    List {
+        #[arg(long)]
+        fee: i32,
+    },
+    /// Add a window, windows are matched in the order they were added
+    Add {
+        #[arg(long)]
+        fee: i32,
+        /// e.g. HT or NT, windows with the same name are summed up
+        #[arg(long)]
+        name: String,
+        /// Weekdays like 1-5 or 6,7 (1 = Monday)
+        #[arg(long, default_value = "")]
+        weekdays: String,
+        /// The window applies on holidays instead of the weekday windows
+        #[arg(long)]
+        holidays: bool,
+        /// Local start time (HH:MM)
+        #[arg(long)]
+        start: String,
+        /// Local end time (HH:MM), before the start for windows over midnight
+        #[arg(long)]
+        end: String,
+        #[arg(long)]
+        price_per_unit: f32,
+    },
+    Delete {
+        id: i32,
+    },
+}
+
+#[derive(Subcommand)]
+enum AdjustmentsCommand {
+    List {
+        #[arg(long)]
+        fee: i32,
+    },
+    /// Add a charge or, with a negative amount, a credit
+    Add {
+        #[arg(long)]
+        fee: i32,
+        #[arg(long, allow_hyphen_values = true)]
+        amount: f32,
+        /// YYYY-MM-DD, within the fee period
+        #[arg(long)]
+        date: String,
+        #[arg(long)]
+        description: String,
+        /// VAT is charged on the amount
+        #[arg(long)]
+        taxable: bool,
+    },
+    Delete {
+        id: i32,
+    },
+}
+
+#[derive(Subcommand)]
+enum PaymentsCommand {
+    List {
+        /// All fees if not set
+        #[arg(long)]
+        fee: Option<i32>,
+    },
+    /// Add a debit or, with a negative amount, a refund
+    Add {
+        #[arg(long, allow_hyphen_values = true)]
+        amount: f32,
+        /// YYYY-MM-DD
+        #[arg(long)]
+        date: String,
+        /// Picked by the date if not set
+        #[arg(long)]
+        fee: Option<i32>,
+        /// Meter whose fee is picked by the date
+        #[arg(long)]
+        meter: Option<i32>,
+    },
+    /// Change the given values of a payment
+    Edit {
+        id: i32,
+        #[arg(long, allow_hyphen_values = true)]
+        amount: Option<f32>,
+        /// YYYY-MM-DD
+        #[arg(long)]
+        date: Option<String>,
+        #[arg(long)]
+        fee: Option<i32>,
+    },
+    Delete {
+        id: i32,
+    },
+    /// Manage the rules recognising direct debits of the supplier in bank statements
+    #[command(subcommand)]
+    Rules(PaymentRulesCommand),
+}
+
+#[derive(Subcommand)]
+enum InvoicesCommand {
+    List,
+    Add {
+        /// Picked by the billing period if not set
+        #[arg(long)]
+        fee: Option<i32>,
+        /// Meter whose fee is picked by the billing period
+        #[arg(long)]
+        meter: Option<i32>,
+        #[arg(long, default_value = "")]
+        number: String,
+        /// First day of the billing period (YYYY-MM-DD)
+        #[arg(long)]
+        start: String,
+        /// Last day of the billing period (YYYY-MM-DD)
+        #[arg(long)]
+        end: String,
+        /// Billed kWh
+        #[arg(long)]
+        consumption: f32,
+        /// Meter value at the start of the period
+        #[arg(long)]
+        reading_start: Option<f32>,
+        /// Meter value at the end of the period
+        #[arg(long)]
+        reading_end: Option<f32>,
+        /// The supplier estimated a meter value
+        #[arg(long)]
+        estimated: bool,
+        /// Gross amount minus the tax if not set
+        #[arg(long)]
+        net: Option<f32>,
+        #[arg(long, default_value_t = 0.0)]
+        tax: f32,
+        #[arg(long)]
+        gross: f32,
+        /// Advance payments deducted on the invoice
+        #[arg(long)]
+        advance_payments: f32,
+        /// Final balance, positive for a credit, the advance payments minus the gross
+        /// amount if not set
+        #[arg(long, allow_hyphen_values = true)]
+        balance: Option<f32>,
+    },
+    Delete {
+        id: i32,
+    },
+    /// Compare the invoice with the costs computed from the own readings
+    Reconcile {
+        id: i32,
+    },
+}
+
+#[derive(Subcommand)]
+enum PaymentRulesCommand {
+    List,
+    /// Add a rule matching the SEPA creditor ID or a text in the creditor name or purpose
+    Add {
+        #[arg(long, required_unless_present = "text")]
+        creditor_id: Option<String>,
+        #[arg(long)]
+        text: Option<String>,
+    },
+    Delete {
+        id: i32,
+    },
+}
+
+#[derive(Subcommand)]
+enum VatCommand {
+    List,
+    Add {
+        /// First day of the rate (YYYY-MM-DD)
+        #[arg(long)]
+        start: String,
+        /// Last day of the rate (YYYY-MM-DD), open ended if not set
+        #[arg(long)]
+        end: Option<String>,
+        /// Rate in percent
+        #[arg(long)]
+        rate: f32,
+    },
+    Delete {
+        id: i32,
+    },
+}
+
+#[derive(Subcommand)]
+enum HolidaysCommand {
+    List,
+    /// Add a holiday or rename it
+    Add {
+        /// YYYY-MM-DD
+        #[arg(value_parser = parse_date)]
+        date: NaiveDate,
+        #[arg(long, default_value = "")]
+        name: String,
+    },
+    Delete {
+        /// YYYY-MM-DD
+        #[arg(value_parser = parse_date)]
+        date: NaiveDate,
+    },
+}
+
+#[derive(Subcommand)]
+enum ReadingsCommand {
+    List,
+    /// Add a reading, the fee of the meter is picked by the reading date
+    Add {
+        value: f32,
+        #[arg(long, default_value_t = DEFAULT_METER_ID)]
+        meter: i32,
+        /// Reading date (YYYY-MM-DD), defaults to now
+        #[arg(long, value_parser = parse_day)]
+        date: Option<NaiveDateTime>,
+    },
+}
+
+#[derive(Subcommand)]
+enum IntervalsCommand {
+    /// Print the consumption per bucket
+    Show {
+        #[arg(long, default_value_t = DEFAULT_METER_ID)]
+        meter: i32,
+        /// 15min, hour or day
+        #[arg(long, value_parser = Resolution::parse, default_value = "hour")]
+        resolution: Resolution,
+        /// First day (YYYY-MM-DD)
+        #[arg(long, value_parser = parse_day)]
+        start: NaiveDateTime,
+        /// Day after the last day (YYYY-MM-DD)
+        #[arg(long, value_parser = parse_day)]
+        end: NaiveDateTime,
+    },
+    /// Delete raw samples, the aggregated buckets are kept
+    Prune {
+        /// Only the samples of this meter, all meters if not set
+        #[arg(long)]
+        meter: Option<i32>,
+        #[arg(long, default_value_t = DEFAULT_RAW_RETENTION_DAYS)]
+        keep_days: i64,
+    },
+}
+
+#[derive(Subcommand)]
+enum SpotPricesCommand {
+    /// Import prices from a JSON (aWATTar, energy-charts) or CSV file
+    Import(SpotPriceImportArgs),
+    /// Print the stored prices in €/kWh
+    Show {
+        /// First day (YYYY-MM-DD)
+        #[arg(long, value_parser = parse_day)]
+        start: NaiveDateTime,
+        /// Day after the last day (YYYY-MM-DD)
+        #[arg(long, value_parser = parse_day)]
+        end: NaiveDateTime,
+    },
+}
+
+#[derive(clap::Args)]
+struct SpotPriceImportArgs {
+    path: String,
+    /// eurPerMwh, ctPerKwh or eurPerKwh
+    #[arg(long, value_parser = PriceUnit::parse, default_value = "eurPerMwh")]
+    unit: PriceUnit,
+    #[arg(long, default_value_t = ',')]
+    delimiter: char,
+    /// The CSV file has no header row
+    #[arg(long)]
+    no_header: bool,
+    /// Date column of CSV files, either the header name or the zero based index
+    #[arg(long, value_parser = parse_column, default_value = "0")]
+    date_column: CsvColumn,
+    /// Price column of CSV files, either the header name or the zero based index
+    #[arg(long, value_parser = parse_column, default_value = "1")]
+    price_column: CsvColumn,
+    /// Format of the UTC dates in CSV files
+    #[arg(long, default_value = "%Y-%m-%d %H:%M")]
+    date_format: String,
+    #[arg(long, default_value_t = '.')]
+    decimal_separator: char,
+}
+
+#[derive(clap::Args)]
+struct ImportArgs {
+    path: String,
+    #[arg(long, default_value_t = ';')]
+    delimiter: char,
+    /// The file has no header row, columns have to be given by index
+    #[arg(long)]
+    no_header: bool,
+    /// Date column, either the header name or the zero based index
+    #[arg(long, value_parser = parse_column)]
+    date_column: CsvColumn,
+    /// Value column, either the header name or the zero based index
+    #[arg(long, value_parser = parse_column)]
+    value_column: CsvColumn,
+    #[arg(long, default_value = "%d.%m.%Y")]
+    date_format: String,
+    #[arg(long, default_value_t = ',')]
+    decimal_separator: char,
+    /// Meter the readings are imported for
+    #[arg(long, default_value_t = DEFAULT_METER_ID)]
+    meter: i32,
+    /// Import valid rows even if some rows could not be parsed
+    #[arg(long)]
+    skip_invalid: bool,
+    /// Only print the parsed rows
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(clap::Args)]
+struct BankStatementArgs {
+    path: String,
+    #[arg(long, default_value_t = ';')]
+    delimiter: char,
+    /// The CSV file has no header row, columns have to be given by index
+    #[arg(long)]
+    no_header: bool,
+    /// Booking date column of CSV files, either the header name or the zero based index
+    #[arg(long, value_parser = parse_column)]
+    date_column: Option<CsvColumn>,
+    /// Amount column of CSV files, debits are negative
+    #[arg(long, value_parser = parse_column)]
+    amount_column: Option<CsvColumn>,
+    /// Creditor or debtor name column of CSV files
+    #[arg(long, value_parser = parse_column)]
+    counterparty_column: Option<CsvColumn>,
+    /// Purpose column of CSV files
+    #[arg(long, value_parser = parse_column)]
+    text_column: Option<CsvColumn>,
+    /// Creditor ID column of CSV files
+    #[arg(long, value_parser = parse_column)]
+    creditor_id_column: Option<CsvColumn>,
+    #[arg(long, default_value = "%d.%m.%Y")]
+    date_format: String,
+    #[arg(long, default_value_t = ',')]
+    decimal_separator: char,
+    /// Only print the matched transactions
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(clap::Args)]
+struct EInvoiceArgs {
+    path: String,
+    /// Only print the invoice and the prefilled fee
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(clap::Args)]
+struct GreenButtonArgs {
+    path: String,
+    /// Id of the usage point, required if the file has several
+    #[arg(long)]
+    usage_point: Option<String>,
+    /// Meter to store the readings for, defaults to the meter with the usage point id as
+    /// external id, then to the default meter
+    #[arg(long)]
+    meter: Option<i32>,
+    /// Import the energy delivered by the customer
+    #[arg(long)]
+    export: bool,
+    /// Counter value at the start of the file, required if the meter has no reading before
+    #[arg(long)]
+    start_value: Option<f64>,
+    /// Store one meter reading per interval, days start at local midnight
+    #[arg(long, default_value_t = 86400)]
+    reading_interval_seconds: i64,
+    /// Only list the usage points and meter readings of the file
+    #[arg(long)]
+    list: bool,
+}
+
+#[derive(clap::Args)]
+struct MsconsArgs {
+    path: String,
+    /// Meter location id, required if the message has several
+    #[arg(long)]
+    location: Option<String>,
+    /// OBIS code of the series, e.g. 1-1:1.29.0, required if the location has several
+    #[arg(long)]
+    obis: Option<String>,
+    /// Meter to store the series for, defaults to the meter with the location id as
+    /// external id, then to the default meter
+    #[arg(long)]
+    meter: Option<i32>,
+    /// Counter value at the start of a load profile, required if the meter has no reading
+    /// before
+    #[arg(long)]
+    start_value: Option<f64>,
+    /// Store one meter reading per interval, days start at local midnight
+    #[arg(long, default_value_t = 86400)]
+    reading_interval_seconds: i64,
+    /// Only list the locations and series of the message
+    #[arg(long)]
+    list: bool,
+}
+
+#[derive(clap::Args)]
+struct DsmrArgs {
+    /// Serial device of the P1 port, read until the process is stopped
+    #[arg(long, conflicts_with = "file", required_unless_present = "file")]
+    device: Option<String>,
+    /// The device is a DSMR 2 or 3 meter (9600 baud 7E1 instead of 115200 baud 8N1)
+    #[arg(long)]
+    legacy: bool,
+    /// File with recorded telegrams
+    #[arg(long)]
+    file: Option<String>,
+    /// import-total, import-tariff1, import-tariff2, export-total, export-tariff1, export-tariff2 or gas
+    #[arg(long, value_parser = parse_register, default_value = "import-total")]
+    register: DsmrRegister,
+    /// Meter the register is stored for, it must count the same (electricity drawn, fed in
+    /// or gas)
+    #[arg(long, default_value_t = DEFAULT_METER_ID)]
+    meter: i32,
+    /// Gas meter the gas register is stored for as well
+    #[arg(long)]
+    gas_meter: Option<i32>,
+    /// Store one reading per interval
+    #[arg(long, default_value_t = 900)]
+    interval_seconds: i64,
+    /// Don't check the CRC of the telegrams
+    #[arg(long)]
+    no_crc: bool,
+}
+
+#[derive(clap::Args)]
+struct SmlArgs {
+    /// Serial device of the IR head, read until the process is stopped
+    #[arg(long, conflicts_with = "file", required_unless_present = "file")]
+    device: Option<String>,
+    /// File with captured frames
+    #[arg(long)]
+    file: Option<String>,
+    /// import (1.8.0) or export (2.8.0)
+    #[arg(long, value_parser = parse_sml_register, default_value = "import")]
+    register: SmlRegister,
+    /// Meter the register is stored for, a feed-in meter for the export register
+    #[arg(long, default_value_t = DEFAULT_METER_ID)]
+    meter: i32,
+    /// Store one reading per interval
+    #[arg(long, default_value_t = 900)]
+    interval_seconds: i64,
+    /// Don't check the CRC of the frames
+    #[arg(long)]
+    no_crc: bool,
+}
+
+#[derive(clap::Args)]
+struct MqttArgs {
+    #[arg(long, env = "QUM_MQTT_HOST")]
+    host: String,
+    #[arg(long, env = "QUM_MQTT_PORT", default_value_t = mqtt::DEFAULT_PORT)]
+    port: u16,
+    #[arg(long, default_value = "qum-cli")]
+    client_id: String,
+    #[arg(long, env = "QUM_MQTT_USERNAME")]
+    username: Option<String>,
+    #[arg(long, env = "QUM_MQTT_PASSWORD")]
+    password: Option<String>,
+    /// Topic and JSON path of the value as `[meter:]topic=json.path`, e.g.
+    /// `tele/meter/SENSOR=SML.Total_in`, without a meter the readings are stored for meter 1
+    #[arg(long, required = true, value_parser = MqttSubscription::parse)]
+    subscribe: Vec<MqttSubscription>,
+    /// Store one reading per interval
+    #[arg(long, env = "QUM_MQTT_INTERVAL_SECONDS", default_value_t = 900)]
+    interval_seconds: i64,
+}
+
+#[derive(Subcommand)]
+enum ExportCommand {
+    Csv {
+        path: String,
+        #[arg(long, value_parser = parse_kind)]
+        kind: ExportKind,
+        #[arg(long, value_parser = parse_locale, default_value = "de")]
+        locale: ExportLocale,
+    },
+    Xlsx {
+        path: String,
+        #[arg(long, value_parser = parse_locale, default_value = "de")]
+        locale: ExportLocale,
+    },
+    Report {
+        fee_id: i32,
+        path: String,
+    },
+}
+
+fn parse_day(value: &str) -> Result<NaiveDateTime, String> {
+    match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        Ok(date) => Ok(date.and_hms_opt(0, 0, 0).unwrap()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+fn parse_block(value: &str) -> Result<PriceBlock, String> {
+    let (up_to, price_per_unit) = value
+        .split_once(':')
+        .ok_or_else(|| "expected <kWh per year>:<price per unit>".to_string())?;
+    Ok(PriceBlock {
+        up_to: up_to
+            .parse()
+            .map_err(|_| format!("invalid kWh '{}'", up_to))?,
+        price_per_unit: price_per_unit
+            .parse()
+            .map_err(|_| format!("invalid price '{}'", price_per_unit))?,
+    })
+}
+
+fn parse_price_brake(value: &str) -> Result<PriceBrake, String> {
+    let parts: Vec<&str> = value.split(':').collect();
+    let number = |index: usize, name: &str| -> Result<f32, String> {
+        parts[index]
+            .parse()
+            .map_err(|_| format!("invalid {} '{}'", name, parts[index]))
+    };
+    if parts.len() < 2 || parts.len() > 3 {
+        return Err("expected <forecast kWh per year>:<cap price>[:<contingent %>]".to_string());
+    }
+    Ok(PriceBrake {
+        forecast: number(0, "forecast")?,
+        cap_price: number(1, "cap price")?,
+        contingent: match parts.len() {
+            3 => number(2, "contingent")?,
+            _ => DEFAULT_CONTINGENT,
+        },
+        date_start: default_brake_start(),
+        date_end: default_brake_end(),
+    })
+}
+
+fn parse_column(value: &str) -> Result<CsvColumn, String> {
+    match value.parse::<usize>() {
+        Ok(index) => Ok(CsvColumn::Index(index)),
+        Err(_) => Ok(CsvColumn::Name(value.to_string())),
+    }
+}
+
+fn parse_register(value: &str) -> Result<DsmrRegister, String> {
+    match value {
+        "import-total" => Ok(DsmrRegister::ImportTotal),
+        "import-tariff1" => Ok(DsmrRegister::ImportTariff1),
+        "import-tariff2" => Ok(DsmrRegister::ImportTariff2),
+        "export-total" => Ok(DsmrRegister::ExportTotal),
+        "export-tariff1" => Ok(DsmrRegister::ExportTariff1),
+        "export-tariff2" => Ok(DsmrRegister::ExportTariff2),
+        "gas" => Ok(DsmrRegister::Gas),
+        _ => Err("expected one of import-total, import-tariff1, import-tariff2, export-total, export-tariff1, export-tariff2, gas".to_string()),
+    }
+}
+
+fn parse_sml_register(value: &str) -> Result<SmlRegister, String> {
+    match value {
+        "import" => Ok(SmlRegister::Import),
+        "export" => Ok(SmlRegister::Export),
+        _ => Err("expected one of import, export".to_string()),
+    }
+}
+
+fn parse_kind(value: &str) -> Result<ExportKind, String> {
+    match value {
+        "readings" => Ok(ExportKind::Readings),
+        "consumption" => Ok(ExportKind::Consumption),
+        "costs" => Ok(ExportKind::Costs),
+        _ => Err("expected one of readings, consumption, costs".to_string()),
+    }
+}
+
+fn parse_locale(value: &str) -> Result<ExportLocale, String> {
+    match value {
+        "de" => Ok(ExportLocale::De),
+        "en" => Ok(ExportLocale::En),
+        _ => Err("expected one of de, en".to_string()),
+    }
+}
+
+fn meters(repository: &mut SqliteRepository, command: MetersCommand) -> Result<(), String> {
+    match command {
+        MetersCommand::List => {
+            println!("id\tname\tkind\tunit\texternal id");
+            for meter in repository.list_meters()? {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}",
+                    meter.id,
+                    meter.name,
+                    meter.kind.name(),
+                    meter.kind.unit(),
+                    meter.external_id.unwrap_or_else(|| "-".to_string())
+                );
+            }
+            Ok(())
+        }
+        MetersCommand::Add {
+            name,
+            kind,
+            external_id,
+        } => {
+            let meter = repository.create_meter(CreateMeterParams {
+                name,
+                kind,
+                external_id,
+            })?;
+            println!("created meter {}", meter.id);
+            Ok(())
+        }
+        MetersCommand::Delete { id } => {
+            repository.delete_meter(id)?;
+            println!("deleted meter {}", id);
+            Ok(())
+        }
+    }
+}
+
+fn fees(repository: &mut SqliteRepository, command: FeesCommand) -> Result<(), String> {
+    match command {
+        FeesCommand::List => {
+            let fees = repository.list_fees()?;
+            println!(
+                "id\tmeter\tstart\tend\tbase fee\tprice per unit\tmonthly discount\tspot surcharge\tblocks\tprices\tprice brake"
+            );
+            for fee in fees {
+                let blocks: Vec<String> = fee
+                    .blocks
+                    .iter()
+                    .map(|block| format!("{}:{}", block.up_to, block.price_per_unit))
+                    .collect();
+                println!(
+                    "{}\t{}\t{}\t{}\t{:.2}\t{:.4}\t{:.2}\t{}\t{}\t{}\t{}",
+                    fee.id,
+                    fee.meter_id,
+                    fee.date_start.date(),
+                    fee.date_end.date(),
+                    fee.base_fee,
+                    fee.price_per_unit,
+                    fee.monthly_discount,
+                    fee.spot_surcharge
+                        .map(|surcharge| format!("{:.4}", surcharge))
+                        .unwrap_or_else(|| "-".to_string()),
+                    blocks.join(" "),
+                    if fee.net_prices { "net" } else { "gross" },
+                    fee.price_brake
+                        .map(|brake| format!(
+                            "{}:{}:{} ({} - {})",
+                            brake.forecast,
+                            brake.cap_price,
+                            brake.contingent,
+                            brake.date_start,
+                            brake.date_end
+                        ))
+                        .unwrap_or_else(|| "-".to_string())
+                );
+            }
+            Ok(())
+        }
+        FeesCommand::Add {
+            meter,
+            base_fee,
+            price_per_unit,
+            monthly_discount,
+            spot_surcharge,
+            blocks,
+            net_prices,
+            price_brake,
+            price_brake_start,
+            price_brake_end,
+            start,
+            end,
+        } => {
+            let price_brake = price_brake.map(|brake| PriceBrake {
+                date_start: price_brake_start.unwrap_or(brake.date_start),
+                date_end: price_brake_end.unwrap_or(brake.date_end),
+                ..brake
+            });
+            let params = CreateFeeParams {
+                meter_id: Some(meter),
+                base_fee,
+                price_per_unit,
+                monthly_discount,
+                spot_surcharge,
+                blocks,
+                net_prices,
+                price_brake,
+                date_start: format_datetime(&start),
+                date_end: format_datetime(&end),
+            };
+            let fee = repository.create_fee(params)?;
+            println!("created fee {}", fee.id);
+            Ok(())
+        }
+        FeesCommand::Delete { id } => {
+            repository.delete_fee(id)?;
+            println!("deleted fee {}", id);
+            Ok(())
+        }
+    }
+}
+
+fn components(repository: &mut SqliteRepository, command: ComponentsCommand) -> Result<(), String> {
+    match command {
+        ComponentsCommand::List { fee } => {
+            println!("id\tkind\tname\tbase fee\tprice per unit\trate");
+            for component in repository.list_price_components(fee)? {
+                println!(
+                    "{}\t{}\t{}\t{:.2}\t{:.4}\t{:.2}",
+                    component.id,
+                    component.kind.name(),
+                    component.name,
+                    component.base_fee,
+                    component.price_per_unit,
+                    component.rate
+                );
+            }
+            Ok(())
+        }
+        ComponentsCommand::Add {
+            fee,
+            kind,
+            name,
+            base_fee,
+            price_per_unit,
+            rate,
+        } => {
+            let component = repository.create_price_component(CreatePriceComponentParams {
+                fee_id: fee,
+                kind,
+                name,
+                base_fee,
+                price_per_unit,
+                rate,
+            })?;
+            println!("created price component {}", component.id);
+            Ok(())
+        }
+        ComponentsCommand::Delete { id } => {
+            repository.delete_price_component(id)?;
+            println!("deleted price component {}", id);
+            Ok(())
+        }
+    }
+}
+
+fn tariffs(repository: &mut SqliteRepository, command: TariffsCommand) -> Result<(), String> {
+    match command {
+        TariffsCommand::List { fee } => {
+            println!("id\tname\tweekdays\tholidays\tstart\tend\tprice per unit");
+            for rule in repository.list_tariff_rules(fee)? {
+                let weekdays: Vec<String> =
+                    rule.weekdays.iter().map(|day| day.to_string()).collect();
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{:.4}",
+                    rule.id,
+                    rule.name,
+                    weekdays.join(","),
+                    rule.holidays,
+                    rule.time_start.format("%H:%M"),
+                    rule.time_end.format("%H:%M"),
+                    rule.price_per_unit
+                );
+            }
+            Ok(())
+        }
+        TariffsCommand::Add {
+            fee,
+            name,
+            weekdays,
+            holidays,
+            start,
+            end,
+            price_per_unit,
+        } => {
+            let rule = repository.create_tariff_rule(CreateTariffRuleParams {
+                fee_id: fee,
+                name,
+                weekdays: parse_weekdays(weekdays.as_str())?,
+                holidays,
+                time_start: start,
+                time_end: end,
+                price_per_unit,
+            })?;
+            println!("created tariff rule {}", rule.id);
+            Ok(())
+        }
+        TariffsCommand::Delete { id } => {
+            repository.delete_tariff_rule(id)?;
+            println!("deleted tariff rule {}", id);
+            Ok(())
+        }
+    }
+}
+
+fn payments(repository: &mut SqliteRepository, command: PaymentsCommand) -> Result<(), String> {
+    match command {
+        PaymentsCommand::List { fee } => {
+            println!("id\tfee\tdate\tamount");
+            for payment in repository.list_payments(fee)? {
+                println!(
+                    "{}\t{}\t{}\t{:.2}",
+                    payment.id, payment.fee_id, payment.date, payment.amount
+                );
+            }
+            Ok(())
+        }
+        PaymentsCommand::Add {
+            amount,
+            date,
+            fee,
+            meter,
+        } => {
+            let payment = repository.create_payment(CreatePaymentParams {
+                fee_id: fee,
+                meter_id: meter,
+                date,
+                amount,
+            })?;
+            println!("created payment {} of fee {}", payment.id, payment.fee_id);
+            Ok(())
+        }
+        PaymentsCommand::Edit {
+            id,
+            amount,
+            date,
+            fee,
+        } => {
+            let payment = repository
+                .list_payments(None)?
+                .into_iter()
+                .find(|payment| payment.id == id)
+                .ok_or_else(|| format!("payment {} not found", id))?;
+            // a new date may belong to another fee of the same meter
+            let fee_id = match (fee, &date) {
+                (Some(fee), _) => Some(fee),
+                (None, Some(_)) => None,
+                (None, None) => Some(payment.fee_id),
+            };
+            let meter_id = match fee {
+                Some(_) => None,
+                None => repository.find_fee(payment.fee_id)?.map(|fee| fee.meter_id),
+            };
+            let payment = repository.edit_payment(
+                id,
+                CreatePaymentParams {
+                    fee_id,
+                    meter_id,
+                    date: date.unwrap_or_else(|| payment.date.format("%Y-%m-%d").to_string()),
+                    amount: amount.unwrap_or(payment.amount),
+                },
+            )?;
+            println!("updated payment {} of fee {}", payment.id, payment.fee_id);
+            Ok(())
+        }
+        PaymentsCommand::Delete { id } => {
+            repository.delete_payment(id)?;
+            println!("deleted payment {}", id);
+            Ok(())
+        }
+        PaymentsCommand::Rules(PaymentRulesCommand::List) => {
+            println!("id\tcreditor id\ttext");
+            for rule in repository.list_payment_rules()? {
+                println!(
+                    "{}\t{}\t{}",
+                    rule.id,
+                    rule.creditor_id.unwrap_or_default(),
+                    rule.text.unwrap_or_default()
+                );
+            }
+            Ok(())
+        }
+        PaymentsCommand::Rules(PaymentRulesCommand::Add { creditor_id, text }) => {
+            let rule =
+                repository.create_payment_rule(CreatePaymentRuleParams { creditor_id, text })?;
+            println!("created payment rule {}", rule.id);
+            Ok(())
+        }
+        PaymentsCommand::Rules(PaymentRulesCommand::Delete { id }) => {
+            repository.delete_payment_rule(id)?;
+            println!("deleted payment rule {}", id);
+            Ok(())
+        }
+    }
+}
+
+fn invoices(repository: &mut SqliteRepository, command: InvoicesCommand) -> Result<(), String> {
+    match command {
+        InvoicesCommand::List => {
+            println!("id\tfee\tnumber\tstart\tend\tconsumption\tgross\tadvance payments\tbalance");
+            for invoice in repository.list_invoices()? {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{:.2}{}\t{:.2}\t{:.2}\t{:.2}",
+                    invoice.id,
+                    invoice.fee_id,
+                    invoice.number,
+                    invoice.date_start,
+                    invoice.date_end,
+                    invoice.consumption,
+                    if invoice.estimated {
+                        " (estimated)"
+                    } else {
+                        ""
+                    },
+                    invoice.gross_amount,
+                    invoice.advance_payments,
+                    invoice.balance
+                );
+            }
+            Ok(())
+        }
+        InvoicesCommand::Add {
+            fee,
+            meter,
+            number,
+            start,
+            end,
+            consumption,
+            reading_start,
+            reading_end,
+            estimated,
+            net,
+            tax,
+            gross,
+            advance_payments,
+            balance,
+        } => {
+            let invoice = repository.create_invoice(CreateInvoiceParams {
+                fee_id: fee,
+                meter_id: meter,
+                number,
+                date_start: start,
+                date_end: end,
+                consumption,
+                reading_start,
+                reading_end,
+                estimated,
+                net_amount: net.unwrap_or(gross - tax),
+                tax_amount: tax,
+                gross_amount: gross,
+                advance_payments,
+                balance: balance.unwrap_or(advance_payments - gross),
+            })?;
+            println!("created invoice {} of fee {}", invoice.id, invoice.fee_id);
+            Ok(())
+        }
+        InvoicesCommand::Delete { id } => {
+            repository.delete_invoice(id)?;
+            println!("deleted invoice {}", id);
+            Ok(())
+        }
+        InvoicesCommand::Reconcile { id } => {
+            let reconciliation = load_reconciliation(repository, id)?;
+            let invoice = &reconciliation.invoice;
+            println!("\tbilled\tcomputed\tdifference");
+            println!(
+                "consumption\t{:.2}\t{:.2}\t{:+.2}",
+                invoice.consumption,
+                reconciliation.consumption,
+                reconciliation.consumption_difference
+            );
+            if let Some(billed) = reconciliation.billed_unit_price {
+                println!(
+                    "price per kWh\t{:.4}\t{:.4}\t{:+.4}",
+                    billed,
+                    reconciliation.unit_price,
+                    billed - reconciliation.unit_price
+                );
+            }
+            println!(
+                "costs\t{:.2}\t{:.2}\t{:+.2}",
+                invoice.gross_amount, reconciliation.costs, reconciliation.cost_difference
+            );
+            println!(
+                "\tof it consumption\t\t{:+.2}",
+                reconciliation.consumption_effect
+            );
+            println!("\tof it prices\t\t{:+.2}", reconciliation.price_effect);
+            println!(
+                "advance payments\t{:.2}\t{:.2}\t{:+.2}",
+                invoice.advance_payments,
+                reconciliation.advance_payments,
+                reconciliation.advance_difference
+            );
+            println!(
+                "balance\t{:.2}\t{:.2}\t{:+.2}",
+                invoice.balance, reconciliation.balance, reconciliation.balance_difference
+            );
+            println!();
+            if reconciliation.findings.is_empty() {
+                println!("the invoice matches the computed costs");
+            }
+            for finding in &reconciliation.findings {
+                println!("- {}", finding.message);
+            }
+            Ok(())
+        }
+    }
+}
+
+fn import_e_invoice(repository: &mut SqliteRepository, args: EInvoiceArgs) -> Result<(), String> {
+    let prefill = e_invoice::prefill(e_invoice::load(args.path.as_str())?)?;
+    let document = &prefill.document;
+    println!(
+        "invoice {} of {}, {} - {}",
+        document.number, document.seller, prefill.invoice.date_start, prefill.invoice.date_end
+    );
+    println!("line\tquantity\tnet amount");
+    for line in &document.lines {
+        println!(
+            "{}\t{} {}\t{:.2}",
+            line.name, line.quantity, line.unit_code, line.net_amount
+        );
+    }
+    println!(
+        "consumption {:.0} kWh, net {:.2}, tax {:.2}, gross {:.2}, prepaid {:.2}, balance {:.2}",
+        prefill.invoice.consumption,
+        prefill.invoice.net_amount,
+        prefill.invoice.tax_amount,
+        prefill.invoice.gross_amount,
+        prefill.invoice.advance_payments,
+        prefill.invoice.balance
+    );
+    println!(
+        "fee prices: base fee {:.2}/month, {:.4}/kWh, advance {:.2}/month (gross)",
+        prefill.fee.base_fee, prefill.fee.price_per_unit, prefill.fee.monthly_discount
+    );
+    if args.dry_run {
+        return Ok(());
+    }
+
+    let result = e_invoice::import(repository, prefill.fee, prefill.invoice)?;
+    match result.fee_created {
+        true => println!("created fee {}", result.fee.id),
+        false => println!("kept the prices of fee {}", result.fee.id),
+    }
+    println!("created invoice {}", result.invoice.id);
+    Ok(())
+}
+
+fn import_bank_statement(
+    repository: &mut SqliteRepository,
+    args: BankStatementArgs,
+) -> Result<(), String> {
+    let csv = match (args.date_column, args.amount_column) {
+        (Some(date_column), Some(amount_column)) => Some(BankCsvOptions {
+            delimiter: args.delimiter,
+            has_header: !args.no_header,
+            date_column,
+            amount_column,
+            counterparty_column: args.counterparty_column,
+            text_column: args.text_column,
+            creditor_id_column: args.creditor_id_column,
+            date_format: args.date_format,
+            decimal_separator: args.decimal_separator,
+        }),
+        _ => None,
+    };
+    let transactions = bank_statement::load(args.path.as_str(), csv.as_ref())?;
+    let matches = match args.dry_run {
+        true => bank_statement::match_transactions(repository, &transactions)?,
+        false => bank_statement::import(repository, &transactions)?,
+    };
+
+    let count = |status: MatchStatus| {
+        matches
+            .iter()
+            .filter(|found| found.status == status)
+            .count()
+    };
+    let recorded = match args.dry_run {
+        true => "to record",
+        false => "recorded",
+    };
+    println!(
+        "{} transactions, {} payments {}, {} already recorded, {} unmatched",
+        matches.len(),
+        count(MatchStatus::New),
+        recorded,
+        count(MatchStatus::Matched),
+        count(MatchStatus::Unmatched)
+    );
+    println!("status\tdate\tamount\tfee\tcounterparty\ttext");
+    for found in &matches {
+        let status = match (found.status, &found.reason) {
+            (MatchStatus::Unmatched, Some(reason)) => reason.as_str(),
+            (MatchStatus::Unmatched, None) => "unmatched",
+            (MatchStatus::New, _) => recorded,
+            (MatchStatus::Matched, _) => "already recorded",
+        };
+        println!(
+            "{}\t{}\t{:.2}\t{}\t{}\t{}",
+            status,
+            found.transaction.date,
+            found.transaction.amount,
+            found
+                .payment
+                .as_ref()
+                .map(|payment| payment.fee_id.to_string())
+                .unwrap_or_default(),
+            found.transaction.counterparty,
+            found.transaction.text
+        );
+    }
+    Ok(())
+}
+
+fn adjustments(
+    repository: &mut SqliteRepository,
+    command: AdjustmentsCommand,
+) -> Result<(), String> {
+    match command {
+        AdjustmentsCommand::List { fee } => {
+            println!("id\tdate\tamount\ttaxable\tdescription");
+            for adjustment in repository.list_adjustments(fee)? {
+                println!(
+                    "{}\t{}\t{:.2}\t{}\t{}",
+                    adjustment.id,
+                    adjustment.date,
+                    adjustment.amount,
+                    adjustment.taxable,
+                    adjustment.description
+                );
+            }
+            Ok(())
+        }
+        AdjustmentsCommand::Add {
+            fee,
+            amount,
+            date,
+            description,
+            taxable,
+        } => {
+            let adjustment = repository.create_adjustment(CreateAdjustmentParams {
+                fee_id: fee,
+                amount,
+                date,
+                description,
+                taxable,
+            })?;
+            println!("created adjustment {}", adjustment.id);
+            Ok(())
+        }
+        AdjustmentsCommand::Delete { id } => {
+            repository.delete_adjustment(id)?;
+            println!("deleted adjustment {}", id);
+            Ok(())
+        }
+    }
+}
+
+fn vat(repository: &mut SqliteRepository, command: VatCommand) -> Result<(), String> {
+    match command {
+        VatCommand::List => {
+            println!("id\tstart\tend\trate");
+            for rate in repository.list_vat_rates()? {
+                println!(
+                    "{}\t{}\t{}\t{:.2}",
+                    rate.id,
+                    rate.date_start,
+                    rate.date_end
+                        .map(|date| date.to_string())
+                        .unwrap_or_else(|| "-".to_string()),
+                    rate.rate
+                );
+            }
+            Ok(())
+        }
+        VatCommand::Add { start, end, rate } => {
+            let rate = repository.create_vat_rate(CreateVatRateParams {
+                date_start: start,
+                date_end: end,
+                rate,
+            })?;
+            println!("created VAT rate {}", rate.id);
+            Ok(())
+        }
+        VatCommand::Delete { id } => {
+            repository.delete_vat_rate(id)?;
+            println!("deleted VAT rate {}", id);
+            Ok(())
+        }
+    }
+}
+
+fn holidays(repository: &mut SqliteRepository, command: HolidaysCommand) -> Result<(), String> {
+    match command {
+        HolidaysCommand::List => {
+            println!("date\tname");
+            for holiday in repository.list_holidays()? {
+                println!("{}\t{}", holiday.date, holiday.name);
+            }
+            Ok(())
+        }
+        HolidaysCommand::Add { date, name } => {
+            repository.upsert_holiday(Holiday { date, name })?;
+            println!("saved holiday {}", date);
+            Ok(())
+        }
+        HolidaysCommand::Delete { date } => {
+            repository.delete_holiday(&date)?;
+            println!("deleted holiday {}", date);
+            Ok(())
+        }
+    }
+}
+
+fn readings(repository: &mut SqliteRepository, command: ReadingsCommand) -> Result<(), String> {
+    match command {
+        ReadingsCommand::List => {
+            let mut readings = repository.list_meter_readings()?;
+            readings.sort_by_key(|reading| reading.date);
+            println!("id\tmeter\tdate\tvalue\tfee");
+            for reading in readings {
+                println!(
+                    "{}\t{}\t{}\t{:.2}\t{}",
+                    reading.id, reading.meter_id, reading.date, reading.value, reading.fee.id
+                );
+            }
+            Ok(())
+        }
+        ReadingsCommand::Add { value, meter, date } => {
+            let date = date.unwrap_or_else(|| chrono::Utc::now().naive_utc());
+            let reading = create_meter_reading_for_date(repository, meter, value, &date)?;
+            println!("created reading {}", reading.id);
+            Ok(())
+        }
+    }
+}
+
+fn intervals(repository: &mut SqliteRepository, command: IntervalsCommand) -> Result<(), String> {
+    match command {
+        IntervalsCommand::Show {
+            meter,
+            resolution,
+            start,
+            end,
+        } => {
+            println!("start\tconsumption");
+            for bucket in load_bucket_consumption(repository, meter, resolution, &start, &end)? {
+                println!("{}\t{:.3}", bucket.date_start, bucket.consumption);
+            }
+            Ok(())
+        }
+        IntervalsCommand::Prune { meter, keep_days } => {
+            let date = chrono::Utc::now().naive_utc() - chrono::Duration::days(keep_days);
+            let meters = match meter {
+                Some(meter) => vec![repository.get_meter(meter)?.id],
+                None => repository
+                    .list_meters()?
+                    .into_iter()
+                    .map(|meter| meter.id)
+                    .collect(),
+            };
+            let mut deleted = 0;
+            for meter in meters {
+                deleted += repository.delete_interval_samples_before(meter, &date)?;
+            }
+            println!("deleted {} samples", deleted);
+            Ok(())
+        }
+    }
+}
+
+fn spot_prices(
+    repository: &mut SqliteRepository,
+    command: SpotPricesCommand,
+) -> Result<(), String> {
+    match command {
+        SpotPricesCommand::Import(args) => {
+            let options = SpotPriceImportOptions {
+                unit: args.unit,
+                csv: Some(CsvImportOptions {
+                    delimiter: args.delimiter,
+                    has_header: !args.no_header,
+                    date_column: args.date_column,
+                    value_column: args.price_column,
+                    date_format: args.date_format,
+                    decimal_separator: args.decimal_separator,
+                    meter_id: DEFAULT_METER_ID,
+                }),
+            };
+            let prices = spot_prices::load(args.path.as_str(), &options)?;
+            let stored = spot_prices::import(repository, &prices)?;
+            match (prices.first(), prices.last()) {
+                (Some(first), Some(last)) => println!(
+                    "stored {} hourly prices from {} to {}",
+                    stored, first.date_start, last.date_start
+                ),
+                _ => println!("file contains no prices"),
+            }
+            Ok(())
+        }
+        SpotPricesCommand::Show { start, end } => {
+            println!("start\tprice");
+            for price in repository.list_spot_prices(&start, &end)? {
+                println!("{}\t{:.5}", price.date_start, price.price);
+            }
+            Ok(())
+        }
+    }
+}
+
+fn import(repository: &mut SqliteRepository, args: ImportArgs) -> Result<(), String> {
+    let options = CsvImportOptions {
+        delimiter: args.delimiter,
+        has_header: !args.no_header,
+        date_column: args.date_column,
+        value_column: args.value_column,
+        date_format: args.date_format,
+        decimal_separator: args.decimal_separator,
+        meter_id: args.meter,
+    };
+
+    let rows = meter_readings_csv::preview(repository, args.path, &options)?;
+    if args.dry_run {
+        println!("line\tdate\tvalue\tfee\terror");
+        for row in rows {
+            println!(
+                "{}\t{}\t{}\t{}\t{}",
+                row.line,
+                row.date.map(|date| date.to_string()).unwrap_or_default(),
+                row.value.map(|value| value.to_string()).unwrap_or_default(),
+                row.fee_id.map(|id| id.to_string()).unwrap_or_default(),
+                row.error.unwrap_or_default()
+            );
+        }
+        return Ok(());
+    }
+
+    let result = meter_readings_csv::import(repository, rows, args.skip_invalid)?;
+    println!(
+        "imported {} readings, skipped {}",
+        result.imported, result.skipped
+    );
+    Ok(())
+}
+
+fn import_green_button(
+    repository: &mut SqliteRepository,
+    args: GreenButtonArgs,
+) -> Result<(), String> {
+    let document = green_button::load(args.path.as_str())?;
+    if args.list {
+        println!("usage point\ttitle\tmeter reading\tflow direction\tstart\tend\ttotal");
+        for usage_point in &document.usage_points {
+            for meter_reading in &usage_point.meter_readings {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{:.3} {}",
+                    usage_point.id,
+                    usage_point.title,
+                    meter_reading.id,
+                    meter_reading.flow_direction,
+                    meter_reading
+                        .date_start
+                        .map(|date| date.to_string())
+                        .unwrap_or_default(),
+                    meter_reading
+                        .date_end
+                        .map(|date| date.to_string())
+                        .unwrap_or_default(),
+                    meter_reading.total,
+                    meter_reading.unit
+                );
+            }
+        }
+        return Ok(());
+    }
+
+    let options = GreenButtonImportOptions {
+        usage_point: args.usage_point,
+        meter_id: args.meter,
+        export: args.export,
+        start_value: args.start_value,
+        reading_interval_seconds: args.reading_interval_seconds,
+    };
+    let result = green_button::import(repository, &document, &options)?;
+    for error in &result.errors {
+        eprintln!("{}", error);
+    }
+    println!(
+        "received {} intervals, stored {} samples and {} readings, skipped {}",
+        result.received, result.samples, result.stored, result.skipped
+    );
+    Ok(())
+}
+
+fn import_mscons(repository: &mut SqliteRepository, args: MsconsArgs) -> Result<(), String> {
+    let document = mscons::load(args.path.as_str())?;
+    for segment in &document.unsupported {
+        eprintln!("unsupported {}", segment);
+    }
+    if args.list {
+        println!("location\tobis\tkind\tvalues\tstart\tend\tunit");
+        for location in &document.locations {
+            for series in &location.series {
+                println!(
+                    "{}\t{}\t{:?}\t{}\t{}\t{}\t{}",
+                    location.id,
+                    series.obis,
+                    series.kind(),
+                    series.quantities.len(),
+                    location
+                        .date_start
+                        .map(|date| date.to_string())
+                        .unwrap_or_default(),
+                    location
+                        .date_end
+                        .map(|date| date.to_string())
+                        .unwrap_or_default(),
+                    series.unit.clone().unwrap_or_default()
+                );
+            }
+        }
+        return Ok(());
+    }
+
+    let options = MsconsImportOptions {
+        location: args.location,
+        obis: args.obis,
+        meter_id: args.meter,
+        start_value: args.start_value,
+        reading_interval_seconds: args.reading_interval_seconds,
+    };
+    let result = mscons::import(repository, &document, &options)?;
+    for error in &result.errors {
+        eprintln!("{}", error);
+    }
+    println!(
+        "received {} values, stored {} samples and {} readings, skipped {}",
+        result.received, result.samples, result.stored, result.skipped
+    );
+    Ok(())
+}
+
+fn smart_meter_dsmr(repository: &mut SqliteRepository, args: DsmrArgs) -> Result<(), String> {
+    let options = DsmrOptions {
+        register: args.register,
+        meter_id: args.meter,
+        gas_meter_id: args.gas_meter,
+        interval_seconds: args.interval_seconds,
+        verify_crc: !args.no_crc,
+    };
+
+    let device = match args.device {
+        Some(device) => device,
+        None => {
+            let path = args.file.unwrap_or_default();
+            let result = dsmr::ingest_file(repository, path.as_str(), options)?;
+            for error in &result.errors {
+                eprintln!("{}", error);
+            }
+            println!(
+                "received {} telegrams, stored {} samples and {} readings, skipped {}",
+                result.received, result.samples, result.stored, result.skipped
+            );
+            return Ok(());
+        }
+    };
+
+    let port = dsmr::open_serial(device.as_str(), args.legacy)?;
+    println!("reading telegrams from {}", device);
+    let mut ingest = DsmrIngest::new(repository, options)?;
+    for raw in TelegramReader::new(port) {
+        let stored = ingest.result.stored;
+        ingest.process(repository, raw?.as_str());
+        for error in ingest.result.errors.drain(..) {
+            eprintln!("{}", error);
+        }
+        if ingest.result.stored > stored {
+            println!("stored reading {}", ingest.result.stored);
+        }
+    }
+    Ok(())
+}
+
+fn smart_meter_sml(repository: &mut SqliteRepository, args: SmlArgs) -> Result<(), String> {
+    let options = SmlOptions {
+        register: args.register,
+        meter_id: args.meter,
+        interval_seconds: args.interval_seconds,
+        verify_crc: !args.no_crc,
+    };
+
+    let device = match args.device {
+        Some(device) => device,
+        None => {
+            let path = args.file.unwrap_or_default();
+            let result = sml::ingest_file(repository, path.as_str(), options)?;
+            for error in &result.errors {
+                eprintln!("{}", error);
+            }
+            println!(
+                "received {} frames, stored {} samples and {} readings, skipped {}",
+                result.received, result.samples, result.stored, result.skipped
+            );
+            return Ok(());
+        }
+    };
+
+    let port = sml::open_serial(device.as_str())?;
+    println!("reading frames from {}", device);
+    let mut ingest = SmlIngest::new(repository, options)?;
+    for frame in FrameReader::new(port) {
+        let stored = ingest.result.stored;
+        ingest.process(repository, frame?.as_slice());
+        for error in ingest.result.errors.drain(..) {
+            eprintln!("{}", error);
+        }
+        if ingest.result.stored > stored {
+            println!("stored reading {}", ingest.result.stored);
+        }
+    }
+    Ok(())
+}
+
+fn export(repository: &mut SqliteRepository, command: ExportCommand) -> Result<(), String> {
+    match command {
+        ExportCommand::Csv { path, kind, locale } => {
+            let data = ExportData::load(repository)?;
+            let file = std::fs::File::create(path).map_err(|err| err.to_string())?;
+            csv_export::write(file, &data, kind, locale)
+        }
+        ExportCommand::Xlsx { path, locale } => {
+            let data = ExportData::load(repository)?;
+            xlsx_export::save(&data, locale, path.as_str())
+        }
+        ExportCommand::Report { fee_id, path } => {
+            let report = AnnualReport::load(repository, fee_id)?;
+            pdf_report::save(&report, path.as_str())
+        }
+    }
+}
+
+fn costs(repository: &mut SqliteRepository) -> Result<(), String> {
+    println!("fee\tstart\tend\tconsumption\ttotal costs\tadvance payments\tbalance");
+    for costs in load_cost_breakdowns(repository)? {
+        println!(
+            "{}\t{}\t{}\t{:.2}\t{:.2}\t{:.2}\t{:.2}",
+            costs.fee_id,
+            costs.date_start.date(),
+            costs.date_end.date(),
+            costs.consumption,
+            costs.total_costs,
+            costs.advance_payments,
+            costs.balance
+        );
+        if costs.recorded_payments > 0 {
+            println!(
+                "\trecorded payments\t\t{}\t\t{:.2}",
+                costs.recorded_payments, costs.advance_payments
+            );
+        }
+        for window in &costs.tariff_windows {
+            println!(
+                "\t{}\t\t{:.2}\t{:.2}",
+                window.name, window.consumption, window.costs
+            );
+        }
+        for block in &costs.blocks {
+            let name = match block.up_to {
+                Some(up_to) => format!("up to {:.0} kWh", up_to),
+                None => "above".to_string(),
+            };
+            println!("\t{}\t\t{:.2}\t{:.2}", name, block.consumption, block.costs);
+        }
+        if let Some(brake) = &costs.price_brake {
+            println!(
+                "\tprice brake\t\t{:.2} kWh/month at {:.4}\t{} x {:.2} relief\t{:.2}",
+                brake.contingent, brake.cap_price, brake.months, brake.monthly_relief, brake.relief
+            );
+        }
+        for adjustment in &costs.adjustments {
+            println!(
+                "\t{}\t\t{}\t{:.2}",
+                adjustment.description, adjustment.date, adjustment.gross
+            );
+        }
+        if costs.tax_costs != 0.0 {
+            for period in &costs.vat_periods {
+                println!(
+                    "\t{} - {}\t\t{:.0} % VAT\tnet {:.2}\ttax {:.2}\tgross {:.2}",
+                    period.date_start,
+                    period.date_end,
+                    period.rate,
+                    period.net,
+                    period.tax,
+                    period.gross
+                );
+            }
+        }
+        for component in &costs.components {
+            let share = match costs.total_costs {
+                total if total != 0.0 => component.costs / total * 100.0,
+                _ => 0.0,
+            };
+            println!(
+                "\t{}\t\t\t{:.2}\t({:.1} %)",
+                component.name, component.costs, share
+            );
+        }
+    }
+    Ok(())
+}
+
+fn advance(
+    repository: &mut SqliteRepository,
+    fee: i32,
+    safety_margin: f32,
+    consumption: Option<f32>,
+) -> Result<(), String> {
+    let proposal = load_advance_payment_proposal(repository, fee, safety_margin, consumption)?;
+    if proposal.history_days > 0.0 {
+        println!(
+            "expected consumption\t{:.2} kWh (from {:.0} days of readings)",
+            proposal.expected_consumption, proposal.history_days
+        );
+    } else {
+        println!(
+            "expected consumption\t{:.2} kWh",
+            proposal.expected_consumption
+        );
+    }
+    println!(
+        "expected costs\t\t{:.2} ({} months)",
+        proposal.expected_costs, proposal.months
+    );
+    if proposal.relief > 0.0 {
+        println!("price brake relief\t{:.2}", proposal.relief);
+    }
+    println!("\tadvance\texpected balance");
+    println!(
+        "current\t{:.2}\t{:.2}",
+        proposal.current_advance, proposal.current_balance
+    );
+    println!(
+        "proposed\t{:.2}\t{:.2}\t(+{:.0} % safety margin)",
+        proposal.proposed_advance, proposal.proposed_balance, proposal.safety_margin
+    );
+    Ok(())
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    let database_file = cli.database.unwrap_or_else(database_file);
+    let mut connection = match establish_connection(database_file) {
+        Ok(conn) => conn,
+        Err(err) => {
+            eprintln!("failed to connect to database: {}", err);
+            std::process::exit(1);
+        }
+    };
+    if let Err(err) = run_migrations(&mut connection) {
+        eprintln!("failed to run migrations: {}", err);
+        std::process::exit(1);
+    }
+
+    if let Command::Serve { address, token } = cli.command {
+        let connection = Arc::new(Mutex::new(connection));
+        let result = api::start(ApiConfig { address, token }, connection);
+        match result {
+            Ok(handle) => handle.join().expect("api thread panicked"),
+            Err(err) => {
+                eprintln!("error: {}", err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Command::Mqtt(args) = cli.command {
+        let config = MqttConfig {
+            host: args.host,
+            port: args.port,
+            client_id: args.client_id,
+            username: args.username,
+            password: args.password,
+            subscriptions: args.subscribe,
+            interval_seconds: args.interval_seconds,
+        };
+        if let Err(err) = mqtt::run(config, &Mutex::new(connection)) {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let mut repository = SqliteRepository::new(&mut connection);
+    let result = match cli.command {
+        Command::Meters(command) => meters(&mut repository, command),
+        Command::Fees(command) => fees(&mut repository, command),
+        Command::Components(command) => components(&mut repository, command),
+        Command::Adjustments(command) => adjustments(&mut repository, command),
+        Command::Payments(command) => payments(&mut repository, command),
+        Command::Invoices(command) => invoices(&mut repository, command),
+        Command::Vat(command) => vat(&mut repository, command),
+        Command::Tariffs(command) => tariffs(&mut repository, command),
+        Command::Holidays(command) => holidays(&mut repository, command),
+        Command::Readings(command) => readings(&mut repository, command),
+        Command::Intervals(command) => intervals(&mut repository, command),
+        Command::SpotPrices(command) => spot_prices(&mut repository, command),
+        Command::Import(args) => import(&mut repository, args),
+        Command::GreenButton(args) => import_green_button(&mut repository, args),
+        Command::BankStatement(args) => import_bank_statement(&mut repository, args),
+        Command::EInvoice(args) => import_e_invoice(&mut repository, args),
+        Command::Mscons(args) => import_mscons(&mut repository, args),
+        Command::Dsmr(args) => smart_meter_dsmr(&mut repository, args),
+        Command::Sml(args) => smart_meter_sml(&mut repository, args),
+        Command::Export(command) => export(&mut repository, command),
+        Command::Costs => costs(&mut repository),
+        Command::Advance {
+            fee,
+            safety_margin,
+            consumption,
+        } => advance(&mut repository, fee, safety_margin, consumption),
+        Command::Serve { .. } | Command::Mqtt(_) => unreachable!(),
+    };
+
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,367 @@
+use std::env;
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use chrono::NaiveDateTime;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::calculation::costs::load_cost_breakdowns;
+use crate::models::fees::CreateFeeParams;
+use crate::models::meter::DEFAULT_METER_ID;
+use crate::models::meter_reading::{CreateMeterReadingParams, MeterReading};
+use crate::models::{format_datetime, parse_datetime};
+use crate::repository::{create_meter_reading_for_date, Repository, SqliteRepository};
+
+pub const OPENAPI: &str = include_str!("openapi.json");
+
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:8787";
+
+/// Larger request bodies are rejected with 413, no request of the api needs more.
+pub const MAX_BODY_BYTES: u64 = 64 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct ApiConfig {
+    pub address: String,
+    pub token: String,
+}
+
+impl ApiConfig {
+    /// The server is enabled by setting `QUM_API_TOKEN`, `QUM_API_ADDRESS` overrides the
+    /// default address which only accepts connections from this machine.
+    pub fn from_env() -> Option<ApiConfig> {
+        let token = env::var("QUM_API_TOKEN").ok()?;
+        let address = env::var("QUM_API_ADDRESS").unwrap_or_else(|_| DEFAULT_ADDRESS.to_string());
+
+        Some(ApiConfig { address, token })
+    }
+}
+
+/// Body of `POST /api/meter-readings`. Without a fee the fee of the meter is picked by the
+/// reading date, without a date the reading is stored for now.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiMeterReadingParams {
+    pub value: f32,
+    #[serde(rename = "meterId")]
+    pub meter_id: Option<i32>,
+    #[serde(rename = "feeId")]
+    pub fee_id: Option<i32>,
+    #[serde(rename = "readingDate")]
+    pub reading_date: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ApiResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+impl ApiResponse {
+    fn json<T: Serialize>(status: u16, value: &T) -> ApiResponse {
+        match serde_json::to_string(value) {
+            Ok(body) => ApiResponse { status, body },
+            Err(err) => ApiResponse::error(500, err.to_string()),
+        }
+    }
+
+    fn error(status: u16, message: String) -> ApiResponse {
+        let body = serde_json::json!({ "error": message }).to_string();
+        ApiResponse { status, body }
+    }
+
+    fn from_result<T: Serialize>(status: u16, result: Result<T, String>) -> ApiResponse {
+        match result {
+            Ok(value) => ApiResponse::json(status, &value),
+            Err(err) => ApiResponse::error(400, err),
+        }
+    }
+}
+
+/// Compares without returning early so the token can't be guessed from response times.
+fn token_matches(expected: &str, given: &str) -> bool {
+    if expected.len() != given.len() {
+        return false;
+    }
+
+    expected
+        .bytes()
+        .zip(given.bytes())
+        .fold(0, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+fn parse_body<T: for<'de> Deserialize<'de>>(body: &str) -> Result<T, String> {
+    serde_json::from_str(body).map_err(|err| format!("invalid body: {}", err))
+}
+
+fn create_meter_reading<R: Repository>(
+    repository: &mut R,
+    params: ApiMeterReadingParams,
+) -> Result<MeterReading, String> {
+    let date: NaiveDateTime = match params.reading_date {
+        Some(date) => parse_datetime(date.as_str())?,
+        None => chrono::Utc::now().naive_utc(),
+    };
+
+    match params.fee_id {
+        Some(fee_id) => {
+            let fee = repository
+                .find_fee(fee_id)?
+                .ok_or_else(|| format!("fee {} not found", fee_id))?;
+            if params
+                .meter_id
+                .is_some_and(|meter_id| meter_id != fee.meter_id)
+            {
+                return Err(format!(
+                    "fee {} belongs to meter {}, not to meter {}",
+                    fee_id,
+                    fee.meter_id,
+                    params.meter_id.unwrap_or_default()
+                ));
+            }
+            repository.create_meter_reading(CreateMeterReadingParams {
+                value: params.value,
+                fee_id,
+                reading_date: format_datetime(&date),
+            })
+        }
+        None => create_meter_reading_for_date(
+            repository,
+            params.meter_id.unwrap_or(DEFAULT_METER_ID),
+            params.value,
+            &date,
+        ),
+    }
+}
+
+/// Handles one request. `authorization` is the value of the `Authorization` header.
+pub fn route<R: Repository>(
+    repository: &mut R,
+    token: &str,
+    method: &Method,
+    path: &str,
+    authorization: Option<&str>,
+    body: &str,
+) -> ApiResponse {
+    let path = path.split('?').next().unwrap_or(path).trim_end_matches('/');
+
+    if path == "/api/openapi.json" && *method == Method::Get {
+        return ApiResponse {
+            status: 200,
+            body: OPENAPI.to_string(),
+        };
+    }
+
+    let authorized = match authorization.and_then(|value| value.strip_prefix("Bearer ")) {
+        Some(given) => token_matches(token, given.trim()),
+        None => false,
+    };
+    if !authorized {
+        return ApiResponse::error(401, "missing or invalid token".to_string());
+    }
+
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    match (method, segments.as_slice()) {
+        (Method::Get, ["api", "meters"]) => ApiResponse::from_result(200, repository.list_meters()),
+        (Method::Get, ["api", "fees"]) => ApiResponse::from_result(200, repository.list_fees()),
+        (Method::Post, ["api", "fees"]) => match parse_body::<CreateFeeParams>(body) {
+            Ok(params) => ApiResponse::from_result(201, repository.create_fee(params)),
+            Err(err) => ApiResponse::error(400, err),
+        },
+        (Method::Delete, ["api", "fees", id]) => match id.parse::<i32>() {
+            Ok(id) => ApiResponse::from_result(200, repository.delete_fee(id)),
+            Err(_) => ApiResponse::error(400, format!("invalid id '{}'", id)),
+        },
+        (Method::Get, ["api", "meter-readings"]) => {
+            ApiResponse::from_result(200, repository.list_meter_readings())
+        }
+        (Method::Post, ["api", "meter-readings"]) => {
+            match parse_body::<ApiMeterReadingParams>(body) {
+                Ok(params) => {
+                    ApiResponse::from_result(201, create_meter_reading(repository, params))
+                }
+                Err(err) => ApiResponse::error(400, err),
+            }
+        }
+        (Method::Get, ["api", "costs"]) => {
+            ApiResponse::from_result(200, load_cost_breakdowns(repository))
+        }
+        _ => ApiResponse::error(404, format!("{} {} not found", method, path)),
+    }
+}
+
+/// Reads at most `MAX_BODY_BYTES`, the error is the response to send instead.
+fn read_body<R: Read>(reader: R) -> Result<String, ApiResponse> {
+    let mut body = vec![];
+    reader
+        .take(MAX_BODY_BYTES + 1)
+        .read_to_end(&mut body)
+        .map_err(|err| ApiResponse::error(400, format!("failed to read body: {}", err)))?;
+    if body.len() as u64 > MAX_BODY_BYTES {
+        return Err(ApiResponse::error(
+            413,
+            format!("body exceeds {} bytes", MAX_BODY_BYTES),
+        ));
+    }
+
+    String::from_utf8(body).map_err(|_| ApiResponse::error(400, "body is not UTF-8".to_string()))
+}
+
+fn handle(mut request: Request, connection: &Mutex<Connection>, token: &str) {
+    let body = read_body(request.as_reader());
+
+    let authorization = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Authorization"))
+        .map(|header| header.value.as_str().to_string());
+
+    // the connection is shared with the app, a panic there must not take the api down
+    let response = match (body, connection.lock()) {
+        (Err(response), _) => response,
+        (Ok(_), Err(_)) => ApiResponse::error(500, "database is not available".to_string()),
+        (Ok(body), Ok(mut connection)) => {
+            let mut repository = SqliteRepository::new(&mut connection);
+            route(
+                &mut repository,
+                token,
+                request.method(),
+                request.url(),
+                authorization.as_deref(),
+                body.as_str(),
+            )
+        }
+    };
+
+    println!(
+        "api: {} {} -> {}",
+        request.method(),
+        request.url(),
+        response.status
+    );
+    let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+    let response = Response::from_string(response.body)
+        .with_status_code(response.status)
+        .with_header(content_type);
+    if let Err(err) = request.respond(response) {
+        println!("api: failed to respond: {}", err);
+    }
+}
+
+/// Starts the server on its own thread, requests are handled one after another.
+pub fn start(
+    config: ApiConfig,
+    connection: Arc<Mutex<Connection>>,
+) -> Result<JoinHandle<()>, String> {
+    if config.token.is_empty() {
+        return Err("api token must not be empty".to_string());
+    }
+
+    let server = Server::http(config.address.as_str()).map_err(|err| err.to_string())?;
+    println!("api: listening on http://{}", config.address);
+
+    Ok(thread::spawn(move || {
+        for request in server.incoming_requests() {
+            handle(request, &connection, config.token.as_str());
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use tiny_http::Method;
+
+    use crate::repository::{FeeRepository, InMemoryRepository};
+
+    use super::{read_body, route, MAX_BODY_BYTES};
+
+    const TOKEN: &str = "secret";
+
+    fn request(
+        repository: &mut InMemoryRepository,
+        method: Method,
+        path: &str,
+        body: &str,
+    ) -> super::ApiResponse {
+        route(
+            repository,
+            TOKEN,
+            &method,
+            path,
+            Some("Bearer secret"),
+            body,
+        )
+    }
+
+    #[test]
+    fn requires_token() {
+        let mut repository = InMemoryRepository::new();
+        let response = route(&mut repository, TOKEN, &Method::Get, "/api/fees", None, "");
+        assert_eq!(response.status, 401);
+
+        let response = route(
+            &mut repository,
+            TOKEN,
+            &Method::Get,
+            "/api/fees",
+            Some("Bearer secreT"),
+            "",
+        );
+        assert_eq!(response.status, 401);
+
+        let response = route(
+            &mut repository,
+            TOKEN,
+            &Method::Get,
+            "/api/openapi.json",
+            None,
+            "",
+        );
+        assert_eq!(response.status, 200);
+    }
+
+    #[test]
+    fn create_fee_and_reading() {
+        let mut repository = InMemoryRepository::new();
+        let fee = r#"{"baseFee": 10, "pricePerUnit": 0.4, "monthlyDiscount": 50, "dateStart": "2022-01-01T00:00:00.000Z", "dateEnd": "2022-12-31T00:00:00.000Z"}"#;
+        let response = request(&mut repository, Method::Post, "/api/fees", fee);
+        assert_eq!(response.status, 201);
+        assert_eq!(repository.list_fees().unwrap().len(), 1);
+
+        let reading = r#"{"value": 1234.5, "readingDate": "2022-03-01T00:00:00.000Z"}"#;
+        let response = request(
+            &mut repository,
+            Method::Post,
+            "/api/meter-readings",
+            reading,
+        );
+        assert_eq!(response.status, 201);
+        assert!(response.body.contains("1234.5"));
+
+        let reading = r#"{"value": 1234.5, "readingDate": "2023-03-01T00:00:00.000Z"}"#;
+        let response = request(
+            &mut repository,
+            Method::Post,
+            "/api/meter-readings",
+            reading,
+        );
+        assert_eq!(response.status, 400);
+
+        let response = request(&mut repository, Method::Get, "/api/costs", "");
+        assert_eq!(response.status, 200);
+
+        let response = request(&mut repository, Method::Get, "/api/unknown", "");
+        assert_eq!(response.status, 404);
+    }
+
+    #[test]
+    fn limits_body_size() {
+        let body = vec![b'x'; MAX_BODY_BYTES as usize];
+        assert!(read_body(body.as_slice()).is_ok());
+
+        let body = vec![b'x'; MAX_BODY_BYTES as usize + 1];
+        assert_eq!(read_body(body.as_slice()).unwrap_err().status, 413);
+    }
+}
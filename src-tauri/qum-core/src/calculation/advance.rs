@@ -0,0 +1,219 @@
+//! Proposal of the monthly advance payment for a fee period, based on the consumption of
+//! the year before the period and the prices of the fee.
+
+use chrono::{Duration, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+use crate::models::fees::Fee;
+use crate::repository::Repository;
+
+use super::consumption::{interval_consumption, IntervalConsumption};
+use super::costs::{apply_stored_charges, cost_breakdown, CostBreakdown};
+
+/// Percent added to the expected costs.
+pub const DEFAULT_SAFETY_MARGIN: f32 = 10.0;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AdvancePaymentProposal {
+    #[serde(rename = "feeId")]
+    pub fee_id: i32,
+    /// Days of meter readings the daily consumption was measured over, 0 if the
+    /// consumption was given
+    #[serde(rename = "historyDays")]
+    pub history_days: f32,
+    #[serde(rename = "expectedConsumption")]
+    pub expected_consumption: f32,
+    /// Gross costs of the expected consumption before the relief of a price brake
+    #[serde(rename = "expectedCosts")]
+    pub expected_costs: f32,
+    /// Relief of a price brake, it is deducted from the advance payments
+    pub relief: f32,
+    #[serde(rename = "safetyMargin")]
+    pub safety_margin: f32,
+    pub months: u32,
+    #[serde(rename = "currentAdvance")]
+    pub current_advance: f32,
+    /// Positive values are a credit, negative values have to be paid.
+    #[serde(rename = "currentBalance")]
+    pub current_balance: f32,
+    #[serde(rename = "proposedAdvance")]
+    pub proposed_advance: f32,
+    #[serde(rename = "proposedBalance")]
+    pub proposed_balance: f32,
+}
+
+/// Average consumption per day of the intervals in the year before `date`, intervals
+/// reaching into that year are counted with their share. Falls back to all intervals if
+/// none is in that year. Returns the kWh per day and the covered days.
+pub fn daily_consumption(
+    intervals: &[IntervalConsumption],
+    date: &NaiveDateTime,
+) -> Option<(f32, f32)> {
+    let measure = |start: NaiveDateTime, end: NaiveDateTime| {
+        let mut consumption = 0.0;
+        let mut days = 0.0;
+        for interval in intervals {
+            let interval_days = interval.days();
+            let from = interval.date_start.max(start);
+            let to = interval.date_end.min(end);
+            if interval_days <= 0.0 || to <= from {
+                continue;
+            }
+            let covered = (to - from).num_seconds() as f32 / 86400.0;
+            consumption += interval.consumption * covered / interval_days;
+            days += covered;
+        }
+        match days > 0.0 {
+            true => Some((consumption / days, days)),
+            false => None,
+        }
+    };
+
+    measure(*date - Duration::days(365), *date)
+        .or_else(|| measure(NaiveDateTime::MIN, NaiveDateTime::MAX))
+}
+
+/// Proposes the smallest advance in whole euros that covers the expected costs plus the
+/// safety margin. `breakdown` holds the costs of the expected consumption.
+pub fn propose_advance_payment(
+    fee: &Fee,
+    breakdown: &CostBreakdown,
+    history_days: f32,
+    safety_margin: f32,
+) -> AdvancePaymentProposal {
+    let relief = breakdown
+        .price_brake
+        .as_ref()
+        .map_or(0.0, |brake| brake.relief + brake.tax);
+    // the relief lowers the costs and the payments alike
+    let expected_costs = breakdown.total_costs + relief;
+    let months = breakdown.months.max(1) as f32;
+    let balance = |advance: f32| advance * months - expected_costs;
+    let proposed_advance = (expected_costs * (1.0 + safety_margin / 100.0) / months).ceil();
+
+    AdvancePaymentProposal {
+        fee_id: fee.id,
+        history_days,
+        expected_consumption: breakdown.consumption,
+        expected_costs,
+        relief,
+        safety_margin,
+        months: breakdown.months,
+        current_advance: fee.monthly_discount,
+        current_balance: balance(fee.monthly_discount),
+        proposed_advance,
+        proposed_balance: balance(proposed_advance),
+    }
+}
+
+/// Proposal for the stored fee. The expected consumption is the daily consumption of the
+/// year before the fee period times its days, unless `consumption` is given.
+pub fn load_advance_payment_proposal<R: Repository>(
+    repository: &mut R,
+    fee_id: i32,
+    safety_margin: f32,
+    consumption: Option<f32>,
+) -> Result<AdvancePaymentProposal, String> {
+    let fee = match repository.find_fee(fee_id)? {
+        Some(fee) => fee,
+        None => return Err(format!("fee {} not found", fee_id)),
+    };
+
+    let (consumption, history_days) = match consumption {
+        Some(consumption) => (consumption, 0.0),
+        None => {
+            let readings: Vec<_> = repository
+                .list_meter_readings()?
+                .into_iter()
+                .filter(|reading| reading.meter_id == fee.meter_id)
+                .collect();
+            let intervals = interval_consumption(&readings);
+            let (daily, days) = daily_consumption(&intervals, &fee.date_start)
+                .ok_or_else(|| "not enough meter readings to expect a consumption".to_string())?;
+            // the last day belongs to the period
+            let fee_days = (fee.date_end - fee.date_start).num_days() + 1;
+            (daily * fee_days as f32, days)
+        }
+    };
+
+    let expected = IntervalConsumption {
+        meter_id: fee.meter_id,
+        fee_id: fee.id,
+        date_start: fee.date_start,
+        date_end: fee.date_end,
+        value_start: 0.0,
+        value_end: consumption,
+        consumption,
+    };
+    let mut breakdown = cost_breakdown(&fee, &[expected]);
+    let rates = repository.list_vat_rates()?;
+    apply_stored_charges(repository, &fee, &mut breakdown, &rates)?;
+
+    Ok(propose_advance_payment(
+        &fee,
+        &breakdown,
+        history_days,
+        safety_margin,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::calculation::consumption::IntervalConsumption;
+    use crate::calculation::costs::cost_breakdown;
+    use crate::models::fees::Fee;
+
+    use super::{daily_consumption, propose_advance_payment};
+
+    #[test]
+    fn proposal() {
+        let date = |year: i32, month: u32, day: u32| {
+            NaiveDate::from_ymd_opt(year, month, day)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+        };
+        let interval = |start, end, consumption| IntervalConsumption {
+            meter_id: 1,
+            fee_id: 1,
+            date_start: start,
+            date_end: end,
+            value_start: 0.0,
+            value_end: consumption,
+            consumption,
+        };
+        // half of the first interval is in the year before 2023
+        let intervals = vec![
+            interval(date(2021, 7, 1), date(2022, 7, 1), 3650.0),
+            interval(date(2022, 7, 1), date(2023, 1, 1), 1840.0),
+        ];
+        let (daily, days) = daily_consumption(&intervals, &date(2023, 1, 1)).unwrap();
+        assert_eq!(days, 365.0);
+        assert!((daily - 10.0).abs() < 0.001);
+
+        let fee = Fee {
+            meter_id: 1,
+            id: 1,
+            base_fee: 10.0,
+            price_per_unit: 0.25,
+            monthly_discount: 80.0,
+            spot_surcharge: None,
+            blocks: vec![],
+            net_prices: false,
+            price_brake: None,
+            date_start: date(2023, 1, 1),
+            date_end: date(2023, 12, 31),
+        };
+        let expected = interval(fee.date_start, fee.date_end, 3650.0);
+        let breakdown = cost_breakdown(&fee, &[expected]);
+        let proposal = propose_advance_payment(&fee, &breakdown, days, 10.0);
+
+        // 120 base and 912.50 consumption costs, 10 % margin
+        assert_eq!(proposal.expected_costs, 1032.5);
+        assert_eq!(proposal.current_balance, -72.5);
+        assert_eq!(proposal.proposed_advance, 95.0);
+        assert_eq!(proposal.proposed_balance, 107.5);
+    }
+}
@@ -0,0 +1,49 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::models::meter_reading::MeterReading;
+
+/// Consumption between two consecutive meter readings of one meter. The interval belongs
+/// to the fee of the reading that closes it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IntervalConsumption {
+    #[serde(rename = "meterId")]
+    pub meter_id: i32,
+    #[serde(rename = "feeId")]
+    pub fee_id: i32,
+    #[serde(rename = "dateStart")]
+    pub date_start: NaiveDateTime,
+    #[serde(rename = "dateEnd")]
+    pub date_end: NaiveDateTime,
+    #[serde(rename = "valueStart")]
+    pub value_start: f32,
+    #[serde(rename = "valueEnd")]
+    pub value_end: f32,
+    pub consumption: f32,
+}
+
+impl IntervalConsumption {
+    pub fn days(&self) -> f32 {
+        (self.date_end - self.date_start).num_seconds() as f32 / 86400.0
+    }
+}
+
+/// Readings are only paired with readings of the same meter.
+pub fn interval_consumption(readings: &[MeterReading]) -> Vec<IntervalConsumption> {
+    let mut sorted: Vec<&MeterReading> = readings.iter().collect();
+    sorted.sort_by_key(|reading| (reading.meter_id, reading.date));
+
+    sorted
+        .windows(2)
+        .filter(|pair| pair[0].meter_id == pair[1].meter_id)
+        .map(|pair| IntervalConsumption {
+            meter_id: pair[1].meter_id,
+            fee_id: pair[1].fee.id,
+            date_start: pair[0].date,
+            date_end: pair[1].date,
+            value_start: pair[0].value,
+            value_end: pair[1].value,
+            consumption: pair[1].value - pair[0].value,
+        })
+        .collect()
+}
@@ -0,0 +1,1125 @@
+use chrono::{Datelike, Local, NaiveDate, NaiveDateTime, TimeZone};
+use serde::{Deserialize, Serialize};
+
+use crate::models::adjustment::Adjustment;
+use crate::models::fees::{Fee, PriceBrake};
+use crate::models::interval::Resolution;
+use crate::models::payment::Payment;
+use crate::models::price_component::{ComponentKind, PriceComponent};
+use crate::models::spot_price::SpotPrice;
+use crate::models::tariff::{find_tariff_rule, TariffRule};
+use crate::models::vat::{vat_periods, VatRate};
+use crate::repository::Repository;
+
+use super::consumption::{interval_consumption, IntervalConsumption};
+use super::intervals::{load_bucket_consumption, BucketConsumption};
+
+/// Consumption and costs of the windows of a time-of-use tariff with the same name.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TariffWindowCosts {
+    pub name: String,
+    pub consumption: f32,
+    pub costs: f32,
+}
+
+/// Consumption and costs of one block of a tiered tariff.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BlockCosts {
+    /// Threshold prorated to the period, `None` for the consumption above the last block
+    #[serde(rename = "upTo")]
+    pub up_to: Option<f32>,
+    #[serde(rename = "pricePerUnit")]
+    pub price_per_unit: f32,
+    pub consumption: f32,
+    pub costs: f32,
+}
+
+/// Costs of one price component, the components add up to the total costs.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ComponentCosts {
+    pub kind: ComponentKind,
+    pub name: String,
+    pub costs: f32,
+}
+
+/// Relief of the energy price brake.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PriceBrakeCosts {
+    /// Months of the fee period the brake applies to
+    pub months: u32,
+    /// kWh per month charged at the cap price
+    pub contingent: f32,
+    #[serde(rename = "capPrice")]
+    pub cap_price: f32,
+    #[serde(rename = "monthlyRelief")]
+    pub monthly_relief: f32,
+    /// Net or gross like the prices of the fee
+    pub relief: f32,
+    /// VAT of the relief of net prices, the advance payments are lowered by both
+    pub tax: f32,
+}
+
+/// One-off charge or credit with its VAT.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AdjustmentCosts {
+    pub id: i32,
+    pub date: NaiveDate,
+    pub description: String,
+    pub taxable: bool,
+    /// As stored, net or gross like the prices of the fee
+    pub amount: f32,
+    pub net: f32,
+    pub tax: f32,
+    pub gross: f32,
+}
+
+/// Costs of the days with the same VAT rate.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VatPeriodCosts {
+    #[serde(rename = "dateStart")]
+    pub date_start: NaiveDate,
+    #[serde(rename = "dateEnd")]
+    pub date_end: NaiveDate,
+    pub rate: f32,
+    pub net: f32,
+    pub tax: f32,
+    pub gross: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CostBreakdown {
+    #[serde(rename = "feeId")]
+    pub fee_id: i32,
+    #[serde(rename = "dateStart")]
+    pub date_start: NaiveDateTime,
+    #[serde(rename = "dateEnd")]
+    pub date_end: NaiveDateTime,
+    pub months: u32,
+    pub consumption: f32,
+    /// Part of the consumption charged at hourly spot prices, the rest at `price_per_unit`
+    #[serde(rename = "spotConsumption")]
+    pub spot_consumption: f32,
+    /// Consumption in the windows of a time-of-use tariff, the rest is charged at
+    /// `price_per_unit`
+    #[serde(rename = "tariffWindows")]
+    pub tariff_windows: Vec<TariffWindowCosts>,
+    /// Blocks of a tiered tariff, they apply to the consumption charged at `price_per_unit`
+    pub blocks: Vec<BlockCosts>,
+    /// Split of the total costs into the price components of the fee, empty if the fee
+    /// has none
+    pub components: Vec<ComponentCosts>,
+    /// Relief of the energy price brake, it is deducted from the costs and the advance
+    /// payments
+    #[serde(rename = "priceBrake")]
+    pub price_brake: Option<PriceBrakeCosts>,
+    /// One-off charges and credits, they are included in the total costs
+    pub adjustments: Vec<AdjustmentCosts>,
+    #[serde(rename = "baseCosts")]
+    pub base_costs: f32,
+    #[serde(rename = "consumptionCosts")]
+    pub consumption_costs: f32,
+    /// Gross costs, the advance payments are compared with them
+    #[serde(rename = "totalCosts")]
+    pub total_costs: f32,
+    #[serde(rename = "netCosts")]
+    pub net_costs: f32,
+    #[serde(rename = "taxCosts")]
+    pub tax_costs: f32,
+    /// Split of the costs at changes of the VAT rate, prorated by days
+    #[serde(rename = "vatPeriods")]
+    pub vat_periods: Vec<VatPeriodCosts>,
+    /// Sum of the recorded payments if there are any, otherwise the agreed monthly
+    /// advance for every month
+    #[serde(rename = "advancePayments")]
+    pub advance_payments: f32,
+    /// Number of recorded payments, 0 if the agreed advance is assumed
+    #[serde(rename = "recordedPayments")]
+    pub recorded_payments: usize,
+    /// Positive values are a credit, negative values have to be paid.
+    pub balance: f32,
+}
+
+/// Number of calendar months touched by the fee period, e.g. January to December is 12.
+pub fn billing_months(fee: &Fee) -> u32 {
+    let start = fee.date_start;
+    let end = fee.date_end;
+    if end < start {
+        return 0;
+    }
+
+    let months = (end.year() - start.year()) * 12 + end.month() as i32 - start.month() as i32;
+    (months + 1) as u32
+}
+
+/// Splits the consumption into the blocks of the fee, the thresholds are prorated to the
+/// period. Returns no blocks if the fee has none.
+pub fn block_costs(fee: &Fee, consumption: f32) -> Vec<BlockCosts> {
+    if fee.blocks.is_empty() {
+        return vec![];
+    }
+
+    let share = fee.year_share();
+    let mut lower = 0.0;
+    let mut blocks: Vec<BlockCosts> = fee
+        .blocks
+        .iter()
+        .map(|block| {
+            let upper = block.up_to * share;
+            let block_consumption = (consumption - lower).clamp(0.0, upper - lower);
+            lower = upper;
+            BlockCosts {
+                up_to: Some(upper),
+                price_per_unit: block.price_per_unit,
+                consumption: block_consumption,
+                costs: block_consumption * block.price_per_unit,
+            }
+        })
+        .collect();
+
+    let rest = (consumption - lower).max(0.0);
+    blocks.push(BlockCosts {
+        up_to: None,
+        price_per_unit: fee.price_per_unit,
+        consumption: rest,
+        costs: rest * fee.price_per_unit,
+    });
+    blocks
+}
+
+/// Relief of the months of the fee period within the period of the brake.
+pub fn price_brake_costs(fee: &Fee, brake: &PriceBrake) -> PriceBrakeCosts {
+    let month = |year: i32, month: u32| year * 12 + month as i32;
+    let first = month(fee.date_start.year(), fee.date_start.month())
+        .max(month(brake.date_start.year(), brake.date_start.month()));
+    let last = month(fee.date_end.year(), fee.date_end.month())
+        .min(month(brake.date_end.year(), brake.date_end.month()));
+    let months = (last - first + 1).max(0) as u32;
+    let monthly_relief = brake.monthly_relief(fee.price_per_unit);
+
+    PriceBrakeCosts {
+        months,
+        contingent: brake.forecast * brake.contingent / 100.0 / 12.0,
+        cap_price: brake.cap_price,
+        monthly_relief,
+        relief: monthly_relief * months as f32,
+        tax: 0.0,
+    }
+}
+
+/// Sums up the total costs and the balance again after a part of the costs changed.
+fn update_totals(breakdown: &mut CostBreakdown) {
+    let relief = breakdown
+        .price_brake
+        .as_ref()
+        .map_or(0.0, |brake| brake.relief);
+    let adjustments: f32 = breakdown
+        .adjustments
+        .iter()
+        .map(|adjustment| adjustment.gross)
+        .sum();
+    breakdown.total_costs =
+        breakdown.base_costs + breakdown.consumption_costs - relief + adjustments;
+    breakdown.balance = breakdown.advance_payments - breakdown.total_costs;
+}
+
+pub fn cost_breakdown(fee: &Fee, intervals: &[IntervalConsumption]) -> CostBreakdown {
+    let months = billing_months(fee);
+    let consumption: f32 = intervals
+        .iter()
+        .filter(|interval| interval.fee_id == fee.id)
+        .map(|interval| interval.consumption)
+        .sum();
+
+    let base_costs = fee.base_fee * months as f32;
+    let blocks = block_costs(fee, consumption);
+    let consumption_costs = match blocks.is_empty() {
+        true => fee.price_per_unit * consumption,
+        false => blocks.iter().map(|block| block.costs).sum(),
+    };
+    let price_brake = fee
+        .price_brake
+        .as_ref()
+        .map(|brake| price_brake_costs(fee, brake));
+    let relief = price_brake.as_ref().map_or(0.0, |brake| brake.relief);
+    let total_costs = base_costs + consumption_costs - relief;
+    let advance_payments = fee.monthly_discount * months as f32 - relief;
+
+    CostBreakdown {
+        fee_id: fee.id,
+        date_start: fee.date_start,
+        date_end: fee.date_end,
+        months,
+        consumption,
+        spot_consumption: 0.0,
+        tariff_windows: vec![],
+        blocks,
+        components: vec![],
+        price_brake,
+        adjustments: vec![],
+        base_costs,
+        consumption_costs,
+        total_costs,
+        net_costs: total_costs,
+        tax_costs: 0.0,
+        vat_periods: vec![],
+        advance_payments,
+        recorded_payments: 0,
+        balance: advance_payments - total_costs,
+    }
+}
+
+/// Charges the hours with consumption and price at the spot price plus the surcharge of
+/// the fee, the remaining consumption stays at `price_per_unit`.
+pub fn apply_spot_prices(
+    breakdown: &mut CostBreakdown,
+    fee: &Fee,
+    hourly: &[BucketConsumption],
+    prices: &[SpotPrice],
+) {
+    let surcharge = match fee.spot_surcharge {
+        Some(surcharge) => surcharge as f64,
+        None => return,
+    };
+
+    let mut spot_consumption = 0.0;
+    let mut spot_costs = 0.0;
+    for hour in hourly {
+        if let Ok(index) = prices.binary_search_by_key(&hour.date_start, |price| price.date_start) {
+            spot_consumption += hour.consumption;
+            spot_costs += hour.consumption * (prices[index].price + surcharge);
+        }
+    }
+
+    breakdown.spot_consumption = charge_partially(breakdown, fee, spot_consumption, spot_costs);
+}
+
+/// Charges the intervals in a window of the time-of-use tariff at the price of the window,
+/// the remaining consumption stays at `price_per_unit`. `to_local` converts the UTC
+/// interval start to the local time of the rules.
+pub fn apply_tariff_rules(
+    breakdown: &mut CostBreakdown,
+    fee: &Fee,
+    rules: &[TariffRule],
+    holidays: &[NaiveDate],
+    intervals: &[BucketConsumption],
+    to_local: impl Fn(&NaiveDateTime) -> NaiveDateTime,
+) {
+    let mut windows: Vec<TariffWindowCosts> = vec![];
+    let mut charged = 0.0;
+    let mut charged_costs = 0.0;
+    for interval in intervals {
+        let rule = match find_tariff_rule(rules, holidays, &to_local(&interval.date_start)) {
+            Some(rule) => rule,
+            None => continue,
+        };
+        let costs = interval.consumption * rule.price_per_unit as f64;
+        charged += interval.consumption;
+        charged_costs += costs;
+
+        let index = match windows.iter().position(|window| window.name == rule.name) {
+            Some(index) => index,
+            None => {
+                windows.push(TariffWindowCosts {
+                    name: rule.name.clone(),
+                    consumption: 0.0,
+                    costs: 0.0,
+                });
+                windows.len() - 1
+            }
+        };
+        windows[index].consumption += interval.consumption as f32;
+        windows[index].costs += costs as f32;
+    }
+
+    charge_partially(breakdown, fee, charged, charged_costs);
+    breakdown.tariff_windows = windows;
+}
+
+/// Charges `charged` kWh at `charged_costs` and the rest of the consumption at
+/// `price_per_unit` or the blocks of the fee, returns the charged consumption.
+fn charge_partially(
+    breakdown: &mut CostBreakdown,
+    fee: &Fee,
+    charged: f64,
+    charged_costs: f64,
+) -> f32 {
+    // interval data and meter readings may cover different parts of the period
+    let charged = (charged as f32).min(breakdown.consumption);
+    let flat_consumption = breakdown.consumption - charged;
+    breakdown.blocks = block_costs(fee, flat_consumption);
+    let flat_costs = match breakdown.blocks.is_empty() {
+        true => flat_consumption * fee.price_per_unit,
+        false => breakdown.blocks.iter().map(|block| block.costs).sum(),
+    };
+    breakdown.consumption_costs = charged_costs as f32 + flat_costs;
+    update_totals(breakdown);
+    charged
+}
+
+/// Adds the one-off charges and credits to the costs, their VAT is split by `apply_vat`.
+pub fn apply_adjustments(breakdown: &mut CostBreakdown, adjustments: &[Adjustment]) {
+    breakdown.adjustments = adjustments
+        .iter()
+        .map(|adjustment| AdjustmentCosts {
+            id: adjustment.id,
+            date: adjustment.date,
+            description: adjustment.description.clone(),
+            taxable: adjustment.taxable,
+            amount: adjustment.amount,
+            net: adjustment.amount,
+            tax: 0.0,
+            gross: adjustment.amount,
+        })
+        .collect();
+    update_totals(breakdown);
+}
+
+/// Net and tax of an amount at the rate, the amount is net or gross.
+fn split_vat(amount: f32, rate: f32, net_amount: bool) -> (f32, f32) {
+    match net_amount {
+        true => (amount, amount * rate / 100.0),
+        false => {
+            let net = amount * 100.0 / (100.0 + rate);
+            (net, amount - net)
+        }
+    }
+}
+
+/// Splits the costs into the periods of the VAT rates, prorated by days. The VAT is added
+/// to net prices and taken out of gross prices, days without a stored rate get `fallback`.
+/// Taxable adjustments are charged at the rate of their date, the others without VAT.
+pub fn apply_vat(breakdown: &mut CostBreakdown, fee: &Fee, rates: &[VatRate], fallback: f32) {
+    let date_start = fee.date_start.date();
+    let date_end = fee.date_end.date();
+    let days = |start: NaiveDate, end: NaiveDate| ((end - start).num_days() + 1).max(0) as f32;
+    let total_days = days(date_start, date_end);
+    if total_days == 0.0 {
+        return;
+    }
+
+    let adjustments: f32 = breakdown
+        .adjustments
+        .iter()
+        .map(|adjustment| adjustment.amount)
+        .sum();
+    let amount = breakdown.total_costs - adjustments;
+    breakdown.vat_periods = vat_periods(rates, fallback, date_start, date_end)
+        .into_iter()
+        .map(|(start, end, rate)| {
+            let share = amount * days(start, end) / total_days;
+            let (net, tax) = split_vat(share, rate, fee.net_prices);
+            VatPeriodCosts {
+                date_start: start,
+                date_end: end,
+                rate,
+                net,
+                tax,
+                gross: net + tax,
+            }
+        })
+        .collect();
+
+    let mut untaxed = 0.0;
+    for adjustment in &mut breakdown.adjustments {
+        let period = breakdown.vat_periods.iter_mut().find(|period| {
+            period.date_start <= adjustment.date && adjustment.date <= period.date_end
+        });
+        let (net, tax) = match (adjustment.taxable, period) {
+            (true, Some(period)) => {
+                let (net, tax) = split_vat(adjustment.amount, period.rate, fee.net_prices);
+                period.net += net;
+                period.tax += tax;
+                period.gross += net + tax;
+                (net, tax)
+            }
+            _ => {
+                untaxed += adjustment.amount;
+                (adjustment.amount, 0.0)
+            }
+        };
+        adjustment.net = net;
+        adjustment.tax = tax;
+        adjustment.gross = net + tax;
+    }
+
+    breakdown.net_costs = breakdown
+        .vat_periods
+        .iter()
+        .map(|period| period.net)
+        .sum::<f32>()
+        + untaxed;
+    breakdown.tax_costs = breakdown.vat_periods.iter().map(|period| period.tax).sum();
+    breakdown.total_costs = breakdown.net_costs + breakdown.tax_costs;
+
+    // the relief of net prices is net, the advance payments are gross
+    if let Some(brake) = breakdown.price_brake.as_mut() {
+        brake.tax = match fee.net_prices {
+            true => breakdown
+                .vat_periods
+                .iter()
+                .map(|period| {
+                    brake.relief * days(period.date_start, period.date_end) / total_days
+                        * period.rate
+                        / 100.0
+                })
+                .sum(),
+            false => 0.0,
+        };
+        if breakdown.recorded_payments == 0 {
+            breakdown.advance_payments =
+                fee.monthly_discount * breakdown.months as f32 - brake.relief - brake.tax;
+        }
+    }
+    breakdown.balance = breakdown.advance_payments - breakdown.total_costs;
+}
+
+/// Splits the total costs into the components. The VAT components share the tax costs by
+/// their rate, adjustments are listed with their net amount and the energy gets what is
+/// left of the net costs after the other components.
+pub fn apply_price_components(breakdown: &mut CostBreakdown, components: &[PriceComponent]) {
+    if components.is_empty() {
+        breakdown.components = vec![];
+        return;
+    }
+
+    let vat_rate: f32 = components
+        .iter()
+        .filter(|component| component.kind == ComponentKind::Vat)
+        .map(|component| component.rate)
+        .sum();
+
+    let mut costs = vec![ComponentCosts {
+        kind: ComponentKind::Energy,
+        name: ComponentKind::Energy.name().to_string(),
+        costs: 0.0,
+    }];
+    for component in components {
+        costs.push(ComponentCosts {
+            kind: component.kind,
+            name: component.name.clone(),
+            costs: match component.kind {
+                ComponentKind::Vat => breakdown.tax_costs * component.rate / vat_rate,
+                _ => {
+                    component.base_fee * breakdown.months as f32
+                        + component.price_per_unit * breakdown.consumption
+                }
+            },
+        });
+    }
+    for adjustment in &breakdown.adjustments {
+        costs.push(ComponentCosts {
+            kind: ComponentKind::Adjustment,
+            name: adjustment.description.clone(),
+            costs: adjustment.net,
+        });
+    }
+    if vat_rate == 0.0 && breakdown.tax_costs != 0.0 {
+        costs.push(ComponentCosts {
+            kind: ComponentKind::Vat,
+            name: ComponentKind::Vat.name().to_string(),
+            costs: breakdown.tax_costs,
+        });
+    }
+    let others: f32 = costs.iter().map(|component| component.costs).sum();
+    costs[0].costs = breakdown.total_costs - others;
+    breakdown.components = costs;
+}
+
+fn local_time(date: &NaiveDateTime) -> NaiveDateTime {
+    Local.from_utc_datetime(date).naive_local()
+}
+
+/// Start of the day after the last day of the fee, the last day is included whatever time
+/// the fee ends on it.
+fn period_end(fee: &Fee) -> NaiveDateTime {
+    let date = fee.date_end.date();
+    date.succ_opt()
+        .unwrap_or(date)
+        .and_hms_opt(0, 0, 0)
+        .unwrap()
+}
+
+/// UTC range `[start, end)` covering the days of the fee in `timezone`, the rules of
+/// time-of-use tariffs are evaluated in local time.
+fn local_period<Tz: TimeZone>(timezone: &Tz, fee: &Fee) -> (NaiveDateTime, NaiveDateTime) {
+    let to_utc = |date: NaiveDate| {
+        let midnight = date.and_hms_opt(0, 0, 0).unwrap();
+        match timezone.from_local_datetime(&midnight).earliest() {
+            Some(midnight) => midnight.naive_utc(),
+            None => midnight,
+        }
+    };
+    let first = timezone.from_utc_datetime(&fee.date_start).date_naive();
+    let last = timezone.from_utc_datetime(&fee.date_end).date_naive();
+    (to_utc(first), to_utc(last.succ_opt().unwrap_or(last)))
+}
+
+pub fn cost_breakdowns(fees: &[Fee], intervals: &[IntervalConsumption]) -> Vec<CostBreakdown> {
+    fees.iter()
+        .map(|fee| cost_breakdown(fee, intervals))
+        .collect()
+}
+
+/// Applies the stored spot prices or time-of-use rules to the breakdowns, `breakdowns` are
+/// in the order of `fees`. A dynamic tariff takes precedence over the rules of a fee.
+pub fn apply_stored_tariffs<R: Repository>(
+    repository: &mut R,
+    fees: &[Fee],
+    breakdowns: &mut [CostBreakdown],
+) -> Result<(), String> {
+    let mut holidays: Option<Vec<NaiveDate>> = None;
+    for (breakdown, fee) in breakdowns.iter_mut().zip(fees) {
+        if fee.spot_surcharge.is_some() {
+            let date_end = period_end(fee);
+            let hourly = load_bucket_consumption(
+                repository,
+                fee.meter_id,
+                Resolution::Hour,
+                &fee.date_start,
+                &date_end,
+            )?;
+            let prices = repository.list_spot_prices(&fee.date_start, &date_end)?;
+            apply_spot_prices(breakdown, fee, &hourly, &prices);
+            continue;
+        }
+
+        let rules = repository.list_tariff_rules(fee.id)?;
+        if rules.is_empty() {
+            continue;
+        }
+        let holidays = match &holidays {
+            Some(holidays) => holidays,
+            None => holidays.insert(
+                repository
+                    .list_holidays()?
+                    .into_iter()
+                    .map(|holiday| holiday.date)
+                    .collect(),
+            ),
+        };
+        let (date_start, date_end) = local_period(&Local, fee);
+        let quarter_hours = load_bucket_consumption(
+            repository,
+            fee.meter_id,
+            Resolution::QuarterHour,
+            &date_start,
+            &date_end,
+        )?;
+        apply_tariff_rules(breakdown, fee, &rules, holidays, &quarter_hours, local_time);
+    }
+    Ok(())
+}
+
+/// Settles the costs against the payments actually debited instead of the agreed advance.
+/// The debits already include the relief of a price brake.
+pub fn apply_payments(breakdown: &mut CostBreakdown, payments: &[Payment]) {
+    if payments.is_empty() {
+        return;
+    }
+    breakdown.advance_payments = payments.iter().map(|payment| payment.amount).sum();
+    breakdown.recorded_payments = payments.len();
+    breakdown.balance = breakdown.advance_payments - breakdown.total_costs;
+}
+
+/// Applies the stored tariffs, adjustments, VAT rates and payments and splits the costs
+/// into the stored price components, `breakdowns` are in the order of `fees`.
+pub fn apply_stored_prices<R: Repository>(
+    repository: &mut R,
+    fees: &[Fee],
+    breakdowns: &mut [CostBreakdown],
+) -> Result<(), String> {
+    apply_stored_tariffs(repository, fees, breakdowns)?;
+    let rates = repository.list_vat_rates()?;
+    for (breakdown, fee) in breakdowns.iter_mut().zip(fees) {
+        apply_stored_charges(repository, fee, breakdown, &rates)?;
+        let payments = repository.list_payments(Some(fee.id))?;
+        apply_payments(breakdown, &payments);
+    }
+    Ok(())
+}
+
+/// Applies the stored adjustments and VAT rates of the fee and splits the costs into its
+/// price components. The rate of a VAT component is used for days without a stored rate.
+pub fn apply_stored_charges<R: Repository>(
+    repository: &mut R,
+    fee: &Fee,
+    breakdown: &mut CostBreakdown,
+    rates: &[VatRate],
+) -> Result<(), String> {
+    let adjustments = repository.list_adjustments(fee.id)?;
+    apply_adjustments(breakdown, &adjustments);
+    let components = repository.list_price_components(fee.id)?;
+    let fallback = components
+        .iter()
+        .filter(|component| component.kind == ComponentKind::Vat)
+        .map(|component| component.rate)
+        .sum();
+    apply_vat(breakdown, fee, rates, fallback);
+    apply_price_components(breakdown, &components);
+    Ok(())
+}
+
+/// Cost breakdown of every stored fee period.
+pub fn load_cost_breakdowns<R: Repository>(
+    repository: &mut R,
+) -> Result<Vec<CostBreakdown>, String> {
+    let fees = repository.list_fees()?;
+    let readings = repository.list_meter_readings()?;
+    let intervals = interval_consumption(&readings);
+
+    let mut breakdowns = cost_breakdowns(&fees, &intervals);
+    apply_stored_prices(repository, &fees, &mut breakdowns)?;
+
+    Ok(breakdowns)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{FixedOffset, NaiveDate, NaiveTime};
+
+    use crate::calculation::consumption::interval_consumption;
+    use crate::calculation::intervals::BucketConsumption;
+    use crate::models::fees::Fee;
+    use crate::models::interval::IntervalSample;
+    use crate::models::meter_reading::MeterReading;
+    use crate::models::spot_price::SpotPrice;
+    use crate::models::tariff::{parse_weekdays, TariffRule};
+    use crate::models::vat::VatRate;
+    use crate::repository::{
+        FeeRepository, InMemoryRepository, IntervalRepository, SpotPriceRepository,
+    };
+
+    use super::{
+        apply_adjustments, apply_payments, apply_price_components, apply_spot_prices,
+        apply_stored_tariffs, apply_tariff_rules, apply_vat, billing_months, cost_breakdown,
+        local_period,
+    };
+    use crate::models::adjustment::Adjustment;
+    use crate::models::fees::{PriceBlock, PriceBrake};
+    use crate::models::payment::Payment;
+    use crate::models::price_component::{ComponentKind, PriceComponent};
+
+    fn fee() -> Fee {
+        Fee {
+            meter_id: 1,
+            id: 1,
+            base_fee: 10.0,
+            price_per_unit: 0.5,
+            monthly_discount: 45.0,
+            spot_surcharge: None,
+            blocks: vec![],
+            net_prices: false,
+            price_brake: None,
+            date_start: NaiveDate::from_ymd_opt(2022, 1, 1)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap(),
+            date_end: NaiveDate::from_ymd_opt(2022, 12, 31)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap(),
+        }
+    }
+
+    fn reading(id: i32, value: f32, month: u32) -> MeterReading {
+        MeterReading {
+            meter_id: 1,
+            id,
+            value,
+            fee: fee(),
+            date: NaiveDate::from_ymd_opt(2022, month, 1)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap(),
+        }
+    }
+
+    #[test]
+    fn months_of_fee_period() {
+        assert_eq!(billing_months(&fee()), 12);
+    }
+
+    #[test]
+    fn breakdown() {
+        let readings = vec![
+            reading(2, 1100.0, 6),
+            reading(1, 100.0, 1),
+            reading(3, 1300.0, 12),
+        ];
+        let intervals = interval_consumption(&readings);
+        assert_eq!(intervals.len(), 2);
+        assert_eq!(intervals[0].consumption, 1000.0);
+
+        let breakdown = cost_breakdown(&fee(), &intervals);
+        assert_eq!(breakdown.consumption, 1200.0);
+        assert_eq!(breakdown.base_costs, 120.0);
+        assert_eq!(breakdown.consumption_costs, 600.0);
+        assert_eq!(breakdown.advance_payments, 540.0);
+        assert_eq!(breakdown.balance, -180.0);
+    }
+
+    #[test]
+    fn spot_prices() {
+        let readings = vec![reading(1, 100.0, 1), reading(2, 1100.0, 6)];
+        let intervals = interval_consumption(&readings);
+        let fee = Fee {
+            spot_surcharge: Some(0.25),
+            ..fee()
+        };
+        let hour = |hour: u32| {
+            NaiveDate::from_ymd_opt(2022, 3, 1)
+                .unwrap()
+                .and_hms_opt(hour, 0, 0)
+                .unwrap()
+        };
+        let hourly = vec![
+            BucketConsumption {
+                date_start: hour(10),
+                consumption: 2.0,
+            },
+            BucketConsumption {
+                date_start: hour(11),
+                consumption: 4.0,
+            },
+            BucketConsumption {
+                date_start: hour(12),
+                consumption: 1.0,
+            },
+        ];
+        let prices = vec![
+            SpotPrice {
+                date_start: hour(10),
+                price: 0.05,
+            },
+            SpotPrice {
+                date_start: hour(11),
+                price: -0.25,
+            },
+        ];
+
+        let mut breakdown = cost_breakdown(&fee, &intervals);
+        apply_spot_prices(&mut breakdown, &fee, &hourly, &prices);
+        assert_eq!(breakdown.spot_consumption, 6.0);
+        // 2 kWh at 0.30, 4 kWh at 0.00 and 994 kWh at 0.50
+        assert_eq!(breakdown.consumption_costs, 497.6);
+        assert_eq!(breakdown.total_costs, 617.6);
+    }
+
+    #[test]
+    fn spot_prices_of_a_fee_ending_before_midnight() {
+        let mut repository = InMemoryRepository::new();
+        let fee = repository
+            .insert_fee(Fee {
+                spot_surcharge: Some(0.25),
+                date_end: NaiveDate::from_ymd_opt(2022, 12, 31)
+                    .unwrap()
+                    .and_hms_opt(23, 59, 59)
+                    .unwrap(),
+                ..fee()
+            })
+            .expect("failed to save fee");
+        let time = |day: NaiveDate, minute: u32| day.and_hms_opt(10, minute, 0).unwrap();
+        let last_day = NaiveDate::from_ymd_opt(2022, 12, 31).unwrap();
+        let next_day = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+        let samples = vec![
+            IntervalSample {
+                date: time(last_day, 0),
+                value: 100.0,
+            },
+            IntervalSample {
+                date: time(last_day, 45),
+                value: 102.0,
+            },
+            IntervalSample {
+                date: time(next_day, 0),
+                value: 200.0,
+            },
+            IntervalSample {
+                date: time(next_day, 45),
+                value: 204.0,
+            },
+        ];
+        repository
+            .insert_interval_samples(1, &samples)
+            .expect("failed to save samples");
+        let prices = [last_day, next_day].map(|day| SpotPrice {
+            date_start: time(day, 0),
+            price: 0.05,
+        });
+        repository
+            .upsert_spot_prices(&prices)
+            .expect("failed to save spot prices");
+
+        let readings = vec![reading(1, 100.0, 1), reading(2, 1100.0, 6)];
+        let mut breakdowns = vec![cost_breakdown(&fee, &interval_consumption(&readings))];
+        apply_stored_tariffs(&mut repository, &[fee], &mut breakdowns)
+            .expect("failed to apply tariffs");
+        // the hour of the following day is not charged
+        assert_eq!(breakdowns[0].spot_consumption, 2.0);
+    }
+
+    #[test]
+    fn tariff_windows() {
+        let readings = vec![reading(1, 100.0, 1), reading(2, 1100.0, 6)];
+        let intervals = interval_consumption(&readings);
+        let rule = |id: i32, name: &str, start: u32, end: u32, price: f32| TariffRule {
+            id,
+            fee_id: 1,
+            name: name.to_string(),
+            weekdays: parse_weekdays("1-5").unwrap(),
+            holidays: false,
+            time_start: NaiveTime::from_hms_opt(start, 0, 0).unwrap(),
+            time_end: NaiveTime::from_hms_opt(end, 0, 0).unwrap(),
+            price_per_unit: price,
+        };
+        let rules = vec![rule(1, "HT", 6, 22, 0.4), rule(2, "NT", 22, 6, 0.2)];
+        let quarter_hour = |day: u32, hour: u32, consumption: f64| BucketConsumption {
+            date_start: NaiveDate::from_ymd_opt(2022, 3, day)
+                .unwrap()
+                .and_hms_opt(hour, 0, 0)
+                .unwrap(),
+            consumption,
+        };
+        // monday noon and night, sunday noon has no window
+        let quarter_hours = vec![
+            quarter_hour(7, 12, 2.0),
+            quarter_hour(7, 23, 4.0),
+            quarter_hour(6, 12, 1.0),
+        ];
+
+        let mut breakdown = cost_breakdown(&fee(), &intervals);
+        apply_tariff_rules(
+            &mut breakdown,
+            &fee(),
+            &rules,
+            &[],
+            &quarter_hours,
+            |date| *date,
+        );
+        assert_eq!(breakdown.tariff_windows.len(), 2);
+        assert_eq!(breakdown.tariff_windows[1].name, "NT");
+        assert_eq!(breakdown.tariff_windows[1].consumption, 4.0);
+        // 2 kWh at 0.40, 4 kWh at 0.20 and 994 kWh at 0.50
+        assert_eq!(breakdown.consumption_costs, 498.6);
+    }
+
+    #[test]
+    fn local_days_of_fee() {
+        // fees are stored in UTC, local midnight of UTC+1 is 23:00 of the previous day
+        let utc = |year: i32, month: u32, day: u32, hour: u32| {
+            NaiveDate::from_ymd_opt(year, month, day)
+                .unwrap()
+                .and_hms_opt(hour, 0, 0)
+                .unwrap()
+        };
+        let fee = Fee {
+            date_start: utc(2021, 12, 31, 23),
+            date_end: utc(2022, 12, 30, 23),
+            ..fee()
+        };
+        let timezone = FixedOffset::east_opt(3600).unwrap();
+        assert_eq!(
+            local_period(&timezone, &fee),
+            (utc(2021, 12, 31, 23), utc(2022, 12, 31, 23))
+        );
+
+        // a fee ending at 23:59:59 local time ends at the same local midnight
+        let fee = Fee {
+            date_end: utc(2022, 12, 31, 22) + chrono::Duration::seconds(3599),
+            ..fee
+        };
+        assert_eq!(local_period(&timezone, &fee).1, utc(2022, 12, 31, 23));
+    }
+
+    #[test]
+    fn price_blocks() {
+        let block = |up_to: f32, price_per_unit: f32| PriceBlock {
+            up_to,
+            price_per_unit,
+        };
+        let fee = Fee {
+            blocks: vec![block(1000.0, 0.3), block(2000.0, 0.4)],
+            ..fee()
+        };
+        let readings = vec![reading(1, 100.0, 1), reading(2, 2600.0, 12)];
+        let breakdown = cost_breakdown(&fee, &interval_consumption(&readings));
+        // 1000 kWh at 0.30, 1000 kWh at 0.40 and 500 kWh at 0.50
+        assert_eq!(breakdown.blocks.len(), 3);
+        assert_eq!(breakdown.blocks[2].consumption, 500.0);
+        assert_eq!(breakdown.consumption_costs, 950.0);
+
+        // half a year halves the thresholds
+        let half_year = Fee {
+            date_end: NaiveDate::from_ymd_opt(2022, 6, 30)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap(),
+            ..fee
+        };
+        let breakdown = cost_breakdown(&half_year, &interval_consumption(&readings));
+        let threshold = breakdown.blocks[1].up_to.unwrap();
+        assert!((threshold - 991.78).abs() < 0.01);
+        assert!((breakdown.blocks[2].consumption - 1508.22).abs() < 0.01);
+    }
+
+    #[test]
+    fn price_components() {
+        let component =
+            |kind: ComponentKind, base_fee: f32, price_per_unit: f32, rate: f32| PriceComponent {
+                id: 0,
+                fee_id: 1,
+                kind,
+                name: kind.name().to_string(),
+                base_fee,
+                price_per_unit,
+                rate,
+            };
+        let components = vec![
+            component(ComponentKind::GridFee, 5.0, 0.1, 0.0),
+            component(ComponentKind::ElectricityTax, 0.0, 0.02, 0.0),
+            component(ComponentKind::Vat, 0.0, 0.0, 24.0),
+        ];
+        let readings = vec![reading(1, 100.0, 1), reading(2, 1100.0, 12)];
+        let mut breakdown = cost_breakdown(&fee(), &interval_consumption(&readings));
+        apply_vat(&mut breakdown, &fee(), &[], 24.0);
+        apply_price_components(&mut breakdown, &components);
+
+        // 620 gross are 120 VAT, 160 grid fee, 20 tax and 320 energy
+        let costs: Vec<f32> = breakdown.components.iter().map(|c| c.costs).collect();
+        assert_eq!(costs, vec![320.0, 160.0, 20.0, 120.0]);
+        assert_eq!(breakdown.components[0].kind, ComponentKind::Energy);
+    }
+
+    #[test]
+    fn vat_rate_change() {
+        let date = |month: u32, day: u32| NaiveDate::from_ymd_opt(2022, month, day).unwrap();
+        let rates = vec![VatRate {
+            id: 1,
+            date_start: date(7, 1),
+            date_end: None,
+            rate: 7.0,
+        }];
+        let fee = Fee {
+            net_prices: true,
+            ..fee()
+        };
+        let readings = vec![reading(1, 100.0, 1), reading(2, 1100.0, 12)];
+        let mut breakdown = cost_breakdown(&fee, &interval_consumption(&readings));
+        apply_vat(&mut breakdown, &fee, &rates, 19.0);
+
+        // 620 net, 181 days at 19 % and 184 days at 7 %
+        assert_eq!(breakdown.vat_periods.len(), 2);
+        assert_eq!(breakdown.vat_periods[1].date_start, date(7, 1));
+        assert!((breakdown.vat_periods[0].net - 307.45).abs() < 0.01);
+        assert!((breakdown.tax_costs - 80.30).abs() < 0.01);
+        assert!((breakdown.total_costs - 700.30).abs() < 0.01);
+        assert_eq!(breakdown.net_costs, 620.0);
+    }
+
+    #[test]
+    fn price_brake() {
+        let date = |month: u32, day: u32| NaiveDate::from_ymd_opt(2022, month, day).unwrap();
+        let fee = Fee {
+            price_brake: Some(PriceBrake {
+                forecast: 1200.0,
+                contingent: 80.0,
+                cap_price: 0.25,
+                date_start: date(3, 1),
+                date_end: date(12, 31),
+            }),
+            ..fee()
+        };
+        let readings = vec![reading(1, 100.0, 1), reading(2, 1100.0, 12)];
+        let breakdown = cost_breakdown(&fee, &interval_consumption(&readings));
+
+        // 80 kWh per month are 0.25 cheaper from march on
+        let brake = breakdown.price_brake.unwrap();
+        assert_eq!(brake.months, 10);
+        assert_eq!(brake.monthly_relief, 20.0);
+        assert_eq!(breakdown.total_costs, 420.0);
+        assert_eq!(breakdown.advance_payments, 340.0);
+        assert_eq!(breakdown.balance, -80.0);
+    }
+
+    #[test]
+    fn price_brake_of_net_prices() {
+        let date = |month: u32, day: u32| NaiveDate::from_ymd_opt(2022, month, day).unwrap();
+        let fee = Fee {
+            net_prices: true,
+            price_brake: Some(PriceBrake {
+                forecast: 1200.0,
+                contingent: 80.0,
+                cap_price: 0.25,
+                date_start: date(1, 1),
+                date_end: date(12, 31),
+            }),
+            ..fee()
+        };
+        let readings = vec![reading(1, 100.0, 1), reading(2, 1100.0, 12)];
+        let mut breakdown = cost_breakdown(&fee, &interval_consumption(&readings));
+        apply_vat(&mut breakdown, &fee, &[], 19.0);
+
+        // 240 net relief, the advance is lowered by the gross relief of 285.60
+        let brake = breakdown.price_brake.as_ref().unwrap();
+        assert_eq!(brake.relief, 240.0);
+        assert!((brake.tax - 45.6).abs() < 0.01);
+        assert!((breakdown.total_costs - 452.2).abs() < 0.01);
+        assert!((breakdown.advance_payments - 254.4).abs() < 0.01);
+        assert!((breakdown.balance + 197.8).abs() < 0.01);
+    }
+
+    #[test]
+    fn adjustments() {
+        let date = |month: u32, day: u32| NaiveDate::from_ymd_opt(2022, month, day).unwrap();
+        let adjustment = |id: i32, amount: f32, month: u32, taxable: bool| Adjustment {
+            id,
+            fee_id: 1,
+            amount,
+            date: date(month, 1),
+            description: format!("adjustment {}", id),
+            taxable,
+        };
+        let rates = vec![VatRate {
+            id: 1,
+            date_start: date(7, 1),
+            date_end: None,
+            rate: 7.0,
+        }];
+        let readings = vec![reading(1, 100.0, 1), reading(2, 1100.0, 12)];
+        let mut breakdown = cost_breakdown(&fee(), &interval_consumption(&readings));
+        // reminder fee with VAT and a bonus without
+        apply_adjustments(
+            &mut breakdown,
+            &[adjustment(1, 10.7, 8, true), adjustment(2, -50.0, 2, false)],
+        );
+        assert_eq!(breakdown.total_costs, 580.7);
+
+        apply_vat(&mut breakdown, &fee(), &rates, 19.0);
+        assert!((breakdown.adjustments[0].net - 10.0).abs() < 0.001);
+        assert_eq!(breakdown.adjustments[1].tax, 0.0);
+        assert!((breakdown.vat_periods[1].gross - 323.25).abs() < 0.01);
+        assert!((breakdown.total_costs - 580.7).abs() < 0.001);
+    }
+
+    #[test]
+    fn recorded_payments() {
+        let readings = vec![reading(1, 100.0, 1), reading(2, 1100.0, 12)];
+        let mut breakdown = cost_breakdown(&fee(), &interval_consumption(&readings));
+        apply_payments(&mut breakdown, &[]);
+        assert_eq!(breakdown.advance_payments, 540.0);
+
+        // the advance was raised for the last debit
+        let payments: Vec<Payment> = (1..=12)
+            .map(|month| Payment {
+                id: month as i32,
+                fee_id: 1,
+                date: NaiveDate::from_ymd_opt(2022, month, 15).unwrap(),
+                amount: if month == 12 { 60.0 } else { 45.0 },
+            })
+            .collect();
+        apply_payments(&mut breakdown, &payments);
+        assert_eq!(breakdown.recorded_payments, 12);
+        assert_eq!(breakdown.advance_payments, 555.0);
+        assert_eq!(breakdown.balance, -65.0);
+    }
+}
@@ -0,0 +1,88 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::models::interval::{IntervalBucket, Resolution};
+use crate::repository::Repository;
+
+/// Consumption of one bucket of interval data, e.g. for charts or hourly prices.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BucketConsumption {
+    #[serde(rename = "dateStart")]
+    pub date_start: NaiveDateTime,
+    pub consumption: f64,
+}
+
+/// A bucket ends with its last sample and starts where the bucket before it ended, so
+/// nothing is lost between buckets. The first bucket only counts its own samples.
+pub fn bucket_consumption(buckets: &[IntervalBucket]) -> Vec<BucketConsumption> {
+    let mut previous: Option<&IntervalBucket> = None;
+    buckets
+        .iter()
+        .map(|bucket| {
+            let start = match previous {
+                Some(previous) => previous.value_last,
+                None => bucket.value_first,
+            };
+            previous = Some(bucket);
+            BucketConsumption {
+                date_start: bucket.date_start,
+                consumption: bucket.value_last - start,
+            }
+        })
+        .collect()
+}
+
+pub fn load_bucket_consumption<R: Repository>(
+    repository: &mut R,
+    meter_id: i32,
+    resolution: Resolution,
+    date_start: &NaiveDateTime,
+    date_end: &NaiveDateTime,
+) -> Result<Vec<BucketConsumption>, String> {
+    let buckets = repository.list_interval_buckets(meter_id, resolution, date_start, date_end)?;
+    Ok(bucket_consumption(&buckets))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::interval::{IntervalSample, Resolution};
+    use crate::models::parse_datetime;
+    use crate::repository::{InMemoryRepository, IntervalRepository};
+
+    use super::load_bucket_consumption;
+
+    #[test]
+    fn hourly_consumption() {
+        let mut repository = InMemoryRepository::new();
+        let samples: Vec<IntervalSample> = [
+            ("2023-01-15T10:00:10.000Z", 100.0),
+            ("2023-01-15T10:59:50.000Z", 101.0),
+            ("2023-01-15T11:00:10.000Z", 101.1),
+            ("2023-01-15T11:59:50.000Z", 102.5),
+            ("2023-01-15T13:30:00.000Z", 103.5),
+        ]
+        .iter()
+        .map(|(date, value)| IntervalSample {
+            date: parse_datetime(date).unwrap(),
+            value: *value,
+        })
+        .collect();
+        repository.insert_interval_samples(1, &samples).unwrap();
+
+        let consumption = load_bucket_consumption(
+            &mut repository,
+            1,
+            Resolution::Hour,
+            &parse_datetime("2023-01-15T00:00:00.000Z").unwrap(),
+            &parse_datetime("2023-01-16T00:00:00.000Z").unwrap(),
+        )
+        .unwrap();
+
+        let values: Vec<f64> = consumption
+            .iter()
+            .map(|bucket| (bucket.consumption * 10.0).round() / 10.0)
+            .collect();
+        assert_eq!(values, vec![1.0, 1.5, 1.0]);
+        assert_eq!(consumption[2].date_start.to_string(), "2023-01-15 13:00:00");
+    }
+}
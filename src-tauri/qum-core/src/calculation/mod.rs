@@ -0,0 +1,5 @@
+pub mod advance;
+pub mod consumption;
+pub mod costs;
+pub mod intervals;
+pub mod reconciliation;
@@ -0,0 +1,373 @@
+//! Comparison of an invoice of the supplier with the costs computed from the own meter
+//! readings and the stored fee. The cost difference is split into the part explained by
+//! the billed consumption and the part caused by other prices.
+
+use chrono::{Duration, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+use crate::models::fees::Fee;
+use crate::models::invoice::Invoice;
+use crate::models::meter_reading::MeterReading;
+use crate::repository::Repository;
+
+use super::costs::{load_cost_breakdowns, CostBreakdown};
+
+/// kWh the billed and the own consumption may differ without a finding.
+pub const CONSUMPTION_TOLERANCE: f32 = 1.0;
+/// Euros the amounts may differ without a finding, invoices round every position.
+pub const AMOUNT_TOLERANCE: f32 = 1.0;
+/// Own readings further away from the period boundaries are mentioned, the consumption up
+/// to the boundary is interpolated over that time.
+pub const READING_GAP_DAYS: i64 = 14;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FindingKind {
+    #[serde(rename = "period")]
+    Period,
+    #[serde(rename = "consumption")]
+    Consumption,
+    #[serde(rename = "price")]
+    Price,
+    #[serde(rename = "payments")]
+    Payments,
+}
+
+/// Difference between the invoice and the computation with its likely cause.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Finding {
+    pub kind: FindingKind,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InvoiceReconciliation {
+    pub invoice: Invoice,
+    /// Own meter values interpolated at the start and the end of the billing period
+    #[serde(rename = "readingStart")]
+    pub reading_start: Option<f32>,
+    #[serde(rename = "readingEnd")]
+    pub reading_end: Option<f32>,
+    /// Consumption between the own meter values, the consumption of the fee period if the
+    /// readings don't cover the billing period
+    pub consumption: f32,
+    /// Average gross price per kWh of the computation
+    #[serde(rename = "unitPrice")]
+    pub unit_price: f32,
+    /// Gross price per kWh of the invoice, assuming the computed base costs
+    #[serde(rename = "billedUnitPrice")]
+    pub billed_unit_price: Option<f32>,
+    /// Computed gross costs of `consumption`
+    pub costs: f32,
+    /// Recorded payments or the agreed advance
+    #[serde(rename = "advancePayments")]
+    pub advance_payments: f32,
+    pub balance: f32,
+    /// Billed minus own consumption
+    #[serde(rename = "consumptionDifference")]
+    pub consumption_difference: f32,
+    /// Billed minus computed costs
+    #[serde(rename = "costDifference")]
+    pub cost_difference: f32,
+    /// Part of the cost difference caused by the consumption difference
+    #[serde(rename = "consumptionEffect")]
+    pub consumption_effect: f32,
+    /// Rest of the cost difference, caused by other prices
+    #[serde(rename = "priceEffect")]
+    pub price_effect: f32,
+    #[serde(rename = "advanceDifference")]
+    pub advance_difference: f32,
+    #[serde(rename = "balanceDifference")]
+    pub balance_difference: f32,
+    /// Empty if the invoice matches the computation
+    pub findings: Vec<Finding>,
+}
+
+/// Own meter value at `date`, interpolated between the readings around it, and the days
+/// to the nearer of them. `None` if no reading lies before or after `date`.
+pub fn meter_value_at(readings: &[MeterReading], date: &NaiveDateTime) -> Option<(f32, i64)> {
+    let before = readings
+        .iter()
+        .filter(|reading| reading.date <= *date)
+        .max_by_key(|reading| reading.date)?;
+    let after = readings
+        .iter()
+        .filter(|reading| reading.date >= *date)
+        .min_by_key(|reading| reading.date)?;
+
+    let gap = (*date - before.date).min(after.date - *date).num_days();
+    let span = (after.date - before.date).num_seconds();
+    if span == 0 {
+        return Some((before.value, gap));
+    }
+    let share = (*date - before.date).num_seconds() as f32 / span as f32;
+    Some((before.value + (after.value - before.value) * share, gap))
+}
+
+pub fn reconcile(
+    invoice: &Invoice,
+    fee: &Fee,
+    breakdown: &CostBreakdown,
+    readings: &[MeterReading],
+) -> InvoiceReconciliation {
+    let mut findings = vec![];
+    let mut finding = |kind: FindingKind, message: String| findings.push(Finding { kind, message });
+
+    let period_start = invoice.date_start.and_hms_opt(0, 0, 0).unwrap_or_default();
+    // the last day belongs to the period
+    let period_end =
+        period_start + Duration::days((invoice.date_end - invoice.date_start).num_days() + 1);
+    if invoice.date_start != fee.date_start.date() || invoice.date_end != fee.date_end.date() {
+        finding(
+            FindingKind::Period,
+            format!(
+                "the billing period {} - {} differs from the fee period {} - {}, the base costs cover other months",
+                invoice.date_start,
+                invoice.date_end,
+                fee.date_start.date(),
+                fee.date_end.date()
+            ),
+        );
+    }
+
+    let start = meter_value_at(readings, &period_start);
+    let end = meter_value_at(readings, &period_end);
+    let consumption = match (start, end) {
+        (Some((start, _)), Some((end, _))) => end - start,
+        _ => breakdown.consumption,
+    };
+
+    // costs of the breakdown moved to the consumption of the billing period
+    let gross_factor = match fee.net_prices && breakdown.net_costs != 0.0 {
+        true => 1.0 + breakdown.tax_costs / breakdown.net_costs,
+        false => 1.0,
+    };
+    let unit_price = match breakdown.consumption {
+        kwh if kwh > 0.0 => breakdown.consumption_costs / kwh * gross_factor,
+        _ => fee.price_per_unit * gross_factor,
+    };
+    let costs = breakdown.total_costs + (consumption - breakdown.consumption) * unit_price;
+    let fixed_costs = costs - consumption * unit_price;
+    let billed_unit_price = match invoice.consumption {
+        kwh if kwh > 0.0 => Some((invoice.gross_amount - fixed_costs) / kwh),
+        _ => None,
+    };
+
+    let consumption_difference = invoice.consumption - consumption;
+    let cost_difference = invoice.gross_amount - costs;
+    let consumption_effect = consumption_difference * unit_price;
+    let price_effect = cost_difference - consumption_effect;
+    let advance_difference = invoice.advance_payments - breakdown.advance_payments;
+    let balance = breakdown.advance_payments - costs;
+
+    if consumption_difference.abs() > CONSUMPTION_TOLERANCE {
+        finding(
+            FindingKind::Consumption,
+            format!(
+                "billed {:.0} kWh, the own readings give {:.0} kWh ({:+.0} kWh, {:+.2} €)",
+                invoice.consumption, consumption, consumption_difference, consumption_effect
+            ),
+        );
+        if invoice.estimated {
+            finding(
+                FindingKind::Consumption,
+                "the invoice is based on an estimated meter value, the difference is settled with the next actual reading".to_string(),
+            );
+        }
+        match (start, end) {
+            (Some((_, start_gap)), Some((_, end_gap))) => {
+                for (gap, boundary) in [(start_gap, "start"), (end_gap, "end")] {
+                    if gap > READING_GAP_DAYS {
+                        finding(
+                            FindingKind::Consumption,
+                            format!(
+                                "the nearest own reading is {} days away from the {} of the billing period, the consumption up to it is interpolated",
+                                gap, boundary
+                            ),
+                        );
+                    }
+                }
+            }
+            _ => finding(
+                FindingKind::Consumption,
+                "the own readings don't cover the billing period, the consumption of the fee period is compared".to_string(),
+            ),
+        }
+        if !invoice.estimated {
+            let values = [
+                (invoice.reading_start, start, "start"),
+                (invoice.reading_end, end, "end"),
+            ];
+            for (billed, own, boundary) in values {
+                if let (Some(billed), Some((own, _))) = (billed, own) {
+                    if (billed - own).abs() > CONSUMPTION_TOLERANCE {
+                        finding(
+                            FindingKind::Consumption,
+                            format!(
+                                "the billed meter value at the {} ({:.1}) differs from the own reading ({:.1}), the meter may have been misread or changed",
+                                boundary, billed, own
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    if price_effect.abs() > AMOUNT_TOLERANCE {
+        let billed = billed_unit_price
+            .map(|price| {
+                format!(
+                    ", about {:.4} €/kWh billed instead of {:.4} €/kWh",
+                    price, unit_price
+                )
+            })
+            .unwrap_or_default();
+        finding(
+            FindingKind::Price,
+            format!(
+                "the costs differ by {:+.2} € beyond the consumption{}, the prices may have changed during the period or price components, adjustments or the price brake are missing",
+                price_effect, billed
+            ),
+        );
+    }
+
+    if advance_difference.abs() > AMOUNT_TOLERANCE {
+        let source = match breakdown.recorded_payments {
+            0 => "agreed",
+            _ => "recorded",
+        };
+        finding(
+            FindingKind::Payments,
+            format!(
+                "the billed advance payments ({:.2} €) differ from the {} ones ({:.2} €), a debit may be missing in the payment ledger or have been returned",
+                invoice.advance_payments, source, breakdown.advance_payments
+            ),
+        );
+    }
+
+    InvoiceReconciliation {
+        invoice: invoice.clone(),
+        reading_start: start.map(|(value, _)| value),
+        reading_end: end.map(|(value, _)| value),
+        consumption,
+        unit_price,
+        billed_unit_price,
+        costs,
+        advance_payments: breakdown.advance_payments,
+        balance,
+        consumption_difference,
+        cost_difference,
+        consumption_effect,
+        price_effect,
+        advance_difference,
+        balance_difference: invoice.balance - balance,
+        findings,
+    }
+}
+
+/// Reconciliation of the stored invoice with the cost breakdown of its fee.
+pub fn load_reconciliation<R: Repository>(
+    repository: &mut R,
+    invoice_id: i32,
+) -> Result<InvoiceReconciliation, String> {
+    let invoice = match repository.find_invoice(invoice_id)? {
+        Some(invoice) => invoice,
+        None => return Err(format!("invoice {} not found", invoice_id)),
+    };
+    let fee = match repository.find_fee(invoice.fee_id)? {
+        Some(fee) => fee,
+        None => return Err(format!("fee {} not found", invoice.fee_id)),
+    };
+    let breakdown = load_cost_breakdowns(repository)?
+        .into_iter()
+        .find(|breakdown| breakdown.fee_id == fee.id)
+        .ok_or_else(|| format!("no costs computed for fee {}", fee.id))?;
+    let readings: Vec<_> = repository
+        .list_meter_readings()?
+        .into_iter()
+        .filter(|reading| reading.meter_id == fee.meter_id)
+        .collect();
+
+    Ok(reconcile(&invoice, &fee, &breakdown, &readings))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::calculation::consumption::interval_consumption;
+    use crate::calculation::costs::cost_breakdown;
+    use crate::models::fees::Fee;
+    use crate::models::invoice::Invoice;
+    use crate::models::meter_reading::MeterReading;
+
+    use super::{reconcile, FindingKind};
+
+    #[test]
+    fn estimated_invoice() {
+        let date =
+            |year: i32, month: u32, day: u32| NaiveDate::from_ymd_opt(year, month, day).unwrap();
+        let fee = Fee {
+            meter_id: 1,
+            id: 1,
+            base_fee: 10.0,
+            price_per_unit: 0.5,
+            monthly_discount: 45.0,
+            spot_surcharge: None,
+            blocks: vec![],
+            net_prices: false,
+            price_brake: None,
+            date_start: date(2022, 1, 1).and_hms_opt(0, 0, 0).unwrap(),
+            date_end: date(2022, 12, 31).and_hms_opt(0, 0, 0).unwrap(),
+        };
+        let reading = |id: i32, value: f32, day: NaiveDate| MeterReading {
+            meter_id: 1,
+            id,
+            value,
+            fee: fee.clone(),
+            date: day.and_hms_opt(0, 0, 0).unwrap(),
+        };
+        let readings = vec![
+            reading(1, 100.0, date(2022, 1, 1)),
+            reading(2, 1100.0, date(2023, 1, 1)),
+        ];
+        let breakdown = cost_breakdown(&fee, &interval_consumption(&readings));
+        // 100 kWh too much and 20 € more than the prices of the fee
+        let invoice = Invoice {
+            id: 1,
+            fee_id: 1,
+            number: "2022-4711".to_string(),
+            date_start: date(2022, 1, 1),
+            date_end: date(2022, 12, 31),
+            consumption: 1100.0,
+            reading_start: Some(100.0),
+            reading_end: Some(1200.0),
+            estimated: true,
+            net_amount: 579.83,
+            tax_amount: 110.17,
+            gross_amount: 690.0,
+            advance_payments: 540.0,
+            balance: -150.0,
+        };
+
+        let reconciliation = reconcile(&invoice, &fee, &breakdown, &readings);
+        assert_eq!(reconciliation.consumption, 1000.0);
+        assert_eq!(reconciliation.costs, 620.0);
+        assert_eq!(reconciliation.consumption_effect, 50.0);
+        assert_eq!(reconciliation.price_effect, 20.0);
+        assert_eq!(reconciliation.balance_difference, -70.0);
+        let kinds: Vec<FindingKind> = reconciliation
+            .findings
+            .iter()
+            .map(|finding| finding.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                FindingKind::Consumption,
+                FindingKind::Consumption,
+                FindingKind::Price
+            ]
+        );
+    }
+}
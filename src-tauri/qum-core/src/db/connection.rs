@@ -0,0 +1,227 @@
+use std::fs;
+use std::path::Path;
+
+use rusqlite::{Connection, Result};
+use rusqlite_migration::{Migrations, M};
+
+/// Path of the database in the user's home, `~/.qum/qum.db`. The folder is created if missing.
+pub fn database_file() -> String {
+    let home_dir = dirs::home_dir();
+    let home_dir = match home_dir {
+        Some(dir) => dir,
+        None => panic!("Can not read home dir"),
+    };
+
+    let home_dir_str = home_dir.into_os_string().into_string();
+    let home_dir_str = match home_dir_str {
+        Ok(str) => str,
+        Err(_) => panic!("Can not read home dir"),
+    };
+
+    let database_folder = Path::new(home_dir_str.as_str()).join(".qum");
+    fs::create_dir_all(&database_folder).expect("Can not create .qum folder");
+
+    let database_folder = match database_folder.into_os_string().into_string() {
+        Ok(path) => path,
+        Err(_) => panic!("Can not read database_folder"),
+    };
+
+    let database_file = Path::new(database_folder.as_str())
+        .join("qum")
+        .with_extension("db");
+    match database_file.into_os_string().into_string() {
+        Ok(path) => path,
+        Err(_) => panic!("Can not read database file"),
+    }
+}
+
+pub fn establish_connection(db_path: String) -> Result<Connection> {
+    Connection::open(db_path)
+}
+
+/// Brings the schema to the latest version.
+pub fn run_migrations(conn: &mut Connection) -> std::result::Result<(), String> {
+    let migrations = Migrations::new(vec![
+        M::up(
+            "CREATE TABLE fees (
+        id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+        base_fee REAL NOT NULL,
+        price_per_unit REAL NOT NULL,
+        monthly_discount REAL NOT NULL,
+        date_start DATETIME NOT NULL,
+        date_end DATETIME NOT NULL
+      )",
+        ),
+        M::up(
+            "CREATE TABLE meter_readings (
+        id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+        value REAL NOT NULL,
+        fee_id INTEGER NOT NULL,
+          reading_date DATETIME NOT NULL,
+          FOREIGN KEY (fee_id)
+            REFERENCES fees (id)
+      )",
+        ),
+        M::up(
+            "CREATE TABLE interval_samples (
+        id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+        sample_date DATETIME NOT NULL,
+        value REAL NOT NULL
+      );
+      CREATE UNIQUE INDEX interval_samples_date ON interval_samples (sample_date);
+      CREATE TABLE interval_buckets (
+        resolution TEXT NOT NULL,
+        date_start DATETIME NOT NULL,
+        date_first DATETIME NOT NULL,
+        value_first REAL NOT NULL,
+        date_last DATETIME NOT NULL,
+        value_last REAL NOT NULL,
+        samples INTEGER NOT NULL,
+        PRIMARY KEY (resolution, date_start)
+      )",
+        ),
+        M::up(
+            "ALTER TABLE fees ADD COLUMN spot_surcharge REAL;
+      CREATE TABLE spot_prices (
+        date_start DATETIME NOT NULL PRIMARY KEY,
+        price REAL NOT NULL
+      )",
+        ),
+        M::up(
+            "CREATE TABLE tariff_rules (
+        id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+        fee_id INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        weekdays TEXT NOT NULL,
+        holidays INTEGER NOT NULL,
+        time_start TEXT NOT NULL,
+        time_end TEXT NOT NULL,
+        price_per_unit REAL NOT NULL,
+        FOREIGN KEY (fee_id)
+          REFERENCES fees (id)
+      );
+      CREATE TABLE holidays (
+        date DATE NOT NULL PRIMARY KEY,
+        name TEXT NOT NULL
+      )",
+        ),
+        M::up("ALTER TABLE fees ADD COLUMN price_blocks TEXT"),
+        M::up(
+            "CREATE TABLE price_components (
+        id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+        fee_id INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        name TEXT NOT NULL,
+        base_fee REAL NOT NULL,
+        price_per_unit REAL NOT NULL,
+        rate REAL NOT NULL,
+        FOREIGN KEY (fee_id)
+          REFERENCES fees (id)
+      )",
+        ),
+        M::up(
+            "ALTER TABLE fees ADD COLUMN net_prices INTEGER NOT NULL DEFAULT 0;
+      CREATE TABLE vat_rates (
+        id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+        date_start DATE NOT NULL,
+        date_end DATE,
+        rate REAL NOT NULL
+      )",
+        ),
+        M::up("ALTER TABLE fees ADD COLUMN price_brake TEXT"),
+        M::up(
+            "CREATE TABLE adjustments (
+        id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+        fee_id INTEGER NOT NULL,
+        amount REAL NOT NULL,
+        date DATE NOT NULL,
+        description TEXT NOT NULL,
+        taxable INTEGER NOT NULL,
+        FOREIGN KEY (fee_id)
+          REFERENCES fees (id)
+      )",
+        ),
+        M::up(
+            "CREATE TABLE payments (
+        id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+        fee_id INTEGER NOT NULL,
+        date DATE NOT NULL,
+        amount REAL NOT NULL,
+        FOREIGN KEY (fee_id)
+          REFERENCES fees (id)
+      )",
+        ),
+        M::up(
+            "CREATE TABLE payment_rules (
+        id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+        creditor_id TEXT,
+        text TEXT
+      )",
+        ),
+        M::up(
+            "CREATE TABLE invoices (
+        id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+        fee_id INTEGER NOT NULL,
+        number TEXT NOT NULL,
+        date_start DATE NOT NULL,
+        date_end DATE NOT NULL,
+        consumption REAL NOT NULL,
+        reading_start REAL,
+        reading_end REAL,
+        estimated INTEGER NOT NULL,
+        net_amount REAL NOT NULL,
+        tax_amount REAL NOT NULL,
+        gross_amount REAL NOT NULL,
+        advance_payments REAL NOT NULL,
+        balance REAL NOT NULL,
+        FOREIGN KEY (fee_id)
+          REFERENCES fees (id)
+      )",
+        ),
+        M::up(
+            "CREATE TABLE meters (
+        id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        external_id TEXT
+      );
+      INSERT INTO meters (id, name, kind) VALUES (1, 'Strom', 'consumption');
+      ALTER TABLE fees ADD COLUMN meter_id INTEGER NOT NULL DEFAULT 1;
+      ALTER TABLE meter_readings ADD COLUMN meter_id INTEGER NOT NULL DEFAULT 1;
+      CREATE TABLE interval_samples_by_meter (
+        id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+        meter_id INTEGER NOT NULL,
+        sample_date DATETIME NOT NULL,
+        value REAL NOT NULL,
+        FOREIGN KEY (meter_id)
+          REFERENCES meters (id)
+      );
+      INSERT INTO interval_samples_by_meter (meter_id, sample_date, value)
+        SELECT 1, sample_date, value FROM interval_samples;
+      DROP TABLE interval_samples;
+      ALTER TABLE interval_samples_by_meter RENAME TO interval_samples;
+      CREATE UNIQUE INDEX interval_samples_date ON interval_samples (meter_id, sample_date);
+      CREATE TABLE interval_buckets_by_meter (
+        meter_id INTEGER NOT NULL,
+        resolution TEXT NOT NULL,
+        date_start DATETIME NOT NULL,
+        date_first DATETIME NOT NULL,
+        value_first REAL NOT NULL,
+        date_last DATETIME NOT NULL,
+        value_last REAL NOT NULL,
+        samples INTEGER NOT NULL,
+        PRIMARY KEY (meter_id, resolution, date_start),
+        FOREIGN KEY (meter_id)
+          REFERENCES meters (id)
+      );
+      INSERT INTO interval_buckets_by_meter
+        SELECT 1, resolution, date_start, date_first, value_first, date_last, value_last, samples
+        FROM interval_buckets;
+      DROP TABLE interval_buckets;
+      ALTER TABLE interval_buckets_by_meter RENAME TO interval_buckets",
+        ),
+        M::up("ALTER TABLE meters ADD COLUMN samples_pruned_before DATETIME"),
+    ]);
+
+    migrations.to_latest(conn).map_err(|err| err.to_string())
+}
@@ -0,0 +1,109 @@
+use std::io::Write;
+
+use super::{cost_values, ExportData, ExportKind, ExportLocale};
+
+pub fn write<W: Write>(
+    writer: W,
+    data: &ExportData,
+    kind: ExportKind,
+    locale: ExportLocale,
+) -> Result<(), String> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(locale.csv_delimiter())
+        .from_writer(writer);
+
+    writer
+        .write_record(locale.labels(kind))
+        .map_err(|err| err.to_string())?;
+
+    let period = |fee_id: i32| match data.fee(fee_id) {
+        Some(fee) => data.period_name(locale, fee),
+        None => String::new(),
+    };
+
+    let records: Vec<Vec<String>> = match kind {
+        ExportKind::Readings => data
+            .readings
+            .iter()
+            .map(|reading| {
+                vec![
+                    locale.format_date(&reading.date),
+                    locale.format_number(reading.value, 2),
+                    period(reading.fee.id),
+                ]
+            })
+            .collect(),
+        ExportKind::Consumption => data
+            .intervals
+            .iter()
+            .map(|interval| {
+                vec![
+                    locale.format_date(&interval.date_start),
+                    locale.format_date(&interval.date_end),
+                    locale.format_number(interval.value_start, 2),
+                    locale.format_number(interval.value_end, 2),
+                    locale.format_number(interval.consumption, 2),
+                    period(interval.fee_id),
+                ]
+            })
+            .collect(),
+        ExportKind::Costs => data
+            .costs
+            .iter()
+            .map(|costs| {
+                let mut record = vec![period(costs.fee_id), costs.months.to_string()];
+                record.extend(
+                    cost_values(costs)
+                        .into_iter()
+                        .map(|value| locale.format_number(value, 2)),
+                );
+                record
+            })
+            .collect(),
+    };
+
+    for record in records {
+        writer.write_record(record).map_err(|err| err.to_string())?;
+    }
+
+    writer.flush().map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use crate::db::connection::run_migrations;
+    use crate::export::{ExportData, ExportKind, ExportLocale};
+    use crate::repository::SqliteRepository;
+
+    use super::write;
+
+    #[test]
+    fn german_costs() {
+        let mut conn = Connection::open_in_memory().expect("could not create memory database");
+        run_migrations(&mut conn).expect("failed to run migrations");
+        conn.execute("INSERT INTO fees (base_fee, price_per_unit, monthly_discount, date_start, date_end) VALUES (?1, ?2, ?3, ?4, ?5)",
+                 (10.0, 0.5, 45.0, "2022-01-01T00:00:00.000Z", "2022-12-31T00:00:00.000Z")).expect("failed to save fee");
+        conn.execute("INSERT INTO meter_readings (value, fee_id, reading_date) VALUES (?1, 1, ?2), (?3, 1, ?4)",
+                 (100.0, "2022-01-01T00:00:00.000Z", 1300.5, "2022-12-31T00:00:00.000Z")).expect("failed to save readings");
+        conn.execute(
+            "INSERT INTO vat_rates (date_start, rate) VALUES ('2020-01-01', 19.0)",
+            [],
+        )
+        .expect("failed to save VAT rate");
+
+        let data =
+            ExportData::load(&mut SqliteRepository::new(&mut conn)).expect("failed to load data");
+        let mut output: Vec<u8> = vec![];
+        write(&mut output, &data, ExportKind::Costs, ExportLocale::De).expect("failed to write");
+
+        let output = String::from_utf8(output).expect("invalid utf8");
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[1],
+            "01.01.2022 - 31.12.2022;12;1200,50;120,00;600,25;0,00;0,00;605,25;115,00;720,25;540,00;-180,25"
+        );
+    }
+}
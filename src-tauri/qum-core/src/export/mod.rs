@@ -0,0 +1,204 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::calculation::consumption::{interval_consumption, IntervalConsumption};
+use crate::calculation::costs::{apply_stored_prices, cost_breakdowns, CostBreakdown};
+use crate::models::fees::Fee;
+use crate::models::meter::Meter;
+use crate::models::meter_reading::MeterReading;
+use crate::repository::Repository;
+
+pub mod csv_export;
+pub mod xlsx_export;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportLocale {
+    #[serde(rename = "de")]
+    De,
+    #[serde(rename = "en")]
+    En,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportKind {
+    #[serde(rename = "readings")]
+    Readings,
+    #[serde(rename = "consumption")]
+    Consumption,
+    #[serde(rename = "costs")]
+    Costs,
+}
+
+impl ExportLocale {
+    pub fn csv_delimiter(&self) -> u8 {
+        match self {
+            ExportLocale::De => b';',
+            ExportLocale::En => b',',
+        }
+    }
+
+    pub fn format_date(&self, date: &NaiveDateTime) -> String {
+        match self {
+            ExportLocale::De => date.format("%d.%m.%Y").to_string(),
+            ExportLocale::En => date.format("%Y-%m-%d").to_string(),
+        }
+    }
+
+    pub fn format_number(&self, value: f32, decimals: usize) -> String {
+        // no "-0,00" for empty sums and negated zeros
+        let value = if value == 0.0 { 0.0 } else { value };
+        let formatted = format!("{:.*}", decimals, value);
+        match self {
+            ExportLocale::De => formatted.replace('.', ","),
+            ExportLocale::En => formatted,
+        }
+    }
+
+    /// Excel number format for dates, the decimal separator is taken from the reader's system.
+    pub fn excel_date_format(&self) -> &'static str {
+        match self {
+            ExportLocale::De => "dd.mm.yyyy",
+            ExportLocale::En => "yyyy-mm-dd",
+        }
+    }
+
+    pub fn period_name(&self, fee: &Fee) -> String {
+        format!(
+            "{} - {}",
+            self.format_date(&fee.date_start),
+            self.format_date(&fee.date_end)
+        )
+    }
+
+    pub fn labels(&self, kind: ExportKind) -> Vec<&'static str> {
+        match (self, kind) {
+            (ExportLocale::De, ExportKind::Readings) => vec!["Datum", "Zählerstand", "Periode"],
+            (ExportLocale::En, ExportKind::Readings) => vec!["Date", "Meter reading", "Period"],
+            (ExportLocale::De, ExportKind::Consumption) => {
+                vec![
+                    "Von",
+                    "Bis",
+                    "Stand Beginn",
+                    "Stand Ende",
+                    "Verbrauch",
+                    "Periode",
+                ]
+            }
+            (ExportLocale::En, ExportKind::Consumption) => {
+                vec![
+                    "From",
+                    "To",
+                    "Start value",
+                    "End value",
+                    "Consumption",
+                    "Period",
+                ]
+            }
+            (ExportLocale::De, ExportKind::Costs) => vec![
+                "Periode",
+                "Monate",
+                "Verbrauch",
+                "Grundpreis",
+                "Verbrauchskosten",
+                "Entlastung",
+                "Anpassungen",
+                "Netto",
+                "USt",
+                "Gesamtkosten",
+                "Abschläge",
+                "Saldo",
+            ],
+            (ExportLocale::En, ExportKind::Costs) => vec![
+                "Period",
+                "Months",
+                "Consumption",
+                "Base costs",
+                "Consumption costs",
+                "Relief",
+                "Adjustments",
+                "Net",
+                "VAT",
+                "Total costs",
+                "Advance payments",
+                "Balance",
+            ],
+        }
+    }
+}
+
+/// Columns of the costs export after the period and the months. The costs up to the
+/// adjustments are net or gross like the prices of the fee and add up to the net or the
+/// total costs, the net costs and the VAT add up to the total costs.
+pub fn cost_values(costs: &CostBreakdown) -> Vec<f32> {
+    let relief = costs.price_brake.as_ref().map_or(0.0, |brake| brake.relief);
+    let adjustments = costs
+        .adjustments
+        .iter()
+        .map(|adjustment| adjustment.amount)
+        .sum();
+    vec![
+        costs.consumption,
+        costs.base_costs,
+        costs.consumption_costs,
+        -relief,
+        adjustments,
+        costs.net_costs,
+        costs.tax_costs,
+        costs.total_costs,
+        costs.advance_payments,
+        costs.balance,
+    ]
+}
+
+/// Everything an export needs, loaded once from the database.
+pub struct ExportData {
+    pub meters: Vec<Meter>,
+    pub fees: Vec<Fee>,
+    pub readings: Vec<MeterReading>,
+    pub intervals: Vec<IntervalConsumption>,
+    pub costs: Vec<CostBreakdown>,
+}
+
+impl ExportData {
+    pub fn load<R: Repository>(repository: &mut R) -> Result<ExportData, String> {
+        let meters = repository.list_meters()?;
+        let fees = repository.list_fees()?;
+        let mut readings = repository.list_meter_readings()?;
+        readings.sort_by_key(|reading| reading.date);
+
+        let intervals = interval_consumption(&readings);
+        let mut costs = cost_breakdowns(&fees, &intervals);
+        apply_stored_prices(repository, &fees, &mut costs)?;
+
+        Ok(ExportData {
+            meters,
+            fees,
+            readings,
+            intervals,
+            costs,
+        })
+    }
+
+    pub fn fee(&self, id: i32) -> Option<&Fee> {
+        self.fees.iter().find(|fee| fee.id == id)
+    }
+
+    /// Name of the meter of the fee, only if there are several meters to tell apart.
+    pub fn meter_name(&self, fee: &Fee) -> Option<&str> {
+        if self.meters.len() < 2 {
+            return None;
+        }
+        self.meters
+            .iter()
+            .find(|meter| meter.id == fee.meter_id)
+            .map(|meter| meter.name.as_str())
+    }
+
+    /// Period of the fee, after the name of its meter if there are several meters.
+    pub fn period_name(&self, locale: ExportLocale, fee: &Fee) -> String {
+        match self.meter_name(fee) {
+            Some(meter) => format!("{} {}", meter, locale.period_name(fee)),
+            None => locale.period_name(fee),
+        }
+    }
+}
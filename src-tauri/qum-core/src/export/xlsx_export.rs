@@ -0,0 +1,147 @@
+use rust_xlsxwriter::{Format, Workbook, Worksheet, XlsxError};
+
+use crate::models::fees::Fee;
+
+use super::{cost_values, ExportData, ExportKind, ExportLocale};
+
+fn write_header(
+    worksheet: &mut Worksheet,
+    row: u32,
+    labels: &[&str],
+    format: &Format,
+) -> Result<(), XlsxError> {
+    for (col, label) in labels.iter().enumerate() {
+        worksheet.write_string_with_format(row, col as u16, *label, format)?;
+    }
+    Ok(())
+}
+
+/// Excel limits sheet names to 31 characters without `[]:*?/\`, the meter name is shortened
+/// so the period stays complete.
+fn sheet_name(data: &ExportData, locale: ExportLocale, fee: &Fee) -> String {
+    let period = locale.period_name(fee);
+    match data.meter_name(fee) {
+        Some(meter) => {
+            let meter: String = meter
+                .chars()
+                .filter(|c| !"[]:*?/\\".contains(*c))
+                .take(30usize.saturating_sub(period.chars().count()))
+                .collect();
+            format!("{} {}", meter.trim_end(), period)
+        }
+        None => period,
+    }
+}
+
+/// Builds a workbook with a cost overview sheet and one sheet per fee period of every meter
+/// containing the readings and the interval consumption of that period.
+pub fn build(data: &ExportData, locale: ExportLocale) -> Result<Workbook, XlsxError> {
+    let mut workbook = Workbook::new();
+    let bold = Format::new().set_bold();
+    let date = Format::new().set_num_format(locale.excel_date_format());
+    let number = Format::new().set_num_format("#,##0.00");
+
+    let overview = workbook.add_worksheet();
+    overview.set_name(match locale {
+        ExportLocale::De => "Kosten",
+        ExportLocale::En => "Costs",
+    })?;
+    write_header(overview, 0, &locale.labels(ExportKind::Costs), &bold)?;
+    for (index, costs) in data.costs.iter().enumerate() {
+        let row = index as u32 + 1;
+        if let Some(fee) = data.fee(costs.fee_id) {
+            overview.write_string(row, 0, data.period_name(locale, fee))?;
+        }
+        overview.write_number(row, 1, costs.months)?;
+        for (col, value) in cost_values(costs).into_iter().enumerate() {
+            overview.write_number_with_format(row, col as u16 + 2, value, &number)?;
+        }
+    }
+    overview.autofit();
+
+    for fee in data.fees.iter() {
+        let worksheet = workbook.add_worksheet();
+        worksheet.set_name(sheet_name(data, locale, fee))?;
+
+        // the period column is left out, it's the sheet itself
+        let labels = locale.labels(ExportKind::Readings);
+        write_header(worksheet, 0, &labels[..labels.len() - 1], &bold)?;
+        let readings = data
+            .readings
+            .iter()
+            .filter(|reading| reading.fee.id == fee.id);
+        let mut row = 1;
+        for reading in readings {
+            worksheet.write_datetime_with_format(row, 0, reading.date, &date)?;
+            worksheet.write_number_with_format(row, 1, reading.value, &number)?;
+            row += 1;
+        }
+
+        row += 1;
+        let labels = locale.labels(ExportKind::Consumption);
+        write_header(worksheet, row, &labels[..labels.len() - 1], &bold)?;
+        row += 1;
+        let intervals = data
+            .intervals
+            .iter()
+            .filter(|interval| interval.fee_id == fee.id);
+        for interval in intervals {
+            worksheet.write_datetime_with_format(row, 0, interval.date_start, &date)?;
+            worksheet.write_datetime_with_format(row, 1, interval.date_end, &date)?;
+            worksheet.write_number_with_format(row, 2, interval.value_start, &number)?;
+            worksheet.write_number_with_format(row, 3, interval.value_end, &number)?;
+            worksheet.write_number_with_format(row, 4, interval.consumption, &number)?;
+            row += 1;
+        }
+        worksheet.autofit();
+    }
+
+    Ok(workbook)
+}
+
+pub fn save(data: &ExportData, locale: ExportLocale, path: &str) -> Result<(), String> {
+    let mut workbook = build(data, locale).map_err(|err| err.to_string())?;
+    workbook.save(path).map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use crate::db::connection::run_migrations;
+    use crate::export::{ExportData, ExportLocale};
+    use crate::repository::SqliteRepository;
+
+    use super::build;
+
+    #[test]
+    fn one_sheet_per_meter_and_period() {
+        let mut conn = Connection::open_in_memory().expect("could not create memory database");
+        run_migrations(&mut conn).expect("failed to run migrations");
+        conn.execute(
+            "INSERT INTO meters (name, kind) VALUES ('Wärmepumpe Keller', 'consumption')",
+            [],
+        )
+        .expect("failed to save meter");
+        conn.execute("INSERT INTO fees (base_fee, price_per_unit, monthly_discount, date_start, date_end, meter_id) VALUES (?1, ?2, ?3, ?4, ?5, 1), (?1, ?2, ?3, ?4, ?5, 2)",
+                 (10.0, 0.5, 45.0, "2022-01-01T00:00:00.000Z", "2022-12-31T00:00:00.000Z")).expect("failed to save fees");
+
+        let data =
+            ExportData::load(&mut SqliteRepository::new(&mut conn)).expect("failed to load data");
+        let mut workbook = build(&data, ExportLocale::De).expect("failed to build workbook");
+
+        let names: Vec<String> = workbook
+            .worksheets_mut()
+            .iter()
+            .map(|worksheet| worksheet.name())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "Kosten",
+                "Strom 01.01.2022 - 31.12.2022",
+                "Wärmepu 01.01.2022 - 31.12.2022",
+            ]
+        );
+    }
+}
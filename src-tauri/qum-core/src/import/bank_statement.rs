@@ -0,0 +1,530 @@
+//! Bank statements as CSV export of the online banking or as ISO 20022 CAMT.053 XML.
+//!
+//! The direct debits of the supplier are recognised by the payment rules and recorded as
+//! payments of the fee period of their booking date. Debits that are already recorded,
+//! e.g. entered by hand or imported from an overlapping statement, are matched instead.
+
+use std::collections::HashSet;
+use std::io::Read;
+
+use chrono::NaiveDate;
+use roxmltree::{Document, Node};
+use serde::{Deserialize, Serialize};
+
+use crate::models::fees::Fee;
+use crate::models::payment::{normalize_creditor_id, Payment, PaymentRule};
+use crate::repository::Repository;
+
+use super::meter_readings_csv::{
+    column_index, csv_delimiter, parse_date, parse_decimal, CsvColumn,
+};
+
+/// Days a recorded payment may lie before or after the booking date of a debit to match
+/// it, banks book direct debits a few days after the due date.
+pub const MATCH_DAYS: i64 = 5;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BankCsvOptions {
+    pub delimiter: char,
+    #[serde(rename = "hasHeader")]
+    pub has_header: bool,
+    #[serde(rename = "dateColumn")]
+    pub date_column: CsvColumn,
+    /// Debits are negative
+    #[serde(rename = "amountColumn")]
+    pub amount_column: CsvColumn,
+    /// Name of the creditor or debtor
+    #[serde(rename = "counterpartyColumn", default)]
+    pub counterparty_column: Option<CsvColumn>,
+    /// Purpose of the transfer, often it holds the creditor ID of direct debits as well
+    #[serde(rename = "textColumn", default)]
+    pub text_column: Option<CsvColumn>,
+    #[serde(rename = "creditorIdColumn", default)]
+    pub creditor_id_column: Option<CsvColumn>,
+    /// chrono format string, e.g. `%d.%m.%Y`
+    #[serde(rename = "dateFormat")]
+    pub date_format: String,
+    #[serde(rename = "decimalSeparator")]
+    pub decimal_separator: char,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BankTransaction {
+    /// Booking date
+    pub date: NaiveDate,
+    /// Debits are negative
+    pub amount: f32,
+    pub counterparty: String,
+    pub text: String,
+    #[serde(rename = "creditorId")]
+    pub creditor_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchStatus {
+    /// A payment is recorded for the transaction on import
+    #[serde(rename = "new")]
+    New,
+    /// The payment is already recorded
+    #[serde(rename = "matched")]
+    Matched,
+    /// Not a direct debit of the supplier or no fee period fits, left for review
+    #[serde(rename = "unmatched")]
+    Unmatched,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TransactionMatch {
+    pub transaction: BankTransaction,
+    pub status: MatchStatus,
+    /// The recorded or, before the import, the proposed payment
+    pub payment: Option<Payment>,
+    /// Why the transaction is unmatched
+    pub reason: Option<String>,
+}
+
+/// Decodes UTF-8 and falls back to ISO 8859-1, which many banks still export.
+fn decode(bytes: Vec<u8>) -> String {
+    match String::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(err) => err.into_bytes().iter().map(|byte| *byte as char).collect(),
+    }
+}
+
+pub fn parse_csv<R: Read>(
+    mut reader: R,
+    options: &BankCsvOptions,
+) -> Result<Vec<BankTransaction>, String> {
+    let mut bytes = vec![];
+    reader
+        .read_to_end(&mut bytes)
+        .map_err(|err| err.to_string())?;
+    let text = decode(bytes);
+    let delimiter = csv_delimiter(options.delimiter)?;
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(options.has_header)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(text.as_bytes());
+
+    let headers = if options.has_header {
+        Some(reader.headers().map_err(|err| err.to_string())?.clone())
+    } else {
+        None
+    };
+    let index = |column: &Option<CsvColumn>| {
+        column
+            .as_ref()
+            .map(|column| column_index(column, headers.as_ref()))
+            .transpose()
+    };
+    let date_index = column_index(&options.date_column, headers.as_ref())?;
+    let amount_index = column_index(&options.amount_column, headers.as_ref())?;
+    let counterparty_index = index(&options.counterparty_column)?;
+    let text_index = index(&options.text_column)?;
+    let creditor_id_index = index(&options.creditor_id_column)?;
+
+    let mut transactions = vec![];
+    for record in reader.records() {
+        let record = record.map_err(|err| err.to_string())?;
+        let line = record.position().map(|pos| pos.line()).unwrap_or(0);
+        let field = |index: Option<usize>| {
+            index
+                .and_then(|index| record.get(index))
+                .unwrap_or("")
+                .to_string()
+        };
+        let date = field(Some(date_index));
+        let amount = field(Some(amount_index));
+        // banks add balances and notes below the transactions
+        if date.is_empty() || amount.is_empty() {
+            continue;
+        }
+
+        let date = parse_date(date.as_str(), options.date_format.as_str())
+            .map_err(|err| format!("line {}: {}", line, err))?;
+        let creditor_id = field(creditor_id_index);
+        transactions.push(BankTransaction {
+            date: date.date(),
+            amount: parse_decimal(amount.as_str(), options.decimal_separator)
+                .map_err(|err| format!("line {}: {}", line, err))?,
+            counterparty: field(counterparty_index),
+            text: field(text_index),
+            creditor_id: Some(creditor_id).filter(|creditor_id| !creditor_id.is_empty()),
+        });
+    }
+    Ok(transactions)
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children()
+        .find(|child| child.is_element() && child.tag_name().name() == name)
+}
+
+fn path<'a, 'input>(node: Node<'a, 'input>, names: &[&str]) -> Option<Node<'a, 'input>> {
+    names.iter().try_fold(node, |node, name| child(node, name))
+}
+
+fn path_text(node: Node, names: &[&str]) -> Option<String> {
+    path(node, names)
+        .and_then(|node| node.text())
+        .map(|text| text.trim().to_string())
+}
+
+fn camt_date(entry: Node, name: &str) -> Option<Result<NaiveDate, String>> {
+    let date = path_text(entry, &[name, "Dt"]).or_else(|| path_text(entry, &[name, "DtTm"]))?;
+    let day = date.get(..10).unwrap_or(date.as_str());
+    Some(NaiveDate::parse_from_str(day, "%Y-%m-%d").map_err(|_| format!("invalid date '{}'", date)))
+}
+
+/// Name of the party, version 2 has it directly below the party, later versions in `Pty`.
+fn party_name(details: Node, party: &str) -> Option<String> {
+    let party = path(details, &["RltdPties", party])?;
+    path_text(party, &["Nm"]).or_else(|| path_text(party, &["Pty", "Nm"]))
+}
+
+/// SEPA creditor ID, an `Othr` identification with the scheme `SEPA`.
+fn creditor_id(details: Node) -> Option<String> {
+    details
+        .descendants()
+        .filter(|node| node.is_element() && node.tag_name().name() == "Othr")
+        .find(|other| path_text(*other, &["SchmeNm", "Prtry"]).as_deref() == Some("SEPA"))
+        .and_then(|other| path_text(other, &["Id"]))
+}
+
+/// Every transaction of a batch booking becomes a transaction of its own.
+pub fn parse_camt(xml: &str) -> Result<Vec<BankTransaction>, String> {
+    let document = Document::parse(xml).map_err(|err| err.to_string())?;
+    let mut transactions = vec![];
+    for entry in document
+        .descendants()
+        .filter(|node| node.is_element() && node.tag_name().name() == "Ntry")
+    {
+        let date = camt_date(entry, "BookgDt")
+            .or_else(|| camt_date(entry, "ValDt"))
+            .ok_or("entry without booking date")??;
+        let amount = |node: Node| -> Result<Option<f32>, String> {
+            match path_text(node, &["Amt"])
+                .or_else(|| path_text(node, &["AmtDtls", "TxAmt", "Amt"]))
+            {
+                Some(amount) => amount
+                    .parse::<f32>()
+                    .map(Some)
+                    .map_err(|_| format!("invalid amount '{}'", amount)),
+                None => Ok(None),
+            }
+        };
+        let debit = path_text(entry, &["CdtDbtInd"]).as_deref() == Some("DBIT");
+        let sign = if debit { -1.0 } else { 1.0 };
+        let entry_amount = amount(entry)?.ok_or("entry without amount")?;
+        let entry_text = path_text(entry, &["AddtlNtryInf"]).unwrap_or_default();
+
+        let details: Vec<Node> = path(entry, &["NtryDtls"])
+            .map(|entry_details| {
+                entry_details
+                    .descendants()
+                    .filter(|node| node.is_element() && node.tag_name().name() == "TxDtls")
+                    .collect()
+            })
+            .unwrap_or_default();
+        if details.is_empty() {
+            transactions.push(BankTransaction {
+                date,
+                amount: sign * entry_amount,
+                counterparty: String::new(),
+                text: entry_text,
+                creditor_id: None,
+            });
+            continue;
+        }
+
+        for transaction in &details {
+            let text: Vec<String> = path(*transaction, &["RmtInf"])
+                .map(|info| {
+                    info.children()
+                        .filter(|child| child.is_element() && child.tag_name().name() == "Ustrd")
+                        .filter_map(|child| child.text())
+                        .map(|text| text.trim().to_string())
+                        .collect()
+                })
+                .unwrap_or_default();
+            let counterparty = match debit {
+                true => party_name(*transaction, "Cdtr"),
+                false => party_name(*transaction, "Dbtr"),
+            };
+            // a single transaction may leave out its amount
+            let transaction_amount = match details.len() {
+                1 => amount(*transaction)?.unwrap_or(entry_amount),
+                _ => amount(*transaction)?.ok_or("batch transaction without amount")?,
+            };
+            transactions.push(BankTransaction {
+                date,
+                amount: sign * transaction_amount,
+                counterparty: counterparty.unwrap_or_default(),
+                text: match text.is_empty() {
+                    true => entry_text.clone(),
+                    false => text.join(" "),
+                },
+                creditor_id: creditor_id(*transaction),
+            });
+        }
+    }
+    Ok(transactions)
+}
+
+/// XML files are read as CAMT.053, other files need the CSV column mapping.
+pub fn load(path: &str, csv: Option<&BankCsvOptions>) -> Result<Vec<BankTransaction>, String> {
+    if path.to_lowercase().ends_with(".xml") {
+        let xml = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        return parse_camt(xml.as_str());
+    }
+
+    let csv = match csv {
+        Some(csv) => csv,
+        None => return Err("CSV files need a column mapping".to_string()),
+    };
+    let file = std::fs::File::open(path).map_err(|err| err.to_string())?;
+    parse_csv(file, csv)
+}
+
+/// The creditor ID is compared with the one of the transaction or searched in its text,
+/// the text is searched in the name of the creditor and the purpose, ignoring case.
+pub fn rule_matches(rule: &PaymentRule, transaction: &BankTransaction) -> bool {
+    let creditor_id = rule.creditor_id.as_ref().is_some_and(|creditor_id| {
+        let purpose = normalize_creditor_id(transaction.text.as_str());
+        transaction
+            .creditor_id
+            .as_ref()
+            .is_some_and(|id| normalize_creditor_id(id) == *creditor_id)
+            || purpose.contains(creditor_id.as_str())
+    });
+    let text = rule.text.as_ref().is_some_and(|text| {
+        let text = text.to_lowercase();
+        transaction.counterparty.to_lowercase().contains(&text)
+            || transaction.text.to_lowercase().contains(&text)
+    });
+    creditor_id || text
+}
+
+fn fee_for_date<'a>(fees: &'a [Fee], date: &NaiveDate) -> Option<&'a Fee> {
+    fees.iter()
+        .find(|fee| fee.date_start.date() <= *date && *date <= fee.date_end.date())
+}
+
+/// Recognises the direct debits of the supplier and looks for recorded payments of the
+/// same amount, every recorded payment matches one debit at most.
+pub fn match_transactions<R: Repository>(
+    repository: &mut R,
+    transactions: &[BankTransaction],
+) -> Result<Vec<TransactionMatch>, String> {
+    let rules = repository.list_payment_rules()?;
+    if rules.is_empty() {
+        return Err("no payment rules, please add the creditor ID of the supplier".to_string());
+    }
+    let fees = repository.list_fees()?;
+    let payments = repository.list_payments(None)?;
+    let mut used: HashSet<i32> = HashSet::new();
+
+    let mut matches = vec![];
+    for transaction in transactions {
+        let unmatched = |reason: &str| TransactionMatch {
+            transaction: transaction.clone(),
+            status: MatchStatus::Unmatched,
+            payment: None,
+            reason: Some(reason.to_string()),
+        };
+        if !rules.iter().any(|rule| rule_matches(rule, transaction)) {
+            matches.push(unmatched("no rule matches"));
+            continue;
+        }
+        if transaction.amount >= 0.0 {
+            matches.push(unmatched("credit of the supplier"));
+            continue;
+        }
+        let fee = match fee_for_date(&fees, &transaction.date) {
+            Some(fee) => fee,
+            None => {
+                matches.push(unmatched("no fee period on this date"));
+                continue;
+            }
+        };
+
+        let amount = -transaction.amount;
+        let recorded = payments.iter().find(|payment| {
+            payment.fee_id == fee.id
+                && !used.contains(&payment.id)
+                && (payment.amount - amount).abs() < 0.005
+                && (payment.date - transaction.date).num_days().abs() <= MATCH_DAYS
+        });
+        let (status, payment) = match recorded {
+            Some(payment) => {
+                used.insert(payment.id);
+                (MatchStatus::Matched, payment.clone())
+            }
+            None => (
+                MatchStatus::New,
+                Payment {
+                    id: 0,
+                    fee_id: fee.id,
+                    date: transaction.date,
+                    amount,
+                },
+            ),
+        };
+        matches.push(TransactionMatch {
+            transaction: transaction.clone(),
+            status,
+            payment: Some(payment),
+            reason: None,
+        });
+    }
+    Ok(matches)
+}
+
+/// Records the new payments, all or none of them, and returns all transactions with the
+/// stored payments.
+pub fn import<R: Repository>(
+    repository: &mut R,
+    transactions: &[BankTransaction],
+) -> Result<Vec<TransactionMatch>, String> {
+    let mut matches = match_transactions(repository, transactions)?;
+    let new: Vec<&mut TransactionMatch> = matches
+        .iter_mut()
+        .filter(|found| found.status == MatchStatus::New && found.payment.is_some())
+        .collect();
+    let payments = new
+        .iter()
+        .filter_map(|found| found.payment.clone())
+        .collect();
+    let stored = repository.insert_payments(payments)?;
+    for (found, payment) in new.into_iter().zip(stored) {
+        found.payment = Some(payment);
+    }
+    Ok(matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::import::meter_readings_csv::CsvColumn;
+    use crate::models::fees::CreateFeeParams;
+    use crate::models::payment::{CreatePaymentParams, CreatePaymentRuleParams};
+    use crate::repository::{FeeRepository, InMemoryRepository, PaymentRepository};
+
+    use super::{import, parse_camt, parse_csv, BankCsvOptions, MatchStatus};
+
+    const CAMT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.02">
+  <BkToCstmrStmt>
+    <Stmt>
+      <Ntry>
+        <Amt Ccy="EUR">45.00</Amt>
+        <CdtDbtInd>DBIT</CdtDbtInd>
+        <BookgDt><Dt>2023-02-01</Dt></BookgDt>
+        <NtryDtls>
+          <TxDtls>
+            <RltdPties>
+              <Cdtr><Nm>Stadtwerke Musterstadt</Nm></Cdtr>
+              <Id><PrvtId><Othr><Id>DE98ZZZ09999999999</Id><SchmeNm><Prtry>SEPA</Prtry></SchmeNm></Othr></PrvtId></Id>
+            </RltdPties>
+            <RmtInf><Ustrd>Abschlag Strom</Ustrd><Ustrd>Vertrag 4711</Ustrd></RmtInf>
+          </TxDtls>
+        </NtryDtls>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="EUR">2500.00</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+        <BookgDt><Dt>2023-02-28</Dt></BookgDt>
+        <NtryDtls>
+          <TxDtls>
+            <RltdPties><Dbtr><Pty><Nm>Employer</Nm></Pty></Dbtr></RltdPties>
+            <RmtInf><Ustrd>Salary</Ustrd></RmtInf>
+          </TxDtls>
+        </NtryDtls>
+      </Ntry>
+    </Stmt>
+  </BkToCstmrStmt>
+</Document>"#;
+
+    #[test]
+    fn parses_camt() {
+        let transactions = parse_camt(CAMT).unwrap();
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[0].amount, -45.0);
+        assert_eq!(transactions[0].counterparty, "Stadtwerke Musterstadt");
+        assert_eq!(transactions[0].text, "Abschlag Strom Vertrag 4711");
+        assert_eq!(
+            transactions[0].creditor_id.as_deref(),
+            Some("DE98ZZZ09999999999")
+        );
+        assert_eq!(transactions[1].amount, 2500.0);
+        assert_eq!(transactions[1].counterparty, "Employer");
+    }
+
+    #[test]
+    fn records_direct_debits() {
+        let mut repository = InMemoryRepository::new();
+        repository
+            .create_fee(CreateFeeParams {
+                meter_id: None,
+                base_fee: 10.0,
+                price_per_unit: 0.3,
+                monthly_discount: 45.0,
+                spot_surcharge: None,
+                blocks: vec![],
+                net_prices: false,
+                price_brake: None,
+                date_start: "2023-01-01T00:00:00.000Z".to_string(),
+                date_end: "2023-12-31T00:00:00.000Z".to_string(),
+            })
+            .unwrap();
+        repository
+            .create_payment_rule(CreatePaymentRuleParams {
+                creditor_id: Some("de98 zzz0 9999 9999 99".to_string()),
+                text: None,
+            })
+            .unwrap();
+        // entered by hand on the due date
+        repository
+            .create_payment(CreatePaymentParams {
+                fee_id: None,
+                meter_id: None,
+                date: "2023-01-01".to_string(),
+                amount: 45.0,
+            })
+            .unwrap();
+
+        let csv = "Buchungstag;Empfänger;Verwendungszweck;Betrag\n\
+            03.01.2023;Stadtwerke;Abschlag Glaeubiger-ID DE98ZZZ09999999999;-45,00\n\
+            01.02.2023;Stadtwerke;Abschlag Glaeubiger-ID DE98ZZZ09999999999;-45,00\n\
+            02.02.2023;Supermarkt;Einkauf;-62,10\n";
+        let options = BankCsvOptions {
+            delimiter: ';',
+            has_header: true,
+            date_column: CsvColumn::Name("Buchungstag".to_string()),
+            amount_column: CsvColumn::Name("Betrag".to_string()),
+            counterparty_column: Some(CsvColumn::Index(1)),
+            text_column: Some(CsvColumn::Name("Verwendungszweck".to_string())),
+            creditor_id_column: None,
+            date_format: "%d.%m.%Y".to_string(),
+            decimal_separator: ',',
+        };
+        let transactions = parse_csv(csv.as_bytes(), &options).unwrap();
+
+        let matches = import(&mut repository, &transactions).unwrap();
+        let statuses: Vec<MatchStatus> = matches.iter().map(|found| found.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                MatchStatus::Matched,
+                MatchStatus::New,
+                MatchStatus::Unmatched
+            ]
+        );
+        assert_eq!(repository.list_payments(None).unwrap().len(), 2);
+
+        // importing the statement again records nothing
+        let matches = import(&mut repository, &transactions).unwrap();
+        assert!(matches.iter().all(|found| found.status != MatchStatus::New));
+    }
+}
@@ -0,0 +1,580 @@
+//! Structured e-invoices: XRechnung in both syntaxes, UN/CEFACT CII and OASIS UBL, and
+//! ZUGFeRD/Factur-X PDFs with the CII XML embedded as attachment.
+//!
+//! The invoice lines are split by their unit: lines billed per kWh make up the price per
+//! unit, the other lines the base fee. Credits like the relief of a price brake are left
+//! out of the prices, they are part of the billed amounts only.
+
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, NaiveDate};
+use roxmltree::{Document, Node};
+use serde::{Deserialize, Serialize};
+
+use crate::models::fees::{CreateFeeParams, Fee};
+use crate::models::invoice::{CreateInvoiceParams, Invoice};
+use crate::models::meter::DEFAULT_METER_ID;
+use crate::models::{format_datetime, parse_datetime};
+use crate::repository::Repository;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EInvoiceSyntax {
+    #[serde(rename = "cii")]
+    Cii,
+    #[serde(rename = "ubl")]
+    Ubl,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EInvoiceLine {
+    pub name: String,
+    pub quantity: f32,
+    /// UN/ECE Recommendation 20 code, e.g. `KWH` or `MON`
+    #[serde(rename = "unitCode")]
+    pub unit_code: String,
+    #[serde(rename = "netAmount")]
+    pub net_amount: f32,
+    /// VAT rate in percent
+    #[serde(rename = "vatRate")]
+    pub vat_rate: Option<f32>,
+    #[serde(rename = "dateStart")]
+    pub date_start: Option<NaiveDate>,
+    #[serde(rename = "dateEnd")]
+    pub date_end: Option<NaiveDate>,
+}
+
+impl EInvoiceLine {
+    /// Billed kWh, `None` for lines with other units.
+    pub fn kwh(&self) -> Option<f32> {
+        match self.unit_code.as_str() {
+            "KWH" => Some(self.quantity),
+            "MWH" => Some(self.quantity * 1000.0),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EInvoice {
+    pub syntax: EInvoiceSyntax,
+    pub number: String,
+    #[serde(rename = "issueDate")]
+    pub issue_date: Option<NaiveDate>,
+    pub seller: String,
+    /// First and last day of the billing period
+    #[serde(rename = "dateStart")]
+    pub date_start: Option<NaiveDate>,
+    #[serde(rename = "dateEnd")]
+    pub date_end: Option<NaiveDate>,
+    pub lines: Vec<EInvoiceLine>,
+    #[serde(rename = "netAmount")]
+    pub net_amount: f32,
+    #[serde(rename = "taxAmount")]
+    pub tax_amount: f32,
+    #[serde(rename = "grossAmount")]
+    pub gross_amount: f32,
+    #[serde(rename = "prepaidAmount")]
+    pub prepaid_amount: f32,
+}
+
+/// Invoice and fee as they would be stored, to be checked before the import.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EInvoicePrefill {
+    pub document: EInvoice,
+    pub fee: CreateFeeParams,
+    pub invoice: CreateInvoiceParams,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EInvoiceImportResult {
+    pub fee: Fee,
+    /// The fee was created from the invoice, otherwise the fee of the period is kept
+    #[serde(rename = "feeCreated")]
+    pub fee_created: bool,
+    pub invoice: Invoice,
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children()
+        .find(|child| child.is_element() && child.tag_name().name() == name)
+}
+
+fn children<'a, 'input>(
+    node: Node<'a, 'input>,
+    name: &'a str,
+) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.children()
+        .filter(move |child| child.is_element() && child.tag_name().name() == name)
+}
+
+fn path<'a, 'input>(node: Node<'a, 'input>, names: &[&str]) -> Option<Node<'a, 'input>> {
+    names.iter().try_fold(node, |node, name| child(node, name))
+}
+
+fn path_text(node: Node, names: &[&str]) -> Option<String> {
+    path(node, names)
+        .and_then(|node| node.text())
+        .map(|text| text.trim().to_string())
+}
+
+fn path_number(node: Node, names: &[&str]) -> Result<Option<f32>, String> {
+    match path_text(node, names) {
+        Some(text) => text
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("invalid {} '{}'", names.join("/"), text)),
+        None => Ok(None),
+    }
+}
+
+/// CII dates are `YYYYMMDD` (format 102), UBL dates `YYYY-MM-DD`.
+fn parse_day(text: Option<String>) -> Result<Option<NaiveDate>, String> {
+    let text = match text {
+        Some(text) => text,
+        None => return Ok(None),
+    };
+    NaiveDate::parse_from_str(text.as_str(), "%Y%m%d")
+        .or_else(|_| NaiveDate::parse_from_str(text.as_str(), "%Y-%m-%d"))
+        .map(Some)
+        .map_err(|_| format!("invalid date '{}'", text))
+}
+
+fn cii_date(node: Node, name: &str) -> Result<Option<NaiveDate>, String> {
+    parse_day(path_text(node, &[name, "DateTimeString"]))
+}
+
+fn parse_cii(root: Node) -> Result<EInvoice, String> {
+    let transaction =
+        child(root, "SupplyChainTradeTransaction").ok_or("invoice without trade transaction")?;
+    let settlement = child(transaction, "ApplicableHeaderTradeSettlement")
+        .ok_or("invoice without trade settlement")?;
+    let period = child(settlement, "BillingSpecifiedPeriod");
+    let totals = child(
+        settlement,
+        "SpecifiedTradeSettlementHeaderMonetarySummation",
+    )
+    .ok_or("invoice without totals")?;
+
+    let mut lines = vec![];
+    for item in children(transaction, "IncludedSupplyChainTradeLineItem") {
+        let line_settlement = child(item, "SpecifiedLineTradeSettlement");
+        let line_period = line_settlement.and_then(|node| child(node, "BillingSpecifiedPeriod"));
+        let quantity = path(item, &["SpecifiedLineTradeDelivery", "BilledQuantity"]);
+        lines.push(EInvoiceLine {
+            name: path_text(item, &["SpecifiedTradeProduct", "Name"]).unwrap_or_default(),
+            quantity: match quantity {
+                Some(quantity) => path_number(quantity, &[])?.unwrap_or(0.0),
+                None => 0.0,
+            },
+            unit_code: quantity
+                .and_then(|quantity| quantity.attribute("unitCode"))
+                .unwrap_or_default()
+                .to_string(),
+            net_amount: match line_settlement {
+                Some(node) => path_number(
+                    node,
+                    &[
+                        "SpecifiedTradeSettlementLineMonetarySummation",
+                        "LineTotalAmount",
+                    ],
+                )?
+                .unwrap_or(0.0),
+                None => 0.0,
+            },
+            vat_rate: match line_settlement {
+                Some(node) => path_number(node, &["ApplicableTradeTax", "RateApplicablePercent"])?,
+                None => None,
+            },
+            date_start: match line_period {
+                Some(node) => cii_date(node, "StartDateTime")?,
+                None => None,
+            },
+            date_end: match line_period {
+                Some(node) => cii_date(node, "EndDateTime")?,
+                None => None,
+            },
+        });
+    }
+
+    let document = child(root, "ExchangedDocument");
+    Ok(EInvoice {
+        syntax: EInvoiceSyntax::Cii,
+        number: document
+            .and_then(|node| path_text(node, &["ID"]))
+            .unwrap_or_default(),
+        issue_date: match document {
+            Some(node) => cii_date(node, "IssueDateTime")?,
+            None => None,
+        },
+        seller: path_text(
+            transaction,
+            &["ApplicableHeaderTradeAgreement", "SellerTradeParty", "Name"],
+        )
+        .unwrap_or_default(),
+        date_start: match period {
+            Some(node) => cii_date(node, "StartDateTime")?,
+            None => None,
+        },
+        date_end: match period {
+            Some(node) => cii_date(node, "EndDateTime")?,
+            None => None,
+        },
+        lines,
+        net_amount: path_number(totals, &["TaxBasisTotalAmount"])?.unwrap_or(0.0),
+        tax_amount: path_number(totals, &["TaxTotalAmount"])?.unwrap_or(0.0),
+        gross_amount: path_number(totals, &["GrandTotalAmount"])?.unwrap_or(0.0),
+        prepaid_amount: path_number(totals, &["TotalPrepaidAmount"])?.unwrap_or(0.0),
+    })
+}
+
+fn parse_ubl(root: Node) -> Result<EInvoice, String> {
+    let totals = child(root, "LegalMonetaryTotal").ok_or("invoice without totals")?;
+    let period = child(root, "InvoicePeriod");
+    let party = path(root, &["AccountingSupplierParty", "Party"]);
+
+    let mut lines = vec![];
+    for line in children(root, "InvoiceLine") {
+        let quantity = child(line, "InvoicedQuantity");
+        let line_period = child(line, "InvoicePeriod");
+        lines.push(EInvoiceLine {
+            name: path_text(line, &["Item", "Name"]).unwrap_or_default(),
+            quantity: path_number(line, &["InvoicedQuantity"])?.unwrap_or(0.0),
+            unit_code: quantity
+                .and_then(|quantity| quantity.attribute("unitCode"))
+                .unwrap_or_default()
+                .to_string(),
+            net_amount: path_number(line, &["LineExtensionAmount"])?.unwrap_or(0.0),
+            vat_rate: path_number(line, &["Item", "ClassifiedTaxCategory", "Percent"])?,
+            date_start: parse_day(line_period.and_then(|node| path_text(node, &["StartDate"])))?,
+            date_end: parse_day(line_period.and_then(|node| path_text(node, &["EndDate"])))?,
+        });
+    }
+
+    Ok(EInvoice {
+        syntax: EInvoiceSyntax::Ubl,
+        number: path_text(root, &["ID"]).unwrap_or_default(),
+        issue_date: parse_day(path_text(root, &["IssueDate"]))?,
+        seller: party
+            .and_then(|party| {
+                path_text(party, &["PartyName", "Name"])
+                    .or_else(|| path_text(party, &["PartyLegalEntity", "RegistrationName"]))
+            })
+            .unwrap_or_default(),
+        date_start: parse_day(period.and_then(|node| path_text(node, &["StartDate"])))?,
+        date_end: parse_day(period.and_then(|node| path_text(node, &["EndDate"])))?,
+        lines,
+        net_amount: path_number(totals, &["TaxExclusiveAmount"])?.unwrap_or(0.0),
+        // a second tax total may follow in the accounting currency
+        tax_amount: path_number(root, &["TaxTotal", "TaxAmount"])?.unwrap_or(0.0),
+        gross_amount: path_number(totals, &["TaxInclusiveAmount"])?.unwrap_or(0.0),
+        prepaid_amount: path_number(totals, &["PrepaidAmount"])?.unwrap_or(0.0),
+    })
+}
+
+pub fn parse(xml: &str) -> Result<EInvoice, String> {
+    let document = Document::parse(xml).map_err(|err| err.to_string())?;
+    let root = document.root_element();
+    match root.tag_name().name() {
+        "CrossIndustryInvoice" => parse_cii(root),
+        "Invoice" => parse_ubl(root),
+        other => Err(format!(
+            "'{}' is no XRechnung, expected a CII or UBL invoice",
+            other
+        )),
+    }
+}
+
+/// XML attachments of a ZUGFeRD/Factur-X PDF, usually there is only the invoice.
+pub fn embedded_xml(pdf: &[u8]) -> Result<Vec<String>, String> {
+    let document = lopdf::Document::load_mem(pdf).map_err(|err| err.to_string())?;
+    let mut files = vec![];
+    for object in document.objects.values() {
+        let stream = match object.as_stream() {
+            Ok(stream) if stream.dict.type_is(b"EmbeddedFile") => stream,
+            _ => continue,
+        };
+        let content = match stream.dict.get(b"Filter") {
+            Ok(_) => stream
+                .decompressed_content()
+                .map_err(|err| err.to_string())?,
+            Err(_) => stream.content.clone(),
+        };
+        if let Ok(text) = String::from_utf8(content) {
+            if text
+                .trim_start_matches('\u{feff}')
+                .trim_start()
+                .starts_with('<')
+            {
+                files.push(text);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Reads XML files and the invoice embedded in PDF files.
+pub fn load(path: &str) -> Result<EInvoice, String> {
+    let bytes = std::fs::read(path).map_err(|err| err.to_string())?;
+    if !bytes.starts_with(b"%PDF") {
+        let xml = String::from_utf8(bytes).map_err(|err| err.to_string())?;
+        return parse(xml.trim_start_matches('\u{feff}'));
+    }
+
+    let mut errors = vec![];
+    for xml in embedded_xml(&bytes)? {
+        match parse(xml.as_str()) {
+            Ok(invoice) => return Ok(invoice),
+            Err(err) => errors.push(err),
+        }
+    }
+    match errors.is_empty() {
+        true => Err("the PDF has no embedded e-invoice".to_string()),
+        false => Err(errors.join(", ")),
+    }
+}
+
+/// Fee and invoice of the billing period, the fee prices are gross and include every
+/// component billed per kWh or per period. The agreed advance is the prepaid amount split
+/// over the months.
+pub fn prefill(document: EInvoice) -> Result<EInvoicePrefill, String> {
+    let (date_start, date_end) = match (document.date_start, document.date_end) {
+        (Some(start), Some(end)) => (start, end),
+        // older invoices state the period on the lines only
+        _ => (
+            document
+                .lines
+                .iter()
+                .filter_map(|line| line.date_start)
+                .min()
+                .ok_or("the invoice has no billing period")?,
+            document
+                .lines
+                .iter()
+                .filter_map(|line| line.date_end)
+                .max()
+                .ok_or("the invoice has no billing period")?,
+        ),
+    };
+    let months = ((date_end.year() - date_start.year()) * 12 + date_end.month() as i32
+        - date_start.month() as i32
+        + 1)
+    .max(1) as f32;
+
+    // lines of the same period bill the same kWh, e.g. energy and grid fees
+    let mut periods: BTreeMap<(Option<NaiveDate>, Option<NaiveDate>), f32> = BTreeMap::new();
+    let mut unit_costs = 0.0;
+    let mut fixed_costs = 0.0;
+    for line in document.lines.iter().filter(|line| line.net_amount >= 0.0) {
+        match line.kwh() {
+            Some(kwh) => {
+                let billed = periods
+                    .entry((line.date_start, line.date_end))
+                    .or_insert(0.0);
+                *billed = billed.max(kwh);
+                unit_costs += line.net_amount;
+            }
+            None => fixed_costs += line.net_amount,
+        }
+    }
+    let consumption: f32 = periods.values().sum();
+    let line_net: f32 = unit_costs + fixed_costs;
+    let gross_factor = match document.net_amount {
+        net if net > 0.0 => 1.0 + document.tax_amount / net,
+        _ => {
+            1.0 + document
+                .lines
+                .iter()
+                .find_map(|line| line.vat_rate)
+                .unwrap_or(0.0)
+                / 100.0
+        }
+    };
+    let round = |value: f32, decimals: i32| {
+        let factor = 10f32.powi(decimals);
+        (value * factor).round() / factor
+    };
+    if line_net <= 0.0 {
+        return Err("the invoice has no lines to take the prices from".to_string());
+    }
+
+    let fee = CreateFeeParams {
+        base_fee: round(fixed_costs / months * gross_factor, 2),
+        price_per_unit: match consumption > 0.0 {
+            true => round(unit_costs / consumption * gross_factor, 4),
+            false => 0.0,
+        },
+        meter_id: None,
+        monthly_discount: round(document.prepaid_amount / months, 2),
+        spot_surcharge: None,
+        blocks: vec![],
+        net_prices: false,
+        price_brake: None,
+        date_start: format_datetime(&date_start.and_hms_opt(0, 0, 0).unwrap_or_default()),
+        date_end: format_datetime(&date_end.and_hms_opt(0, 0, 0).unwrap_or_default()),
+    };
+    let invoice = CreateInvoiceParams {
+        fee_id: None,
+        meter_id: None,
+        number: document.number.clone(),
+        date_start: date_start.format("%Y-%m-%d").to_string(),
+        date_end: date_end.format("%Y-%m-%d").to_string(),
+        consumption,
+        reading_start: None,
+        reading_end: None,
+        estimated: false,
+        net_amount: document.net_amount,
+        tax_amount: document.tax_amount,
+        gross_amount: document.gross_amount,
+        advance_payments: document.prepaid_amount,
+        balance: round(document.prepaid_amount - document.gross_amount, 2),
+    };
+
+    Ok(EInvoicePrefill {
+        document,
+        fee,
+        invoice,
+    })
+}
+
+/// Stores the invoice with the fee of its period, the fee is created if there is none.
+pub fn import<R: Repository>(
+    repository: &mut R,
+    fee: CreateFeeParams,
+    invoice: CreateInvoiceParams,
+) -> Result<EInvoiceImportResult, String> {
+    let start = parse_datetime(fee.date_start.as_str())?;
+    let end = parse_datetime(fee.date_end.as_str())?;
+    let meter_id = fee.meter_id.unwrap_or(DEFAULT_METER_ID);
+    let (fee, fee_created) = match repository.find_fee_in_time_range(meter_id, &start, &end)? {
+        Some(fee) => (fee, false),
+        None => (repository.create_fee(fee)?, true),
+    };
+    let invoice = repository.create_invoice(CreateInvoiceParams {
+        fee_id: Some(fee.id),
+        ..invoice
+    })?;
+
+    Ok(EInvoiceImportResult {
+        fee,
+        fee_created,
+        invoice,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repository::{FeeRepository, InMemoryRepository};
+
+    use super::{import, parse, prefill, EInvoiceSyntax};
+
+    const CII: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rsm:CrossIndustryInvoice xmlns:rsm="urn:un:unece:uncefact:data:standard:CrossIndustryInvoice:100" xmlns:ram="urn:un:unece:uncefact:data:standard:ReusableAggregateBusinessInformationEntity:100" xmlns:udt="urn:un:unece:uncefact:data:standard:UnqualifiedDataType:100">
+  <rsm:ExchangedDocument>
+    <ram:ID>R-2022-4711</ram:ID>
+    <ram:IssueDateTime><udt:DateTimeString format="102">20230115</udt:DateTimeString></ram:IssueDateTime>
+  </rsm:ExchangedDocument>
+  <rsm:SupplyChainTradeTransaction>
+    <ram:IncludedSupplyChainTradeLineItem>
+      <ram:SpecifiedTradeProduct><ram:Name>Arbeitspreis</ram:Name></ram:SpecifiedTradeProduct>
+      <ram:SpecifiedLineTradeDelivery><ram:BilledQuantity unitCode="KWH">2000</ram:BilledQuantity></ram:SpecifiedLineTradeDelivery>
+      <ram:SpecifiedLineTradeSettlement>
+        <ram:ApplicableTradeTax><ram:RateApplicablePercent>19</ram:RateApplicablePercent></ram:ApplicableTradeTax>
+        <ram:SpecifiedTradeSettlementLineMonetarySummation><ram:LineTotalAmount>400.00</ram:LineTotalAmount></ram:SpecifiedTradeSettlementLineMonetarySummation>
+      </ram:SpecifiedLineTradeSettlement>
+    </ram:IncludedSupplyChainTradeLineItem>
+    <ram:IncludedSupplyChainTradeLineItem>
+      <ram:SpecifiedTradeProduct><ram:Name>Netzentgelt</ram:Name></ram:SpecifiedTradeProduct>
+      <ram:SpecifiedLineTradeDelivery><ram:BilledQuantity unitCode="KWH">2000</ram:BilledQuantity></ram:SpecifiedLineTradeDelivery>
+      <ram:SpecifiedLineTradeSettlement>
+        <ram:SpecifiedTradeSettlementLineMonetarySummation><ram:LineTotalAmount>200.00</ram:LineTotalAmount></ram:SpecifiedTradeSettlementLineMonetarySummation>
+      </ram:SpecifiedLineTradeSettlement>
+    </ram:IncludedSupplyChainTradeLineItem>
+    <ram:IncludedSupplyChainTradeLineItem>
+      <ram:SpecifiedTradeProduct><ram:Name>Grundpreis</ram:Name></ram:SpecifiedTradeProduct>
+      <ram:SpecifiedLineTradeDelivery><ram:BilledQuantity unitCode="MON">12</ram:BilledQuantity></ram:SpecifiedLineTradeDelivery>
+      <ram:SpecifiedLineTradeSettlement>
+        <ram:SpecifiedTradeSettlementLineMonetarySummation><ram:LineTotalAmount>120.00</ram:LineTotalAmount></ram:SpecifiedTradeSettlementLineMonetarySummation>
+      </ram:SpecifiedLineTradeSettlement>
+    </ram:IncludedSupplyChainTradeLineItem>
+    <ram:ApplicableHeaderTradeAgreement>
+      <ram:SellerTradeParty><ram:Name>Stadtwerke Musterstadt</ram:Name></ram:SellerTradeParty>
+    </ram:ApplicableHeaderTradeAgreement>
+    <ram:ApplicableHeaderTradeSettlement>
+      <ram:BillingSpecifiedPeriod>
+        <ram:StartDateTime><udt:DateTimeString format="102">20220101</udt:DateTimeString></ram:StartDateTime>
+        <ram:EndDateTime><udt:DateTimeString format="102">20221231</udt:DateTimeString></ram:EndDateTime>
+      </ram:BillingSpecifiedPeriod>
+      <ram:SpecifiedTradeSettlementHeaderMonetarySummation>
+        <ram:LineTotalAmount>720.00</ram:LineTotalAmount>
+        <ram:TaxBasisTotalAmount>720.00</ram:TaxBasisTotalAmount>
+        <ram:TaxTotalAmount currencyID="EUR">136.80</ram:TaxTotalAmount>
+        <ram:GrandTotalAmount>856.80</ram:GrandTotalAmount>
+        <ram:TotalPrepaidAmount>840.00</ram:TotalPrepaidAmount>
+        <ram:DuePayableAmount>16.80</ram:DuePayableAmount>
+      </ram:SpecifiedTradeSettlementHeaderMonetarySummation>
+    </ram:ApplicableHeaderTradeSettlement>
+  </rsm:SupplyChainTradeTransaction>
+</rsm:CrossIndustryInvoice>"#;
+
+    const UBL: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Invoice xmlns="urn:oasis:names:specification:ubl:schema:xsd:Invoice-2" xmlns:cac="urn:oasis:names:specification:ubl:schema:xsd:CommonAggregateComponents-2" xmlns:cbc="urn:oasis:names:specification:ubl:schema:xsd:CommonBasicComponents-2">
+  <cbc:ID>4711</cbc:ID>
+  <cbc:IssueDate>2023-01-15</cbc:IssueDate>
+  <cac:InvoicePeriod><cbc:StartDate>2022-01-01</cbc:StartDate><cbc:EndDate>2022-12-31</cbc:EndDate></cac:InvoicePeriod>
+  <cac:AccountingSupplierParty><cac:Party><cac:PartyName><cbc:Name>Stadtwerke</cbc:Name></cac:PartyName></cac:Party></cac:AccountingSupplierParty>
+  <cac:TaxTotal><cbc:TaxAmount currencyID="EUR">136.80</cbc:TaxAmount></cac:TaxTotal>
+  <cac:LegalMonetaryTotal>
+    <cbc:LineExtensionAmount currencyID="EUR">720.00</cbc:LineExtensionAmount>
+    <cbc:TaxExclusiveAmount currencyID="EUR">720.00</cbc:TaxExclusiveAmount>
+    <cbc:TaxInclusiveAmount currencyID="EUR">856.80</cbc:TaxInclusiveAmount>
+    <cbc:PrepaidAmount currencyID="EUR">840.00</cbc:PrepaidAmount>
+    <cbc:PayableAmount currencyID="EUR">16.80</cbc:PayableAmount>
+  </cac:LegalMonetaryTotal>
+  <cac:InvoiceLine>
+    <cbc:InvoicedQuantity unitCode="MWH">2</cbc:InvoicedQuantity>
+    <cbc:LineExtensionAmount currencyID="EUR">600.00</cbc:LineExtensionAmount>
+    <cac:Item><cbc:Name>Strom</cbc:Name><cac:ClassifiedTaxCategory><cbc:Percent>19</cbc:Percent></cac:ClassifiedTaxCategory></cac:Item>
+  </cac:InvoiceLine>
+  <cac:InvoiceLine>
+    <cbc:InvoicedQuantity unitCode="MON">12</cbc:InvoicedQuantity>
+    <cbc:LineExtensionAmount currencyID="EUR">120.00</cbc:LineExtensionAmount>
+    <cac:Item><cbc:Name>Grundpreis</cbc:Name></cac:Item>
+  </cac:InvoiceLine>
+</Invoice>"#;
+
+    #[test]
+    fn prefills_from_cii() {
+        let document = parse(CII).unwrap();
+        assert_eq!(document.syntax, EInvoiceSyntax::Cii);
+        assert_eq!(document.number, "R-2022-4711");
+        assert_eq!(document.seller, "Stadtwerke Musterstadt");
+        assert_eq!(document.lines.len(), 3);
+
+        // energy and grid fee bill the same 2000 kWh
+        let prefill = prefill(document).unwrap();
+        assert_eq!(prefill.invoice.consumption, 2000.0);
+        assert_eq!(prefill.invoice.balance, -16.8);
+        assert_eq!(prefill.fee.price_per_unit, 0.357);
+        assert_eq!(prefill.fee.base_fee, 11.9);
+        assert_eq!(prefill.fee.monthly_discount, 70.0);
+
+        let mut repository = InMemoryRepository::new();
+        let result = import(&mut repository, prefill.fee, prefill.invoice).unwrap();
+        assert!(result.fee_created);
+        assert_eq!(result.invoice.fee_id, result.fee.id);
+        assert_eq!(repository.list_fees().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn parses_ubl() {
+        let document = parse(UBL).unwrap();
+        assert_eq!(document.syntax, EInvoiceSyntax::Ubl);
+        assert_eq!(document.seller, "Stadtwerke");
+        assert_eq!(document.lines[0].kwh(), Some(2000.0));
+        assert_eq!(document.gross_amount, 856.8);
+
+        let prefill = prefill(document).unwrap();
+        assert_eq!(prefill.invoice.date_end, "2022-12-31");
+        assert_eq!(prefill.fee.price_per_unit, 0.357);
+    }
+}
@@ -0,0 +1,649 @@
+//! Green Button "Download My Data" files (NAESB ESPI, Atom feed with XML content).
+//!
+//! Every `entry` holds one resource and links to related ones by `href`: a `MeterReading`
+//! lives below its `UsagePoint` (`.../UsagePoint/1/MeterReading/1`), its `IntervalBlock`s
+//! below the meter reading and its `ReadingType` is a `related` link.
+
+use std::collections::HashMap;
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime};
+use roxmltree::{Document, Node};
+use serde::{Deserialize, Serialize};
+
+use crate::models::meter::{MeterKind, DEFAULT_METER_ID};
+use crate::repository::Repository;
+use crate::smart_meter::{store_interval_consumption, IngestResult, IntervalFilter};
+
+/// ESPI unit of measure codes
+const UOM_WH: i32 = 72;
+const UOM_M3: i32 = 42;
+const UOM_THERM: i32 = 169;
+/// ESPI service kind codes
+const SERVICE_ELECTRICITY: i32 = 0;
+const SERVICE_GAS: i32 = 1;
+/// ESPI flow direction codes
+const FLOW_FORWARD: i32 = 1;
+const FLOW_REVERSE: i32 = 19;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GreenButtonInterval {
+    pub start: NaiveDateTime,
+    pub duration: i64,
+    /// Energy is converted to kWh, other units keep their unit with the multiplier applied
+    pub value: f64,
+}
+
+impl GreenButtonInterval {
+    pub fn end(&self) -> NaiveDateTime {
+        self.start + chrono::Duration::seconds(self.duration)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GreenButtonMeterReading {
+    pub id: String,
+    /// 1 = delivered to the customer, 19 = delivered by the customer
+    #[serde(rename = "flowDirection")]
+    pub flow_direction: i32,
+    pub unit: String,
+    #[serde(rename = "dateStart")]
+    pub date_start: Option<NaiveDateTime>,
+    #[serde(rename = "dateEnd")]
+    pub date_end: Option<NaiveDateTime>,
+    pub total: f64,
+    #[serde(skip)]
+    pub intervals: Vec<GreenButtonInterval>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GreenButtonUsagePoint {
+    pub id: String,
+    pub title: String,
+    /// ESPI service kind, 0 = electricity, 1 = gas, 2 = water
+    #[serde(rename = "serviceKind")]
+    pub service_kind: Option<i32>,
+    #[serde(rename = "meterReadings")]
+    pub meter_readings: Vec<GreenButtonMeterReading>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GreenButtonDocument {
+    #[serde(rename = "usagePoints")]
+    pub usage_points: Vec<GreenButtonUsagePoint>,
+    /// Offset of the standard time to UTC in seconds, e.g. `-18000` for EST
+    #[serde(rename = "tzOffset")]
+    pub tz_offset: i64,
+    /// Added to `tz_offset` in daylight saving time
+    #[serde(rename = "dstOffset")]
+    pub dst_offset: i64,
+    /// Encoded rules of the start and the end of daylight saving time, see `dst_change`
+    #[serde(rename = "dstStartRule")]
+    pub dst_start_rule: Option<u32>,
+    #[serde(rename = "dstEndRule")]
+    pub dst_end_rule: Option<u32>,
+}
+
+impl GreenButtonDocument {
+    /// Periods of daylight saving time in UTC of the years `year_start..=year_end`, empty
+    /// if the file has no rules.
+    pub fn daylight_saving(
+        &self,
+        year_start: i32,
+        year_end: i32,
+    ) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+        let (start_rule, end_rule) = match (self.dst_start_rule, self.dst_end_rule) {
+            (Some(start_rule), Some(end_rule)) if self.dst_offset != 0 => (start_rule, end_rule),
+            _ => return Vec::new(),
+        };
+        (year_start..=year_end)
+            .filter_map(|year| {
+                // the start is given in standard time, the end in daylight saving time
+                let start = dst_change(start_rule, year)? - Duration::seconds(self.tz_offset);
+                let mut end = dst_change(end_rule, year)?;
+                if end < start {
+                    // southern hemisphere, daylight saving time ends in the next year
+                    end = dst_change(end_rule, year + 1)?;
+                }
+                Some((
+                    start,
+                    end - Duration::seconds(self.tz_offset + self.dst_offset),
+                ))
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GreenButtonImportOptions {
+    /// Id of the usage point, may be left out if the file has only one
+    #[serde(rename = "usagePoint")]
+    pub usage_point: Option<String>,
+    /// Meter the readings are stored for, defaults to the meter with the id of the usage
+    /// point as external id, then to the default meter. It must count what the usage point
+    /// measures, a feed-in meter for the export
+    #[serde(rename = "meterId")]
+    pub meter_id: Option<i32>,
+    /// Import the energy delivered by the customer instead of the consumption
+    pub export: bool,
+    /// Counter value at the start of the first interval, required if the meter has no
+    /// reading before
+    #[serde(rename = "startValue")]
+    pub start_value: Option<f64>,
+    /// One meter reading per interval is stored, days start at local midnight
+    #[serde(rename = "readingIntervalSeconds")]
+    pub reading_interval_seconds: i64,
+}
+
+impl Default for GreenButtonImportOptions {
+    fn default() -> Self {
+        GreenButtonImportOptions {
+            usage_point: None,
+            meter_id: None,
+            export: false,
+            start_value: None,
+            reading_interval_seconds: 24 * 60 * 60,
+        }
+    }
+}
+
+struct ReadingType {
+    flow_direction: i32,
+    uom: i32,
+    power_of_ten_multiplier: i32,
+}
+
+struct Entry<'a, 'input> {
+    href: String,
+    related: Vec<String>,
+    title: String,
+    content: Node<'a, 'input>,
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children()
+        .find(|child| child.is_element() && child.tag_name().name() == name)
+}
+
+fn child_text(node: Node, name: &str) -> Option<String> {
+    child(node, name)
+        .and_then(|child| child.text())
+        .map(|text| text.trim().to_string())
+}
+
+fn child_number<T: std::str::FromStr>(node: Node, name: &str) -> Result<Option<T>, String> {
+    match child_text(node, name) {
+        Some(text) => text
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("invalid {} '{}'", name, text)),
+        None => Ok(None),
+    }
+}
+
+fn entries<'a, 'input>(document: &'a Document<'input>) -> Vec<Entry<'a, 'input>> {
+    document
+        .descendants()
+        .filter(|node| node.is_element() && node.tag_name().name() == "entry")
+        .filter_map(|entry| {
+            let links: Vec<(&str, &str)> = entry
+                .children()
+                .filter(|child| child.is_element() && child.tag_name().name() == "link")
+                .map(|link| {
+                    (
+                        link.attribute("rel").unwrap_or(""),
+                        link.attribute("href").unwrap_or(""),
+                    )
+                })
+                .collect();
+            let content = child(entry, "content")?
+                .children()
+                .find(|child| child.is_element())?;
+
+            Some(Entry {
+                href: links
+                    .iter()
+                    .find(|(rel, _)| *rel == "self")
+                    .map(|(_, href)| href.to_string())
+                    .unwrap_or_default(),
+                related: links
+                    .iter()
+                    .filter(|(rel, _)| *rel == "related")
+                    .map(|(_, href)| href.to_string())
+                    .collect(),
+                title: child_text(entry, "title").unwrap_or_default(),
+                content,
+            })
+        })
+        .collect()
+}
+
+fn intervals(block: Node) -> Result<Vec<(NaiveDateTime, i64, i64)>, String> {
+    block
+        .children()
+        .filter(|child| child.is_element() && child.tag_name().name() == "IntervalReading")
+        .map(|reading| {
+            let period =
+                child(reading, "timePeriod").ok_or("IntervalReading without timePeriod")?;
+            let start: i64 = child_number(period, "start")?.ok_or("timePeriod without start")?;
+            let duration: i64 = child_number(period, "duration")?.unwrap_or(0);
+            let value: i64 =
+                child_number(reading, "value")?.ok_or("IntervalReading without value")?;
+            let start = NaiveDateTime::from_timestamp_opt(start, 0)
+                .ok_or_else(|| format!("invalid start {}", start))?;
+            Ok((start, duration, value))
+        })
+        .collect()
+}
+
+fn first_weekday_from(date: NaiveDate, weekday: u32) -> NaiveDate {
+    let days = (weekday + 7 - date.weekday().number_from_monday()) % 7;
+    date + Duration::days(days as i64)
+}
+
+/// Local date and time of a daylight saving time change in `year`. The rule is a bit map of
+/// the month (bits 28-31), the operator (25-27), the day of the month (20-24), the weekday
+/// (17-19, Monday = 1), the hour (12-16) and the seconds (0-11). The operator picks the day:
+/// 0 = the day of the month, 1 = the weekday on or after the day of the month,
+/// 2-6 = the first to fifth weekday of the month, 7 = the last weekday of the month.
+fn dst_change(rule: u32, year: i32) -> Option<NaiveDateTime> {
+    let seconds = rule & 0xfff;
+    let hour = (rule >> 12) & 0x1f;
+    let weekday = (rule >> 17) & 0x7;
+    let day = (rule >> 20) & 0x1f;
+    let operator = (rule >> 25) & 0x7;
+    let month = rule >> 28;
+
+    let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+    let date = match operator {
+        0 => NaiveDate::from_ymd_opt(year, month, day)?,
+        _ if weekday == 0 => return None,
+        1 => first_weekday_from(NaiveDate::from_ymd_opt(year, month, day)?, weekday),
+        2..=6 => Some(first_weekday_from(first, weekday) + Duration::weeks(operator as i64 - 2))
+            .filter(|date| date.month() == month)?,
+        _ => {
+            let next_month = first + Duration::days(31);
+            let last = next_month - Duration::days(next_month.day() as i64);
+            first_weekday_from(last - Duration::days(6), weekday)
+        }
+    };
+    Some(date.and_hms_opt(hour, 0, 0)? + Duration::seconds(seconds as i64))
+}
+
+fn child_rule(node: Node, name: &str) -> Result<Option<u32>, String> {
+    match child_text(node, name) {
+        Some(text) => u32::from_str_radix(text.trim_start_matches("0x"), 16)
+            .map(Some)
+            .map_err(|_| format!("invalid {} '{}'", name, text)),
+        None => Ok(None),
+    }
+}
+
+fn last_segment(href: &str) -> String {
+    href.trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or(href)
+        .to_string()
+}
+
+pub fn parse(xml: &str) -> Result<GreenButtonDocument, String> {
+    let document = Document::parse(xml).map_err(|err| err.to_string())?;
+    let entries = entries(&document);
+
+    let mut tz_offset = 0;
+    let mut dst_offset = 0;
+    let mut dst_start_rule = None;
+    let mut dst_end_rule = None;
+    let mut reading_types: HashMap<&str, ReadingType> = HashMap::new();
+    for entry in &entries {
+        match entry.content.tag_name().name() {
+            "LocalTimeParameters" => {
+                tz_offset = child_number(entry.content, "tzOffset")?.unwrap_or(0);
+                dst_offset = child_number(entry.content, "dstOffset")?.unwrap_or(0);
+                dst_start_rule = child_rule(entry.content, "dstStartRule")?;
+                dst_end_rule = child_rule(entry.content, "dstEndRule")?;
+            }
+            "ReadingType" => {
+                let reading_type = ReadingType {
+                    flow_direction: child_number(entry.content, "flowDirection")?
+                        .unwrap_or(FLOW_FORWARD),
+                    uom: child_number(entry.content, "uom")?.unwrap_or(UOM_WH),
+                    power_of_ten_multiplier: child_number(entry.content, "powerOfTenMultiplier")?
+                        .unwrap_or(0),
+                };
+                reading_types.insert(entry.href.as_str(), reading_type);
+            }
+            _ => {}
+        }
+    }
+
+    let mut usage_points: Vec<GreenButtonUsagePoint> = Vec::new();
+    for entry in entries
+        .iter()
+        .filter(|entry| entry.content.tag_name().name() == "MeterReading")
+    {
+        let reading_type = entry
+            .related
+            .iter()
+            .find_map(|href| reading_types.get(href.as_str()));
+        let (flow_direction, uom, multiplier) = match reading_type {
+            Some(reading_type) => (
+                reading_type.flow_direction,
+                reading_type.uom,
+                reading_type.power_of_ten_multiplier,
+            ),
+            None => (FLOW_FORWARD, UOM_WH, 0),
+        };
+        let (factor, unit) = match uom {
+            UOM_WH => (10f64.powi(multiplier) / 1000.0, "kWh".to_string()),
+            UOM_M3 => (10f64.powi(multiplier), MeterKind::Gas.unit().to_string()),
+            UOM_THERM => (10f64.powi(multiplier), "therm".to_string()),
+            other => (10f64.powi(multiplier), format!("uom {}", other)),
+        };
+
+        let prefix = format!("{}/", entry.href.trim_end_matches('/'));
+        let mut readings = Vec::new();
+        for block in entries.iter().filter(|block| {
+            block.content.tag_name().name() == "IntervalBlock" && block.href.starts_with(&prefix)
+        }) {
+            for (start, duration, value) in intervals(block.content)? {
+                readings.push(GreenButtonInterval {
+                    start,
+                    duration,
+                    value: value as f64 * factor,
+                });
+            }
+        }
+        readings.sort_by_key(|interval| interval.start);
+
+        let meter_reading = GreenButtonMeterReading {
+            id: last_segment(&entry.href),
+            flow_direction,
+            unit,
+            date_start: readings.first().map(|interval| interval.start),
+            date_end: readings.last().map(|interval| interval.end()),
+            total: readings.iter().map(|interval| interval.value).sum(),
+            intervals: readings,
+        };
+
+        let usage_point_href = match entry.href.find("/MeterReading") {
+            Some(index) => &entry.href[..index],
+            None => entry.href.as_str(),
+        };
+        let id = last_segment(usage_point_href);
+        match usage_points
+            .iter_mut()
+            .find(|usage_point| usage_point.id == id)
+        {
+            Some(usage_point) => usage_point.meter_readings.push(meter_reading),
+            None => {
+                let usage_point = entries.iter().find(|entry| {
+                    entry.content.tag_name().name() == "UsagePoint"
+                        && entry.href == usage_point_href
+                });
+                let service_kind =
+                    match usage_point.and_then(|entry| child(entry.content, "ServiceCategory")) {
+                        Some(category) => child_number(category, "kind")?,
+                        None => None,
+                    };
+                usage_points.push(GreenButtonUsagePoint {
+                    id,
+                    title: usage_point
+                        .map(|entry| entry.title.clone())
+                        .unwrap_or_default(),
+                    service_kind,
+                    meter_readings: vec![meter_reading],
+                });
+            }
+        }
+    }
+
+    if usage_points.is_empty() {
+        return Err("file contains no meter readings".to_string());
+    }
+    Ok(GreenButtonDocument {
+        usage_points,
+        tz_offset,
+        dst_offset,
+        dst_start_rule,
+        dst_end_rule,
+    })
+}
+
+pub fn load(path: &str) -> Result<GreenButtonDocument, String> {
+    let xml = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    parse(xml.as_str())
+}
+
+/// Imports the intervals of the chosen usage point as interval data and meter readings of
+/// its meter.
+pub fn import<R: Repository>(
+    repository: &mut R,
+    document: &GreenButtonDocument,
+    options: &GreenButtonImportOptions,
+) -> Result<IngestResult, String> {
+    let usage_point = match &options.usage_point {
+        Some(id) => document
+            .usage_points
+            .iter()
+            .find(|usage_point| usage_point.id == *id)
+            .ok_or_else(|| format!("usage point '{}' not found", id))?,
+        None if document.usage_points.len() == 1 => &document.usage_points[0],
+        None => return Err("file contains several usage points, please choose one".to_string()),
+    };
+    let kind = match (
+        usage_point.service_kind.unwrap_or(SERVICE_ELECTRICITY),
+        options.export,
+    ) {
+        (SERVICE_ELECTRICITY, false) => MeterKind::Consumption,
+        (SERVICE_ELECTRICITY, true) => MeterKind::FeedIn,
+        (SERVICE_GAS, false) => MeterKind::Gas,
+        (SERVICE_GAS, true) => return Err("gas can't be exported".to_string()),
+        (other, _) => return Err(format!("service kind {} is not supported", other)),
+    };
+    let meter = match options.meter_id {
+        Some(id) => repository.get_meter(id)?,
+        None => match repository.find_meter_by_external_id(usage_point.id.as_str())? {
+            Some(meter) => meter,
+            None => repository.get_meter(DEFAULT_METER_ID)?,
+        },
+    };
+    meter.expect_kind(kind)?;
+
+    let flow_direction = if options.export {
+        FLOW_REVERSE
+    } else {
+        FLOW_FORWARD
+    };
+    let meter_reading = usage_point
+        .meter_readings
+        .iter()
+        .find(|meter_reading| meter_reading.flow_direction == flow_direction)
+        .ok_or_else(|| {
+            format!(
+                "usage point '{}' has no readings in this direction",
+                usage_point.id
+            )
+        })?;
+    if meter_reading.unit != kind.unit() {
+        return Err(format!(
+            "readings in {} can't be stored for a meter counting {}",
+            meter_reading.unit,
+            kind.unit()
+        ));
+    }
+
+    let intervals: Vec<(NaiveDateTime, NaiveDateTime, f64)> = meter_reading
+        .intervals
+        .iter()
+        .map(|interval| (interval.start, interval.end(), interval.value))
+        .collect();
+    let daylight_saving = match (meter_reading.date_start, meter_reading.date_end) {
+        (Some(start), Some(end)) => document.daylight_saving(start.year() - 1, end.year()),
+        _ => Vec::new(),
+    };
+    let filter = IntervalFilter::new(options.reading_interval_seconds)
+        .with_offset(document.tz_offset)
+        .with_daylight_saving(document.dst_offset, daylight_saving)
+        .resume(repository, meter.id)?;
+    store_interval_consumption(
+        repository,
+        meter.id,
+        filter,
+        &intervals,
+        options.start_value,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::fees::CreateFeeParams;
+    use crate::models::meter::{CreateMeterParams, MeterKind};
+    use crate::repository::{
+        FeeRepository, InMemoryRepository, MeterReadingRepository, MeterRepository,
+    };
+
+    use super::{dst_change, import, parse, GreenButtonImportOptions};
+
+    const FEED: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom" xmlns:espi="http://naesb.org/espi">
+  <entry>
+    <link rel="self" href="https://utility.example/espi/1_1/resource/LocalTimeParameters/1"/>
+    <content><espi:LocalTimeParameters><espi:dstEndRule>B40E2000</espi:dstEndRule><espi:dstOffset>3600</espi:dstOffset><espi:dstStartRule>360E2000</espi:dstStartRule><espi:tzOffset>-18000</espi:tzOffset></espi:LocalTimeParameters></content>
+  </entry>
+  <entry>
+    <link rel="self" href="https://utility.example/espi/1_1/resource/Subscription/5/UsagePoint/1"/>
+    <title>Home</title>
+    <content><espi:UsagePoint><espi:ServiceCategory><espi:kind>0</espi:kind></espi:ServiceCategory></espi:UsagePoint></content>
+  </entry>
+  <entry>
+    <link rel="self" href="https://utility.example/espi/1_1/resource/Subscription/5/UsagePoint/1/MeterReading/1"/>
+    <link rel="related" href="https://utility.example/espi/1_1/resource/ReadingType/07"/>
+    <content><espi:MeterReading/></content>
+  </entry>
+  <entry>
+    <link rel="self" href="https://utility.example/espi/1_1/resource/ReadingType/07"/>
+    <content><espi:ReadingType><espi:flowDirection>1</espi:flowDirection><espi:intervalLength>3600</espi:intervalLength><espi:powerOfTenMultiplier>-1</espi:powerOfTenMultiplier><espi:uom>72</espi:uom></espi:ReadingType></content>
+  </entry>
+  <entry>
+    <link rel="self" href="https://utility.example/espi/1_1/resource/Subscription/5/UsagePoint/1/MeterReading/1/IntervalBlock/1"/>
+    <content>
+      <espi:IntervalBlock>
+        <espi:interval><espi:duration>10800</espi:duration><espi:start>1673845200</espi:start></espi:interval>
+        <espi:IntervalReading><espi:timePeriod><espi:duration>3600</espi:duration><espi:start>1673852400</espi:start></espi:timePeriod><espi:value>6000</espi:value></espi:IntervalReading>
+        <espi:IntervalReading><espi:timePeriod><espi:duration>3600</espi:duration><espi:start>1673845200</espi:start></espi:timePeriod><espi:value>5000</espi:value></espi:IntervalReading>
+        <espi:IntervalReading><espi:timePeriod><espi:duration>3600</espi:duration><espi:start>1673848800</espi:start></espi:timePeriod><espi:value>4000</espi:value></espi:IntervalReading>
+      </espi:IntervalBlock>
+    </content>
+  </entry>
+</feed>"#;
+
+    #[test]
+    fn parses_feed() {
+        let document = parse(FEED).unwrap();
+        assert_eq!(document.tz_offset, -18000);
+        assert_eq!(document.dst_start_rule, Some(0x360E2000));
+        assert_eq!(document.usage_points.len(), 1);
+
+        let usage_point = &document.usage_points[0];
+        assert_eq!(usage_point.id, "1");
+        assert_eq!(usage_point.title, "Home");
+        assert_eq!(usage_point.service_kind, Some(0));
+
+        let meter_reading = &usage_point.meter_readings[0];
+        assert_eq!(meter_reading.unit, "kWh");
+        assert_eq!(meter_reading.intervals.len(), 3);
+        assert_eq!(meter_reading.intervals[0].value, 0.5);
+        assert!((meter_reading.total - 1.5).abs() < 1e-9);
+        assert_eq!(
+            meter_reading.date_start.unwrap().to_string(),
+            "2023-01-16 05:00:00"
+        );
+    }
+
+    #[test]
+    fn applies_daylight_saving_rules() {
+        // US rules: second Sunday in March to the first Sunday in November, at 2:00
+        assert_eq!(
+            dst_change(0x360E2000, 2023).unwrap().to_string(),
+            "2023-03-12 02:00:00"
+        );
+        assert_eq!(
+            dst_change(0xB40E2000, 2023).unwrap().to_string(),
+            "2023-11-05 02:00:00"
+        );
+        // last Sunday in March at 2:00
+        assert_eq!(
+            dst_change(0x3E0E2000, 2024).unwrap().to_string(),
+            "2024-03-31 02:00:00"
+        );
+
+        let document = parse(FEED).unwrap();
+        let periods = document.daylight_saving(2023, 2023);
+        assert_eq!(periods.len(), 1);
+        assert_eq!(periods[0].0.to_string(), "2023-03-12 07:00:00");
+        assert_eq!(periods[0].1.to_string(), "2023-11-05 06:00:00");
+    }
+
+    #[test]
+    fn imports_counter_values() {
+        let mut repository = InMemoryRepository::new();
+        // the usage point is mapped to the meter with its id
+        let meter = repository
+            .create_meter(CreateMeterParams {
+                name: "Home".to_string(),
+                kind: MeterKind::Consumption,
+                external_id: Some("1".to_string()),
+            })
+            .unwrap();
+        repository
+            .create_fee(CreateFeeParams {
+                meter_id: Some(meter.id),
+                base_fee: 10.0,
+                price_per_unit: 0.3,
+                monthly_discount: 50.0,
+                spot_surcharge: None,
+                blocks: vec![],
+                net_prices: false,
+                price_brake: None,
+                date_start: "2023-01-01T00:00:00.000Z".to_string(),
+                date_end: "2023-12-31T00:00:00.000Z".to_string(),
+            })
+            .unwrap();
+        let document = parse(FEED).unwrap();
+
+        // the meter has no reading before, so the counter value at the start is needed
+        assert!(import(
+            &mut repository,
+            &document,
+            &GreenButtonImportOptions::default()
+        )
+        .is_err());
+
+        let options = GreenButtonImportOptions {
+            start_value: Some(100.0),
+            ..Default::default()
+        };
+        let result = import(&mut repository, &document, &options).unwrap();
+        assert_eq!(result.received, 3);
+        assert_eq!(result.samples, 4);
+        assert!(result.errors.is_empty());
+
+        // 05:00 UTC is midnight EST, so the first sample opens a new day
+        let readings = repository.list_meter_readings().unwrap();
+        assert_eq!(readings.len(), 1);
+        assert_eq!(readings[0].value, 100.0);
+        assert_eq!(readings[0].meter_id, meter.id);
+
+        let options = GreenButtonImportOptions {
+            export: true,
+            ..Default::default()
+        };
+        assert!(import(&mut repository, &document, &options).is_err());
+
+        // volumes are not stored for a meter counting kWh
+        let gas = parse(FEED.replace("<espi:uom>72", "<espi:uom>42").as_str()).unwrap();
+        assert_eq!(gas.usage_points[0].meter_readings[0].unit, "m³");
+        assert!(import(&mut repository, &gas, &options).is_err());
+    }
+}
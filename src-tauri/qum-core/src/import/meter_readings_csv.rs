@@ -0,0 +1,370 @@
+use std::fs::File;
+use std::io::Read;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+use crate::models::fees::Fee;
+use crate::models::format_datetime;
+use crate::models::meter::default_meter_id;
+use crate::models::meter_reading::CreateMeterReadingParams;
+use crate::repository::{FeeRepository, MeterReadingRepository};
+
+/// A column is addressed either by its zero based position or by its header name.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum CsvColumn {
+    Index(usize),
+    Name(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CsvImportOptions {
+    pub delimiter: char,
+    #[serde(rename = "hasHeader")]
+    pub has_header: bool,
+    #[serde(rename = "dateColumn")]
+    pub date_column: CsvColumn,
+    #[serde(rename = "valueColumn")]
+    pub value_column: CsvColumn,
+    /// chrono format string, e.g. `%d.%m.%Y` or `%Y-%m-%d %H:%M`
+    #[serde(rename = "dateFormat")]
+    pub date_format: String,
+    #[serde(rename = "decimalSeparator")]
+    pub decimal_separator: char,
+    /// Meter the readings are imported for, rows get the fee of this meter
+    #[serde(rename = "meterId", default = "default_meter_id")]
+    pub meter_id: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CsvPreviewRow {
+    pub line: u64,
+    pub date: Option<NaiveDateTime>,
+    pub value: Option<f32>,
+    #[serde(rename = "feeId")]
+    pub fee_id: Option<i32>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CsvImportResult {
+    pub imported: usize,
+    pub skipped: usize,
+}
+
+pub fn parse_date(value: &str, format: &str) -> Result<NaiveDateTime, String> {
+    if let Ok(datetime) = NaiveDateTime::parse_from_str(value, format) {
+        return Ok(datetime);
+    }
+
+    match NaiveDate::parse_from_str(value, format) {
+        Ok(date) => Ok(date.and_hms_opt(0, 0, 0).unwrap()),
+        Err(err) => Err(format!("invalid date '{}': {}", value, err)),
+    }
+}
+
+/// Parses numbers like `1.234,5` (German) or `1,234.5` depending on the decimal separator.
+/// The other separator is only accepted between groups of three digits, `1.5` with `,` as
+/// decimal separator is an error instead of 15.
+pub fn parse_decimal(value: &str, decimal_separator: char) -> Result<f32, String> {
+    let thousands_separator = if decimal_separator == ',' { '.' } else { ',' };
+    let invalid = || format!("invalid number '{}'", value);
+    let compact: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+
+    let (integer, fraction) = match compact.split_once(decimal_separator) {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (compact.as_str(), None),
+    };
+    if fraction.is_some_and(|fraction| fraction.contains(thousands_separator)) {
+        return Err(invalid());
+    }
+    let digits = integer.trim_start_matches(['+', '-']);
+    if digits.contains(thousands_separator) {
+        let mut groups = digits.split(thousands_separator);
+        let first = groups.next().unwrap_or_default();
+        if first.is_empty() || first.len() > 3 || groups.any(|group| group.len() != 3) {
+            return Err(invalid());
+        }
+    }
+
+    let mut normalized = integer.replace(thousands_separator, "");
+    if let Some(fraction) = fraction {
+        normalized.push('.');
+        normalized.push_str(fraction);
+    }
+    normalized.parse::<f32>().map_err(|_| invalid())
+}
+
+pub(crate) fn column_index(
+    column: &CsvColumn,
+    headers: Option<&csv::StringRecord>,
+) -> Result<usize, String> {
+    match column {
+        CsvColumn::Index(index) => Ok(*index),
+        CsvColumn::Name(name) => {
+            let headers = match headers {
+                Some(headers) => headers,
+                None => return Err(format!("column '{}' requires a header row", name)),
+            };
+            match headers.iter().position(|header| header == name) {
+                Some(index) => Ok(index),
+                None => Err(format!("column '{}' not found", name)),
+            }
+        }
+    }
+}
+
+/// The csv reader splits on a single byte, other characters would be truncated.
+pub(crate) fn csv_delimiter(delimiter: char) -> Result<u8, String> {
+    if !delimiter.is_ascii() {
+        return Err(format!(
+            "delimiter '{}' is not an ASCII character",
+            delimiter
+        ));
+    }
+    Ok(delimiter as u8)
+}
+
+fn fee_for_date<'a>(fees: &'a [Fee], meter_id: i32, date: &NaiveDateTime) -> Option<&'a Fee> {
+    fees.iter()
+        .find(|fee| fee.meter_id == meter_id && fee.date_start <= *date && *date <= fee.date_end)
+}
+
+pub fn parse<R: Read>(
+    reader: R,
+    options: &CsvImportOptions,
+    fees: &[Fee],
+) -> Result<Vec<CsvPreviewRow>, String> {
+    let delimiter = csv_delimiter(options.delimiter)?;
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(options.has_header)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+
+    let headers = if options.has_header {
+        Some(reader.headers().map_err(|err| err.to_string())?.clone())
+    } else {
+        None
+    };
+
+    let date_index = column_index(&options.date_column, headers.as_ref())?;
+    let value_index = column_index(&options.value_column, headers.as_ref())?;
+
+    let mut rows: Vec<CsvPreviewRow> = vec![];
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(err) => {
+                let line = err.position().map(|pos| pos.line()).unwrap_or(0);
+                rows.push(CsvPreviewRow {
+                    line,
+                    date: None,
+                    value: None,
+                    fee_id: None,
+                    error: Some(err.to_string()),
+                });
+                continue;
+            }
+        };
+
+        let line = record.position().map(|pos| pos.line()).unwrap_or(0);
+        let mut row = CsvPreviewRow {
+            line,
+            date: None,
+            value: None,
+            fee_id: None,
+            error: None,
+        };
+
+        let date = match record.get(date_index) {
+            Some(date) => parse_date(date, options.date_format.as_str()),
+            None => Err(format!("missing date column {}", date_index)),
+        };
+        let value = match record.get(value_index) {
+            Some(value) => parse_decimal(value, options.decimal_separator),
+            None => Err(format!("missing value column {}", value_index)),
+        };
+
+        match date {
+            Ok(date) => row.date = Some(date),
+            Err(err) => row.error = Some(err),
+        }
+        match value {
+            Ok(value) => row.value = Some(value),
+            Err(err) => {
+                if row.error.is_none() {
+                    row.error = Some(err)
+                }
+            }
+        }
+
+        if let Some(date) = row.date {
+            match fee_for_date(fees, options.meter_id, &date) {
+                Some(fee) => row.fee_id = Some(fee.id),
+                None => {
+                    if row.error.is_none() {
+                        row.error = Some(format!("no fee found for {}", date))
+                    }
+                }
+            }
+        }
+
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
+
+pub fn preview<R: FeeRepository>(
+    repository: &mut R,
+    path: String,
+    options: &CsvImportOptions,
+) -> Result<Vec<CsvPreviewRow>, String> {
+    let file = File::open(path).map_err(|err| err.to_string())?;
+    let fees = repository.list_fees()?;
+    parse(file, options, &fees)
+}
+
+/// Inserts all valid rows in one transaction. Rows with errors abort the import
+/// unless `skip_invalid` is set.
+pub fn import<R: MeterReadingRepository>(
+    repository: &mut R,
+    rows: Vec<CsvPreviewRow>,
+    skip_invalid: bool,
+) -> Result<CsvImportResult, String> {
+    let invalid = rows.iter().filter(|row| row.error.is_some()).count();
+    if invalid > 0 && !skip_invalid {
+        return Err(format!("{} rows could not be parsed", invalid));
+    }
+
+    let readings: Vec<CreateMeterReadingParams> = rows
+        .into_iter()
+        .filter(|row| row.error.is_none())
+        .filter_map(|row| match (row.date, row.value, row.fee_id) {
+            (Some(date), Some(value), Some(fee_id)) => Some(CreateMeterReadingParams {
+                value,
+                fee_id,
+                reading_date: format_datetime(&date),
+            }),
+            _ => None,
+        })
+        .collect();
+
+    let imported = repository.create_meter_readings(readings)?;
+
+    Ok(CsvImportResult {
+        imported,
+        skipped: invalid,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::fees::Fee;
+    use crate::models::parse_datetime;
+    use crate::repository::{FeeRepository, InMemoryRepository, MeterReadingRepository};
+
+    use super::{import, parse, parse_decimal, CsvColumn, CsvImportOptions};
+
+    fn german_options() -> CsvImportOptions {
+        CsvImportOptions {
+            delimiter: ';',
+            has_header: true,
+            date_column: CsvColumn::Name("Datum".to_string()),
+            value_column: CsvColumn::Name("Zählerstand".to_string()),
+            date_format: "%d.%m.%Y".to_string(),
+            decimal_separator: ',',
+            meter_id: 1,
+        }
+    }
+
+    fn setup() -> InMemoryRepository {
+        let mut repository = InMemoryRepository::new();
+        repository
+            .insert_fee(Fee {
+                meter_id: 1,
+                id: 0,
+                base_fee: 10.0,
+                price_per_unit: 0.5,
+                monthly_discount: 45.0,
+                spot_surcharge: None,
+                blocks: vec![],
+                net_prices: false,
+                price_brake: None,
+                date_start: parse_datetime("2022-01-01T00:00:00.000Z").unwrap(),
+                date_end: parse_datetime("2022-12-31T23:59:59.000Z").unwrap(),
+            })
+            .expect("failed to save fee");
+        repository
+    }
+
+    #[test]
+    fn parse_german_decimal() {
+        assert_eq!(parse_decimal("1.234,5", ','), Ok(1234.5));
+        assert_eq!(parse_decimal("1,234.5", '.'), Ok(1234.5));
+        assert!(parse_decimal("abc", ',').is_err());
+        assert_eq!(parse_decimal("-12.345,5", ','), Ok(-12345.5));
+        assert!(parse_decimal("1.5", ',').is_err());
+        assert!(parse_decimal("1,5", '.').is_err());
+        assert!(parse_decimal("1,2345.6", '.').is_err());
+    }
+
+    #[test]
+    fn preview_reports_row_errors() {
+        let mut repository = setup();
+        let fees = repository.list_fees().expect("failed to get fees list");
+        let data = "Datum;Zählerstand\n01.02.2022;1.234,5\nkaputt;12\n01.02.2023;13\n";
+
+        let rows = parse(data.as_bytes(), &german_options(), &fees).expect("failed to parse");
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].value, Some(1234.5));
+        assert_eq!(rows[0].fee_id, Some(1));
+        assert!(rows[0].error.is_none());
+        assert_eq!(rows[1].line, 3);
+        assert!(rows[1].error.is_some());
+        assert!(rows[2].error.is_some());
+
+        // the fee of another meter is not taken
+        let options = CsvImportOptions {
+            meter_id: 2,
+            ..german_options()
+        };
+        let rows = parse(data.as_bytes(), &options, &fees).expect("failed to parse");
+        assert_eq!(rows[0].fee_id, None);
+        assert!(rows[0].error.is_some());
+    }
+
+    #[test]
+    fn rejects_non_ascii_delimiter() {
+        let data = "Datum§Zählerstand\n01.02.2022§100\n";
+        let options = CsvImportOptions {
+            delimiter: '§',
+            ..german_options()
+        };
+        assert!(parse(data.as_bytes(), &options, &[]).is_err());
+    }
+
+    #[test]
+    fn import_inserts_valid_rows() {
+        let mut repository = setup();
+        let fees = repository.list_fees().expect("failed to get fees list");
+        let data = "Datum;Zählerstand\n01.02.2022;100,5\n01.03.2022;200\nkaputt;12\n";
+        let rows = parse(data.as_bytes(), &german_options(), &fees).expect("failed to parse");
+
+        assert!(import(&mut repository, rows, false).is_err());
+
+        let rows = parse(data.as_bytes(), &german_options(), &fees).expect("failed to parse");
+        let result = import(&mut repository, rows, true).expect("failed to import");
+        assert_eq!(result.imported, 2);
+        assert_eq!(result.skipped, 1);
+
+        let readings = repository
+            .list_meter_readings()
+            .expect("failed to list readings");
+        assert_eq!(readings.len(), 2);
+        assert_eq!(readings[0].value, 100.5);
+    }
+}
@@ -0,0 +1,6 @@
+pub mod bank_statement;
+pub mod e_invoice;
+pub mod green_button;
+pub mod meter_readings_csv;
+pub mod mscons;
+pub mod spot_prices;
@@ -0,0 +1,625 @@
+//! EDIFACT MSCONS messages of German grid and metering point operators.
+//!
+//! After `UNS+D` every `LOC` starts a meter location, each `LIN` a series whose OBIS code is
+//! given by `PIA`, followed by `QTY` quantities with their `DTM` dates. Series with OBIS
+//! `x.8.x` are counter values, series with `x.29.x` energy per interval (load profiles).
+
+use chrono::{Duration, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+use crate::models::meter::{MeterKind, DEFAULT_METER_ID};
+use crate::repository::Repository;
+use crate::smart_meter::{store_interval_consumption, IngestResult, IntervalFilter};
+
+/// Segments that are understood or deliberately ignored.
+const KNOWN_SEGMENTS: [&str; 18] = [
+    "UNA", "UNB", "UNH", "BGM", "DTM", "RFF", "NAD", "CTA", "COM", "UNS", "LOC", "LIN", "PIA",
+    "QTY", "STS", "CCI", "UNT", "UNZ",
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MsconsSeriesKind {
+    #[serde(rename = "counter")]
+    Counter,
+    #[serde(rename = "interval")]
+    Interval,
+    #[serde(rename = "unknown")]
+    Unknown,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MsconsQuantity {
+    pub value: f64,
+    /// 220 = true value, 67 = substitute value, 201 = provisional value
+    pub qualifier: String,
+    #[serde(rename = "dateStart")]
+    pub date_start: Option<NaiveDateTime>,
+    #[serde(rename = "dateEnd")]
+    pub date_end: Option<NaiveDateTime>,
+    /// Reading date of counter values
+    pub date: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MsconsSeries {
+    pub obis: String,
+    pub unit: Option<String>,
+    pub quantities: Vec<MsconsQuantity>,
+}
+
+impl MsconsSeries {
+    pub fn kind(&self) -> MsconsSeriesKind {
+        let value_group = self
+            .obis
+            .split(':')
+            .nth(1)
+            .and_then(|cde| cde.split('.').nth(1));
+        match value_group {
+            Some("8") => MsconsSeriesKind::Counter,
+            Some("29") => MsconsSeriesKind::Interval,
+            _ => MsconsSeriesKind::Unknown,
+        }
+    }
+
+    /// What the OBIS code counts: medium 7 is gas, energy of channel 2 is fed in.
+    pub fn meter_kind(&self) -> MeterKind {
+        let mut groups = self.obis.split(&['-', ':', '.'][..]);
+        let medium = groups.next();
+        let channel = groups.nth(1);
+        match (medium, channel) {
+            (Some("7"), _) => MeterKind::Gas,
+            (_, Some("2")) => MeterKind::FeedIn,
+            _ => MeterKind::Consumption,
+        }
+    }
+
+    /// Factor and unit the values are stored in, energy is converted to kWh.
+    pub fn scale(&self) -> Result<(f64, &'static str), String> {
+        match self.unit.as_deref() {
+            Some("WH") => Ok((0.001, "kWh")),
+            Some("KWH") => Ok((1.0, "kWh")),
+            Some("MWH") => Ok((1000.0, "kWh")),
+            Some("MTQ") => Ok((1.0, "m³")),
+            Some(unit) => Err(format!("unsupported unit '{}'", unit)),
+            None => Err(format!("series {} has no unit", self.obis)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MsconsLocation {
+    /// Meter location (Messlokation) or market location id
+    pub id: String,
+    #[serde(rename = "dateStart")]
+    pub date_start: Option<NaiveDateTime>,
+    #[serde(rename = "dateEnd")]
+    pub date_end: Option<NaiveDateTime>,
+    pub series: Vec<MsconsSeries>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MsconsDocument {
+    pub locations: Vec<MsconsLocation>,
+    /// Segments that were not understood, e.g. `segment 14: FTX+ACB`
+    pub unsupported: Vec<String>,
+    /// UTC offset of the first date in seconds, used to align days to local midnight
+    #[serde(rename = "utcOffset")]
+    pub utc_offset: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MsconsImportOptions {
+    /// May be left out if the file has only one location
+    pub location: Option<String>,
+    /// May be left out if the location has only one series
+    pub obis: Option<String>,
+    /// Meter the series is stored for, defaults to the meter with the location id as
+    /// external id, then to the default meter. It must count what the series counts
+    #[serde(rename = "meterId")]
+    pub meter_id: Option<i32>,
+    /// Counter value at the start of the first interval of a load profile, required if the
+    /// meter has no reading before
+    #[serde(rename = "startValue")]
+    pub start_value: Option<f64>,
+    /// One meter reading per interval is stored, days start at local midnight
+    #[serde(rename = "readingIntervalSeconds")]
+    pub reading_interval_seconds: i64,
+}
+
+impl Default for MsconsImportOptions {
+    fn default() -> Self {
+        MsconsImportOptions {
+            location: None,
+            obis: None,
+            meter_id: None,
+            start_value: None,
+            reading_interval_seconds: 24 * 60 * 60,
+        }
+    }
+}
+
+struct Segment {
+    number: usize,
+    tag: String,
+    elements: Vec<Vec<String>>,
+}
+
+impl Segment {
+    fn value(&self, element: usize, component: usize) -> Option<&str> {
+        self.elements
+            .get(element)
+            .and_then(|components| components.get(component))
+            .map(|value| value.as_str())
+            .filter(|value| !value.is_empty())
+    }
+
+    fn describe(&self) -> String {
+        match self.value(0, 0) {
+            Some(qualifier) => format!("segment {}: {}+{}", self.number, self.tag, qualifier),
+            None => format!("segment {}: {}", self.number, self.tag),
+        }
+    }
+}
+
+struct Delimiters {
+    component: char,
+    element: char,
+    decimal: char,
+    release: char,
+    segment: char,
+}
+
+/// Splits the interchange into segments, `UNA` overrides the default delimiters.
+fn segments(text: &str) -> (Vec<Segment>, char) {
+    let mut delimiters = Delimiters {
+        component: ':',
+        element: '+',
+        decimal: '.',
+        release: '?',
+        segment: '\'',
+    };
+    let mut text = text.trim_start_matches('\u{feff}').trim_start();
+    if let Some(una) = text.strip_prefix("UNA") {
+        let chars: Vec<char> = una.chars().take(6).collect();
+        if chars.len() == 6 {
+            delimiters = Delimiters {
+                component: chars[0],
+                element: chars[1],
+                decimal: chars[2],
+                release: chars[3],
+                segment: chars[5],
+            };
+            text = &una[chars.iter().map(|c| c.len_utf8()).sum::<usize>()..];
+        }
+    }
+
+    let mut segments = Vec::new();
+    let mut elements: Vec<Vec<String>> = vec![vec![String::new()]];
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == delimiters.release {
+            if let Some(next) = chars.next() {
+                elements.last_mut().unwrap().last_mut().unwrap().push(next);
+            }
+        } else if c == delimiters.segment {
+            let tag = elements[0][0].trim().to_string();
+            if !tag.is_empty() {
+                segments.push(Segment {
+                    number: segments.len() + 1,
+                    tag,
+                    elements: elements.split_off(1),
+                });
+            }
+            elements = vec![vec![String::new()]];
+        } else if c == delimiters.element {
+            elements.push(vec![String::new()]);
+        } else if c == delimiters.component {
+            elements.last_mut().unwrap().push(String::new());
+        } else if c != '\r' && c != '\n' {
+            elements.last_mut().unwrap().last_mut().unwrap().push(c);
+        }
+    }
+
+    (segments, delimiters.decimal)
+}
+
+/// `DTM` value with format 102 (`CCYYMMDD`), 203 (`CCYYMMDDHHMM`) or 303
+/// (`CCYYMMDDHHMM` with UTC offset in hours, e.g. `+01`). Returns the UTC date and offset.
+fn parse_date(value: &str, format: &str) -> Result<(NaiveDateTime, i64), String> {
+    let error = || format!("invalid date '{}' with format {}", value, format);
+    match format {
+        "102" => chrono::NaiveDate::parse_from_str(value, "%Y%m%d")
+            .map(|date| (date.and_hms_opt(0, 0, 0).unwrap(), 0))
+            .map_err(|_| error()),
+        "203" => NaiveDateTime::parse_from_str(value, "%Y%m%d%H%M")
+            .map(|date| (date, 0))
+            .map_err(|_| error()),
+        "303" => {
+            let local = value.get(..12).ok_or_else(error)?;
+            let local = NaiveDateTime::parse_from_str(local, "%Y%m%d%H%M").map_err(|_| error())?;
+            let zone = &value[12..];
+            let (sign, digits) = match zone.chars().next() {
+                Some('+') => (1, &zone[1..]),
+                Some('-') => (-1, &zone[1..]),
+                _ => return Err(error()),
+            };
+            let hours: i64 = digits
+                .get(..2)
+                .ok_or_else(error)?
+                .parse()
+                .map_err(|_| error())?;
+            let minutes: i64 = match digits.get(2..4) {
+                Some(minutes) => minutes.parse().map_err(|_| error())?,
+                None => 0,
+            };
+            let offset = sign * (hours * 3600 + minutes * 60);
+            Ok((local - Duration::seconds(offset), offset))
+        }
+        _ => Err(format!("unsupported date format {}", format)),
+    }
+}
+
+pub fn parse(text: &str) -> Result<MsconsDocument, String> {
+    let (segments, decimal) = segments(text);
+    if !segments.iter().any(|segment| segment.tag == "UNH") {
+        return Err("file contains no EDIFACT message".to_string());
+    }
+
+    let mut document = MsconsDocument {
+        locations: Vec::new(),
+        unsupported: Vec::new(),
+        utc_offset: 0,
+    };
+    let mut offset_found = false;
+
+    for segment in &segments {
+        if !KNOWN_SEGMENTS.contains(&segment.tag.as_str()) {
+            document.unsupported.push(segment.describe());
+            continue;
+        }
+
+        let location = document.locations.last_mut();
+        match (segment.tag.as_str(), location) {
+            ("UNH", _) => {
+                let message_type = segment.value(1, 0).unwrap_or_default();
+                if message_type != "MSCONS" {
+                    return Err(format!("unsupported message type '{}'", message_type));
+                }
+            }
+            ("LOC", _) => {
+                let id = segment
+                    .value(1, 0)
+                    .ok_or_else(|| format!("segment {}: LOC without id", segment.number))?;
+                document.locations.push(MsconsLocation {
+                    id: id.to_string(),
+                    date_start: None,
+                    date_end: None,
+                    series: Vec::new(),
+                });
+            }
+            ("LIN", Some(location)) => location.series.push(MsconsSeries {
+                obis: String::new(),
+                unit: None,
+                quantities: Vec::new(),
+            }),
+            ("PIA", Some(location)) => match location.series.last_mut() {
+                Some(series) => series.obis = segment.value(1, 0).unwrap_or_default().to_string(),
+                None => document.unsupported.push(segment.describe()),
+            },
+            ("QTY", Some(location)) => {
+                let series = match location.series.last_mut() {
+                    Some(series) => series,
+                    None => {
+                        document.unsupported.push(segment.describe());
+                        continue;
+                    }
+                };
+                let value = segment
+                    .value(0, 1)
+                    .ok_or_else(|| format!("segment {}: QTY without value", segment.number))?;
+                let value: f64 = value.replace(decimal, ".").parse().map_err(|_| {
+                    format!("segment {}: invalid quantity '{}'", segment.number, value)
+                })?;
+                if let Some(unit) = segment.value(0, 2) {
+                    series.unit = Some(unit.to_string());
+                }
+                series.quantities.push(MsconsQuantity {
+                    value,
+                    qualifier: segment.value(0, 0).unwrap_or_default().to_string(),
+                    date_start: None,
+                    date_end: None,
+                    date: None,
+                });
+            }
+            ("DTM", location) => {
+                let qualifier = segment.value(0, 0).unwrap_or_default();
+                if qualifier == "137" {
+                    continue;
+                }
+                let (date, offset) = parse_date(
+                    segment.value(0, 1).unwrap_or_default(),
+                    segment.value(0, 2).unwrap_or_default(),
+                )
+                .map_err(|err| format!("segment {}: {}", segment.number, err))?;
+                if !offset_found && segment.value(0, 2) == Some("303") {
+                    document.utc_offset = offset;
+                    offset_found = true;
+                }
+
+                let location = match location {
+                    Some(location) => location,
+                    None => {
+                        document.unsupported.push(segment.describe());
+                        continue;
+                    }
+                };
+                // dates after a quantity belong to it, otherwise to the location
+                let quantity = location
+                    .series
+                    .last_mut()
+                    .and_then(|series| series.quantities.last_mut());
+                match (qualifier, quantity) {
+                    ("163", Some(quantity)) => quantity.date_start = Some(date),
+                    ("164", Some(quantity)) => quantity.date_end = Some(date),
+                    ("7", Some(quantity)) | ("9", Some(quantity)) => quantity.date = Some(date),
+                    ("163", None) => location.date_start = Some(date),
+                    ("164", None) => location.date_end = Some(date),
+                    _ => document.unsupported.push(segment.describe()),
+                }
+            }
+            ("LIN", None) | ("PIA", None) | ("QTY", None) => {
+                document.unsupported.push(segment.describe())
+            }
+            _ => {}
+        }
+    }
+
+    if document.locations.is_empty() {
+        return Err("message contains no meter location".to_string());
+    }
+    Ok(document)
+}
+
+pub fn load(path: &str) -> Result<MsconsDocument, String> {
+    let bytes = std::fs::read(path).map_err(|err| err.to_string())?;
+    // UNOC is ISO 8859-1, the ids and numbers we need are ASCII either way
+    let text: String = match String::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(err) => err.into_bytes().iter().map(|byte| *byte as char).collect(),
+    };
+    parse(text.as_str())
+}
+
+fn choose<'a, T>(
+    items: &'a [T],
+    wanted: &Option<String>,
+    id: impl Fn(&T) -> &str,
+    name: &str,
+) -> Result<&'a T, String> {
+    match wanted {
+        Some(wanted) => items
+            .iter()
+            .find(|item| id(item) == wanted)
+            .ok_or_else(|| format!("{} '{}' not found", name, wanted)),
+        None if items.len() == 1 => Ok(&items[0]),
+        None => Err(format!("file contains several, please choose a {}", name)),
+    }
+}
+
+/// Imports the chosen series as interval data and meter readings of the meter of the location.
+pub fn import<R: Repository>(
+    repository: &mut R,
+    document: &MsconsDocument,
+    options: &MsconsImportOptions,
+) -> Result<IngestResult, String> {
+    let location = choose(
+        &document.locations,
+        &options.location,
+        |location| location.id.as_str(),
+        "location",
+    )?;
+    let series = choose(
+        &location.series,
+        &options.obis,
+        |series| series.obis.as_str(),
+        "OBIS code",
+    )?;
+    let meter = match options.meter_id {
+        Some(id) => repository.get_meter(id)?,
+        None => match repository.find_meter_by_external_id(location.id.as_str())? {
+            Some(meter) => meter,
+            None => repository.get_meter(DEFAULT_METER_ID)?,
+        },
+    };
+    meter.expect_kind(series.meter_kind())?;
+    let (factor, unit) = series.scale()?;
+    if unit != meter.kind.unit() {
+        return Err(format!(
+            "values in {} can't be stored for a meter counting {}",
+            unit,
+            meter.kind.unit()
+        ));
+    }
+
+    let mut filter = IntervalFilter::new(options.reading_interval_seconds)
+        .with_offset(document.utc_offset)
+        .resume(repository, meter.id)?;
+    match series.kind() {
+        MsconsSeriesKind::Counter => {
+            let mut result = IngestResult::default();
+            for quantity in &series.quantities {
+                result.received += 1;
+                match quantity.date.or(quantity.date_end).or(quantity.date_start) {
+                    Some(date) => result.store(
+                        repository,
+                        meter.id,
+                        &mut filter,
+                        quantity.value * factor,
+                        &date,
+                    ),
+                    None => result
+                        .errors
+                        .push(format!("counter value {} has no date", quantity.value)),
+                }
+            }
+            Ok(result)
+        }
+        MsconsSeriesKind::Interval => {
+            let intervals = series
+                .quantities
+                .iter()
+                .map(|quantity| match (quantity.date_start, quantity.date_end) {
+                    (Some(start), Some(end)) => Ok((start, end, quantity.value * factor)),
+                    _ => Err(format!("quantity {} has no period", quantity.value)),
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            store_interval_consumption(
+                repository,
+                meter.id,
+                filter,
+                &intervals,
+                options.start_value,
+            )
+        }
+        MsconsSeriesKind::Unknown => Err(format!("unsupported OBIS code '{}'", series.obis)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::fees::CreateFeeParams;
+    use crate::models::meter::{CreateMeterParams, MeterKind};
+    use crate::repository::{
+        FeeRepository, InMemoryRepository, MeterReadingRepository, MeterRepository,
+    };
+
+    use super::{import, parse, MsconsImportOptions, MsconsSeriesKind};
+
+    const MESSAGE: &str = "UNA:+,? '
+UNB+UNOC:3+9900000000003:500+9900000000004:500+230201:0800+REF1'
+UNH+1+MSCONS:D:04B:UN:2.4b'
+BGM+7+MSI5422+9'
+DTM+137:202302010800?+00:303'
+NAD+MS+9900000000003::293'
+UNS+D'
+NAD+DP'
+LOC+172+DE0001234567890000000000000012345'
+DTM+163:202301010000?+01:303'
+DTM+164:202301010030?+01:303'
+LIN+1'
+PIA+5+1-1?:1.29.0:SRW'
+QTY+220:0,25:KWH'
+DTM+163:202301010000?+01:303'
+DTM+164:202301010015?+01:303'
+QTY+67:0,5'
+DTM+163:202301010015?+01:303'
+DTM+164:202301010030?+01:303'
+FTX+ACB+++Hinweis'
+LIN+2'
+PIA+5+1-1?:1.8.0:SRW'
+QTY+220:1234,5:KWH'
+DTM+7:202301010000?+01:303'
+UNT+22+1'
+UNZ+1+REF1'";
+
+    #[test]
+    fn parses_message() {
+        let document = parse(MESSAGE).unwrap();
+        assert_eq!(document.utc_offset, 3600);
+        assert_eq!(
+            document.unsupported,
+            vec!["segment 19: FTX+ACB".to_string()]
+        );
+
+        let location = &document.locations[0];
+        assert_eq!(location.id, "DE0001234567890000000000000012345");
+        assert_eq!(
+            location.date_start.unwrap().to_string(),
+            "2022-12-31 23:00:00"
+        );
+        assert_eq!(location.series.len(), 2);
+
+        let profile = &location.series[0];
+        assert_eq!(profile.obis, "1-1:1.29.0");
+        assert_eq!(profile.kind(), MsconsSeriesKind::Interval);
+        assert_eq!(profile.unit.as_deref(), Some("KWH"));
+        assert_eq!(profile.quantities[1].value, 0.5);
+        assert_eq!(profile.quantities[1].qualifier, "67");
+
+        let counter = &location.series[1];
+        assert_eq!(counter.kind(), MsconsSeriesKind::Counter);
+        assert_eq!(counter.quantities[0].value, 1234.5);
+    }
+
+    #[test]
+    fn imports_series() {
+        let mut repository = InMemoryRepository::new();
+        repository
+            .create_fee(CreateFeeParams {
+                meter_id: None,
+                base_fee: 10.0,
+                price_per_unit: 0.3,
+                monthly_discount: 50.0,
+                spot_surcharge: None,
+                blocks: vec![],
+                net_prices: false,
+                price_brake: None,
+                date_start: "2022-12-01T00:00:00.000Z".to_string(),
+                date_end: "2023-12-31T00:00:00.000Z".to_string(),
+            })
+            .unwrap();
+        let document = parse(MESSAGE).unwrap();
+
+        let result = import(&mut repository, &document, &MsconsImportOptions::default());
+        assert!(result.is_err());
+
+        let options = MsconsImportOptions {
+            obis: Some("1-1:1.8.0".to_string()),
+            ..Default::default()
+        };
+        let result = import(&mut repository, &document, &options).unwrap();
+        assert_eq!(result.stored, 1);
+        assert_eq!(repository.list_meter_readings().unwrap()[0].value, 1234.5);
+    }
+
+    #[test]
+    fn imports_for_the_meter_of_the_location() {
+        let mut repository = InMemoryRepository::new();
+        let meter = repository
+            .create_meter(CreateMeterParams {
+                name: "Wärmepumpe".to_string(),
+                kind: MeterKind::Consumption,
+                external_id: Some("DE0001234567890000000000000012345".to_string()),
+            })
+            .unwrap();
+        repository
+            .create_fee(CreateFeeParams {
+                meter_id: Some(meter.id),
+                base_fee: 10.0,
+                price_per_unit: 0.3,
+                monthly_discount: 50.0,
+                spot_surcharge: None,
+                blocks: vec![],
+                net_prices: false,
+                price_brake: None,
+                date_start: "2022-12-01T00:00:00.000Z".to_string(),
+                date_end: "2023-12-31T00:00:00.000Z".to_string(),
+            })
+            .unwrap();
+        let options = MsconsImportOptions {
+            obis: Some("1-1:1.8.0".to_string()),
+            ..Default::default()
+        };
+
+        let document = parse(MESSAGE.replace("1234,5:KWH", "1234,5:KVARH").as_str()).unwrap();
+        assert!(import(&mut repository, &document, &options).is_err());
+
+        // Wh are stored as kWh
+        let document = parse(MESSAGE.replace("1234,5:KWH", "1234567:WH").as_str()).unwrap();
+        let result = import(&mut repository, &document, &options).unwrap();
+        assert_eq!(result.stored, 1);
+        let readings = repository.list_meter_readings().unwrap();
+        assert_eq!(readings[0].meter_id, meter.id);
+        assert!((readings[0].value - 1234.567).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,241 @@
+//! Day-ahead prices of dynamic tariffs from CSV or JSON files.
+//!
+//! JSON files may be a list of `{"dateStart": ..., "price": ...}` objects, a response of the
+//! aWATTar API (`data` with `start_timestamp` in milliseconds and `marketprice`) or of the
+//! energy-charts API (`unix_seconds` and `price` arrays). Prices of shorter periods, e.g.
+//! quarter hours, are averaged per hour.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::models::interval::Resolution;
+use crate::models::parse_datetime;
+use crate::models::spot_price::SpotPrice;
+use crate::repository::SpotPriceRepository;
+
+use super::meter_readings_csv::{
+    column_index, csv_delimiter, parse_date, parse_decimal, CsvImportOptions,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PriceUnit {
+    #[serde(rename = "eurPerMwh")]
+    EurPerMwh,
+    #[serde(rename = "ctPerKwh")]
+    CentPerKwh,
+    #[serde(rename = "eurPerKwh")]
+    EurPerKwh,
+}
+
+impl PriceUnit {
+    pub fn parse(name: &str) -> Result<PriceUnit, String> {
+        match name {
+            "eurPerMwh" => Ok(PriceUnit::EurPerMwh),
+            "ctPerKwh" => Ok(PriceUnit::CentPerKwh),
+            "eurPerKwh" => Ok(PriceUnit::EurPerKwh),
+            _ => Err("expected one of eurPerMwh, ctPerKwh, eurPerKwh".to_string()),
+        }
+    }
+
+    pub fn to_eur_per_kwh(&self, value: f64) -> f64 {
+        match self {
+            PriceUnit::EurPerMwh => value / 1000.0,
+            PriceUnit::CentPerKwh => value / 100.0,
+            PriceUnit::EurPerKwh => value,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SpotPriceImportOptions {
+    /// Unit of the prices in the file, the market publishes €/MWh
+    pub unit: PriceUnit,
+    /// Column mapping of CSV files, the value column holds the price and dates are UTC
+    pub csv: Option<CsvImportOptions>,
+}
+
+/// Averages the prices per hour and converts them to €/kWh.
+fn hourly(prices: Vec<(NaiveDateTime, f64)>, unit: PriceUnit) -> Vec<SpotPrice> {
+    let mut hours: BTreeMap<NaiveDateTime, (f64, u32)> = BTreeMap::new();
+    for (date, price) in prices {
+        let hour = hours
+            .entry(Resolution::Hour.bucket_start(&date))
+            .or_insert((0.0, 0));
+        hour.0 += price;
+        hour.1 += 1;
+    }
+
+    hours
+        .into_iter()
+        .map(|(date_start, (sum, count))| SpotPrice {
+            date_start,
+            price: unit.to_eur_per_kwh(sum / count as f64),
+        })
+        .collect()
+}
+
+pub fn parse_csv<R: Read>(
+    reader: R,
+    options: &CsvImportOptions,
+    unit: PriceUnit,
+) -> Result<Vec<SpotPrice>, String> {
+    let delimiter = csv_delimiter(options.delimiter)?;
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(options.has_header)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+
+    let headers = if options.has_header {
+        Some(reader.headers().map_err(|err| err.to_string())?.clone())
+    } else {
+        None
+    };
+    let date_index = column_index(&options.date_column, headers.as_ref())?;
+    let price_index = column_index(&options.value_column, headers.as_ref())?;
+
+    let mut prices = vec![];
+    for record in reader.records() {
+        let record = record.map_err(|err| err.to_string())?;
+        let line = record.position().map(|pos| pos.line()).unwrap_or(0);
+        let (date, price) = match (record.get(date_index), record.get(price_index)) {
+            (Some(date), Some(price)) => (date, price),
+            _ => return Err(format!("line {}: missing column", line)),
+        };
+        let date = parse_date(date, options.date_format.as_str())
+            .map_err(|err| format!("line {}: {}", line, err))?;
+        let price = parse_decimal(price, options.decimal_separator)
+            .map_err(|err| format!("line {}: {}", line, err))?;
+        prices.push((date, price as f64));
+    }
+
+    Ok(hourly(prices, unit))
+}
+
+fn timestamp(value: Option<&Value>, divisor: i64) -> Option<NaiveDateTime> {
+    NaiveDateTime::from_timestamp_opt(value?.as_i64()? / divisor, 0)
+}
+
+pub fn parse_json(text: &str, unit: PriceUnit) -> Result<Vec<SpotPrice>, String> {
+    let json: Value = serde_json::from_str(text).map_err(|err| err.to_string())?;
+    let invalid = |entry: &Value| format!("invalid price entry {}", entry);
+
+    let mut prices = vec![];
+    if let Some(entries) = json.as_array() {
+        for entry in entries {
+            let date = entry
+                .get("dateStart")
+                .and_then(Value::as_str)
+                .ok_or_else(|| invalid(entry))?;
+            let price = entry
+                .get("price")
+                .and_then(Value::as_f64)
+                .ok_or_else(|| invalid(entry))?;
+            prices.push((parse_datetime(date)?, price));
+        }
+    } else if let Some(entries) = json.get("data").and_then(Value::as_array) {
+        for entry in entries {
+            let date =
+                timestamp(entry.get("start_timestamp"), 1000).ok_or_else(|| invalid(entry))?;
+            let price = entry
+                .get("marketprice")
+                .and_then(Value::as_f64)
+                .ok_or_else(|| invalid(entry))?;
+            prices.push((date, price));
+        }
+    } else if let (Some(dates), Some(values)) = (
+        json.get("unix_seconds").and_then(Value::as_array),
+        json.get("price").and_then(Value::as_array),
+    ) {
+        // hours without a price yet are null
+        for (date, price) in dates.iter().zip(values) {
+            if let (Some(date), Some(price)) = (timestamp(Some(date), 1), price.as_f64()) {
+                prices.push((date, price));
+            }
+        }
+    } else {
+        return Err("unknown JSON format of spot prices".to_string());
+    }
+
+    Ok(hourly(prices, unit))
+}
+
+/// Files ending with `.json` are read as JSON, everything else as CSV.
+pub fn load(path: &str, options: &SpotPriceImportOptions) -> Result<Vec<SpotPrice>, String> {
+    if path.to_lowercase().ends_with(".json") {
+        let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        return parse_json(text.as_str(), options.unit);
+    }
+
+    let csv = match &options.csv {
+        Some(csv) => csv,
+        None => return Err("CSV files need a column mapping".to_string()),
+    };
+    let file = File::open(path).map_err(|err| err.to_string())?;
+    parse_csv(file, csv, options.unit)
+}
+
+/// Stores the prices, prices of hours that are already stored are replaced.
+pub fn import<R: SpotPriceRepository>(
+    repository: &mut R,
+    prices: &[SpotPrice],
+) -> Result<usize, String> {
+    repository.upsert_spot_prices(prices)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::import::meter_readings_csv::{CsvColumn, CsvImportOptions};
+
+    use super::{parse_csv, parse_json, PriceUnit};
+
+    #[test]
+    fn json_formats() {
+        let list = r#"[
+            {"dateStart": "2023-01-15T10:00:00.000Z", "price": 0.12},
+            {"dateStart": "2023-01-15T11:00:00.000Z", "price": 0.10}
+        ]"#;
+        let prices = parse_json(list, PriceUnit::EurPerKwh).unwrap();
+        assert_eq!(prices.len(), 2);
+        assert_eq!(prices[1].price, 0.10);
+
+        let awattar = r#"{"object": "list", "data": [
+            {"start_timestamp": 1673776800000, "end_timestamp": 1673780400000, "marketprice": 120.0, "unit": "Eur/MWh"}
+        ]}"#;
+        let prices = parse_json(awattar, PriceUnit::EurPerMwh).unwrap();
+        assert_eq!(prices[0].date_start.to_string(), "2023-01-15 10:00:00");
+        assert_eq!(prices[0].price, 0.12);
+
+        let energy_charts = r#"{"unix_seconds": [1673776800, 1673777700, 1673778600, 1673779500, 1673780400],
+            "price": [100.0, 110.0, 130.0, 140.0, null], "unit": "EUR / MWh"}"#;
+        let prices = parse_json(energy_charts, PriceUnit::EurPerMwh).unwrap();
+        assert_eq!(prices.len(), 1);
+        assert_eq!(prices[0].price, 0.12);
+
+        assert!(parse_json("{}", PriceUnit::EurPerMwh).is_err());
+    }
+
+    #[test]
+    fn csv_file() {
+        let options = CsvImportOptions {
+            delimiter: ';',
+            has_header: true,
+            date_column: CsvColumn::Name("Datum".to_string()),
+            value_column: CsvColumn::Name("Preis".to_string()),
+            date_format: "%d.%m.%Y %H:%M".to_string(),
+            decimal_separator: ',',
+            meter_id: 1,
+        };
+        let file = "Datum;Preis\n15.01.2023 10:00;12,5\n15.01.2023 11:00;-0,5\n";
+        let prices = parse_csv(file.as_bytes(), &options, PriceUnit::CentPerKwh).unwrap();
+        assert_eq!(prices.len(), 2);
+        assert_eq!(prices[0].price, 0.125);
+        assert_eq!(prices[1].price, -0.005);
+    }
+}
@@ -0,0 +1,10 @@
+#[cfg(feature = "api")]
+pub mod api;
+pub mod calculation;
+pub mod db;
+pub mod export;
+pub mod import;
+pub mod models;
+pub mod report;
+pub mod repository;
+pub mod smart_meter;
@@ -0,0 +1,28 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateAdjustmentParams {
+    #[serde(rename = "feeId")]
+    pub fee_id: i32,
+    pub amount: f32,
+    /// `YYYY-MM-DD`
+    pub date: String,
+    pub description: String,
+    pub taxable: bool,
+}
+
+/// One-off charge or credit of a fee period, e.g. a sign-up bonus or a reminder fee.
+/// Charges are positive and credits negative, taxable amounts are net or gross like the
+/// prices of the fee.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Adjustment {
+    pub id: i32,
+    #[serde(rename = "feeId")]
+    pub fee_id: i32,
+    pub amount: f32,
+    pub date: NaiveDate,
+    pub description: String,
+    /// VAT is charged on the amount
+    pub taxable: bool,
+}
@@ -0,0 +1,152 @@
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+use super::meter::default_meter_id;
+
+/// Block of a tiered tariff, consumption up to `up_to` kWh per year is charged at
+/// `price_per_unit`. Consumption above the last block is charged at the price of the fee.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PriceBlock {
+    #[serde(rename = "upTo")]
+    pub up_to: f32,
+    #[serde(rename = "pricePerUnit")]
+    pub price_per_unit: f32,
+}
+
+/// Blocks have to be ordered by their threshold.
+pub fn validate_blocks(blocks: &[PriceBlock]) -> Result<(), String> {
+    let mut previous = 0.0;
+    for block in blocks {
+        if block.up_to <= previous {
+            return Err(format!(
+                "block thresholds have to be ascending, {} follows {}",
+                block.up_to, previous
+            ));
+        }
+        previous = block.up_to;
+    }
+    Ok(())
+}
+
+/// Energy price brake of 2023: `contingent` percent of the forecast consumption are charged
+/// at `cap_price` instead of the price of the fee. The relief only depends on the forecast
+/// and is deducted from the advance payments of the months from `date_start` to `date_end`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PriceBrake {
+    /// kWh per year forecast by the supplier
+    pub forecast: f32,
+    /// Percent of the forecast
+    #[serde(default = "default_contingent")]
+    pub contingent: f32,
+    /// Price per kWh, net or gross like the prices of the fee
+    #[serde(rename = "capPrice")]
+    pub cap_price: f32,
+    #[serde(rename = "dateStart", default = "default_brake_start")]
+    pub date_start: NaiveDate,
+    #[serde(rename = "dateEnd", default = "default_brake_end")]
+    pub date_end: NaiveDate,
+}
+
+pub const DEFAULT_CONTINGENT: f32 = 80.0;
+
+fn default_contingent() -> f32 {
+    DEFAULT_CONTINGENT
+}
+
+pub fn default_brake_start() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2023, 1, 1).unwrap()
+}
+
+pub fn default_brake_end() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2023, 12, 31).unwrap()
+}
+
+impl PriceBrake {
+    /// Relief per month for the price of the fee, nothing if the price is below the cap.
+    pub fn monthly_relief(&self, price_per_unit: f32) -> f32 {
+        let contingent = self.forecast * self.contingent / 100.0;
+        (price_per_unit - self.cap_price).max(0.0) * contingent / 12.0
+    }
+}
+
+pub fn validate_price_brake(brake: &PriceBrake) -> Result<(), String> {
+    if brake.forecast < 0.0 || !(0.0..=100.0).contains(&brake.contingent) {
+        return Err("price brake needs a forecast and a contingent of 0 to 100 %".to_string());
+    }
+    if brake.date_end < brake.date_start {
+        return Err("price brake ends before it starts".to_string());
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateFeeParams {
+    /// The default meter if not set
+    #[serde(rename = "meterId", default)]
+    pub meter_id: Option<i32>,
+    #[serde(rename = "baseFee")]
+    pub base_fee: f32,
+    #[serde(rename = "pricePerUnit")]
+    pub price_per_unit: f32,
+    #[serde(rename = "monthlyDiscount")]
+    pub monthly_discount: f32,
+    /// Set for dynamic tariffs: consumption with interval data is charged at the hourly
+    /// spot price plus this surcharge per kWh, the rest at `price_per_unit`
+    #[serde(rename = "spotSurcharge", default)]
+    pub spot_surcharge: Option<f32>,
+    /// Tiered tariff, empty if every kWh costs `price_per_unit`
+    #[serde(default)]
+    pub blocks: Vec<PriceBlock>,
+    /// Prices exclude VAT, which is added for the rates of the period
+    #[serde(rename = "netPrices", default)]
+    pub net_prices: bool,
+    #[serde(rename = "priceBrake", default)]
+    pub price_brake: Option<PriceBrake>,
+    #[serde(rename = "dateStart")]
+    pub date_start: String,
+    #[serde(rename = "dateEnd")]
+    pub date_end: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Fee {
+    pub id: i32,
+    /// Meter whose readings are charged, periods of one meter don't overlap
+    #[serde(rename = "meterId", default = "default_meter_id")]
+    pub meter_id: i32,
+    #[serde(rename = "baseFee")]
+    pub base_fee: f32,
+    #[serde(rename = "pricePerUnit")]
+    pub price_per_unit: f32,
+    #[serde(rename = "monthlyDiscount")]
+    pub monthly_discount: f32,
+    /// Set for dynamic tariffs: consumption with interval data is charged at the hourly
+    /// spot price plus this surcharge per kWh, the rest at `price_per_unit`
+    #[serde(rename = "spotSurcharge", default)]
+    pub spot_surcharge: Option<f32>,
+    /// Tiered tariff, empty if every kWh costs `price_per_unit`
+    #[serde(default)]
+    pub blocks: Vec<PriceBlock>,
+    /// Prices exclude VAT, which is added for the rates of the period
+    #[serde(rename = "netPrices", default)]
+    pub net_prices: bool,
+    #[serde(rename = "priceBrake", default)]
+    pub price_brake: Option<PriceBrake>,
+    #[serde(rename = "dateStart")]
+    pub date_start: NaiveDateTime,
+    #[serde(rename = "dateEnd")]
+    pub date_end: NaiveDateTime,
+}
+
+impl Fee {
+    /// Share of a year covered by the period, block thresholds are multiplied with it.
+    pub fn year_share(&self) -> f32 {
+        let start = self.date_start.date();
+        let year_later = start
+            .with_year(start.year() + 1)
+            .unwrap_or_else(|| start + chrono::Duration::days(365));
+        // the last day belongs to the period
+        let days = (self.date_end.date() - start).num_days() + 1;
+        days.max(0) as f32 / (year_later - start).num_days() as f32
+    }
+}
@@ -0,0 +1,105 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+/// Raw samples older than this are deleted, their buckets are kept.
+pub const DEFAULT_RAW_RETENTION_DAYS: i64 = 30;
+
+/// Bucket size of the aggregated interval data, buckets are aligned to UTC.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Resolution {
+    #[serde(rename = "15min")]
+    QuarterHour,
+    #[serde(rename = "hour")]
+    Hour,
+    #[serde(rename = "day")]
+    Day,
+}
+
+impl Resolution {
+    pub const ALL: [Resolution; 3] = [Resolution::QuarterHour, Resolution::Hour, Resolution::Day];
+
+    pub fn seconds(&self) -> i64 {
+        match self {
+            Resolution::QuarterHour => 15 * 60,
+            Resolution::Hour => 60 * 60,
+            Resolution::Day => 24 * 60 * 60,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Resolution::QuarterHour => "15min",
+            Resolution::Hour => "hour",
+            Resolution::Day => "day",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Resolution, String> {
+        Resolution::ALL
+            .into_iter()
+            .find(|resolution| resolution.name() == name)
+            .ok_or_else(|| format!("unknown resolution '{}'", name))
+    }
+
+    pub fn bucket_start(&self, date: &NaiveDateTime) -> NaiveDateTime {
+        let seconds = date.timestamp();
+        let start = seconds - seconds.rem_euclid(self.seconds());
+        NaiveDateTime::from_timestamp_opt(start, 0).unwrap_or(*date)
+    }
+}
+
+/// A counter value (kWh) as sent by a smart meter.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IntervalSample {
+    pub date: NaiveDateTime,
+    pub value: f64,
+}
+
+/// First and last sample of a bucket.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IntervalBucket {
+    pub resolution: Resolution,
+    #[serde(rename = "dateStart")]
+    pub date_start: NaiveDateTime,
+    #[serde(rename = "dateFirst")]
+    pub date_first: NaiveDateTime,
+    #[serde(rename = "valueFirst")]
+    pub value_first: f64,
+    #[serde(rename = "dateLast")]
+    pub date_last: NaiveDateTime,
+    #[serde(rename = "valueLast")]
+    pub value_last: f64,
+    pub samples: i64,
+}
+
+impl IntervalBucket {
+    pub fn new(resolution: Resolution, sample: &IntervalSample) -> IntervalBucket {
+        IntervalBucket {
+            resolution,
+            date_start: resolution.bucket_start(&sample.date),
+            date_first: sample.date,
+            value_first: sample.value,
+            date_last: sample.date,
+            value_last: sample.value,
+            samples: 1,
+        }
+    }
+
+    /// Adds a sample of the same bucket, samples may arrive out of order.
+    pub fn add(&mut self, sample: &IntervalSample) {
+        if sample.date < self.date_first {
+            self.date_first = sample.date;
+            self.value_first = sample.value;
+        }
+        if sample.date > self.date_last {
+            self.date_last = sample.date;
+            self.value_last = sample.value;
+        }
+        self.samples += 1;
+    }
+
+    /// The date lies between the first and the last sample of the bucket.
+    pub fn covers(&self, date: &NaiveDateTime) -> bool {
+        self.date_first <= *date && *date <= self.date_last
+    }
+}
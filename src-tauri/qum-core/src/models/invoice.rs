@@ -0,0 +1,71 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CreateInvoiceParams {
+    /// Picked by the billing period if not set
+    #[serde(rename = "feeId", default)]
+    pub fee_id: Option<i32>,
+    /// Meter whose fee is picked, the default meter if not set
+    #[serde(rename = "meterId", default)]
+    pub meter_id: Option<i32>,
+    #[serde(default)]
+    pub number: String,
+    /// `YYYY-MM-DD`
+    #[serde(rename = "dateStart")]
+    pub date_start: String,
+    /// `YYYY-MM-DD`
+    #[serde(rename = "dateEnd")]
+    pub date_end: String,
+    pub consumption: f32,
+    #[serde(rename = "readingStart", default)]
+    pub reading_start: Option<f32>,
+    #[serde(rename = "readingEnd", default)]
+    pub reading_end: Option<f32>,
+    #[serde(default)]
+    pub estimated: bool,
+    #[serde(rename = "netAmount")]
+    pub net_amount: f32,
+    #[serde(rename = "taxAmount")]
+    pub tax_amount: f32,
+    #[serde(rename = "grossAmount")]
+    pub gross_amount: f32,
+    #[serde(rename = "advancePayments")]
+    pub advance_payments: f32,
+    pub balance: f32,
+}
+
+/// Annual invoice of the supplier as billed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Invoice {
+    pub id: i32,
+    #[serde(rename = "feeId")]
+    pub fee_id: i32,
+    pub number: String,
+    /// First day of the billing period
+    #[serde(rename = "dateStart")]
+    pub date_start: NaiveDate,
+    /// Last day of the billing period
+    #[serde(rename = "dateEnd")]
+    pub date_end: NaiveDate,
+    /// Billed kWh
+    pub consumption: f32,
+    /// Meter values at the start and the end of the billing period
+    #[serde(rename = "readingStart")]
+    pub reading_start: Option<f32>,
+    #[serde(rename = "readingEnd")]
+    pub reading_end: Option<f32>,
+    /// The supplier estimated a meter value instead of reading it
+    pub estimated: bool,
+    #[serde(rename = "netAmount")]
+    pub net_amount: f32,
+    #[serde(rename = "taxAmount")]
+    pub tax_amount: f32,
+    #[serde(rename = "grossAmount")]
+    pub gross_amount: f32,
+    /// Advance payments deducted on the invoice
+    #[serde(rename = "advancePayments")]
+    pub advance_payments: f32,
+    /// Positive values are a credit, negative values have to be paid.
+    pub balance: f32,
+}
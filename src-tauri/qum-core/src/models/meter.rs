@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+
+/// The meter of the readings stored before there were several meters, it's created with
+/// the database and can't be deleted.
+pub const DEFAULT_METER_ID: i32 = 1;
+
+/// What a meter or register counts, readings of different kinds are never mixed.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeterKind {
+    /// Electricity drawn from the grid
+    #[serde(rename = "consumption")]
+    Consumption,
+    /// Electricity fed into the grid, e.g. by a PV system
+    #[serde(rename = "feedIn")]
+    FeedIn,
+    #[serde(rename = "gas")]
+    Gas,
+}
+
+impl MeterKind {
+    pub fn parse(name: &str) -> Result<MeterKind, String> {
+        match name {
+            "consumption" => Ok(MeterKind::Consumption),
+            "feedIn" => Ok(MeterKind::FeedIn),
+            "gas" => Ok(MeterKind::Gas),
+            _ => Err(format!(
+                "invalid meter kind '{}', expected one of consumption, feedIn, gas",
+                name
+            )),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MeterKind::Consumption => "consumption",
+            MeterKind::FeedIn => "feedIn",
+            MeterKind::Gas => "gas",
+        }
+    }
+
+    /// Unit of the readings and of the price per unit of the fees.
+    pub fn unit(&self) -> &'static str {
+        match self {
+            MeterKind::Consumption | MeterKind::FeedIn => "kWh",
+            MeterKind::Gas => "m³",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateMeterParams {
+    pub name: String,
+    pub kind: MeterKind,
+    #[serde(rename = "externalId", default)]
+    pub external_id: Option<String>,
+}
+
+/// A meter or one register of it, every meter has its own readings, interval data and fees.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Meter {
+    pub id: i32,
+    pub name: String,
+    pub kind: MeterKind,
+    /// Id of the meter at the grid operator or the supplier, e.g. the MeLo/MaLo of MSCONS
+    /// messages or the usage point of Green Button files. Imports find the meter by it.
+    #[serde(rename = "externalId")]
+    pub external_id: Option<String>,
+}
+
+impl Meter {
+    /// Fails with a message naming the meter if it doesn't count `kind`.
+    pub fn expect_kind(&self, kind: MeterKind) -> Result<(), String> {
+        match self.kind == kind {
+            true => Ok(()),
+            false => Err(format!(
+                "meter {} ({}) counts {} in {}, not {} in {}",
+                self.id,
+                self.name,
+                self.kind.name(),
+                self.kind.unit(),
+                kind.name(),
+                kind.unit()
+            )),
+        }
+    }
+}
+
+pub fn default_meter_id() -> i32 {
+    DEFAULT_METER_ID
+}
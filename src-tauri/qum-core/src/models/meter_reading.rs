@@ -0,0 +1,22 @@
+use super::fees::Fee;
+use serde::{Deserialize, Serialize};
+
+/// The reading belongs to the meter of the fee.
+#[derive(Serialize, Deserialize)]
+pub struct CreateMeterReadingParams {
+    pub value: f32,
+    #[serde(rename = "feeId")]
+    pub fee_id: i32,
+    #[serde(rename = "readingDate")]
+    pub reading_date: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MeterReading {
+    pub id: i32,
+    #[serde(rename = "meterId")]
+    pub meter_id: i32,
+    pub value: f32,
+    pub fee: Fee,
+    pub date: chrono::NaiveDateTime,
+}
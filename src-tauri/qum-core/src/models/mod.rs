@@ -0,0 +1,30 @@
+use chrono::{NaiveDate, NaiveDateTime};
+
+pub mod adjustment;
+pub mod fees;
+pub mod interval;
+pub mod invoice;
+pub mod meter;
+pub mod meter_reading;
+pub mod payment;
+pub mod price_component;
+pub mod spot_price;
+pub mod tariff;
+pub mod vat;
+
+/// Parses a date the way the frontend stores them (`Date.toISOString()`).
+pub fn parse_datetime(datetime: &str) -> Result<NaiveDateTime, String> {
+    NaiveDateTime::parse_from_str(datetime, "%Y-%m-%dT%H:%M:%S%.fZ")
+        .map_err(|err| format!("invalid date '{}': {}", datetime, err))
+}
+
+/// Formats a date the same way the frontend stores them (`Date.toISOString()`).
+pub fn format_datetime(datetime: &NaiveDateTime) -> String {
+    datetime.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()
+}
+
+/// Parses a day like `2023-12-25`.
+pub fn parse_date(date: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|err| format!("invalid date '{}': {}", date, err))
+}
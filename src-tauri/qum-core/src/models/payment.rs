@@ -0,0 +1,52 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreatePaymentParams {
+    /// Picked by the date if not set
+    #[serde(rename = "feeId", default)]
+    pub fee_id: Option<i32>,
+    /// Meter whose fee is picked, the default meter if not set
+    #[serde(rename = "meterId", default)]
+    pub meter_id: Option<i32>,
+    /// `YYYY-MM-DD`
+    pub date: String,
+    pub amount: f32,
+}
+
+/// Advance payment actually debited, refunds are negative.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Payment {
+    pub id: i32,
+    #[serde(rename = "feeId")]
+    pub fee_id: i32,
+    pub date: NaiveDate,
+    pub amount: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreatePaymentRuleParams {
+    #[serde(rename = "creditorId", default)]
+    pub creditor_id: Option<String>,
+    #[serde(default)]
+    pub text: Option<String>,
+}
+
+/// Recognises the direct debits of the supplier in bank statements, by the SEPA creditor
+/// ID or by a text in the name of the creditor or the purpose of the transfer.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PaymentRule {
+    pub id: i32,
+    #[serde(rename = "creditorId")]
+    pub creditor_id: Option<String>,
+    pub text: Option<String>,
+}
+
+/// Upper case without spaces, creditor IDs are printed in groups.
+pub fn normalize_creditor_id(creditor_id: &str) -> String {
+    creditor_id
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_uppercase()
+}
@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+
+/// Parts of the price as they are listed on the invoice of the supplier.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComponentKind {
+    /// Not stored, the energy costs are what is left of the total costs after the other
+    /// components, so they include dynamic, time-of-use and tiered prices
+    #[serde(rename = "energy")]
+    Energy,
+    #[serde(rename = "gridFee")]
+    GridFee,
+    #[serde(rename = "meteringPoint")]
+    MeteringPoint,
+    #[serde(rename = "concessionLevy")]
+    ConcessionLevy,
+    #[serde(rename = "surcharge")]
+    Surcharge,
+    #[serde(rename = "electricityTax")]
+    ElectricityTax,
+    #[serde(rename = "vat")]
+    Vat,
+    /// Not stored, one-off charges and credits of the fee period
+    #[serde(rename = "adjustment")]
+    Adjustment,
+}
+
+impl ComponentKind {
+    pub fn parse(name: &str) -> Result<ComponentKind, String> {
+        match name {
+            "energy" => Ok(ComponentKind::Energy),
+            "gridFee" => Ok(ComponentKind::GridFee),
+            "meteringPoint" => Ok(ComponentKind::MeteringPoint),
+            "concessionLevy" => Ok(ComponentKind::ConcessionLevy),
+            "surcharge" => Ok(ComponentKind::Surcharge),
+            "electricityTax" => Ok(ComponentKind::ElectricityTax),
+            "vat" => Ok(ComponentKind::Vat),
+            "adjustment" => Ok(ComponentKind::Adjustment),
+            _ => Err(format!(
+                "invalid component '{}', expected one of gridFee, meteringPoint, concessionLevy, surcharge, electricityTax, vat",
+                name
+            )),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ComponentKind::Energy => "energy",
+            ComponentKind::GridFee => "gridFee",
+            ComponentKind::MeteringPoint => "meteringPoint",
+            ComponentKind::ConcessionLevy => "concessionLevy",
+            ComponentKind::Surcharge => "surcharge",
+            ComponentKind::ElectricityTax => "electricityTax",
+            ComponentKind::Vat => "vat",
+            ComponentKind::Adjustment => "adjustment",
+        }
+    }
+
+    pub fn is_tax(&self) -> bool {
+        matches!(self, ComponentKind::ElectricityTax | ComponentKind::Vat)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreatePriceComponentParams {
+    #[serde(rename = "feeId")]
+    pub fee_id: i32,
+    pub kind: ComponentKind,
+    /// e.g. the name of a surcharge, defaults to the kind
+    #[serde(default)]
+    pub name: String,
+    #[serde(rename = "baseFee", default)]
+    pub base_fee: f32,
+    #[serde(rename = "pricePerUnit", default)]
+    pub price_per_unit: f32,
+    /// VAT rate in percent
+    #[serde(default)]
+    pub rate: f32,
+}
+
+/// Share of the prices of a fee. Components are net, the prices of the fee include them
+/// and the VAT, which is `rate` percent of the net amount.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PriceComponent {
+    pub id: i32,
+    #[serde(rename = "feeId")]
+    pub fee_id: i32,
+    pub kind: ComponentKind,
+    pub name: String,
+    /// Per month
+    #[serde(rename = "baseFee")]
+    pub base_fee: f32,
+    /// Per kWh
+    #[serde(rename = "pricePerUnit")]
+    pub price_per_unit: f32,
+    /// VAT rate in percent, 0 for the other kinds
+    pub rate: f32,
+}
+
+/// VAT components need a rate and no prices, energy and adjustments are not stored.
+pub fn validate_component(params: &CreatePriceComponentParams) -> Result<(), String> {
+    match params.kind {
+        ComponentKind::Energy => {
+            Err("energy costs are the rest of the costs and can't be stored".to_string())
+        }
+        ComponentKind::Adjustment => Err("adjustments are stored separately".to_string()),
+        ComponentKind::Vat if params.rate <= 0.0 => Err("VAT needs a rate".to_string()),
+        ComponentKind::Vat if params.base_fee != 0.0 || params.price_per_unit != 0.0 => {
+            Err("VAT has a rate instead of prices".to_string())
+        }
+        kind if kind != ComponentKind::Vat && params.rate != 0.0 => {
+            Err("only VAT has a rate".to_string())
+        }
+        _ => Ok(()),
+    }
+}
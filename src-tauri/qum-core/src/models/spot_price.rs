@@ -0,0 +1,10 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+/// Day-ahead market price of one hour in €/kWh, without surcharges and taxes.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SpotPrice {
+    #[serde(rename = "dateStart")]
+    pub date_start: NaiveDateTime,
+    pub price: f64,
+}
@@ -0,0 +1,167 @@
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateTariffRuleParams {
+    #[serde(rename = "feeId")]
+    pub fee_id: i32,
+    pub name: String,
+    pub weekdays: Vec<u32>,
+    pub holidays: bool,
+    /// `HH:MM`
+    #[serde(rename = "timeStart")]
+    pub time_start: String,
+    /// `HH:MM`
+    #[serde(rename = "timeEnd")]
+    pub time_end: String,
+    #[serde(rename = "pricePerUnit")]
+    pub price_per_unit: f32,
+}
+
+/// Price window of a time-of-use tariff, e.g. the low tariff from 22:00 to 06:00.
+/// Times are local, a window ending before it starts runs over midnight and equal times
+/// cover the whole day.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TariffRule {
+    pub id: i32,
+    #[serde(rename = "feeId")]
+    pub fee_id: i32,
+    pub name: String,
+    /// ISO weekdays, 1 = Monday to 7 = Sunday
+    pub weekdays: Vec<u32>,
+    /// The rule applies on holidays, whatever weekday they are
+    pub holidays: bool,
+    #[serde(rename = "timeStart")]
+    pub time_start: NaiveTime,
+    #[serde(rename = "timeEnd")]
+    pub time_end: NaiveTime,
+    #[serde(rename = "pricePerUnit")]
+    pub price_per_unit: f32,
+}
+
+impl TariffRule {
+    pub fn contains_time(&self, time: &NaiveTime) -> bool {
+        if self.time_start < self.time_end {
+            self.time_start <= *time && *time < self.time_end
+        } else {
+            *time >= self.time_start || *time < self.time_end
+        }
+    }
+
+    /// The weekday of a window running over midnight is the day it started.
+    fn contains_day(&self, local: &NaiveDateTime, holidays: &[NaiveDate]) -> (bool, bool) {
+        let date = if self.time_start >= self.time_end && local.time() < self.time_end {
+            local.date().pred_opt().unwrap_or(local.date())
+        } else {
+            local.date()
+        };
+        let weekday = self.weekdays.contains(&date.weekday().number_from_monday());
+        (weekday, holidays.contains(&date))
+    }
+}
+
+/// First rule for the local date, on holidays rules for holidays take precedence over
+/// the weekday rules.
+pub fn find_tariff_rule<'a>(
+    rules: &'a [TariffRule],
+    holidays: &[NaiveDate],
+    local: &NaiveDateTime,
+) -> Option<&'a TariffRule> {
+    let time = local.time();
+    let candidates = || rules.iter().filter(|rule| rule.contains_time(&time));
+
+    candidates()
+        .find(|rule| rule.holidays && rule.contains_day(local, holidays).1)
+        .or_else(|| {
+            candidates().find(|rule| {
+                let (weekday, holiday) = rule.contains_day(local, holidays);
+                weekday && !(holiday && rules.iter().any(|rule| rule.holidays))
+            })
+        })
+}
+
+/// Parses weekdays like `1-5` or `6,7`.
+pub fn parse_weekdays(value: &str) -> Result<Vec<u32>, String> {
+    let mut weekdays = vec![];
+    for part in value
+        .split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+    {
+        let (first, last) = part.split_once('-').unwrap_or((part, part));
+        let parse = |day: &str| match day.trim().parse::<u32>() {
+            Ok(day) if (1..=7).contains(&day) => Ok(day),
+            _ => Err(format!(
+                "invalid weekday '{}', expected 1 (Monday) to 7",
+                day
+            )),
+        };
+        weekdays.extend(parse(first)?..=parse(last)?);
+    }
+    weekdays.sort_unstable();
+    weekdays.dedup();
+    Ok(weekdays)
+}
+
+pub fn parse_time(value: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(value, "%H:%M")
+        .map_err(|err| format!("invalid time '{}': {}", value, err))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Holiday {
+    pub date: NaiveDate,
+    pub name: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveTime};
+
+    use super::{find_tariff_rule, parse_weekdays, TariffRule};
+
+    fn rule(id: i32, weekdays: &str, holidays: bool, start: u32, end: u32) -> TariffRule {
+        TariffRule {
+            id,
+            fee_id: 1,
+            name: format!("rule {}", id),
+            weekdays: parse_weekdays(weekdays).unwrap(),
+            holidays,
+            time_start: NaiveTime::from_hms_opt(start, 0, 0).unwrap(),
+            time_end: NaiveTime::from_hms_opt(end, 0, 0).unwrap(),
+            price_per_unit: 0.3,
+        }
+    }
+
+    #[test]
+    fn rule_for_date() {
+        let rules = vec![
+            rule(1, "1-5", false, 6, 22),
+            rule(2, "1-5", false, 22, 6),
+            rule(3, "6,7", true, 0, 0),
+        ];
+        // 2023-01-06 is a friday and a holiday in some states
+        let holidays = vec![NaiveDate::from_ymd_opt(2023, 1, 6).unwrap()];
+        let at = |day: u32, hour: u32| {
+            let local = NaiveDate::from_ymd_opt(2023, 1, day)
+                .unwrap()
+                .and_hms_opt(hour, 30, 0)
+                .unwrap();
+            find_tariff_rule(&rules, &holidays, &local).map(|rule| rule.id)
+        };
+
+        assert_eq!(at(2, 12), Some(1));
+        assert_eq!(at(2, 23), Some(2));
+        assert_eq!(at(3, 5), Some(2));
+        assert_eq!(at(6, 12), Some(3));
+        // the night window started on the holiday, saturday is weekend
+        assert_eq!(at(7, 3), Some(3));
+        assert_eq!(at(8, 12), Some(3));
+        // the window of sunday night is not defined
+        assert_eq!(at(9, 3), None);
+        assert_eq!(at(9, 7), Some(1));
+
+        assert_eq!(parse_weekdays("1-3, 7").unwrap(), vec![1, 2, 3, 7]);
+        assert!(parse_weekdays("8").is_err());
+    }
+}
@@ -0,0 +1,105 @@
+use chrono::{Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateVatRateParams {
+    /// `YYYY-MM-DD`
+    #[serde(rename = "dateStart")]
+    pub date_start: String,
+    /// `YYYY-MM-DD`, open ended if not set
+    #[serde(rename = "dateEnd", default)]
+    pub date_end: Option<String>,
+    pub rate: f32,
+}
+
+/// VAT rate in percent from `date_start` to `date_end`, both days included.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VatRate {
+    pub id: i32,
+    #[serde(rename = "dateStart")]
+    pub date_start: NaiveDate,
+    #[serde(rename = "dateEnd")]
+    pub date_end: Option<NaiveDate>,
+    pub rate: f32,
+}
+
+impl VatRate {
+    pub fn contains(&self, date: &NaiveDate) -> bool {
+        self.date_start <= *date && self.date_end.is_none_or(|end| *date <= end)
+    }
+
+    pub fn overlaps(&self, other: &VatRate) -> bool {
+        let before = |first: &VatRate, second: &VatRate| {
+            first.date_end.is_some_and(|end| end < second.date_start)
+        };
+        !before(self, other) && !before(other, self)
+    }
+}
+
+/// Days from `date_start` to `date_end` with the same rate, as `(first day, last day, rate)`.
+/// Days without a stored rate get the `fallback` rate.
+pub fn vat_periods(
+    rates: &[VatRate],
+    fallback: f32,
+    date_start: NaiveDate,
+    date_end: NaiveDate,
+) -> Vec<(NaiveDate, NaiveDate, f32)> {
+    let mut periods: Vec<(NaiveDate, NaiveDate, f32)> = vec![];
+    let mut date = date_start;
+    while date <= date_end {
+        let rate = rates
+            .iter()
+            .find(|rate| rate.contains(&date))
+            .map_or(fallback, |rate| rate.rate);
+        match periods.last_mut() {
+            Some(period) if period.2 == rate => period.1 = date,
+            _ => periods.push((date, date, rate)),
+        }
+        date += Duration::days(1);
+    }
+    periods
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::{vat_periods, VatRate};
+
+    #[test]
+    fn periods_of_rate_changes() {
+        let date = |year: i32, month: u32, day: u32| NaiveDate::from_ymd_opt(year, month, day);
+        // reduced rate on gas from October 2022 to March 2024
+        let rates = vec![
+            VatRate {
+                id: 1,
+                date_start: date(2007, 1, 1).unwrap(),
+                date_end: date(2022, 9, 30),
+                rate: 19.0,
+            },
+            VatRate {
+                id: 2,
+                date_start: date(2022, 10, 1).unwrap(),
+                date_end: date(2024, 3, 31),
+                rate: 7.0,
+            },
+        ];
+
+        let periods = vat_periods(
+            &rates,
+            19.0,
+            date(2022, 6, 1).unwrap(),
+            date(2024, 5, 31).unwrap(),
+        );
+        assert_eq!(periods.len(), 3);
+        assert_eq!(periods[0].1, date(2022, 9, 30).unwrap());
+        assert_eq!(periods[1].2, 7.0);
+        // no stored rate after the reduction
+        assert_eq!(
+            periods[2],
+            (date(2024, 4, 1).unwrap(), date(2024, 5, 31).unwrap(), 19.0)
+        );
+        assert!(rates[0].overlaps(&rates[0]));
+        assert!(!rates[0].overlaps(&rates[1]));
+    }
+}
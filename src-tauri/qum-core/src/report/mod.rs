@@ -0,0 +1,63 @@
+use crate::calculation::consumption::IntervalConsumption;
+use crate::calculation::costs::CostBreakdown;
+use crate::export::ExportData;
+use crate::models::fees::Fee;
+use crate::models::meter::Meter;
+use crate::models::meter_reading::MeterReading;
+use crate::repository::Repository;
+
+pub mod pdf_report;
+
+/// Data of one fee period of a meter as it is printed on the annual report.
+pub struct AnnualReport {
+    pub meter: Meter,
+    pub fee: Fee,
+    pub readings: Vec<MeterReading>,
+    pub intervals: Vec<IntervalConsumption>,
+    pub costs: CostBreakdown,
+}
+
+impl AnnualReport {
+    pub fn from_data(data: ExportData, fee_id: i32) -> Result<AnnualReport, String> {
+        let costs = match data.costs.iter().find(|costs| costs.fee_id == fee_id) {
+            Some(costs) => costs.clone(),
+            None => return Err(format!("fee {} not found", fee_id)),
+        };
+        let fee = match data.fees.into_iter().find(|fee| fee.id == fee_id) {
+            Some(fee) => fee,
+            None => return Err(format!("fee {} not found", fee_id)),
+        };
+        let meter = match data
+            .meters
+            .into_iter()
+            .find(|meter| meter.id == fee.meter_id)
+        {
+            Some(meter) => meter,
+            None => return Err(format!("meter {} not found", fee.meter_id)),
+        };
+
+        let readings = data
+            .readings
+            .into_iter()
+            .filter(|reading| reading.fee.id == fee_id)
+            .collect();
+        let intervals = data
+            .intervals
+            .into_iter()
+            .filter(|interval| interval.fee_id == fee_id)
+            .collect();
+
+        Ok(AnnualReport {
+            meter,
+            fee,
+            readings,
+            intervals,
+            costs,
+        })
+    }
+
+    pub fn load<R: Repository>(repository: &mut R, fee_id: i32) -> Result<AnnualReport, String> {
+        let data = ExportData::load(repository)?;
+        AnnualReport::from_data(data, fee_id)
+    }
+}
@@ -0,0 +1,425 @@
+use std::fs;
+
+use printpdf::path::PaintMode;
+use printpdf::{
+    BuiltinFont, Color, IndirectFontRef, Line, Mm, PdfDocument, PdfDocumentReference,
+    PdfLayerReference, Point, Rect, Rgb,
+};
+
+use crate::export::ExportLocale;
+
+use super::AnnualReport;
+
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 20.0;
+const LINE_HEIGHT: f32 = 6.0;
+const CHART_HEIGHT: f32 = 60.0;
+
+/// Writes text top to bottom and starts a new page when the current one is full.
+struct PageWriter {
+    doc: PdfDocumentReference,
+    layer: PdfLayerReference,
+    font: IndirectFontRef,
+    bold: IndirectFontRef,
+    y: f32,
+}
+
+impl PageWriter {
+    fn new(title: &str) -> Result<PageWriter, String> {
+        let (doc, page, layer) =
+            PdfDocument::new(title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+        let font = doc
+            .add_builtin_font(BuiltinFont::Helvetica)
+            .map_err(|err| err.to_string())?;
+        let bold = doc
+            .add_builtin_font(BuiltinFont::HelveticaBold)
+            .map_err(|err| err.to_string())?;
+        let layer = doc.get_page(page).get_layer(layer);
+
+        Ok(PageWriter {
+            doc,
+            layer,
+            font,
+            bold,
+            y: PAGE_HEIGHT - MARGIN,
+        })
+    }
+
+    fn ensure_space(&mut self, height: f32) {
+        if self.y - height >= MARGIN {
+            return;
+        }
+
+        let (page, layer) = self
+            .doc
+            .add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+        self.layer = self.doc.get_page(page).get_layer(layer);
+        self.y = PAGE_HEIGHT - MARGIN;
+    }
+
+    fn title(&mut self, text: &str) {
+        self.ensure_space(LINE_HEIGHT * 2.0);
+        self.layer
+            .use_text(text, 18.0, Mm(MARGIN), Mm(self.y), &self.bold);
+        self.y -= LINE_HEIGHT * 2.0;
+    }
+
+    fn heading(&mut self, text: &str) {
+        self.ensure_space(LINE_HEIGHT * 3.0);
+        self.y -= LINE_HEIGHT / 2.0;
+        self.layer
+            .use_text(text, 13.0, Mm(MARGIN), Mm(self.y), &self.bold);
+        self.y -= LINE_HEIGHT * 1.5;
+    }
+
+    /// Writes one row of cells, each cell starts at the given x offset from the margin.
+    fn row(&mut self, cells: &[(f32, String)], bold: bool) {
+        self.ensure_space(LINE_HEIGHT);
+        let font = if bold { &self.bold } else { &self.font };
+        for (x, text) in cells {
+            self.layer
+                .use_text(text.as_str(), 10.0, Mm(MARGIN + x), Mm(self.y), font);
+        }
+        self.y -= LINE_HEIGHT;
+    }
+
+    fn line(&self, from: (f32, f32), to: (f32, f32)) {
+        self.layer.add_line(Line {
+            points: vec![
+                (Point::new(Mm(from.0), Mm(from.1)), false),
+                (Point::new(Mm(to.0), Mm(to.1)), false),
+            ],
+            is_closed: false,
+        });
+    }
+
+    /// Bar chart of the consumption per reading interval.
+    fn consumption_chart(&mut self, report: &AnnualReport, locale: ExportLocale) {
+        self.ensure_space(CHART_HEIGHT + LINE_HEIGHT * 2.0);
+        let width = PAGE_WIDTH - MARGIN * 2.0;
+        let bottom = self.y - CHART_HEIGHT;
+
+        self.layer.set_outline_thickness(0.5);
+        self.line((MARGIN, bottom), (MARGIN + width, bottom));
+        self.line((MARGIN, bottom), (MARGIN, self.y));
+
+        let max = report
+            .intervals
+            .iter()
+            .map(|interval| interval.consumption)
+            .fold(0.0, f32::max);
+        if max > 0.0 {
+            self.layer.use_text(
+                locale.format_number(max, 0),
+                7.0,
+                Mm(MARGIN + 1.0),
+                Mm(self.y - 3.0),
+                &self.font,
+            );
+
+            let slot = width / report.intervals.len() as f32;
+            let show_labels = report.intervals.len() <= 12;
+            self.layer
+                .set_fill_color(Color::Rgb(Rgb::new(0.2, 0.45, 0.7, None)));
+            for (index, interval) in report.intervals.iter().enumerate() {
+                let height = (interval.consumption.max(0.0) / max) * (CHART_HEIGHT - 5.0);
+                let left = MARGIN + slot * index as f32 + slot * 0.15;
+                let right = left + slot * 0.7;
+                self.layer.add_rect(
+                    Rect::new(Mm(left), Mm(bottom), Mm(right), Mm(bottom + height))
+                        .with_mode(PaintMode::Fill),
+                );
+
+                if show_labels {
+                    self.layer.use_text(
+                        locale.format_date(&interval.date_end),
+                        6.0,
+                        Mm(left),
+                        Mm(bottom - 4.0),
+                        &self.font,
+                    );
+                }
+            }
+            self.layer
+                .set_fill_color(Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
+        }
+
+        self.y = bottom - LINE_HEIGHT * 1.5;
+    }
+}
+
+pub fn render(report: &AnnualReport) -> Result<Vec<u8>, String> {
+    let locale = ExportLocale::De;
+    let meter = &report.meter;
+    let unit = meter.kind.unit();
+    let fee = &report.fee;
+    let costs = &report.costs;
+    let euro = |value: f32| format!("{} €", locale.format_number(value, 2));
+
+    let mut writer = PageWriter::new("Jahresabrechnung")?;
+    writer.title(
+        format!(
+            "Jahresabrechnung {} {}",
+            meter.name,
+            locale.period_name(fee)
+        )
+        .as_str(),
+    );
+
+    writer.heading("Tarif");
+    let meter_name = match &meter.external_id {
+        Some(external_id) => format!("{} ({})", meter.name, external_id),
+        None => meter.name.clone(),
+    };
+    writer.row(&[(0.0, "Zähler".to_string()), (70.0, meter_name)], false);
+    writer.row(
+        &[
+            (0.0, "Grundpreis pro Monat".to_string()),
+            (70.0, euro(fee.base_fee)),
+        ],
+        false,
+    );
+    writer.row(
+        &[
+            (0.0, format!("Preis pro {}", unit)),
+            (
+                70.0,
+                format!("{} €", locale.format_number(fee.price_per_unit, 4)),
+            ),
+        ],
+        false,
+    );
+    writer.row(
+        &[
+            (0.0, "Monatlicher Abschlag".to_string()),
+            (70.0, euro(fee.monthly_discount)),
+        ],
+        false,
+    );
+
+    writer.heading("Zählerstände");
+    writer.row(
+        &[
+            (0.0, "Datum".to_string()),
+            (40.0, format!("Zählerstand ({})", unit)),
+        ],
+        true,
+    );
+    for reading in report.readings.iter() {
+        writer.row(
+            &[
+                (0.0, locale.format_date(&reading.date)),
+                (40.0, locale.format_number(reading.value, 2)),
+            ],
+            false,
+        );
+    }
+
+    if !report.intervals.is_empty() {
+        writer.heading("Verbrauch");
+        writer.consumption_chart(report, locale);
+    }
+
+    writer.heading("Kosten");
+    writer.row(
+        &[
+            (0.0, format!("Grundpreis ({} Monate)", costs.months)),
+            (70.0, euro(costs.base_costs)),
+        ],
+        false,
+    );
+    writer.row(
+        &[
+            (
+                0.0,
+                format!(
+                    "Verbrauch ({} {})",
+                    locale.format_number(costs.consumption, 2),
+                    unit
+                ),
+            ),
+            (70.0, euro(costs.consumption_costs)),
+        ],
+        false,
+    );
+    if let Some(brake) = &costs.price_brake {
+        writer.row(
+            &[
+                (
+                    0.0,
+                    format!("Entlastung Preisbremse ({} Monate)", brake.months),
+                ),
+                (70.0, euro(-brake.relief)),
+            ],
+            false,
+        );
+    }
+    for adjustment in costs.adjustments.iter() {
+        writer.row(
+            &[
+                (
+                    0.0,
+                    format!(
+                        "{} ({})",
+                        adjustment.description,
+                        adjustment.date.format("%d.%m.%Y")
+                    ),
+                ),
+                (70.0, euro(adjustment.amount)),
+            ],
+            false,
+        );
+    }
+    let vat_rows = |writer: &mut PageWriter| {
+        for period in costs.vat_periods.iter().filter(|period| period.tax != 0.0) {
+            writer.row(
+                &[
+                    (
+                        0.0,
+                        format!(
+                            "USt {} % ({} - {})",
+                            locale.format_number(period.rate, 0),
+                            period.date_start.format("%d.%m.%Y"),
+                            period.date_end.format("%d.%m.%Y")
+                        ),
+                    ),
+                    (70.0, euro(period.tax)),
+                ],
+                false,
+            );
+        }
+    };
+    // net prices get the VAT added, gross prices contain it
+    if fee.net_prices {
+        writer.row(
+            &[
+                (0.0, "Summe netto".to_string()),
+                (70.0, euro(costs.net_costs)),
+            ],
+            true,
+        );
+        vat_rows(&mut writer);
+    }
+    writer.row(
+        &[
+            (0.0, "Gesamtkosten".to_string()),
+            (70.0, euro(costs.total_costs)),
+        ],
+        true,
+    );
+    if !fee.net_prices && costs.tax_costs != 0.0 {
+        writer.row(
+            &[
+                (0.0, "darin netto".to_string()),
+                (70.0, euro(costs.net_costs)),
+            ],
+            false,
+        );
+        vat_rows(&mut writer);
+    }
+
+    if !costs.components.is_empty() {
+        writer.heading("Preisbestandteile");
+        for component in costs.components.iter() {
+            writer.row(
+                &[(0.0, component.name.clone()), (70.0, euro(component.costs))],
+                false,
+            );
+        }
+    }
+
+    writer.heading("Abschläge");
+    let relief = costs
+        .price_brake
+        .as_ref()
+        .map_or(0.0, |brake| brake.relief + brake.tax);
+    match costs.recorded_payments {
+        // the debits already include the relief
+        0 => {
+            writer.row(
+                &[
+                    (
+                        0.0,
+                        format!("{} x {}", costs.months, euro(fee.monthly_discount)),
+                    ),
+                    (70.0, euro(fee.monthly_discount * costs.months as f32)),
+                ],
+                false,
+            );
+            if relief != 0.0 {
+                writer.row(
+                    &[
+                        (0.0, "Entlastung Preisbremse".to_string()),
+                        (70.0, euro(-relief)),
+                    ],
+                    false,
+                );
+                writer.row(
+                    &[
+                        (0.0, "Summe Abschläge".to_string()),
+                        (70.0, euro(costs.advance_payments)),
+                    ],
+                    true,
+                );
+            }
+        }
+        payments => writer.row(
+            &[
+                (0.0, format!("{} Zahlungen", payments)),
+                (70.0, euro(costs.advance_payments)),
+            ],
+            false,
+        ),
+    }
+
+    writer.heading("Abrechnung");
+    let balance_label = if costs.balance >= 0.0 {
+        "Guthaben"
+    } else {
+        "Nachzahlung"
+    };
+    writer.row(
+        &[
+            (0.0, balance_label.to_string()),
+            (70.0, euro(costs.balance.abs())),
+        ],
+        true,
+    );
+
+    writer.doc.save_to_bytes().map_err(|err| err.to_string())
+}
+
+pub fn save(report: &AnnualReport, path: &str) -> Result<(), String> {
+    let bytes = render(report)?;
+    fs::write(path, bytes).map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use crate::db::connection::run_migrations;
+    use crate::report::AnnualReport;
+    use crate::repository::SqliteRepository;
+
+    use super::render;
+
+    #[test]
+    fn renders_pdf() {
+        let mut conn = Connection::open_in_memory().expect("could not create memory database");
+        run_migrations(&mut conn).expect("failed to run migrations");
+        conn.execute("INSERT INTO fees (base_fee, price_per_unit, monthly_discount, date_start, date_end) VALUES (?1, ?2, ?3, ?4, ?5)",
+                 (10.0, 0.5, 45.0, "2022-01-01T00:00:00.000Z", "2022-12-31T00:00:00.000Z")).expect("failed to save fee");
+        conn.execute("INSERT INTO meter_readings (value, fee_id, reading_date) VALUES (?1, 1, ?2), (?3, 1, ?4)",
+                 (100.0, "2022-01-01T00:00:00.000Z", 1300.5, "2022-12-31T00:00:00.000Z")).expect("failed to save readings");
+
+        let report = AnnualReport::load(&mut SqliteRepository::new(&mut conn), 1)
+            .expect("failed to load report");
+        assert_eq!(report.meter.name, "Strom");
+        let bytes = render(&report).expect("failed to render");
+        assert!(bytes.starts_with(b"%PDF"));
+
+        assert!(AnnualReport::load(&mut SqliteRepository::new(&mut conn), 2).is_err());
+    }
+}
@@ -0,0 +1,712 @@
+use std::collections::BTreeMap;
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::models::adjustment::Adjustment;
+use crate::models::fees::Fee;
+use crate::models::interval::{IntervalBucket, IntervalSample, Resolution};
+use crate::models::invoice::Invoice;
+use crate::models::meter::{Meter, MeterKind, DEFAULT_METER_ID};
+use crate::models::meter_reading::{CreateMeterReadingParams, MeterReading};
+use crate::models::parse_datetime;
+use crate::models::payment::{Payment, PaymentRule};
+use crate::models::price_component::PriceComponent;
+use crate::models::spot_price::SpotPrice;
+use crate::models::tariff::{Holiday, TariffRule};
+use crate::models::vat::VatRate;
+
+use super::{
+    fee_has_readings, meter_has_fees, AdjustmentRepository, FeeRepository, IntervalRepository,
+    InvoiceRepository, MeterReadingRepository, MeterRepository, PaymentRepository,
+    PriceComponentRepository, SpotPriceRepository, TariffRepository, VatRepository,
+};
+
+struct StoredMeterReading {
+    id: i32,
+    value: f32,
+    fee_id: i32,
+    date: NaiveDateTime,
+}
+
+/// Keeps everything in memory, used to test business logic without a database.
+#[derive(Default)]
+pub struct InMemoryRepository {
+    meters: Vec<Meter>,
+    fees: Vec<Fee>,
+    meter_readings: Vec<StoredMeterReading>,
+    interval_samples: BTreeMap<(i32, NaiveDateTime), f64>,
+    interval_buckets: BTreeMap<(i32, Resolution, NaiveDateTime), IntervalBucket>,
+    /// Per meter, the date raw samples were deleted before
+    samples_pruned_before: BTreeMap<i32, NaiveDateTime>,
+    spot_prices: BTreeMap<NaiveDateTime, f64>,
+    tariff_rules: Vec<TariffRule>,
+    holidays: BTreeMap<NaiveDate, String>,
+    price_components: Vec<PriceComponent>,
+    vat_rates: Vec<VatRate>,
+    adjustments: Vec<Adjustment>,
+    payments: Vec<Payment>,
+    payment_rules: Vec<PaymentRule>,
+    invoices: Vec<Invoice>,
+    last_id: i32,
+}
+
+impl InMemoryRepository {
+    /// Starts with the default meter, like a migrated database.
+    pub fn new() -> InMemoryRepository {
+        InMemoryRepository {
+            meters: vec![Meter {
+                id: DEFAULT_METER_ID,
+                name: "Strom".to_string(),
+                kind: MeterKind::Consumption,
+                external_id: None,
+            }],
+            ..InMemoryRepository::default()
+        }
+    }
+
+    fn next_id(&mut self) -> i32 {
+        self.last_id += 1;
+        self.last_id
+    }
+
+    fn to_meter_reading(&self, stored: &StoredMeterReading) -> Result<MeterReading, String> {
+        match self.fees.iter().find(|fee| fee.id == stored.fee_id) {
+            Some(fee) => Ok(MeterReading {
+                id: stored.id,
+                meter_id: fee.meter_id,
+                value: stored.value,
+                fee: fee.clone(),
+                date: stored.date,
+            }),
+            None => Err(format!("fee {} not found", stored.fee_id)),
+        }
+    }
+
+    fn validate(&self, params: &CreateMeterReadingParams) -> Result<NaiveDateTime, String> {
+        if !self.fees.iter().any(|fee| fee.id == params.fee_id) {
+            return Err(format!("fee {} not found", params.fee_id));
+        }
+        parse_datetime(params.reading_date.as_str())
+    }
+}
+
+impl MeterRepository for InMemoryRepository {
+    fn list_meters(&mut self) -> Result<Vec<Meter>, String> {
+        Ok(self.meters.clone())
+    }
+
+    fn find_meter(&mut self, id: i32) -> Result<Option<Meter>, String> {
+        Ok(self.meters.iter().find(|meter| meter.id == id).cloned())
+    }
+
+    fn insert_meter(&mut self, meter: Meter) -> Result<Meter, String> {
+        // meters have their own ids like the table, the ids of the other records don't move
+        let id = self.meters.iter().map(|meter| meter.id).max().unwrap_or(0) + 1;
+        let meter = Meter { id, ..meter };
+        self.meters.push(meter.clone());
+        Ok(meter)
+    }
+
+    fn delete_meter(&mut self, id: i32) -> Result<(), String> {
+        if id == DEFAULT_METER_ID {
+            return Err("the default meter can't be deleted".to_string());
+        }
+        let fees = self.fees.iter().filter(|fee| fee.meter_id == id).count();
+        if fees > 0 {
+            return Err(meter_has_fees(id, fees as i64));
+        }
+
+        self.interval_samples
+            .retain(|(meter_id, _), _| *meter_id != id);
+        self.interval_buckets
+            .retain(|(meter_id, _, _), _| *meter_id != id);
+        self.samples_pruned_before.remove(&id);
+        self.meters.retain(|meter| meter.id != id);
+        Ok(())
+    }
+}
+
+impl FeeRepository for InMemoryRepository {
+    fn list_fees(&mut self) -> Result<Vec<Fee>, String> {
+        Ok(self.fees.clone())
+    }
+
+    fn find_fee(&mut self, id: i32) -> Result<Option<Fee>, String> {
+        Ok(self.fees.iter().find(|fee| fee.id == id).cloned())
+    }
+
+    fn find_fee_in_time_range(
+        &mut self,
+        meter_id: i32,
+        date_start: &NaiveDateTime,
+        date_end: &NaiveDateTime,
+    ) -> Result<Option<Fee>, String> {
+        Ok(self
+            .fees
+            .iter()
+            .find(|fee| {
+                fee.meter_id == meter_id
+                    && fee.date_start <= *date_end
+                    && fee.date_end >= *date_start
+            })
+            .cloned())
+    }
+
+    fn insert_fee(&mut self, fee: Fee) -> Result<Fee, String> {
+        let fee = Fee {
+            id: self.next_id(),
+            ..fee
+        };
+        self.fees.push(fee.clone());
+        Ok(fee)
+    }
+
+    fn delete_fee(&mut self, id: i32) -> Result<(), String> {
+        let readings = self
+            .meter_readings
+            .iter()
+            .filter(|reading| reading.fee_id == id)
+            .count();
+        if readings > 0 {
+            return Err(fee_has_readings(id, readings as i64));
+        }
+
+        self.tariff_rules.retain(|rule| rule.fee_id != id);
+        self.price_components
+            .retain(|component| component.fee_id != id);
+        self.adjustments
+            .retain(|adjustment| adjustment.fee_id != id);
+        self.payments.retain(|payment| payment.fee_id != id);
+        self.invoices.retain(|invoice| invoice.fee_id != id);
+        self.fees.retain(|fee| fee.id != id);
+        Ok(())
+    }
+}
+
+impl MeterReadingRepository for InMemoryRepository {
+    fn list_meter_readings(&mut self) -> Result<Vec<MeterReading>, String> {
+        self.meter_readings
+            .iter()
+            .map(|stored| self.to_meter_reading(stored))
+            .collect()
+    }
+
+    fn create_meter_reading(
+        &mut self,
+        params: CreateMeterReadingParams,
+    ) -> Result<MeterReading, String> {
+        let date = self.validate(&params)?;
+        let stored = StoredMeterReading {
+            id: self.next_id(),
+            value: params.value,
+            fee_id: params.fee_id,
+            date,
+        };
+        let meter_reading = self.to_meter_reading(&stored)?;
+        self.meter_readings.push(stored);
+
+        Ok(meter_reading)
+    }
+
+    fn create_meter_readings(
+        &mut self,
+        params: Vec<CreateMeterReadingParams>,
+    ) -> Result<usize, String> {
+        let dates = params
+            .iter()
+            .map(|params| self.validate(params))
+            .collect::<Result<Vec<NaiveDateTime>, String>>()?;
+
+        for (params, date) in params.iter().zip(dates) {
+            let id = self.next_id();
+            self.meter_readings.push(StoredMeterReading {
+                id,
+                value: params.value,
+                fee_id: params.fee_id,
+                date,
+            });
+        }
+
+        Ok(params.len())
+    }
+}
+
+impl IntervalRepository for InMemoryRepository {
+    fn insert_interval_samples(
+        &mut self,
+        meter_id: i32,
+        samples: &[IntervalSample],
+    ) -> Result<usize, String> {
+        let pruned_before = self.samples_pruned_before.get(&meter_id).copied();
+        let mut count = 0;
+        for sample in samples {
+            if self.interval_samples.contains_key(&(meter_id, sample.date)) {
+                continue;
+            }
+            let pruned = pruned_before.is_some_and(|pruned_before| sample.date < pruned_before);
+            let mut added = false;
+            for resolution in Resolution::ALL {
+                let key = (meter_id, resolution, resolution.bucket_start(&sample.date));
+                match self.interval_buckets.get_mut(&key) {
+                    Some(bucket) if pruned && bucket.covers(&sample.date) => {}
+                    Some(bucket) => {
+                        bucket.add(sample);
+                        added = true;
+                    }
+                    None => {
+                        self.interval_buckets
+                            .insert(key, IntervalBucket::new(resolution, sample));
+                        added = true;
+                    }
+                }
+            }
+            if added {
+                count += 1;
+            }
+            if !pruned {
+                self.interval_samples
+                    .insert((meter_id, sample.date), sample.value);
+            }
+        }
+
+        Ok(count)
+    }
+
+    fn list_interval_samples(
+        &mut self,
+        meter_id: i32,
+        date_start: &NaiveDateTime,
+        date_end: &NaiveDateTime,
+    ) -> Result<Vec<IntervalSample>, String> {
+        Ok(self
+            .interval_samples
+            .range((meter_id, *date_start)..(meter_id, *date_end))
+            .map(|((_, date), value)| IntervalSample {
+                date: *date,
+                value: *value,
+            })
+            .collect())
+    }
+
+    fn list_interval_buckets(
+        &mut self,
+        meter_id: i32,
+        resolution: Resolution,
+        date_start: &NaiveDateTime,
+        date_end: &NaiveDateTime,
+    ) -> Result<Vec<IntervalBucket>, String> {
+        Ok(self
+            .interval_buckets
+            .range((meter_id, resolution, *date_start)..(meter_id, resolution, *date_end))
+            .map(|(_, bucket)| bucket.clone())
+            .collect())
+    }
+
+    fn delete_interval_samples_before(
+        &mut self,
+        meter_id: i32,
+        date: &NaiveDateTime,
+    ) -> Result<usize, String> {
+        let count = self.interval_samples.len();
+        self.interval_samples
+            .retain(|(sample_meter_id, sample_date), _| {
+                *sample_meter_id != meter_id || sample_date >= date
+            });
+        let pruned_before = self.samples_pruned_before.entry(meter_id).or_insert(*date);
+        *pruned_before = (*pruned_before).max(*date);
+        Ok(count - self.interval_samples.len())
+    }
+}
+
+impl SpotPriceRepository for InMemoryRepository {
+    fn upsert_spot_prices(&mut self, prices: &[SpotPrice]) -> Result<usize, String> {
+        for price in prices {
+            self.spot_prices.insert(price.date_start, price.price);
+        }
+        Ok(prices.len())
+    }
+
+    fn list_spot_prices(
+        &mut self,
+        date_start: &NaiveDateTime,
+        date_end: &NaiveDateTime,
+    ) -> Result<Vec<SpotPrice>, String> {
+        Ok(self
+            .spot_prices
+            .range(*date_start..*date_end)
+            .map(|(date_start, price)| SpotPrice {
+                date_start: *date_start,
+                price: *price,
+            })
+            .collect())
+    }
+}
+
+impl TariffRepository for InMemoryRepository {
+    fn list_tariff_rules(&mut self, fee_id: i32) -> Result<Vec<TariffRule>, String> {
+        Ok(self
+            .tariff_rules
+            .iter()
+            .filter(|rule| rule.fee_id == fee_id)
+            .cloned()
+            .collect())
+    }
+
+    fn insert_tariff_rule(&mut self, rule: TariffRule) -> Result<TariffRule, String> {
+        let rule = TariffRule {
+            id: self.next_id(),
+            ..rule
+        };
+        self.tariff_rules.push(rule.clone());
+        Ok(rule)
+    }
+
+    fn delete_tariff_rule(&mut self, id: i32) -> Result<(), String> {
+        self.tariff_rules.retain(|rule| rule.id != id);
+        Ok(())
+    }
+
+    fn list_holidays(&mut self) -> Result<Vec<Holiday>, String> {
+        Ok(self
+            .holidays
+            .iter()
+            .map(|(date, name)| Holiday {
+                date: *date,
+                name: name.clone(),
+            })
+            .collect())
+    }
+
+    fn upsert_holiday(&mut self, holiday: Holiday) -> Result<(), String> {
+        self.holidays.insert(holiday.date, holiday.name);
+        Ok(())
+    }
+
+    fn delete_holiday(&mut self, date: &NaiveDate) -> Result<(), String> {
+        self.holidays.remove(date);
+        Ok(())
+    }
+}
+
+impl PriceComponentRepository for InMemoryRepository {
+    fn list_price_components(&mut self, fee_id: i32) -> Result<Vec<PriceComponent>, String> {
+        Ok(self
+            .price_components
+            .iter()
+            .filter(|component| component.fee_id == fee_id)
+            .cloned()
+            .collect())
+    }
+
+    fn insert_price_component(
+        &mut self,
+        component: PriceComponent,
+    ) -> Result<PriceComponent, String> {
+        let component = PriceComponent {
+            id: self.next_id(),
+            ..component
+        };
+        self.price_components.push(component.clone());
+        Ok(component)
+    }
+
+    fn delete_price_component(&mut self, id: i32) -> Result<(), String> {
+        self.price_components.retain(|component| component.id != id);
+        Ok(())
+    }
+}
+
+impl VatRepository for InMemoryRepository {
+    fn list_vat_rates(&mut self) -> Result<Vec<VatRate>, String> {
+        let mut rates = self.vat_rates.clone();
+        rates.sort_by_key(|rate| rate.date_start);
+        Ok(rates)
+    }
+
+    fn insert_vat_rate(&mut self, rate: VatRate) -> Result<VatRate, String> {
+        let rate = VatRate {
+            id: self.next_id(),
+            ..rate
+        };
+        self.vat_rates.push(rate.clone());
+        Ok(rate)
+    }
+
+    fn delete_vat_rate(&mut self, id: i32) -> Result<(), String> {
+        self.vat_rates.retain(|rate| rate.id != id);
+        Ok(())
+    }
+}
+
+impl AdjustmentRepository for InMemoryRepository {
+    fn list_adjustments(&mut self, fee_id: i32) -> Result<Vec<Adjustment>, String> {
+        let mut adjustments: Vec<Adjustment> = self
+            .adjustments
+            .iter()
+            .filter(|adjustment| adjustment.fee_id == fee_id)
+            .cloned()
+            .collect();
+        adjustments.sort_by_key(|adjustment| (adjustment.date, adjustment.id));
+        Ok(adjustments)
+    }
+
+    fn insert_adjustment(&mut self, adjustment: Adjustment) -> Result<Adjustment, String> {
+        let adjustment = Adjustment {
+            id: self.next_id(),
+            ..adjustment
+        };
+        self.adjustments.push(adjustment.clone());
+        Ok(adjustment)
+    }
+
+    fn delete_adjustment(&mut self, id: i32) -> Result<(), String> {
+        self.adjustments.retain(|adjustment| adjustment.id != id);
+        Ok(())
+    }
+}
+
+impl PaymentRepository for InMemoryRepository {
+    fn list_payments(&mut self, fee_id: Option<i32>) -> Result<Vec<Payment>, String> {
+        let mut payments: Vec<Payment> = self
+            .payments
+            .iter()
+            .filter(|payment| fee_id.is_none_or(|fee_id| payment.fee_id == fee_id))
+            .cloned()
+            .collect();
+        payments.sort_by_key(|payment| (payment.date, payment.id));
+        Ok(payments)
+    }
+
+    fn insert_payment(&mut self, payment: Payment) -> Result<Payment, String> {
+        let payment = Payment {
+            id: self.next_id(),
+            ..payment
+        };
+        self.payments.push(payment.clone());
+        Ok(payment)
+    }
+
+    fn insert_payments(&mut self, payments: Vec<Payment>) -> Result<Vec<Payment>, String> {
+        if let Some(payment) = payments
+            .iter()
+            .find(|payment| !self.fees.iter().any(|fee| fee.id == payment.fee_id))
+        {
+            return Err(format!("fee {} not found", payment.fee_id));
+        }
+        payments
+            .into_iter()
+            .map(|payment| self.insert_payment(payment))
+            .collect()
+    }
+
+    fn update_payment(&mut self, payment: Payment) -> Result<Payment, String> {
+        match self
+            .payments
+            .iter_mut()
+            .find(|stored| stored.id == payment.id)
+        {
+            Some(stored) => {
+                *stored = payment.clone();
+                Ok(payment)
+            }
+            None => Err(format!("payment {} not found", payment.id)),
+        }
+    }
+
+    fn delete_payment(&mut self, id: i32) -> Result<(), String> {
+        self.payments.retain(|payment| payment.id != id);
+        Ok(())
+    }
+
+    fn list_payment_rules(&mut self) -> Result<Vec<PaymentRule>, String> {
+        Ok(self.payment_rules.clone())
+    }
+
+    fn insert_payment_rule(&mut self, rule: PaymentRule) -> Result<PaymentRule, String> {
+        let rule = PaymentRule {
+            id: self.next_id(),
+            ..rule
+        };
+        self.payment_rules.push(rule.clone());
+        Ok(rule)
+    }
+
+    fn delete_payment_rule(&mut self, id: i32) -> Result<(), String> {
+        self.payment_rules.retain(|rule| rule.id != id);
+        Ok(())
+    }
+}
+
+impl InvoiceRepository for InMemoryRepository {
+    fn list_invoices(&mut self) -> Result<Vec<Invoice>, String> {
+        let mut invoices = self.invoices.clone();
+        invoices.sort_by_key(|invoice| (invoice.date_start, invoice.id));
+        Ok(invoices)
+    }
+
+    fn find_invoice(&mut self, id: i32) -> Result<Option<Invoice>, String> {
+        Ok(self
+            .invoices
+            .iter()
+            .find(|invoice| invoice.id == id)
+            .cloned())
+    }
+
+    fn insert_invoice(&mut self, invoice: Invoice) -> Result<Invoice, String> {
+        let invoice = Invoice {
+            id: self.next_id(),
+            ..invoice
+        };
+        self.invoices.push(invoice.clone());
+        Ok(invoice)
+    }
+
+    fn delete_invoice(&mut self, id: i32) -> Result<(), String> {
+        self.invoices.retain(|invoice| invoice.id != id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::fees::CreateFeeParams;
+    use crate::models::meter::{CreateMeterParams, MeterKind};
+    use crate::models::meter_reading::CreateMeterReadingParams;
+    use crate::models::payment::CreatePaymentParams;
+    use crate::repository::{
+        FeeRepository, MeterReadingRepository, MeterRepository, PaymentRepository,
+    };
+
+    use super::InMemoryRepository;
+
+    fn fee_params(date_start: &str, date_end: &str) -> CreateFeeParams {
+        CreateFeeParams {
+            meter_id: None,
+            base_fee: 10.0,
+            price_per_unit: 0.5,
+            monthly_discount: 45.0,
+            spot_surcharge: None,
+            blocks: vec![],
+            net_prices: false,
+            price_brake: None,
+            date_start: date_start.to_string(),
+            date_end: date_end.to_string(),
+        }
+    }
+
+    #[test]
+    fn create_rejects_overlapping_fees() {
+        let mut repository = InMemoryRepository::new();
+        repository
+            .create_fee(fee_params(
+                "2022-01-01T00:00:00.000Z",
+                "2022-12-31T00:00:00.000Z",
+            ))
+            .expect("failed to create fee");
+
+        let overlapping = fee_params("2022-06-01T00:00:00.000Z", "2023-05-31T00:00:00.000Z");
+        assert!(repository.create_fee(overlapping).is_err());
+        let enclosing = fee_params("2021-06-01T00:00:00.000Z", "2024-01-31T00:00:00.000Z");
+        assert!(repository.create_fee(enclosing).is_err());
+        assert_eq!(repository.list_fees().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn meter_readings_need_a_fee() {
+        let mut repository = InMemoryRepository::new();
+        let fee = repository
+            .create_fee(fee_params(
+                "2022-01-01T00:00:00.000Z",
+                "2022-12-31T00:00:00.000Z",
+            ))
+            .expect("failed to create fee");
+
+        let reading = |fee_id: i32| CreateMeterReadingParams {
+            value: 100.0,
+            fee_id,
+            reading_date: "2022-02-01T00:00:00.000Z".to_string(),
+        };
+
+        assert!(repository
+            .create_meter_readings(vec![reading(fee.id), reading(fee.id + 1)])
+            .is_err());
+        assert_eq!(repository.list_meter_readings().unwrap().len(), 0);
+
+        let created = repository
+            .create_meter_reading(reading(fee.id))
+            .expect("failed to create reading");
+        assert_eq!(created.fee.id, fee.id);
+        assert_eq!(repository.list_meter_readings().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn payments_get_the_fee_of_their_meter() {
+        let mut repository = InMemoryRepository::new();
+        let power = repository
+            .create_fee(fee_params(
+                "2022-01-01T00:00:00.000Z",
+                "2022-12-31T00:00:00.000Z",
+            ))
+            .expect("failed to create fee");
+        let meter = repository
+            .create_meter(CreateMeterParams {
+                name: "Gas".to_string(),
+                kind: MeterKind::Gas,
+                external_id: None,
+            })
+            .expect("failed to create meter");
+        let gas = repository
+            .create_fee(CreateFeeParams {
+                meter_id: Some(meter.id),
+                ..fee_params("2022-01-01T00:00:00.000Z", "2022-12-31T00:00:00.000Z")
+            })
+            .expect("failed to create fee");
+
+        let payment = |fee_id: Option<i32>, meter_id: Option<i32>| CreatePaymentParams {
+            fee_id,
+            meter_id,
+            date: "2022-01-15".to_string(),
+            amount: 45.0,
+        };
+        let created = repository.create_payment(payment(None, None)).unwrap();
+        assert_eq!(created.fee_id, power.id);
+        let created = repository
+            .create_payment(payment(None, Some(meter.id)))
+            .unwrap();
+        assert_eq!(created.fee_id, gas.id);
+        assert!(repository
+            .create_payment(payment(Some(power.id), Some(meter.id)))
+            .is_err());
+    }
+
+    #[test]
+    fn delete_fee_keeps_fees_with_readings() {
+        let mut repository = InMemoryRepository::new();
+        let fee = repository
+            .create_fee(fee_params(
+                "2022-01-01T00:00:00.000Z",
+                "2022-12-31T00:00:00.000Z",
+            ))
+            .expect("failed to create fee");
+        repository
+            .create_payment(CreatePaymentParams {
+                fee_id: None,
+                meter_id: None,
+                date: "2022-01-15".to_string(),
+                amount: 45.0,
+            })
+            .expect("failed to create payment");
+        repository
+            .create_meter_reading(CreateMeterReadingParams {
+                value: 100.0,
+                fee_id: fee.id,
+                reading_date: "2022-02-01T00:00:00.000Z".to_string(),
+            })
+            .expect("failed to create reading");
+
+        assert!(repository.delete_fee(fee.id).is_err());
+        assert_eq!(repository.list_fees().unwrap().len(), 1);
+        assert_eq!(repository.list_payments(None).unwrap().len(), 1);
+        assert_eq!(repository.list_meter_readings().unwrap().len(), 1);
+    }
+}
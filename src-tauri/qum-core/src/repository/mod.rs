@@ -0,0 +1,569 @@
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::models::adjustment::{Adjustment, CreateAdjustmentParams};
+use crate::models::fees::{validate_blocks, validate_price_brake, CreateFeeParams, Fee};
+use crate::models::interval::{IntervalBucket, IntervalSample, Resolution};
+use crate::models::invoice::{CreateInvoiceParams, Invoice};
+use crate::models::meter::{CreateMeterParams, Meter, DEFAULT_METER_ID};
+use crate::models::meter_reading::{CreateMeterReadingParams, MeterReading};
+use crate::models::payment::{
+    normalize_creditor_id, CreatePaymentParams, CreatePaymentRuleParams, Payment, PaymentRule,
+};
+use crate::models::price_component::{
+    validate_component, CreatePriceComponentParams, PriceComponent,
+};
+use crate::models::spot_price::SpotPrice;
+use crate::models::tariff::{parse_time, CreateTariffRuleParams, Holiday, TariffRule};
+use crate::models::vat::{CreateVatRateParams, VatRate};
+use crate::models::{format_datetime, parse_date, parse_datetime};
+
+pub mod memory;
+pub mod sqlite;
+
+pub use memory::InMemoryRepository;
+pub use sqlite::SqliteRepository;
+
+/// Meters and registers, every series of readings and interval data belongs to one.
+pub trait MeterRepository {
+    /// Meters ordered by id, the default meter comes first.
+    fn list_meters(&mut self) -> Result<Vec<Meter>, String>;
+
+    fn find_meter(&mut self, id: i32) -> Result<Option<Meter>, String>;
+
+    /// Stores the meter and returns it with its new id, the id of the passed meter is
+    /// ignored.
+    fn insert_meter(&mut self, meter: Meter) -> Result<Meter, String>;
+
+    /// Meters with fees are kept, the default meter as well.
+    fn delete_meter(&mut self, id: i32) -> Result<(), String>;
+
+    fn create_meter(&mut self, params: CreateMeterParams) -> Result<Meter, String> {
+        let name = params.name.trim().to_string();
+        if name.is_empty() {
+            return Err("a meter needs a name".to_string());
+        }
+        let external_id = params
+            .external_id
+            .map(|external_id| external_id.trim().to_string())
+            .filter(|external_id| !external_id.is_empty());
+        if let Some(external_id) = &external_id {
+            if let Some(other) = self.find_meter_by_external_id(external_id)? {
+                return Err(format!(
+                    "meter {} ({}) already has the id {}",
+                    other.id, other.name, external_id
+                ));
+            }
+        }
+
+        self.insert_meter(Meter {
+            id: 0,
+            name,
+            kind: params.kind,
+            external_id,
+        })
+    }
+
+    fn find_meter_by_external_id(&mut self, external_id: &str) -> Result<Option<Meter>, String> {
+        Ok(self
+            .list_meters()?
+            .into_iter()
+            .find(|meter| meter.external_id.as_deref() == Some(external_id)))
+    }
+
+    /// The meter or an error naming the id.
+    fn get_meter(&mut self, id: i32) -> Result<Meter, String> {
+        self.find_meter(id)?
+            .ok_or_else(|| format!("meter {} not found", id))
+    }
+}
+
+pub trait FeeRepository: MeterRepository {
+    fn list_fees(&mut self) -> Result<Vec<Fee>, String>;
+
+    fn find_fee(&mut self, id: i32) -> Result<Option<Fee>, String>;
+
+    /// First fee of the meter whose period overlaps `date_start..=date_end`.
+    fn find_fee_in_time_range(
+        &mut self,
+        meter_id: i32,
+        date_start: &NaiveDateTime,
+        date_end: &NaiveDateTime,
+    ) -> Result<Option<Fee>, String>;
+
+    /// Stores the fee and returns it with its new id, the id of the passed fee is ignored.
+    fn insert_fee(&mut self, fee: Fee) -> Result<Fee, String>;
+
+    /// Deletes the fee with its rules, components, adjustments, payments and invoices.
+    /// Fees with meter readings are kept, the readings would lose their prices.
+    fn delete_fee(&mut self, id: i32) -> Result<(), String>;
+
+    fn create_fee(&mut self, params: CreateFeeParams) -> Result<Fee, String> {
+        let date_start = parse_datetime(params.date_start.as_str())?;
+        let date_end = parse_datetime(params.date_end.as_str())?;
+        let meter_id = params.meter_id.unwrap_or(DEFAULT_METER_ID);
+        self.get_meter(meter_id)?;
+
+        if self
+            .find_fee_in_time_range(meter_id, &date_start, &date_end)?
+            .is_some()
+        {
+            return Err("Fee already exist for date range".to_string());
+        }
+        validate_blocks(&params.blocks)?;
+        if let Some(brake) = &params.price_brake {
+            validate_price_brake(brake)?;
+        }
+
+        self.insert_fee(Fee {
+            id: 0,
+            meter_id,
+            base_fee: params.base_fee,
+            price_per_unit: params.price_per_unit,
+            monthly_discount: params.monthly_discount,
+            spot_surcharge: params.spot_surcharge,
+            blocks: params.blocks,
+            net_prices: params.net_prices,
+            price_brake: params.price_brake,
+            date_start,
+            date_end,
+        })
+    }
+}
+
+fn meter_has_fees(id: i32, fees: i64) -> String {
+    format!("meter {} can't be deleted, it still has {} fees", id, fees)
+}
+
+fn fee_has_readings(id: i32, readings: i64) -> String {
+    format!(
+        "fee {} can't be deleted, it still has {} meter readings",
+        id, readings
+    )
+}
+
+pub trait MeterReadingRepository {
+    fn list_meter_readings(&mut self) -> Result<Vec<MeterReading>, String>;
+
+    fn create_meter_reading(
+        &mut self,
+        params: CreateMeterReadingParams,
+    ) -> Result<MeterReading, String>;
+
+    /// Stores all readings or none of them.
+    fn create_meter_readings(
+        &mut self,
+        params: Vec<CreateMeterReadingParams>,
+    ) -> Result<usize, String>;
+}
+
+/// High resolution counter values of smart meters. Every stored sample is added to the
+/// buckets of all resolutions, the buckets outlive the raw samples.
+/// Every meter has its own samples and buckets.
+pub trait IntervalRepository {
+    /// Samples with a date that is already stored are ignored. Samples before the date the raw
+    /// samples were deleted before are only added to the buckets, and only to buckets that
+    /// don't cover their date yet, so a sample sent again is not counted twice.
+    /// Returns how many were stored or added to buckets.
+    fn insert_interval_samples(
+        &mut self,
+        meter_id: i32,
+        samples: &[IntervalSample],
+    ) -> Result<usize, String>;
+
+    /// Samples with `date_start <= date < date_end`, ordered by date.
+    fn list_interval_samples(
+        &mut self,
+        meter_id: i32,
+        date_start: &NaiveDateTime,
+        date_end: &NaiveDateTime,
+    ) -> Result<Vec<IntervalSample>, String>;
+
+    /// Buckets starting in `date_start <= date < date_end`, ordered by date.
+    fn list_interval_buckets(
+        &mut self,
+        meter_id: i32,
+        resolution: Resolution,
+        date_start: &NaiveDateTime,
+        date_end: &NaiveDateTime,
+    ) -> Result<Vec<IntervalBucket>, String>;
+
+    /// Deletes raw samples older than `date`, returns how many were deleted. Raw samples before
+    /// `date` that are inserted later are not kept.
+    fn delete_interval_samples_before(
+        &mut self,
+        meter_id: i32,
+        date: &NaiveDateTime,
+    ) -> Result<usize, String>;
+}
+
+/// Hourly prices of dynamic tariffs.
+pub trait SpotPriceRepository {
+    /// Replaces the prices of hours that are already stored, returns how many were stored.
+    fn upsert_spot_prices(&mut self, prices: &[SpotPrice]) -> Result<usize, String>;
+
+    /// Prices of the hours starting in `date_start <= date < date_end`, ordered by date.
+    fn list_spot_prices(
+        &mut self,
+        date_start: &NaiveDateTime,
+        date_end: &NaiveDateTime,
+    ) -> Result<Vec<SpotPrice>, String>;
+}
+
+/// Time-of-use windows of fees and the holidays they refer to.
+pub trait TariffRepository: FeeRepository {
+    /// Rules of the fee in the order they were created, which is the order they are matched.
+    fn list_tariff_rules(&mut self, fee_id: i32) -> Result<Vec<TariffRule>, String>;
+
+    /// Stores the rule and returns it with its new id, the id of the passed rule is ignored.
+    fn insert_tariff_rule(&mut self, rule: TariffRule) -> Result<TariffRule, String>;
+
+    fn delete_tariff_rule(&mut self, id: i32) -> Result<(), String>;
+
+    /// Holidays ordered by date.
+    fn list_holidays(&mut self) -> Result<Vec<Holiday>, String>;
+
+    /// Replaces the name of a holiday that is already stored.
+    fn upsert_holiday(&mut self, holiday: Holiday) -> Result<(), String>;
+
+    fn delete_holiday(&mut self, date: &NaiveDate) -> Result<(), String>;
+
+    fn create_tariff_rule(&mut self, params: CreateTariffRuleParams) -> Result<TariffRule, String> {
+        if self.find_fee(params.fee_id)?.is_none() {
+            return Err(format!("fee {} not found", params.fee_id));
+        }
+        if params.weekdays.is_empty() && !params.holidays {
+            return Err("rule applies to no day".to_string());
+        }
+        if let Some(day) = params.weekdays.iter().find(|day| !(1..=7).contains(*day)) {
+            return Err(format!("invalid weekday {}, expected 1 (Monday) to 7", day));
+        }
+
+        self.insert_tariff_rule(TariffRule {
+            id: 0,
+            fee_id: params.fee_id,
+            name: params.name,
+            weekdays: params.weekdays,
+            holidays: params.holidays,
+            time_start: parse_time(params.time_start.as_str())?,
+            time_end: parse_time(params.time_end.as_str())?,
+            price_per_unit: params.price_per_unit,
+        })
+    }
+}
+
+/// Split of the fee prices into grid fees, levies and taxes.
+pub trait PriceComponentRepository: FeeRepository {
+    /// Components of the fee in the order they were created.
+    fn list_price_components(&mut self, fee_id: i32) -> Result<Vec<PriceComponent>, String>;
+
+    /// Stores the component and returns it with its new id, the id of the passed component
+    /// is ignored.
+    fn insert_price_component(
+        &mut self,
+        component: PriceComponent,
+    ) -> Result<PriceComponent, String>;
+
+    fn delete_price_component(&mut self, id: i32) -> Result<(), String>;
+
+    fn create_price_component(
+        &mut self,
+        params: CreatePriceComponentParams,
+    ) -> Result<PriceComponent, String> {
+        if self.find_fee(params.fee_id)?.is_none() {
+            return Err(format!("fee {} not found", params.fee_id));
+        }
+        validate_component(&params)?;
+
+        let name = match params.name.trim() {
+            "" => params.kind.name().to_string(),
+            name => name.to_string(),
+        };
+        self.insert_price_component(PriceComponent {
+            id: 0,
+            fee_id: params.fee_id,
+            kind: params.kind,
+            name,
+            base_fee: params.base_fee,
+            price_per_unit: params.price_per_unit,
+            rate: params.rate,
+        })
+    }
+}
+
+/// One-off charges and credits of fee periods.
+pub trait AdjustmentRepository: FeeRepository {
+    /// Adjustments of the fee ordered by date.
+    fn list_adjustments(&mut self, fee_id: i32) -> Result<Vec<Adjustment>, String>;
+
+    /// Stores the adjustment and returns it with its new id, the id of the passed adjustment
+    /// is ignored.
+    fn insert_adjustment(&mut self, adjustment: Adjustment) -> Result<Adjustment, String>;
+
+    fn delete_adjustment(&mut self, id: i32) -> Result<(), String>;
+
+    fn create_adjustment(&mut self, params: CreateAdjustmentParams) -> Result<Adjustment, String> {
+        let fee = match self.find_fee(params.fee_id)? {
+            Some(fee) => fee,
+            None => return Err(format!("fee {} not found", params.fee_id)),
+        };
+        let date = parse_date(params.date.as_str())?;
+        if date < fee.date_start.date() || fee.date_end.date() < date {
+            return Err(format!("{} is not in the period of fee {}", date, fee.id));
+        }
+
+        self.insert_adjustment(Adjustment {
+            id: 0,
+            fee_id: params.fee_id,
+            amount: params.amount,
+            date,
+            description: params.description,
+            taxable: params.taxable,
+        })
+    }
+}
+
+/// Advance payments actually debited.
+pub trait PaymentRepository: FeeRepository {
+    /// Payments of the fee or of all fees, ordered by date.
+    fn list_payments(&mut self, fee_id: Option<i32>) -> Result<Vec<Payment>, String>;
+
+    /// Stores the payment and returns it with its new id, the id of the passed payment is
+    /// ignored.
+    fn insert_payment(&mut self, payment: Payment) -> Result<Payment, String>;
+
+    /// Stores all payments or none of them, returns them with their new ids.
+    fn insert_payments(&mut self, payments: Vec<Payment>) -> Result<Vec<Payment>, String>;
+
+    /// Replaces the stored payment with the same id.
+    fn update_payment(&mut self, payment: Payment) -> Result<Payment, String>;
+
+    fn delete_payment(&mut self, id: i32) -> Result<(), String>;
+
+    fn create_payment(&mut self, params: CreatePaymentParams) -> Result<Payment, String> {
+        let payment = payment_from_params(self, 0, params)?;
+        self.insert_payment(payment)
+    }
+
+    fn edit_payment(&mut self, id: i32, params: CreatePaymentParams) -> Result<Payment, String> {
+        let payment = payment_from_params(self, id, params)?;
+        self.update_payment(payment)
+    }
+
+    fn list_payment_rules(&mut self) -> Result<Vec<PaymentRule>, String>;
+
+    /// Stores the rule and returns it with its new id, the id of the passed rule is ignored.
+    fn insert_payment_rule(&mut self, rule: PaymentRule) -> Result<PaymentRule, String>;
+
+    fn delete_payment_rule(&mut self, id: i32) -> Result<(), String>;
+
+    fn create_payment_rule(
+        &mut self,
+        params: CreatePaymentRuleParams,
+    ) -> Result<PaymentRule, String> {
+        let creditor_id = params
+            .creditor_id
+            .map(|creditor_id| normalize_creditor_id(creditor_id.as_str()))
+            .filter(|creditor_id| !creditor_id.is_empty());
+        let text = params
+            .text
+            .map(|text| text.trim().to_string())
+            .filter(|text| !text.is_empty());
+        if creditor_id.is_none() && text.is_none() {
+            return Err("a rule needs a creditor ID or a text".to_string());
+        }
+
+        self.insert_payment_rule(PaymentRule {
+            id: 0,
+            creditor_id,
+            text,
+        })
+    }
+}
+
+/// Fails if a meter is given and the fee belongs to another one.
+fn check_fee_meter(fee: &Fee, meter_id: Option<i32>) -> Result<(), String> {
+    match meter_id {
+        Some(meter_id) if meter_id != fee.meter_id => Err(format!(
+            "fee {} belongs to meter {}, not to meter {}",
+            fee.id, fee.meter_id, meter_id
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Checks the fee of the payment or picks the fee of the meter by the date.
+fn payment_from_params<R: FeeRepository + ?Sized>(
+    repository: &mut R,
+    id: i32,
+    params: CreatePaymentParams,
+) -> Result<Payment, String> {
+    let date = parse_date(params.date.as_str())?;
+    let fee = match params.fee_id {
+        Some(fee_id) => repository.find_fee(fee_id)?,
+        None => {
+            let datetime = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+            let meter_id = params.meter_id.unwrap_or(DEFAULT_METER_ID);
+            repository.find_fee_in_time_range(meter_id, &datetime, &datetime)?
+        }
+    };
+    let fee = match fee {
+        Some(fee) => fee,
+        None => return Err(format!("no fee found for payment of {}", date)),
+    };
+    check_fee_meter(&fee, params.meter_id)?;
+
+    Ok(Payment {
+        id,
+        fee_id: fee.id,
+        date,
+        amount: params.amount,
+    })
+}
+
+/// Invoices of the supplier.
+pub trait InvoiceRepository: FeeRepository {
+    /// Invoices ordered by the start of the billing period.
+    fn list_invoices(&mut self) -> Result<Vec<Invoice>, String>;
+
+    fn find_invoice(&mut self, id: i32) -> Result<Option<Invoice>, String>;
+
+    /// Stores the invoice and returns it with its new id, the id of the passed invoice is
+    /// ignored.
+    fn insert_invoice(&mut self, invoice: Invoice) -> Result<Invoice, String>;
+
+    fn delete_invoice(&mut self, id: i32) -> Result<(), String>;
+
+    fn create_invoice(&mut self, params: CreateInvoiceParams) -> Result<Invoice, String> {
+        let date_start = parse_date(params.date_start.as_str())?;
+        let date_end = parse_date(params.date_end.as_str())?;
+        if date_end < date_start {
+            return Err("the billing period ends before it starts".to_string());
+        }
+        let fee = match params.fee_id {
+            Some(fee_id) => self.find_fee(fee_id)?,
+            None => {
+                let start = date_start.and_hms_opt(0, 0, 0).unwrap_or_default();
+                let end = date_end.and_hms_opt(0, 0, 0).unwrap_or_default();
+                let meter_id = params.meter_id.unwrap_or(DEFAULT_METER_ID);
+                self.find_fee_in_time_range(meter_id, &start, &end)?
+            }
+        };
+        let fee = match fee {
+            Some(fee) => fee,
+            None => {
+                return Err(format!(
+                    "no fee found for the billing period {} - {}",
+                    date_start, date_end
+                ))
+            }
+        };
+        check_fee_meter(&fee, params.meter_id)?;
+
+        self.insert_invoice(Invoice {
+            id: 0,
+            fee_id: fee.id,
+            number: params.number,
+            date_start,
+            date_end,
+            consumption: params.consumption,
+            reading_start: params.reading_start,
+            reading_end: params.reading_end,
+            estimated: params.estimated,
+            net_amount: params.net_amount,
+            tax_amount: params.tax_amount,
+            gross_amount: params.gross_amount,
+            advance_payments: params.advance_payments,
+            balance: params.balance,
+        })
+    }
+}
+
+/// VAT rates per date range.
+pub trait VatRepository {
+    /// Rates ordered by their start.
+    fn list_vat_rates(&mut self) -> Result<Vec<VatRate>, String>;
+
+    /// Stores the rate and returns it with its new id, the id of the passed rate is ignored.
+    fn insert_vat_rate(&mut self, rate: VatRate) -> Result<VatRate, String>;
+
+    fn delete_vat_rate(&mut self, id: i32) -> Result<(), String>;
+
+    fn create_vat_rate(&mut self, params: CreateVatRateParams) -> Result<VatRate, String> {
+        let rate = VatRate {
+            id: 0,
+            date_start: parse_date(params.date_start.as_str())?,
+            date_end: match &params.date_end {
+                Some(date_end) => Some(parse_date(date_end.as_str())?),
+                None => None,
+            },
+            rate: params.rate,
+        };
+        if rate.rate < 0.0 {
+            return Err("VAT rate can't be negative".to_string());
+        }
+        if rate.date_end.is_some_and(|end| end < rate.date_start) {
+            return Err("VAT rate ends before it starts".to_string());
+        }
+        if let Some(other) = self
+            .list_vat_rates()?
+            .into_iter()
+            .find(|other| other.overlaps(&rate))
+        {
+            return Err(format!(
+                "VAT rate overlaps the rate of {} ({} %)",
+                other.date_start, other.rate
+            ));
+        }
+
+        self.insert_vat_rate(rate)
+    }
+}
+
+/// Everything the calculations, imports and exports need from the storage.
+pub trait Repository:
+    FeeRepository
+    + MeterReadingRepository
+    + IntervalRepository
+    + SpotPriceRepository
+    + TariffRepository
+    + PriceComponentRepository
+    + VatRepository
+    + AdjustmentRepository
+    + PaymentRepository
+    + InvoiceRepository
+{
+}
+
+impl<
+        T: FeeRepository
+            + MeterReadingRepository
+            + IntervalRepository
+            + SpotPriceRepository
+            + TariffRepository
+            + PriceComponentRepository
+            + VatRepository
+            + AdjustmentRepository
+            + PaymentRepository
+            + InvoiceRepository,
+    > Repository for T
+{
+}
+
+/// Stores a reading of the meter and assigns its fee whose period contains the reading date.
+pub fn create_meter_reading_for_date<R: Repository>(
+    repository: &mut R,
+    meter_id: i32,
+    value: f32,
+    date: &NaiveDateTime,
+) -> Result<MeterReading, String> {
+    let fee = match repository.find_fee_in_time_range(meter_id, date, date)? {
+        Some(fee) => fee,
+        None => return Err(format!("no fee of meter {} found for {}", meter_id, date)),
+    };
+
+    repository.create_meter_reading(CreateMeterReadingParams {
+        value,
+        fee_id: fee.id,
+        reading_date: format_datetime(date),
+    })
+}
@@ -0,0 +1,1346 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use rusqlite::types::Type;
+use rusqlite::{params, Connection, Row};
+
+use crate::models::adjustment::Adjustment;
+use crate::models::fees::Fee;
+use crate::models::interval::{IntervalBucket, IntervalSample, Resolution};
+use crate::models::invoice::Invoice;
+use crate::models::meter::{Meter, MeterKind, DEFAULT_METER_ID};
+use crate::models::meter_reading::{CreateMeterReadingParams, MeterReading};
+use crate::models::payment::{Payment, PaymentRule};
+use crate::models::price_component::{ComponentKind, PriceComponent};
+use crate::models::spot_price::SpotPrice;
+use crate::models::tariff::{parse_time, parse_weekdays, Holiday, TariffRule};
+use crate::models::vat::VatRate;
+use crate::models::{format_datetime, parse_date, parse_datetime};
+
+use super::{
+    fee_has_readings, meter_has_fees, AdjustmentRepository, FeeRepository, IntervalRepository,
+    InvoiceRepository, MeterReadingRepository, MeterRepository, PaymentRepository,
+    PriceComponentRepository, SpotPriceRepository, TariffRepository, VatRepository,
+};
+
+const FEE_COLUMNS: &str =
+    "f.id, f.base_fee, f.price_per_unit, f.monthly_discount, f.date_start, f.date_end, f.spot_surcharge, f.price_blocks, f.net_prices, f.price_brake, f.meter_id";
+
+pub struct SqliteRepository<'a> {
+    conn: &'a mut Connection,
+}
+
+impl<'a> SqliteRepository<'a> {
+    pub fn new(conn: &'a mut Connection) -> SqliteRepository<'a> {
+        SqliteRepository { conn }
+    }
+}
+
+fn datetime_from_row(row: &Row, index: usize) -> rusqlite::Result<NaiveDateTime> {
+    let datetime: String = row.get(index)?;
+    parse_datetime(datetime.as_str())
+        .map_err(|err| rusqlite::Error::FromSqlConversionFailure(index, Type::Text, err.into()))
+}
+
+fn conversion_error(index: usize, err: String) -> rusqlite::Error {
+    rusqlite::Error::FromSqlConversionFailure(index, Type::Text, err.into())
+}
+
+/// Maps the `FEE_COLUMNS` starting at `offset`.
+fn fee_from_row(row: &Row, offset: usize) -> rusqlite::Result<Fee> {
+    Ok(Fee {
+        id: row.get(offset)?,
+        base_fee: row.get(offset + 1)?,
+        price_per_unit: row.get(offset + 2)?,
+        monthly_discount: row.get(offset + 3)?,
+        date_start: datetime_from_row(row, offset + 4)?,
+        date_end: datetime_from_row(row, offset + 5)?,
+        spot_surcharge: row.get(offset + 6)?,
+        blocks: match row.get::<_, Option<String>>(offset + 7)? {
+            Some(blocks) => serde_json::from_str(blocks.as_str())
+                .map_err(|err| conversion_error(offset + 7, err.to_string()))?,
+            None => vec![],
+        },
+        net_prices: row.get(offset + 8)?,
+        price_brake: match row.get::<_, Option<String>>(offset + 9)? {
+            Some(brake) => Some(
+                serde_json::from_str(brake.as_str())
+                    .map_err(|err| conversion_error(offset + 9, err.to_string()))?,
+            ),
+            None => None,
+        },
+        meter_id: row.get(offset + 10)?,
+    })
+}
+
+fn meter_from_row(row: &Row) -> rusqlite::Result<Meter> {
+    let kind: String = row.get(2)?;
+    Ok(Meter {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        kind: MeterKind::parse(kind.as_str()).map_err(|err| conversion_error(2, err))?,
+        external_id: row.get(3)?,
+    })
+}
+
+fn meter_reading_from_row(row: &Row) -> rusqlite::Result<MeterReading> {
+    Ok(MeterReading {
+        id: row.get(0)?,
+        value: row.get(1)?,
+        date: datetime_from_row(row, 2)?,
+        meter_id: row.get(3)?,
+        fee: fee_from_row(row, 4)?,
+    })
+}
+
+/// Readings belong to the meter of their fee.
+const INSERT_METER_READING: &str = "INSERT INTO meter_readings (value, fee_id, reading_date, meter_id) SELECT ?1, id, ?3, meter_id FROM fees WHERE id = ?2";
+
+fn meter_reading_sql(condition: &str) -> String {
+    format!(
+        "SELECT m.id, m.value, m.reading_date, m.meter_id, {} FROM meter_readings m LEFT JOIN fees f ON f.id = m.fee_id {}",
+        FEE_COLUMNS, condition
+    )
+}
+
+fn tariff_rule_from_row(row: &Row) -> rusqlite::Result<TariffRule> {
+    let weekdays: String = row.get(3)?;
+    let time_start: String = row.get(5)?;
+    let time_end: String = row.get(6)?;
+    Ok(TariffRule {
+        id: row.get(0)?,
+        fee_id: row.get(1)?,
+        name: row.get(2)?,
+        weekdays: parse_weekdays(weekdays.as_str()).map_err(|err| conversion_error(3, err))?,
+        holidays: row.get(4)?,
+        time_start: parse_time(time_start.as_str()).map_err(|err| conversion_error(5, err))?,
+        time_end: parse_time(time_end.as_str()).map_err(|err| conversion_error(6, err))?,
+        price_per_unit: row.get(7)?,
+    })
+}
+
+fn interval_bucket_from_row(row: &Row) -> rusqlite::Result<IntervalBucket> {
+    let resolution: String = row.get(0)?;
+    Ok(IntervalBucket {
+        resolution: Resolution::parse(resolution.as_str())
+            .map_err(|err| rusqlite::Error::FromSqlConversionFailure(0, Type::Text, err.into()))?,
+        date_start: datetime_from_row(row, 1)?,
+        date_first: datetime_from_row(row, 2)?,
+        value_first: row.get(3)?,
+        date_last: datetime_from_row(row, 4)?,
+        value_last: row.get(5)?,
+        samples: row.get(6)?,
+    })
+}
+
+impl<'a> MeterRepository for SqliteRepository<'a> {
+    fn list_meters(&mut self) -> Result<Vec<Meter>, String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, name, kind, external_id FROM meters ORDER BY id")
+            .map_err(|err| err.to_string())?;
+        let meters = stmt
+            .query_map([], meter_from_row)
+            .map_err(|err| err.to_string())?;
+
+        meters
+            .collect::<Result<Vec<Meter>, _>>()
+            .map_err(|err| err.to_string())
+    }
+
+    fn find_meter(&mut self, id: i32) -> Result<Option<Meter>, String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, name, kind, external_id FROM meters WHERE id = ?")
+            .map_err(|err| err.to_string())?;
+        let mut meters = stmt
+            .query_map(params![id], meter_from_row)
+            .map_err(|err| err.to_string())?;
+
+        meters.next().transpose().map_err(|err| err.to_string())
+    }
+
+    fn insert_meter(&mut self, meter: Meter) -> Result<Meter, String> {
+        self.conn
+            .execute(
+                "INSERT INTO meters (name, kind, external_id) VALUES (?, ?, ?)",
+                (&meter.name, meter.kind.name(), &meter.external_id),
+            )
+            .map_err(|err| err.to_string())?;
+
+        Ok(Meter {
+            id: self.conn.last_insert_rowid() as i32,
+            ..meter
+        })
+    }
+
+    fn delete_meter(&mut self, id: i32) -> Result<(), String> {
+        if id == DEFAULT_METER_ID {
+            return Err("the default meter can't be deleted".to_string());
+        }
+        let tx = self.conn.transaction().map_err(|err| err.to_string())?;
+        let fees: i64 = tx
+            .query_row(
+                "SELECT COUNT(*) FROM fees WHERE meter_id = ?",
+                [id],
+                |row| row.get(0),
+            )
+            .map_err(|err| err.to_string())?;
+        if fees > 0 {
+            return Err(meter_has_fees(id, fees));
+        }
+
+        for table in ["interval_samples", "interval_buckets", "meters"] {
+            let column = if table == "meters" { "id" } else { "meter_id" };
+            tx.execute(&format!("DELETE FROM {} WHERE {} = ?", table, column), [id])
+                .map_err(|err| err.to_string())?;
+        }
+        tx.commit().map_err(|err| err.to_string())
+    }
+}
+
+impl<'a> FeeRepository for SqliteRepository<'a> {
+    fn list_fees(&mut self) -> Result<Vec<Fee>, String> {
+        let sql = format!("SELECT {} FROM fees f", FEE_COLUMNS);
+        let mut stmt = self.conn.prepare(&sql).map_err(|err| err.to_string())?;
+        let fees = stmt
+            .query_map([], |row| fee_from_row(row, 0))
+            .map_err(|err| err.to_string())?;
+
+        fees.collect::<Result<Vec<Fee>, _>>()
+            .map_err(|err| err.to_string())
+    }
+
+    fn find_fee(&mut self, id: i32) -> Result<Option<Fee>, String> {
+        let sql = format!("SELECT {} FROM fees f WHERE f.id = ?", FEE_COLUMNS);
+        let mut stmt = self.conn.prepare(&sql).map_err(|err| err.to_string())?;
+        let mut fees = stmt
+            .query_map(params![id], |row| fee_from_row(row, 0))
+            .map_err(|err| err.to_string())?;
+
+        fees.next().transpose().map_err(|err| err.to_string())
+    }
+
+    fn find_fee_in_time_range(
+        &mut self,
+        meter_id: i32,
+        date_start: &NaiveDateTime,
+        date_end: &NaiveDateTime,
+    ) -> Result<Option<Fee>, String> {
+        let sql = format!("SELECT {} FROM fees f WHERE f.meter_id = ?1 AND f.date_start <= ?3 AND f.date_end >= ?2", FEE_COLUMNS);
+        let mut stmt = self.conn.prepare(&sql).map_err(|err| err.to_string())?;
+        let mut fees = stmt
+            .query_map(
+                params![
+                    meter_id,
+                    format_datetime(date_start),
+                    format_datetime(date_end)
+                ],
+                |row| fee_from_row(row, 0),
+            )
+            .map_err(|err| err.to_string())?;
+
+        fees.next().transpose().map_err(|err| err.to_string())
+    }
+
+    fn insert_fee(&mut self, fee: Fee) -> Result<Fee, String> {
+        let blocks = serde_json::to_string(&fee.blocks).map_err(|err| err.to_string())?;
+        let price_brake = match &fee.price_brake {
+            Some(brake) => Some(serde_json::to_string(brake).map_err(|err| err.to_string())?),
+            None => None,
+        };
+        self.conn.execute("INSERT INTO fees (base_fee, price_per_unit, monthly_discount, date_start, date_end, spot_surcharge, price_blocks, net_prices, price_brake, meter_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                 (fee.base_fee, fee.price_per_unit, fee.monthly_discount, format_datetime(&fee.date_start), format_datetime(&fee.date_end), fee.spot_surcharge, blocks, fee.net_prices, price_brake, fee.meter_id))
+            .map_err(|err| err.to_string())?;
+
+        let id = self.conn.last_insert_rowid() as i32;
+        match self.find_fee(id)? {
+            Some(fee) => Ok(fee),
+            None => Err("unknown error".to_string()),
+        }
+    }
+
+    fn delete_fee(&mut self, id: i32) -> Result<(), String> {
+        let tx = self.conn.transaction().map_err(|err| err.to_string())?;
+        let readings: i64 = tx
+            .query_row(
+                "SELECT COUNT(*) FROM meter_readings WHERE fee_id = ?",
+                [id],
+                |row| row.get(0),
+            )
+            .map_err(|err| err.to_string())?;
+        if readings > 0 {
+            return Err(fee_has_readings(id, readings));
+        }
+
+        for table in [
+            "tariff_rules",
+            "price_components",
+            "adjustments",
+            "payments",
+            "invoices",
+        ] {
+            tx.execute(&format!("DELETE FROM {} WHERE fee_id = ?", table), [id])
+                .map_err(|err| err.to_string())?;
+        }
+        tx.execute("DELETE FROM fees WHERE id = ?", [id])
+            .map_err(|err| err.to_string())?;
+        tx.commit().map_err(|err| err.to_string())
+    }
+}
+
+impl<'a> MeterReadingRepository for SqliteRepository<'a> {
+    fn list_meter_readings(&mut self) -> Result<Vec<MeterReading>, String> {
+        let sql = meter_reading_sql("");
+        let mut stmt = self.conn.prepare(&sql).map_err(|err| err.to_string())?;
+        let meter_readings = stmt
+            .query_map([], meter_reading_from_row)
+            .map_err(|err| err.to_string())?;
+
+        meter_readings
+            .collect::<Result<Vec<MeterReading>, _>>()
+            .map_err(|err| err.to_string())
+    }
+
+    fn create_meter_reading(
+        &mut self,
+        params: CreateMeterReadingParams,
+    ) -> Result<MeterReading, String> {
+        let inserted = self
+            .conn
+            .execute(
+                INSERT_METER_READING,
+                (params.value, params.fee_id, params.reading_date),
+            )
+            .map_err(|err| err.to_string())?;
+        if inserted == 0 {
+            return Err(format!("fee {} not found", params.fee_id));
+        }
+
+        let id = self.conn.last_insert_rowid();
+        let sql = meter_reading_sql("WHERE m.id = ?");
+        let mut stmt = self.conn.prepare(&sql).map_err(|err| err.to_string())?;
+        let mut meter_readings = stmt
+            .query_map(params![id], meter_reading_from_row)
+            .map_err(|err| err.to_string())?;
+
+        match meter_readings.next() {
+            Some(meter_reading) => meter_reading.map_err(|err| err.to_string()),
+            None => Err("unknown error".to_string()),
+        }
+    }
+
+    fn create_meter_readings(
+        &mut self,
+        params: Vec<CreateMeterReadingParams>,
+    ) -> Result<usize, String> {
+        let tx = self.conn.transaction().map_err(|err| err.to_string())?;
+        {
+            let mut stmt = tx
+                .prepare(INSERT_METER_READING)
+                .map_err(|err| err.to_string())?;
+
+            for meter_reading in params.iter() {
+                let inserted = stmt
+                    .execute((
+                        meter_reading.value,
+                        meter_reading.fee_id,
+                        &meter_reading.reading_date,
+                    ))
+                    .map_err(|err| err.to_string())?;
+                if inserted == 0 {
+                    return Err(format!("fee {} not found", meter_reading.fee_id));
+                }
+            }
+        }
+        tx.commit().map_err(|err| err.to_string())?;
+
+        Ok(params.len())
+    }
+}
+
+impl<'a> IntervalRepository for SqliteRepository<'a> {
+    fn insert_interval_samples(
+        &mut self,
+        meter_id: i32,
+        samples: &[IntervalSample],
+    ) -> Result<usize, String> {
+        let mut count = 0;
+        let tx = self.conn.transaction().map_err(|err| err.to_string())?;
+        {
+            // dates are stored in a sortable format, so they are compared as text
+            let pruned_before: Option<String> = tx
+                .query_row(
+                    "SELECT MAX(samples_pruned_before) FROM meters WHERE id = ?",
+                    [meter_id],
+                    |row| row.get(0),
+                )
+                .map_err(|err| err.to_string())?;
+            let mut insert_sample = tx
+                .prepare(
+                    "INSERT OR IGNORE INTO interval_samples (meter_id, sample_date, value) VALUES (?, ?, ?)",
+                )
+                .map_err(|err| err.to_string())?;
+            // all expressions of the update see the values before the update, samples of the
+            // pruned period (?6) only update buckets that don't cover them yet
+            let mut upsert_bucket = tx
+                .prepare(
+                    "INSERT INTO interval_buckets (meter_id, resolution, date_start, date_first, value_first, date_last, value_last, samples)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?4, ?5, 1)
+                    ON CONFLICT (meter_id, resolution, date_start) DO UPDATE SET
+                        value_first = CASE WHEN excluded.date_first < date_first THEN excluded.value_first ELSE value_first END,
+                        date_first = MIN(date_first, excluded.date_first),
+                        value_last = CASE WHEN excluded.date_last > date_last THEN excluded.value_last ELSE value_last END,
+                        date_last = MAX(date_last, excluded.date_last),
+                        samples = samples + 1
+                    WHERE NOT ?6 OR excluded.date_first < date_first OR excluded.date_last > date_last",
+                )
+                .map_err(|err| err.to_string())?;
+
+            for sample in samples {
+                let date = format_datetime(&sample.date);
+                let pruned = pruned_before
+                    .as_ref()
+                    .is_some_and(|pruned_before| date < *pruned_before);
+                if !pruned {
+                    let inserted = insert_sample
+                        .execute((meter_id, &date, sample.value))
+                        .map_err(|err| err.to_string())?;
+                    if inserted == 0 {
+                        continue;
+                    }
+                }
+                let mut added = 0;
+                for resolution in Resolution::ALL {
+                    let date_start = format_datetime(&resolution.bucket_start(&sample.date));
+                    added += upsert_bucket
+                        .execute((
+                            meter_id,
+                            resolution.name(),
+                            date_start,
+                            &date,
+                            sample.value,
+                            pruned,
+                        ))
+                        .map_err(|err| err.to_string())?;
+                }
+                if added > 0 {
+                    count += 1;
+                }
+            }
+        }
+        tx.commit().map_err(|err| err.to_string())?;
+
+        Ok(count)
+    }
+
+    fn list_interval_samples(
+        &mut self,
+        meter_id: i32,
+        date_start: &NaiveDateTime,
+        date_end: &NaiveDateTime,
+    ) -> Result<Vec<IntervalSample>, String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT sample_date, value FROM interval_samples WHERE meter_id = ? AND sample_date >= ? AND sample_date < ? ORDER BY sample_date")
+            .map_err(|err| err.to_string())?;
+        let samples = stmt
+            .query_map(
+                params![
+                    meter_id,
+                    format_datetime(date_start),
+                    format_datetime(date_end)
+                ],
+                |row| {
+                    Ok(IntervalSample {
+                        date: datetime_from_row(row, 0)?,
+                        value: row.get(1)?,
+                    })
+                },
+            )
+            .map_err(|err| err.to_string())?;
+
+        samples
+            .collect::<Result<Vec<IntervalSample>, _>>()
+            .map_err(|err| err.to_string())
+    }
+
+    fn list_interval_buckets(
+        &mut self,
+        meter_id: i32,
+        resolution: Resolution,
+        date_start: &NaiveDateTime,
+        date_end: &NaiveDateTime,
+    ) -> Result<Vec<IntervalBucket>, String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT resolution, date_start, date_first, value_first, date_last, value_last, samples FROM interval_buckets WHERE meter_id = ? AND resolution = ? AND date_start >= ? AND date_start < ? ORDER BY date_start")
+            .map_err(|err| err.to_string())?;
+        let buckets = stmt
+            .query_map(
+                params![
+                    meter_id,
+                    resolution.name(),
+                    format_datetime(date_start),
+                    format_datetime(date_end)
+                ],
+                interval_bucket_from_row,
+            )
+            .map_err(|err| err.to_string())?;
+
+        buckets
+            .collect::<Result<Vec<IntervalBucket>, _>>()
+            .map_err(|err| err.to_string())
+    }
+
+    fn delete_interval_samples_before(
+        &mut self,
+        meter_id: i32,
+        date: &NaiveDateTime,
+    ) -> Result<usize, String> {
+        let date = format_datetime(date);
+        let tx = self.conn.transaction().map_err(|err| err.to_string())?;
+        let deleted = tx
+            .execute(
+                "DELETE FROM interval_samples WHERE meter_id = ? AND sample_date < ?",
+                params![meter_id, &date],
+            )
+            .map_err(|err| err.to_string())?;
+        tx.execute(
+            "UPDATE meters SET samples_pruned_before = MAX(COALESCE(samples_pruned_before, ?2), ?2) WHERE id = ?1",
+            params![meter_id, &date],
+        )
+        .map_err(|err| err.to_string())?;
+        tx.commit().map_err(|err| err.to_string())?;
+
+        Ok(deleted)
+    }
+}
+
+impl<'a> SpotPriceRepository for SqliteRepository<'a> {
+    fn upsert_spot_prices(&mut self, prices: &[SpotPrice]) -> Result<usize, String> {
+        let tx = self.conn.transaction().map_err(|err| err.to_string())?;
+        {
+            let mut stmt = tx
+                .prepare("INSERT OR REPLACE INTO spot_prices (date_start, price) VALUES (?, ?)")
+                .map_err(|err| err.to_string())?;
+            for price in prices {
+                stmt.execute((format_datetime(&price.date_start), price.price))
+                    .map_err(|err| err.to_string())?;
+            }
+        }
+        tx.commit().map_err(|err| err.to_string())?;
+
+        Ok(prices.len())
+    }
+
+    fn list_spot_prices(
+        &mut self,
+        date_start: &NaiveDateTime,
+        date_end: &NaiveDateTime,
+    ) -> Result<Vec<SpotPrice>, String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT date_start, price FROM spot_prices WHERE date_start >= ? AND date_start < ? ORDER BY date_start")
+            .map_err(|err| err.to_string())?;
+        let prices = stmt
+            .query_map(
+                params![format_datetime(date_start), format_datetime(date_end)],
+                |row| {
+                    Ok(SpotPrice {
+                        date_start: datetime_from_row(row, 0)?,
+                        price: row.get(1)?,
+                    })
+                },
+            )
+            .map_err(|err| err.to_string())?;
+
+        prices
+            .collect::<Result<Vec<SpotPrice>, _>>()
+            .map_err(|err| err.to_string())
+    }
+}
+
+impl<'a> TariffRepository for SqliteRepository<'a> {
+    fn list_tariff_rules(&mut self, fee_id: i32) -> Result<Vec<TariffRule>, String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, fee_id, name, weekdays, holidays, time_start, time_end, price_per_unit FROM tariff_rules WHERE fee_id = ? ORDER BY id")
+            .map_err(|err| err.to_string())?;
+        let rules = stmt
+            .query_map([fee_id], tariff_rule_from_row)
+            .map_err(|err| err.to_string())?;
+
+        rules
+            .collect::<Result<Vec<TariffRule>, _>>()
+            .map_err(|err| err.to_string())
+    }
+
+    fn insert_tariff_rule(&mut self, rule: TariffRule) -> Result<TariffRule, String> {
+        let weekdays: Vec<String> = rule.weekdays.iter().map(|day| day.to_string()).collect();
+        self.conn
+            .execute(
+                "INSERT INTO tariff_rules (fee_id, name, weekdays, holidays, time_start, time_end, price_per_unit) VALUES (?, ?, ?, ?, ?, ?, ?)",
+                (
+                    rule.fee_id,
+                    &rule.name,
+                    weekdays.join(","),
+                    rule.holidays,
+                    rule.time_start.format("%H:%M").to_string(),
+                    rule.time_end.format("%H:%M").to_string(),
+                    rule.price_per_unit,
+                ),
+            )
+            .map_err(|err| err.to_string())?;
+
+        Ok(TariffRule {
+            id: self.conn.last_insert_rowid() as i32,
+            ..rule
+        })
+    }
+
+    fn delete_tariff_rule(&mut self, id: i32) -> Result<(), String> {
+        self.conn
+            .execute("DELETE FROM tariff_rules WHERE id = ?", [id])
+            .map_err(|err| err.to_string())?;
+
+        Ok(())
+    }
+
+    fn list_holidays(&mut self) -> Result<Vec<Holiday>, String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT date, name FROM holidays ORDER BY date")
+            .map_err(|err| err.to_string())?;
+        let holidays = stmt
+            .query_map([], |row| {
+                let date: String = row.get(0)?;
+                Ok(Holiday {
+                    date: parse_date(date.as_str()).map_err(|err| conversion_error(0, err))?,
+                    name: row.get(1)?,
+                })
+            })
+            .map_err(|err| err.to_string())?;
+
+        holidays
+            .collect::<Result<Vec<Holiday>, _>>()
+            .map_err(|err| err.to_string())
+    }
+
+    fn upsert_holiday(&mut self, holiday: Holiday) -> Result<(), String> {
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO holidays (date, name) VALUES (?, ?)",
+                (holiday.date.format("%Y-%m-%d").to_string(), holiday.name),
+            )
+            .map_err(|err| err.to_string())?;
+
+        Ok(())
+    }
+
+    fn delete_holiday(&mut self, date: &NaiveDate) -> Result<(), String> {
+        self.conn
+            .execute(
+                "DELETE FROM holidays WHERE date = ?",
+                [date.format("%Y-%m-%d").to_string()],
+            )
+            .map_err(|err| err.to_string())?;
+
+        Ok(())
+    }
+}
+
+fn price_component_from_row(row: &Row) -> rusqlite::Result<PriceComponent> {
+    let kind: String = row.get(2)?;
+    Ok(PriceComponent {
+        id: row.get(0)?,
+        fee_id: row.get(1)?,
+        kind: ComponentKind::parse(kind.as_str()).map_err(|err| conversion_error(2, err))?,
+        name: row.get(3)?,
+        base_fee: row.get(4)?,
+        price_per_unit: row.get(5)?,
+        rate: row.get(6)?,
+    })
+}
+
+impl<'a> PriceComponentRepository for SqliteRepository<'a> {
+    fn list_price_components(&mut self, fee_id: i32) -> Result<Vec<PriceComponent>, String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, fee_id, kind, name, base_fee, price_per_unit, rate FROM price_components WHERE fee_id = ? ORDER BY id")
+            .map_err(|err| err.to_string())?;
+        let components = stmt
+            .query_map([fee_id], price_component_from_row)
+            .map_err(|err| err.to_string())?;
+
+        components
+            .collect::<Result<Vec<PriceComponent>, _>>()
+            .map_err(|err| err.to_string())
+    }
+
+    fn insert_price_component(
+        &mut self,
+        component: PriceComponent,
+    ) -> Result<PriceComponent, String> {
+        self.conn
+            .execute(
+                "INSERT INTO price_components (fee_id, kind, name, base_fee, price_per_unit, rate) VALUES (?, ?, ?, ?, ?, ?)",
+                (
+                    component.fee_id,
+                    component.kind.name(),
+                    &component.name,
+                    component.base_fee,
+                    component.price_per_unit,
+                    component.rate,
+                ),
+            )
+            .map_err(|err| err.to_string())?;
+
+        Ok(PriceComponent {
+            id: self.conn.last_insert_rowid() as i32,
+            ..component
+        })
+    }
+
+    fn delete_price_component(&mut self, id: i32) -> Result<(), String> {
+        self.conn
+            .execute("DELETE FROM price_components WHERE id = ?", [id])
+            .map_err(|err| err.to_string())?;
+
+        Ok(())
+    }
+}
+
+fn vat_rate_from_row(row: &Row) -> rusqlite::Result<VatRate> {
+    let date_start: String = row.get(1)?;
+    let date_end: Option<String> = row.get(2)?;
+    Ok(VatRate {
+        id: row.get(0)?,
+        date_start: parse_date(date_start.as_str()).map_err(|err| conversion_error(1, err))?,
+        date_end: match date_end {
+            Some(date_end) => {
+                Some(parse_date(date_end.as_str()).map_err(|err| conversion_error(2, err))?)
+            }
+            None => None,
+        },
+        rate: row.get(3)?,
+    })
+}
+
+impl<'a> VatRepository for SqliteRepository<'a> {
+    fn list_vat_rates(&mut self) -> Result<Vec<VatRate>, String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, date_start, date_end, rate FROM vat_rates ORDER BY date_start")
+            .map_err(|err| err.to_string())?;
+        let rates = stmt
+            .query_map([], vat_rate_from_row)
+            .map_err(|err| err.to_string())?;
+
+        rates
+            .collect::<Result<Vec<VatRate>, _>>()
+            .map_err(|err| err.to_string())
+    }
+
+    fn insert_vat_rate(&mut self, rate: VatRate) -> Result<VatRate, String> {
+        let format = |date: &NaiveDate| date.format("%Y-%m-%d").to_string();
+        self.conn
+            .execute(
+                "INSERT INTO vat_rates (date_start, date_end, rate) VALUES (?, ?, ?)",
+                (
+                    format(&rate.date_start),
+                    rate.date_end.as_ref().map(format),
+                    rate.rate,
+                ),
+            )
+            .map_err(|err| err.to_string())?;
+
+        Ok(VatRate {
+            id: self.conn.last_insert_rowid() as i32,
+            ..rate
+        })
+    }
+
+    fn delete_vat_rate(&mut self, id: i32) -> Result<(), String> {
+        self.conn
+            .execute("DELETE FROM vat_rates WHERE id = ?", [id])
+            .map_err(|err| err.to_string())?;
+
+        Ok(())
+    }
+}
+
+fn adjustment_from_row(row: &Row) -> rusqlite::Result<Adjustment> {
+    let date: String = row.get(3)?;
+    Ok(Adjustment {
+        id: row.get(0)?,
+        fee_id: row.get(1)?,
+        amount: row.get(2)?,
+        date: parse_date(date.as_str()).map_err(|err| conversion_error(3, err))?,
+        description: row.get(4)?,
+        taxable: row.get(5)?,
+    })
+}
+
+impl<'a> AdjustmentRepository for SqliteRepository<'a> {
+    fn list_adjustments(&mut self, fee_id: i32) -> Result<Vec<Adjustment>, String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, fee_id, amount, date, description, taxable FROM adjustments WHERE fee_id = ? ORDER BY date, id")
+            .map_err(|err| err.to_string())?;
+        let adjustments = stmt
+            .query_map([fee_id], adjustment_from_row)
+            .map_err(|err| err.to_string())?;
+
+        adjustments
+            .collect::<Result<Vec<Adjustment>, _>>()
+            .map_err(|err| err.to_string())
+    }
+
+    fn insert_adjustment(&mut self, adjustment: Adjustment) -> Result<Adjustment, String> {
+        self.conn
+            .execute(
+                "INSERT INTO adjustments (fee_id, amount, date, description, taxable) VALUES (?, ?, ?, ?, ?)",
+                (
+                    adjustment.fee_id,
+                    adjustment.amount,
+                    adjustment.date.format("%Y-%m-%d").to_string(),
+                    &adjustment.description,
+                    adjustment.taxable,
+                ),
+            )
+            .map_err(|err| err.to_string())?;
+
+        Ok(Adjustment {
+            id: self.conn.last_insert_rowid() as i32,
+            ..adjustment
+        })
+    }
+
+    fn delete_adjustment(&mut self, id: i32) -> Result<(), String> {
+        self.conn
+            .execute("DELETE FROM adjustments WHERE id = ?", [id])
+            .map_err(|err| err.to_string())?;
+
+        Ok(())
+    }
+}
+
+fn payment_from_row(row: &Row) -> rusqlite::Result<Payment> {
+    let date: String = row.get(2)?;
+    Ok(Payment {
+        id: row.get(0)?,
+        fee_id: row.get(1)?,
+        date: parse_date(date.as_str()).map_err(|err| conversion_error(2, err))?,
+        amount: row.get(3)?,
+    })
+}
+
+impl<'a> PaymentRepository for SqliteRepository<'a> {
+    fn list_payments(&mut self, fee_id: Option<i32>) -> Result<Vec<Payment>, String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, fee_id, date, amount FROM payments WHERE ?1 IS NULL OR fee_id = ?1 ORDER BY date, id")
+            .map_err(|err| err.to_string())?;
+        let payments = stmt
+            .query_map([fee_id], payment_from_row)
+            .map_err(|err| err.to_string())?;
+
+        payments
+            .collect::<Result<Vec<Payment>, _>>()
+            .map_err(|err| err.to_string())
+    }
+
+    fn insert_payment(&mut self, payment: Payment) -> Result<Payment, String> {
+        self.conn
+            .execute(
+                "INSERT INTO payments (fee_id, date, amount) VALUES (?, ?, ?)",
+                (
+                    payment.fee_id,
+                    payment.date.format("%Y-%m-%d").to_string(),
+                    payment.amount,
+                ),
+            )
+            .map_err(|err| err.to_string())?;
+
+        Ok(Payment {
+            id: self.conn.last_insert_rowid() as i32,
+            ..payment
+        })
+    }
+
+    fn insert_payments(&mut self, payments: Vec<Payment>) -> Result<Vec<Payment>, String> {
+        let tx = self.conn.transaction().map_err(|err| err.to_string())?;
+        let mut stored = vec![];
+        {
+            let mut stmt = tx
+                .prepare(
+                    "INSERT INTO payments (fee_id, date, amount) SELECT id, ?2, ?3 FROM fees WHERE id = ?1",
+                )
+                .map_err(|err| err.to_string())?;
+            for payment in payments {
+                let inserted = stmt
+                    .execute((
+                        payment.fee_id,
+                        payment.date.format("%Y-%m-%d").to_string(),
+                        payment.amount,
+                    ))
+                    .map_err(|err| err.to_string())?;
+                if inserted == 0 {
+                    return Err(format!("fee {} not found", payment.fee_id));
+                }
+                stored.push(Payment {
+                    id: tx.last_insert_rowid() as i32,
+                    ..payment
+                });
+            }
+        }
+        tx.commit().map_err(|err| err.to_string())?;
+
+        Ok(stored)
+    }
+
+    fn update_payment(&mut self, payment: Payment) -> Result<Payment, String> {
+        let updated = self
+            .conn
+            .execute(
+                "UPDATE payments SET fee_id = ?, date = ?, amount = ? WHERE id = ?",
+                (
+                    payment.fee_id,
+                    payment.date.format("%Y-%m-%d").to_string(),
+                    payment.amount,
+                    payment.id,
+                ),
+            )
+            .map_err(|err| err.to_string())?;
+
+        match updated {
+            0 => Err(format!("payment {} not found", payment.id)),
+            _ => Ok(payment),
+        }
+    }
+
+    fn delete_payment(&mut self, id: i32) -> Result<(), String> {
+        self.conn
+            .execute("DELETE FROM payments WHERE id = ?", [id])
+            .map_err(|err| err.to_string())?;
+
+        Ok(())
+    }
+
+    fn list_payment_rules(&mut self) -> Result<Vec<PaymentRule>, String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, creditor_id, text FROM payment_rules ORDER BY id")
+            .map_err(|err| err.to_string())?;
+        let rules = stmt
+            .query_map([], |row| {
+                Ok(PaymentRule {
+                    id: row.get(0)?,
+                    creditor_id: row.get(1)?,
+                    text: row.get(2)?,
+                })
+            })
+            .map_err(|err| err.to_string())?;
+
+        rules
+            .collect::<Result<Vec<PaymentRule>, _>>()
+            .map_err(|err| err.to_string())
+    }
+
+    fn insert_payment_rule(&mut self, rule: PaymentRule) -> Result<PaymentRule, String> {
+        self.conn
+            .execute(
+                "INSERT INTO payment_rules (creditor_id, text) VALUES (?, ?)",
+                (&rule.creditor_id, &rule.text),
+            )
+            .map_err(|err| err.to_string())?;
+
+        Ok(PaymentRule {
+            id: self.conn.last_insert_rowid() as i32,
+            ..rule
+        })
+    }
+
+    fn delete_payment_rule(&mut self, id: i32) -> Result<(), String> {
+        self.conn
+            .execute("DELETE FROM payment_rules WHERE id = ?", [id])
+            .map_err(|err| err.to_string())?;
+
+        Ok(())
+    }
+}
+
+const INVOICE_COLUMNS: &str = "id, fee_id, number, date_start, date_end, consumption, reading_start, reading_end, estimated, net_amount, tax_amount, gross_amount, advance_payments, balance";
+
+fn invoice_from_row(row: &Row) -> rusqlite::Result<Invoice> {
+    let date_start: String = row.get(3)?;
+    let date_end: String = row.get(4)?;
+    Ok(Invoice {
+        id: row.get(0)?,
+        fee_id: row.get(1)?,
+        number: row.get(2)?,
+        date_start: parse_date(date_start.as_str()).map_err(|err| conversion_error(3, err))?,
+        date_end: parse_date(date_end.as_str()).map_err(|err| conversion_error(4, err))?,
+        consumption: row.get(5)?,
+        reading_start: row.get(6)?,
+        reading_end: row.get(7)?,
+        estimated: row.get(8)?,
+        net_amount: row.get(9)?,
+        tax_amount: row.get(10)?,
+        gross_amount: row.get(11)?,
+        advance_payments: row.get(12)?,
+        balance: row.get(13)?,
+    })
+}
+
+impl<'a> InvoiceRepository for SqliteRepository<'a> {
+    fn list_invoices(&mut self) -> Result<Vec<Invoice>, String> {
+        let sql = format!(
+            "SELECT {} FROM invoices ORDER BY date_start, id",
+            INVOICE_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&sql).map_err(|err| err.to_string())?;
+        let invoices = stmt
+            .query_map([], invoice_from_row)
+            .map_err(|err| err.to_string())?;
+
+        invoices
+            .collect::<Result<Vec<Invoice>, _>>()
+            .map_err(|err| err.to_string())
+    }
+
+    fn find_invoice(&mut self, id: i32) -> Result<Option<Invoice>, String> {
+        let sql = format!("SELECT {} FROM invoices WHERE id = ?", INVOICE_COLUMNS);
+        let mut stmt = self.conn.prepare(&sql).map_err(|err| err.to_string())?;
+        let mut invoices = stmt
+            .query_map([id], invoice_from_row)
+            .map_err(|err| err.to_string())?;
+
+        invoices.next().transpose().map_err(|err| err.to_string())
+    }
+
+    fn insert_invoice(&mut self, invoice: Invoice) -> Result<Invoice, String> {
+        self.conn
+            .execute(
+                "INSERT INTO invoices (fee_id, number, date_start, date_end, consumption, reading_start, reading_end, estimated, net_amount, tax_amount, gross_amount, advance_payments, balance) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+                params![
+                    invoice.fee_id,
+                    invoice.number,
+                    invoice.date_start.format("%Y-%m-%d").to_string(),
+                    invoice.date_end.format("%Y-%m-%d").to_string(),
+                    invoice.consumption,
+                    invoice.reading_start,
+                    invoice.reading_end,
+                    invoice.estimated,
+                    invoice.net_amount,
+                    invoice.tax_amount,
+                    invoice.gross_amount,
+                    invoice.advance_payments,
+                    invoice.balance,
+                ],
+            )
+            .map_err(|err| err.to_string())?;
+
+        Ok(Invoice {
+            id: self.conn.last_insert_rowid() as i32,
+            ..invoice
+        })
+    }
+
+    fn delete_invoice(&mut self, id: i32) -> Result<(), String> {
+        self.conn
+            .execute("DELETE FROM invoices WHERE id = ?", [id])
+            .map_err(|err| err.to_string())?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use crate::db::connection::run_migrations;
+    use crate::models::fees::CreateFeeParams;
+    use crate::models::interval::{IntervalSample, Resolution};
+    use crate::models::meter::{CreateMeterParams, MeterKind};
+    use crate::models::meter_reading::CreateMeterReadingParams;
+    use crate::models::parse_datetime;
+    use crate::models::payment::{CreatePaymentParams, Payment};
+    use crate::repository::{
+        FeeRepository, IntervalRepository, MeterReadingRepository, MeterRepository,
+        PaymentRepository,
+    };
+
+    use super::SqliteRepository;
+
+    #[test]
+    fn list_is_empty() {
+        let mut conn = Connection::open_in_memory().expect("could not create memory database");
+        run_migrations(&mut conn).expect("failed to run migrations");
+        let fees = SqliteRepository::new(&mut conn)
+            .list_fees()
+            .expect("failed to get fees list");
+        assert_eq!(fees.len(), 0);
+    }
+
+    #[test]
+    fn list_has_data() {
+        let mut conn = Connection::open_in_memory().expect("could not create memory database");
+        run_migrations(&mut conn).expect("failed to run migrations");
+
+        let base_fee: f32 = 10.0;
+        let price_per_unit: f32 = 0.5;
+        let monthly_discount: f32 = 45.0;
+        let start_date = "2022-12-01T05:00:00.000Z".to_string();
+        let end_date = "2022-12-01T05:00:00.000Z".to_string();
+        conn.execute("INSERT INTO fees (base_fee, price_per_unit, monthly_discount, date_start, date_end) VALUES (?1, ?2, ?3, ?4, ?5)",
+                 (base_fee, price_per_unit, monthly_discount, start_date, end_date)).expect("failed to save fee");
+
+        let fees = SqliteRepository::new(&mut conn)
+            .list_fees()
+            .expect("failed to get fees list");
+        assert_eq!(fees.len(), 1);
+        let fee = fees.first().expect("failed to retrieve fee");
+        assert_eq!(fee.base_fee, base_fee);
+        assert_eq!(fee.price_per_unit, price_per_unit);
+        assert_eq!(fee.monthly_discount, monthly_discount);
+    }
+
+    #[test]
+    fn create() {
+        let mut conn = Connection::open_in_memory().expect("could not create memory database");
+        run_migrations(&mut conn).expect("failed to run migrations");
+
+        let params = CreateFeeParams {
+            meter_id: None,
+            base_fee: 10.0,
+            price_per_unit: 0.5,
+            monthly_discount: 45.0,
+            spot_surcharge: None,
+            blocks: vec![],
+            net_prices: false,
+            price_brake: None,
+            date_start: "2022-12-01T05:00:00.000Z".to_string(),
+            date_end: "2022-12-01T05:00:00.000Z".to_string(),
+        };
+
+        let mut repository = SqliteRepository::new(&mut conn);
+        let fee = repository.create_fee(params).expect("failed to create fee");
+        assert_eq!(fee.id, 1);
+
+        let params = CreateFeeParams {
+            meter_id: None,
+            base_fee: 10.0,
+            price_per_unit: 0.5,
+            monthly_discount: 45.0,
+            spot_surcharge: None,
+            blocks: vec![],
+            net_prices: false,
+            price_brake: None,
+            date_start: "2022-11-01T05:00:00.000Z".to_string(),
+            date_end: "2022-12-01T05:00:00.000Z".to_string(),
+        };
+        assert!(repository.create_fee(params).is_err());
+    }
+
+    #[test]
+    fn find_in_time_range() {
+        let mut conn = Connection::open_in_memory().expect("could not create memory database");
+        run_migrations(&mut conn).expect("failed to run migrations");
+
+        let base_fee: f32 = 10.0;
+        let price_per_unit: f32 = 0.5;
+        let monthly_discount: f32 = 45.0;
+        let start_date = "2022-01-01T05:00:00.000Z".to_string();
+        let end_date = "2022-12-01T05:00:00.000Z".to_string();
+        conn.execute("INSERT INTO fees (base_fee, price_per_unit, monthly_discount, date_start, date_end) VALUES (?1, ?2, ?3, ?4, ?5)",
+                 (base_fee, price_per_unit, monthly_discount, start_date, end_date)).expect("failed to save fee");
+
+        let fee = SqliteRepository::new(&mut conn)
+            .find_fee_in_time_range(
+                1,
+                &parse_datetime("2022-03-01T05:00:00.000Z").unwrap(),
+                &parse_datetime("2022-06-01T05:00:00.000Z").unwrap(),
+            )
+            .expect("failed to find fee");
+
+        assert!(fee.is_some());
+
+        // a range enclosing the whole fee overlaps it as well
+        let fee = SqliteRepository::new(&mut conn)
+            .find_fee_in_time_range(
+                1,
+                &parse_datetime("2021-06-01T00:00:00.000Z").unwrap(),
+                &parse_datetime("2024-01-31T00:00:00.000Z").unwrap(),
+            )
+            .expect("failed to find fee");
+        assert!(fee.is_some());
+    }
+
+    #[test]
+    fn interval_buckets() {
+        let mut conn = Connection::open_in_memory().expect("could not create memory database");
+        run_migrations(&mut conn).expect("failed to run migrations");
+        let mut repository = SqliteRepository::new(&mut conn);
+
+        let sample = |date: &str, value: f64| IntervalSample {
+            date: parse_datetime(date).unwrap(),
+            value,
+        };
+        repository
+            .insert_interval_samples(
+                1,
+                &[
+                    sample("2023-01-15T10:05:00.000Z", 100.2),
+                    sample("2023-01-15T10:01:00.000Z", 100.0),
+                    sample("2023-01-15T10:20:00.000Z", 100.5),
+                    sample("2023-01-16T08:00:00.000Z", 110.0),
+                ],
+            )
+            .expect("failed to insert samples");
+        let inserted = repository
+            .insert_interval_samples(1, &[sample("2023-01-15T10:01:00.000Z", 90.0)])
+            .unwrap();
+        assert_eq!(inserted, 0);
+
+        // another meter has its own series, the same date is no duplicate there
+        let gas = repository
+            .create_meter(CreateMeterParams {
+                name: "Gas".to_string(),
+                kind: MeterKind::Gas,
+                external_id: None,
+            })
+            .unwrap();
+        let inserted = repository
+            .insert_interval_samples(gas.id, &[sample("2023-01-15T10:01:00.000Z", 5.0)])
+            .unwrap();
+        assert_eq!(inserted, 1);
+
+        let start = parse_datetime("2023-01-15T00:00:00.000Z").unwrap();
+        let end = parse_datetime("2023-01-17T00:00:00.000Z").unwrap();
+        let quarters = repository
+            .list_interval_buckets(1, Resolution::QuarterHour, &start, &end)
+            .unwrap();
+        assert_eq!(quarters.len(), 3);
+        assert_eq!(quarters[0].value_first, 100.0);
+        assert_eq!(quarters[0].value_last, 100.2);
+        assert_eq!(quarters[0].samples, 2);
+
+        let days = repository
+            .list_interval_buckets(1, Resolution::Day, &start, &end)
+            .unwrap();
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].value_last, 100.5);
+        assert_eq!(days[0].samples, 3);
+
+        let deleted = repository
+            .delete_interval_samples_before(1, &parse_datetime("2023-01-16T00:00:00.000Z").unwrap())
+            .unwrap();
+        assert_eq!(deleted, 3);
+        assert_eq!(
+            repository
+                .list_interval_samples(1, &start, &end)
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // a sample sent again after its raw sample was deleted is not counted twice
+        let inserted = repository
+            .insert_interval_samples(1, &[sample("2023-01-15T10:05:00.000Z", 100.2)])
+            .unwrap();
+        assert_eq!(inserted, 0);
+        let quarters = repository
+            .list_interval_buckets(1, Resolution::QuarterHour, &start, &end)
+            .unwrap();
+        assert_eq!(quarters[0].samples, 2);
+
+        // an older sample still gets its buckets, only the raw sample is not kept
+        let older = parse_datetime("2023-01-14T09:00:00.000Z").unwrap();
+        let inserted = repository
+            .insert_interval_samples(1, &[sample("2023-01-14T09:00:00.000Z", 95.0)])
+            .unwrap();
+        assert_eq!(inserted, 1);
+        assert!(repository
+            .list_interval_samples(1, &older, &start)
+            .unwrap()
+            .is_empty());
+        let hours = repository
+            .list_interval_buckets(1, Resolution::Hour, &older, &start)
+            .unwrap();
+        assert_eq!(hours.len(), 1);
+        assert_eq!(hours[0].value_first, 95.0);
+        assert_eq!(
+            repository
+                .list_interval_buckets(1, Resolution::Hour, &start, &end)
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn delete_fee_keeps_fees_with_readings() {
+        let mut conn = Connection::open_in_memory().expect("could not create memory database");
+        run_migrations(&mut conn).expect("failed to run migrations");
+        let mut repository = SqliteRepository::new(&mut conn);
+
+        let fee_params = |year: i32| CreateFeeParams {
+            meter_id: None,
+            base_fee: 10.0,
+            price_per_unit: 0.5,
+            monthly_discount: 45.0,
+            spot_surcharge: None,
+            blocks: vec![],
+            net_prices: false,
+            price_brake: None,
+            date_start: format!("{}-01-01T00:00:00.000Z", year),
+            date_end: format!("{}-12-31T00:00:00.000Z", year),
+        };
+        let payment = |fee_id: i32, date: &str| CreatePaymentParams {
+            fee_id: Some(fee_id),
+            meter_id: None,
+            date: date.to_string(),
+            amount: 45.0,
+        };
+        let used = repository.create_fee(fee_params(2022)).unwrap();
+        let unused = repository.create_fee(fee_params(2023)).unwrap();
+        repository
+            .create_payment(payment(used.id, "2022-01-15"))
+            .unwrap();
+        repository
+            .create_payment(payment(unused.id, "2023-01-15"))
+            .unwrap();
+        repository
+            .create_meter_reading(CreateMeterReadingParams {
+                value: 100.0,
+                fee_id: used.id,
+                reading_date: "2022-02-01T00:00:00.000Z".to_string(),
+            })
+            .unwrap();
+
+        assert!(repository.delete_fee(used.id).is_err());
+        assert!(repository.find_fee(used.id).unwrap().is_some());
+        assert_eq!(repository.list_payments(Some(used.id)).unwrap().len(), 1);
+
+        repository
+            .delete_fee(unused.id)
+            .expect("failed to delete fee");
+        assert!(repository.find_fee(unused.id).unwrap().is_none());
+        assert_eq!(repository.list_payments(None).unwrap().len(), 1);
+
+        // payments of a deleted fee are not stored, none of them
+        let stored = Payment {
+            id: 0,
+            fee_id: used.id,
+            date: chrono::NaiveDate::from_ymd_opt(2022, 2, 15).unwrap(),
+            amount: 45.0,
+        };
+        let orphan = Payment {
+            fee_id: unused.id,
+            ..stored.clone()
+        };
+        assert!(repository.insert_payments(vec![stored, orphan]).is_err());
+        assert_eq!(repository.list_payments(None).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,647 @@
+//! P1 telegrams of Dutch and Belgian smart meters (DSMR 4 and 5, e-MUCS).
+//!
+//! A telegram starts with `/` and the meter id, followed by one COSEM object per line
+//! (`1-0:1.8.1(001234.567*kWh)`) and ends with `!` and the CRC16 of everything from
+//! `/` up to and including `!`.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, ErrorKind};
+
+use chrono::{Duration, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+use super::{IngestResult, IntervalFilter};
+use crate::models::meter::{default_meter_id, MeterKind};
+use crate::repository::Repository;
+
+/// The register that is stored as meter reading.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DsmrRegister {
+    /// Tariff 1 + tariff 2 delivered to the customer
+    #[serde(rename = "importTotal")]
+    ImportTotal,
+    #[serde(rename = "importTariff1")]
+    ImportTariff1,
+    #[serde(rename = "importTariff2")]
+    ImportTariff2,
+    /// Tariff 1 + tariff 2 delivered by the customer
+    #[serde(rename = "exportTotal")]
+    ExportTotal,
+    #[serde(rename = "exportTariff1")]
+    ExportTariff1,
+    #[serde(rename = "exportTariff2")]
+    ExportTariff2,
+    #[serde(rename = "gas")]
+    Gas,
+}
+
+impl DsmrRegister {
+    /// What the register counts, it's only stored for meters of this kind.
+    pub fn kind(&self) -> MeterKind {
+        match self {
+            DsmrRegister::ImportTotal
+            | DsmrRegister::ImportTariff1
+            | DsmrRegister::ImportTariff2 => MeterKind::Consumption,
+            DsmrRegister::ExportTotal
+            | DsmrRegister::ExportTariff1
+            | DsmrRegister::ExportTariff2 => MeterKind::FeedIn,
+            DsmrRegister::Gas => MeterKind::Gas,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DsmrOptions {
+    pub register: DsmrRegister,
+    /// Meter the register is stored for, it must count the same as the register
+    #[serde(rename = "meterId", default = "default_meter_id")]
+    pub meter_id: i32,
+    /// Gas meter the gas register of the telegrams is stored for as well
+    #[serde(rename = "gasMeterId", default)]
+    pub gas_meter_id: Option<i32>,
+    /// Only the first telegram of every interval is stored
+    #[serde(rename = "intervalSeconds")]
+    pub interval_seconds: i64,
+    #[serde(rename = "verifyCrc")]
+    pub verify_crc: bool,
+}
+
+impl Default for DsmrOptions {
+    fn default() -> Self {
+        DsmrOptions {
+            register: DsmrRegister::ImportTotal,
+            meter_id: default_meter_id(),
+            gas_meter_id: None,
+            interval_seconds: 15 * 60,
+            verify_crc: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CosemObject {
+    pub obis: String,
+    pub values: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GasReading {
+    pub date: NaiveDateTime,
+    /// m³
+    pub value: f64,
+}
+
+/// Registers are in kWh, power in kW, dates in UTC.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Telegram {
+    pub header: String,
+    pub version: Option<String>,
+    pub date: Option<NaiveDateTime>,
+    pub equipment_id: Option<String>,
+    pub import_tariff1: Option<f64>,
+    pub import_tariff2: Option<f64>,
+    pub export_tariff1: Option<f64>,
+    pub export_tariff2: Option<f64>,
+    pub tariff: Option<u8>,
+    pub power_import: Option<f64>,
+    pub power_export: Option<f64>,
+    pub gas: Option<GasReading>,
+    pub objects: Vec<CosemObject>,
+}
+
+impl Telegram {
+    pub fn register(&self, register: DsmrRegister) -> Option<f64> {
+        let sum = |a: Option<f64>, b: Option<f64>| Some(a? + b?);
+        match register {
+            DsmrRegister::ImportTotal => sum(self.import_tariff1, self.import_tariff2),
+            DsmrRegister::ImportTariff1 => self.import_tariff1,
+            DsmrRegister::ImportTariff2 => self.import_tariff2,
+            DsmrRegister::ExportTotal => sum(self.export_tariff1, self.export_tariff2),
+            DsmrRegister::ExportTariff1 => self.export_tariff1,
+            DsmrRegister::ExportTariff2 => self.export_tariff2,
+            DsmrRegister::Gas => self.gas.as_ref().map(|gas| gas.value),
+        }
+    }
+}
+
+/// CRC-16/ARC as used by DSMR 4 and later.
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for byte in data {
+        crc ^= *byte as u16;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xA001
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+/// Telegrams of DSMR 2 and 3 have no checksum, these are accepted as is.
+pub fn verify_crc(raw: &str) -> Result<(), String> {
+    let start = raw.find('/').ok_or("telegram has no header")?;
+    let end = raw.rfind('!').ok_or("telegram has no end")?;
+    if end < start {
+        return Err("telegram has no end".to_string());
+    }
+
+    let given = raw[end + 1..].trim();
+    if given.is_empty() {
+        return Ok(());
+    }
+    let given = u16::from_str_radix(given, 16).map_err(|_| format!("invalid crc '{}'", given))?;
+    let expected = crc16(&raw.as_bytes()[start..=end]);
+    if given != expected {
+        return Err(format!(
+            "crc mismatch: telegram has {:04X}, computed {:04X}",
+            given, expected
+        ));
+    }
+    Ok(())
+}
+
+/// `YYMMDDhhmmssX` where X is `S` for summer (CEST) and `W` for winter time (CET).
+fn parse_timestamp(value: &str) -> Result<NaiveDateTime, String> {
+    if value.len() != 13 || !value.is_ascii() {
+        return Err(format!("invalid timestamp '{}'", value));
+    }
+    let local = NaiveDateTime::parse_from_str(&value[..12], "%y%m%d%H%M%S")
+        .map_err(|_| format!("invalid timestamp '{}'", value))?;
+    let offset = match &value[12..] {
+        "S" => 2,
+        "W" => 1,
+        _ => return Err(format!("invalid timestamp '{}'", value)),
+    };
+    Ok(local - Duration::hours(offset))
+}
+
+/// Parses `001234.567*kWh`, Wh are converted to kWh and W to kW.
+fn parse_quantity(value: &str) -> Result<f64, String> {
+    let (number, unit) = value.split_once('*').unwrap_or((value, ""));
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid value '{}'", value))?;
+    match unit {
+        "Wh" | "W" => Ok(number / 1000.0),
+        _ => Ok(number),
+    }
+}
+
+fn decode_hex(value: &str) -> Option<String> {
+    if !value.len().is_multiple_of(2) {
+        return None;
+    }
+    let bytes = (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()
+}
+
+fn parse_object(line: &str) -> Option<CosemObject> {
+    let open = line.find('(')?;
+    let obis = line[..open].trim().to_string();
+    let values = line[open..]
+        .split(')')
+        .filter_map(|part| part.trim().strip_prefix('('))
+        .map(|value| value.to_string())
+        .collect();
+    Some(CosemObject { obis, values })
+}
+
+/// Parses a telegram, the checksum is not checked here.
+pub fn parse(raw: &str) -> Result<Telegram, String> {
+    let mut lines = raw
+        .lines()
+        .map(|line| line.trim())
+        .skip_while(|line| !line.starts_with('/'));
+    let header = lines.next().ok_or("telegram has no header")?;
+
+    let mut telegram = Telegram {
+        header: header[1..].to_string(),
+        ..Default::default()
+    };
+
+    for line in lines {
+        if line.starts_with('!') {
+            return Ok(telegram);
+        }
+        let object = match parse_object(line) {
+            Some(object) => object,
+            None => continue,
+        };
+        let first = object.values.first().map(|value| value.as_str());
+        let last = object.values.last().map(|value| value.as_str());
+
+        match (object.obis.as_str(), first) {
+            ("1-3:0.2.8", Some(version)) | ("0-0:96.1.4", Some(version)) => {
+                telegram.version = Some(version.to_string())
+            }
+            ("0-0:1.0.0", Some(date)) => telegram.date = Some(parse_timestamp(date)?),
+            ("0-0:96.1.1", Some(id)) => telegram.equipment_id = decode_hex(id),
+            ("1-0:1.8.1", Some(value)) => telegram.import_tariff1 = Some(parse_quantity(value)?),
+            ("1-0:1.8.2", Some(value)) => telegram.import_tariff2 = Some(parse_quantity(value)?),
+            ("1-0:2.8.1", Some(value)) => telegram.export_tariff1 = Some(parse_quantity(value)?),
+            ("1-0:2.8.2", Some(value)) => telegram.export_tariff2 = Some(parse_quantity(value)?),
+            ("0-0:96.14.0", Some(tariff)) => telegram.tariff = tariff.parse().ok(),
+            ("1-0:1.7.0", Some(value)) => telegram.power_import = Some(parse_quantity(value)?),
+            ("1-0:2.7.0", Some(value)) => telegram.power_export = Some(parse_quantity(value)?),
+            (obis, Some(date))
+                if telegram.gas.is_none()
+                    && (obis.ends_with(":24.2.1") || obis.ends_with(":24.2.3"))
+                    && last.is_some_and(|value| value.ends_with("*m3")) =>
+            {
+                telegram.gas = Some(GasReading {
+                    date: parse_timestamp(date)?,
+                    value: parse_quantity(last.unwrap_or_default())?,
+                })
+            }
+            _ => {}
+        }
+        telegram.objects.push(object);
+    }
+
+    Err("telegram has no end".to_string())
+}
+
+/// Splits a byte stream into raw telegrams, data before the first header is skipped.
+/// Read timeouts of serial ports are retried.
+pub struct TelegramReader<S: BufRead> {
+    source: S,
+}
+
+impl<S: BufRead> TelegramReader<S> {
+    pub fn new(source: S) -> TelegramReader<S> {
+        TelegramReader { source }
+    }
+
+    fn read_line(&mut self, line: &mut Vec<u8>) -> Result<usize, String> {
+        loop {
+            match self.source.read_until(b'\n', line) {
+                Ok(read) => return Ok(read),
+                Err(err)
+                    if err.kind() == ErrorKind::TimedOut
+                        || err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(err.to_string()),
+            }
+        }
+    }
+}
+
+impl<S: BufRead> Iterator for TelegramReader<S> {
+    type Item = Result<String, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut telegram: Vec<u8> = Vec::new();
+        loop {
+            let mut line = Vec::new();
+            match self.read_line(&mut line) {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(err) => return Some(Err(err)),
+            }
+
+            if line.starts_with(b"/") {
+                telegram.clear();
+            } else if telegram.is_empty() {
+                continue;
+            }
+            telegram.extend_from_slice(&line);
+
+            if line.starts_with(b"!") {
+                return Some(String::from_utf8(telegram).map_err(|err| err.to_string()));
+            }
+        }
+    }
+}
+
+/// Register of the telegrams stored for one meter.
+struct DsmrSeries {
+    register: DsmrRegister,
+    meter_id: i32,
+    filter: IntervalFilter,
+}
+
+/// Turns telegrams into meter readings of the configured register and, if configured, of
+/// the gas register. Every register is stored for its own meter.
+pub struct DsmrIngest {
+    options: DsmrOptions,
+    series: Vec<DsmrSeries>,
+    pub result: IngestResult,
+}
+
+impl DsmrIngest {
+    /// Fails if a meter doesn't count what its register counts, e.g. m³ of gas would be
+    /// added to kWh.
+    pub fn new<R: Repository>(
+        repository: &mut R,
+        options: DsmrOptions,
+    ) -> Result<DsmrIngest, String> {
+        let mut registers = vec![(options.register, options.meter_id)];
+        if let Some(gas_meter_id) = options.gas_meter_id {
+            if options.register == DsmrRegister::Gas {
+                return Err("the gas register is already stored".to_string());
+            }
+            registers.push((DsmrRegister::Gas, gas_meter_id));
+        }
+
+        let mut series = vec![];
+        for (register, meter_id) in registers {
+            repository
+                .get_meter(meter_id)?
+                .expect_kind(register.kind())?;
+            series.push(DsmrSeries {
+                register,
+                meter_id,
+                filter: IntervalFilter::new(options.interval_seconds)
+                    .resume(repository, meter_id)?,
+            });
+        }
+        if series[1..]
+            .iter()
+            .any(|other| other.meter_id == series[0].meter_id)
+        {
+            return Err(format!(
+                "the registers need their own meters, not both meter {}",
+                series[0].meter_id
+            ));
+        }
+
+        Ok(DsmrIngest {
+            options,
+            series,
+            result: IngestResult::default(),
+        })
+    }
+
+    /// Telegrams without a timestamp are stored for now.
+    pub fn process<R: Repository>(&mut self, repository: &mut R, raw: &str) {
+        self.result.received += 1;
+
+        let telegram = match self.check(raw) {
+            Ok(telegram) => telegram,
+            Err(err) => {
+                self.result.errors.push(err);
+                return;
+            }
+        };
+
+        for series in self.series.iter_mut() {
+            let value = match telegram.register(series.register) {
+                Some(value) => value,
+                None => {
+                    self.result
+                        .errors
+                        .push(format!("telegram has no {:?} register", series.register));
+                    continue;
+                }
+            };
+            let date = match series.register {
+                DsmrRegister::Gas => telegram.gas.as_ref().map(|gas| gas.date),
+                _ => telegram.date,
+            }
+            .unwrap_or_else(|| chrono::Utc::now().naive_utc());
+
+            self.result.store(
+                repository,
+                series.meter_id,
+                &mut series.filter,
+                value,
+                &date,
+            );
+        }
+    }
+
+    fn check(&self, raw: &str) -> Result<Telegram, String> {
+        if self.options.verify_crc {
+            verify_crc(raw)?;
+        }
+        parse(raw)
+    }
+}
+
+pub fn ingest<R: Repository, S: BufRead>(
+    repository: &mut R,
+    source: S,
+    options: DsmrOptions,
+) -> Result<IngestResult, String> {
+    let mut ingest = DsmrIngest::new(repository, options)?;
+    for raw in TelegramReader::new(source) {
+        ingest.process(repository, raw?.as_str());
+    }
+    Ok(ingest.result)
+}
+
+/// Imports a file with recorded telegrams, e.g. written with `cat /dev/ttyUSB0 > p1.txt`.
+pub fn ingest_file<R: Repository>(
+    repository: &mut R,
+    path: &str,
+    options: DsmrOptions,
+) -> Result<IngestResult, String> {
+    let file = File::open(path).map_err(|err| err.to_string())?;
+    ingest(repository, BufReader::new(file), options)
+}
+
+/// Opens the P1 port, DSMR 4 and 5 use 115200 baud 8N1, DSMR 2 and 3 9600 baud 7E1.
+#[cfg(feature = "serial")]
+pub fn open_serial(
+    device: &str,
+    legacy: bool,
+) -> Result<BufReader<Box<dyn serialport::SerialPort>>, String> {
+    use serialport::{DataBits, Parity};
+
+    let builder = match legacy {
+        true => serialport::new(device, 9600)
+            .data_bits(DataBits::Seven)
+            .parity(Parity::Even),
+        false => serialport::new(device, 115200),
+    };
+    let port = builder
+        .timeout(std::time::Duration::from_secs(60))
+        .open()
+        .map_err(|err| err.to_string())?;
+    Ok(BufReader::new(port))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::models::fees::CreateFeeParams;
+    use crate::models::meter::{CreateMeterParams, MeterKind};
+    use crate::repository::{
+        FeeRepository, InMemoryRepository, MeterReadingRepository, MeterRepository,
+    };
+
+    use super::{crc16, ingest, parse, verify_crc, DsmrOptions, DsmrRegister};
+
+    fn telegram(time: &str, import1: &str, import2: &str) -> String {
+        let body = format!(
+            "/ISK5\\2M550T-1012\r\n\r\n\
+             1-3:0.2.8(50)\r\n\
+             0-0:1.0.0({time})\r\n\
+             0-0:96.1.1(4530303434303037313331363530363137)\r\n\
+             1-0:1.8.1({import1}*kWh)\r\n\
+             1-0:1.8.2({import2}*kWh)\r\n\
+             1-0:2.8.1(000012.000*kWh)\r\n\
+             1-0:2.8.2(000003.500*kWh)\r\n\
+             0-0:96.14.0(0002)\r\n\
+             1-0:1.7.0(00.345*kW)\r\n\
+             1-0:2.7.0(00.000*kW)\r\n\
+             0-0:96.7.21(00010)\r\n\
+             1-0:99.97.0(1)(0-0:96.7.19)(000101000001W)(2147483647*s)\r\n\
+             0-1:24.1.0(003)\r\n\
+             0-1:24.2.1({time})(01234.567*m3)\r\n\
+             !"
+        );
+        format!("{}{:04X}\r\n", body, crc16(body.as_bytes()))
+    }
+
+    #[test]
+    fn crc() {
+        assert_eq!(crc16(b"123456789"), 0xBB3D);
+
+        let raw = telegram("230115120000W", "001000.100", "000500.200");
+        assert!(verify_crc(raw.as_str()).is_ok());
+        let tampered = raw.replace("001000.100", "001000.900");
+        assert!(verify_crc(tampered.as_str()).is_err());
+    }
+
+    #[test]
+    fn parses_telegram() {
+        let raw = telegram("230715120000S", "001000.250", "000500.250");
+        let telegram = parse(raw.as_str()).unwrap();
+
+        assert_eq!(telegram.header, "ISK5\\2M550T-1012");
+        assert_eq!(telegram.version.as_deref(), Some("50"));
+        assert_eq!(
+            telegram.date,
+            NaiveDate::from_ymd_opt(2023, 7, 15)
+                .unwrap()
+                .and_hms_opt(10, 0, 0)
+        );
+        assert_eq!(telegram.equipment_id.as_deref(), Some("E0044007131650617"));
+        assert_eq!(telegram.tariff, Some(2));
+        assert_eq!(telegram.power_import, Some(0.345));
+        assert_eq!(telegram.register(DsmrRegister::ImportTotal), Some(1500.5));
+        assert_eq!(telegram.register(DsmrRegister::ExportTotal), Some(15.5));
+        assert_eq!(telegram.register(DsmrRegister::Gas), Some(1234.567));
+    }
+
+    #[test]
+    fn stores_one_reading_per_interval() {
+        let mut repository = InMemoryRepository::new();
+        repository
+            .create_fee(CreateFeeParams {
+                meter_id: None,
+                base_fee: 10.0,
+                price_per_unit: 0.3,
+                monthly_discount: 50.0,
+                spot_surcharge: None,
+                blocks: vec![],
+                net_prices: false,
+                price_brake: None,
+                date_start: "2023-01-01T00:00:00.000Z".to_string(),
+                date_end: "2023-12-31T00:00:00.000Z".to_string(),
+            })
+            .unwrap();
+
+        let mut recording = String::from("0-0:96.7.21(00010)\r\n!1234\r\n");
+        recording.push_str(&telegram("230115120000W", "001000.000", "000500.000"));
+        recording.push_str(&telegram("230115120500W", "001000.100", "000500.000"));
+        recording.push_str(
+            &telegram("230115121500W", "001000.200", "000500.000").replace("*kWh", "*kWh "),
+        );
+        recording.push_str(&telegram("230115121510W", "001000.300", "000500.000"));
+
+        let result = ingest(
+            &mut repository,
+            recording.as_bytes(),
+            DsmrOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(result.received, 4);
+        assert_eq!(result.samples, 3);
+        assert_eq!(result.stored, 2);
+        assert_eq!(result.skipped, 1);
+        assert_eq!(result.errors.len(), 1);
+
+        let mut readings = repository.list_meter_readings().unwrap();
+        readings.sort_by_key(|reading| reading.date);
+        assert_eq!(readings.len(), 2);
+        assert_eq!(readings[0].value, 1500.0);
+        assert_eq!(readings[1].value, 1500.3);
+
+        // the newest stored reading is taken into account on the next run
+        let result = ingest(
+            &mut repository,
+            recording.as_bytes(),
+            DsmrOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(result.samples, 0);
+        assert_eq!(result.stored, 0);
+    }
+
+    #[test]
+    fn stores_gas_for_its_own_meter() {
+        let mut repository = InMemoryRepository::new();
+        let gas = repository
+            .create_meter(CreateMeterParams {
+                name: "Gas".to_string(),
+                kind: MeterKind::Gas,
+                external_id: None,
+            })
+            .unwrap();
+        for meter_id in [1, gas.id] {
+            repository
+                .create_fee(CreateFeeParams {
+                    meter_id: Some(meter_id),
+                    base_fee: 10.0,
+                    price_per_unit: 0.3,
+                    monthly_discount: 50.0,
+                    spot_surcharge: None,
+                    blocks: vec![],
+                    net_prices: false,
+                    price_brake: None,
+                    date_start: "2023-01-01T00:00:00.000Z".to_string(),
+                    date_end: "2023-12-31T00:00:00.000Z".to_string(),
+                })
+                .unwrap();
+        }
+        let recording = telegram("230115120000W", "001000.000", "000500.000");
+
+        // m³ are not stored for an electricity meter, kWh not for a gas meter
+        let gas_into_power = DsmrOptions {
+            register: DsmrRegister::Gas,
+            ..DsmrOptions::default()
+        };
+        assert!(ingest(&mut repository, recording.as_bytes(), gas_into_power).is_err());
+        let export_into_gas = DsmrOptions {
+            register: DsmrRegister::ExportTotal,
+            meter_id: gas.id,
+            ..DsmrOptions::default()
+        };
+        assert!(ingest(&mut repository, recording.as_bytes(), export_into_gas).is_err());
+
+        let options = DsmrOptions {
+            gas_meter_id: Some(gas.id),
+            ..DsmrOptions::default()
+        };
+        let result = ingest(&mut repository, recording.as_bytes(), options).unwrap();
+        assert_eq!(result.stored, 2);
+
+        let readings = repository.list_meter_readings().unwrap();
+        let value_of = |meter_id: i32| {
+            readings
+                .iter()
+                .find(|reading| reading.meter_id == meter_id)
+                .map(|reading| reading.value)
+        };
+        assert_eq!(value_of(1), Some(1500.0));
+        assert_eq!(value_of(gas.id), Some(1234.567));
+    }
+}
@@ -0,0 +1,277 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+use crate::models::interval::{IntervalSample, DEFAULT_RAW_RETENTION_DAYS};
+use crate::repository::{create_meter_reading_for_date, Repository};
+
+pub mod dsmr;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+pub mod sml;
+
+/// Lets through one reading per interval. Intervals are aligned to the unix epoch, so
+/// with 15 minutes the first reading after 10:00, 10:15, 10:30, ... is taken.
+pub struct IntervalFilter {
+    interval_seconds: i64,
+    offset_seconds: i64,
+    /// Periods in UTC that `dst_offset_seconds` is added in
+    daylight_saving: Vec<(NaiveDateTime, NaiveDateTime)>,
+    dst_offset_seconds: i64,
+    last_interval: Option<i64>,
+}
+
+impl IntervalFilter {
+    pub fn new(interval_seconds: i64) -> IntervalFilter {
+        IntervalFilter {
+            interval_seconds: interval_seconds.max(1),
+            offset_seconds: 0,
+            daylight_saving: Vec::new(),
+            dst_offset_seconds: 0,
+            last_interval: None,
+        }
+    }
+
+    /// Aligns the intervals to a time zone, e.g. `3600` for days starting at midnight CET.
+    pub fn with_offset(self, offset_seconds: i64) -> IntervalFilter {
+        IntervalFilter {
+            offset_seconds,
+            ..self
+        }
+    }
+
+    /// Adds `dst_offset_seconds` to the offset in the periods (`start <= date < end`, UTC)
+    /// of daylight saving time.
+    pub fn with_daylight_saving(
+        self,
+        dst_offset_seconds: i64,
+        periods: Vec<(NaiveDateTime, NaiveDateTime)>,
+    ) -> IntervalFilter {
+        IntervalFilter {
+            daylight_saving: periods,
+            dst_offset_seconds,
+            ..self
+        }
+    }
+
+    /// Continues after the newest stored reading of the meter, so older or already covered
+    /// readings are not stored twice.
+    pub fn resume<R: Repository>(
+        self,
+        repository: &mut R,
+        meter_id: i32,
+    ) -> Result<IntervalFilter, String> {
+        let newest = repository
+            .list_meter_readings()?
+            .into_iter()
+            .filter(|reading| reading.meter_id == meter_id)
+            .map(|reading| reading.date)
+            .max();
+        let last_interval = newest.map(|newest| self.interval_of(&newest));
+        Ok(IntervalFilter {
+            last_interval,
+            ..self
+        })
+    }
+
+    fn interval_of(&self, date: &NaiveDateTime) -> i64 {
+        let dst_offset_seconds = if self
+            .daylight_saving
+            .iter()
+            .any(|(start, end)| start <= date && date < end)
+        {
+            self.dst_offset_seconds
+        } else {
+            0
+        };
+        (date.timestamp() + self.offset_seconds + dst_offset_seconds)
+            .div_euclid(self.interval_seconds)
+    }
+
+    pub fn accept(&mut self, date: &NaiveDateTime) -> bool {
+        let interval = self.interval_of(date);
+        match self.last_interval {
+            Some(last) if interval <= last => false,
+            _ => {
+                self.last_interval = Some(interval);
+                true
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Default, PartialEq, Eq)]
+pub struct IngestResult {
+    pub received: usize,
+    /// Samples stored as interval data
+    pub samples: usize,
+    /// Meter readings stored
+    pub stored: usize,
+    pub skipped: usize,
+    pub errors: Vec<String>,
+}
+
+impl IngestResult {
+    /// Stores every value as interval sample of the meter and, if the filter lets it through,
+    /// as meter reading. Raw samples older than the retention before now are deleted with every
+    /// stored reading.
+    /// Errors are collected instead of stopping the ingestion, `received` is counted by
+    /// the caller.
+    pub fn store<R: Repository>(
+        &mut self,
+        repository: &mut R,
+        meter_id: i32,
+        filter: &mut IntervalFilter,
+        value: f64,
+        date: &NaiveDateTime,
+    ) {
+        let sample = IntervalSample { date: *date, value };
+        match repository.insert_interval_samples(meter_id, &[sample]) {
+            Ok(count) => self.samples += count,
+            Err(err) => self.errors.push(err),
+        }
+
+        if !filter.accept(date) {
+            self.skipped += 1;
+            return;
+        }
+
+        match create_meter_reading_for_date(repository, meter_id, value as f32, date) {
+            Ok(_) => self.stored += 1,
+            Err(err) => {
+                self.errors.push(err);
+                return;
+            }
+        }
+
+        let retention =
+            chrono::Utc::now().naive_utc() - chrono::Duration::days(DEFAULT_RAW_RETENTION_DAYS);
+        if let Err(err) = repository.delete_interval_samples_before(meter_id, &retention) {
+            self.errors.push(err);
+        }
+    }
+}
+
+/// Adds up consumption per interval (`start`, `end`, `value`) to counter values, starting
+/// at the last meter reading of the meter before the first interval, and stores them.
+/// `start_value` is the counter value at the start of the first interval, it's only used and
+/// required if the meter has no reading before.
+pub fn store_interval_consumption<R: Repository>(
+    repository: &mut R,
+    meter_id: i32,
+    mut filter: IntervalFilter,
+    intervals: &[(NaiveDateTime, NaiveDateTime, f64)],
+    start_value: Option<f64>,
+) -> Result<IngestResult, String> {
+    let mut intervals = intervals.to_vec();
+    intervals.sort_by_key(|(start, _, _)| *start);
+    let first = match intervals.first() {
+        Some((start, _, _)) => *start,
+        None => return Ok(IngestResult::default()),
+    };
+
+    let mut value = repository
+        .list_meter_readings()?
+        .into_iter()
+        .filter(|reading| reading.meter_id == meter_id && reading.date <= first)
+        .max_by_key(|reading| reading.date)
+        .map(|reading| reading.value as f64)
+        .or(start_value)
+        .ok_or_else(|| {
+            format!(
+                "meter {} has no reading before {}, the counter value at the start is needed",
+                meter_id, first
+            )
+        })?;
+
+    let mut result = IngestResult::default();
+    result.store(repository, meter_id, &mut filter, value, &first);
+    for (_, end, consumption) in intervals {
+        result.received += 1;
+        value += consumption;
+        result.store(repository, meter_id, &mut filter, value, &end);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDateTime};
+
+    use crate::models::fees::CreateFeeParams;
+    use crate::models::format_datetime;
+    use crate::models::interval::Resolution;
+    use crate::models::meter::DEFAULT_METER_ID;
+    use crate::repository::{FeeRepository, InMemoryRepository, IntervalRepository};
+
+    use super::{IngestResult, IntervalFilter};
+
+    /// Three hours of quarter-hourly counter values, like a recorded file.
+    fn ingest(repository: &mut InMemoryRepository, start: NaiveDateTime) -> IngestResult {
+        let mut filter = IntervalFilter::new(3600)
+            .resume(repository, DEFAULT_METER_ID)
+            .unwrap();
+        let mut result = IngestResult::default();
+        for quarter in 0..12 {
+            result.received += 1;
+            let date = start + Duration::minutes(15 * quarter);
+            let value = 100.0 + quarter as f64 * 0.25;
+            result.store(repository, DEFAULT_METER_ID, &mut filter, value, &date);
+        }
+        result
+    }
+
+    #[test]
+    fn backfills_buckets_of_an_older_file() {
+        let today = chrono::Utc::now()
+            .naive_utc()
+            .date()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        let mut repository = InMemoryRepository::new();
+        repository
+            .create_fee(CreateFeeParams {
+                meter_id: None,
+                base_fee: 10.0,
+                price_per_unit: 0.3,
+                monthly_discount: 50.0,
+                spot_surcharge: None,
+                blocks: vec![],
+                net_prices: false,
+                price_brake: None,
+                date_start: format_datetime(&(today - Duration::days(120))),
+                date_end: format_datetime(&(today + Duration::days(1))),
+            })
+            .unwrap();
+
+        let newer = ingest(&mut repository, today - Duration::days(1));
+        assert_eq!(newer.samples, 12);
+        assert!(newer.errors.is_empty());
+
+        // the older file is past the retention, its raw samples are not kept
+        let start = today - Duration::days(90);
+        let end = start + Duration::hours(3);
+        let older = ingest(&mut repository, start);
+        assert_eq!(older.samples, 12);
+        assert!(older.errors.is_empty());
+        assert!(repository
+            .list_interval_samples(DEFAULT_METER_ID, &start, &end)
+            .unwrap()
+            .is_empty());
+
+        let hours = repository
+            .list_interval_buckets(DEFAULT_METER_ID, Resolution::Hour, &start, &end)
+            .unwrap();
+        assert_eq!(hours.len(), 3);
+        assert_eq!(hours[0].value_first, 100.0);
+        assert_eq!(hours[0].value_last, 100.75);
+        assert_eq!(hours[2].value_last, 102.75);
+        assert_eq!(hours[2].samples, 4);
+
+        // sent again, the older file is not counted twice
+        assert_eq!(ingest(&mut repository, start).samples, 0);
+        let hours = repository
+            .list_interval_buckets(DEFAULT_METER_ID, Resolution::Hour, &start, &end)
+            .unwrap();
+        assert_eq!(hours[2].samples, 4);
+    }
+}
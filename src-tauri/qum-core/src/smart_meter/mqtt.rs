@@ -0,0 +1,513 @@
+//! Readings published over MQTT, e.g. by Tasmota IR heads (`tele/<device>/SENSOR` with
+//! `{"SML": {"Total_in": 1234.5}}`) or ESPHome (`<node>/sensor/<name>/state` with `1234.5`).
+
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use rumqttc::{Client, Event, MqttOptions, Packet, QoS};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{IngestResult, IntervalFilter};
+use crate::models::meter::{default_meter_id, DEFAULT_METER_ID};
+use crate::repository::{Repository, SqliteRepository};
+
+pub const DEFAULT_PORT: u16 = 1883;
+
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MqttSubscription {
+    /// May contain the wildcards `+` and `#`
+    pub topic: String,
+    /// Dot separated path of the value in a JSON payload, empty for plain numbers
+    #[serde(rename = "jsonPath")]
+    pub json_path: String,
+    /// Meter the readings are stored for, every subscription needs its own
+    #[serde(rename = "meterId", default = "default_meter_id")]
+    pub meter_id: i32,
+}
+
+impl MqttSubscription {
+    /// Parses `topic` or `topic=json.path`, prefixed with `meter:` to store the readings for
+    /// another than the default meter, e.g. `2:tele/gas/SENSOR=SML.Total_in`.
+    pub fn parse(value: &str) -> Result<MqttSubscription, String> {
+        let (meter_id, subscription) = match value.split_once(':') {
+            Some((meter_id, subscription))
+                if !meter_id.trim().is_empty()
+                    && meter_id.trim().chars().all(|c| c.is_ascii_digit()) =>
+            {
+                let meter_id = meter_id
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("invalid meter in subscription '{}'", value))?;
+                (meter_id, subscription)
+            }
+            _ => (DEFAULT_METER_ID, value),
+        };
+        let (topic, json_path) = subscription.split_once('=').unwrap_or((subscription, ""));
+        if topic.trim().is_empty() {
+            return Err(format!("invalid subscription '{}'", value));
+        }
+        Ok(MqttSubscription {
+            topic: topic.trim().to_string(),
+            json_path: json_path.trim().to_string(),
+            meter_id,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MqttConfig {
+    pub host: String,
+    pub port: u16,
+    #[serde(rename = "clientId")]
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub subscriptions: Vec<MqttSubscription>,
+    /// Only the first message of every interval is stored per subscription
+    #[serde(rename = "intervalSeconds")]
+    pub interval_seconds: i64,
+}
+
+impl MqttConfig {
+    /// Enabled by setting `QUM_MQTT_HOST` and `QUM_MQTT_SUBSCRIBE` (comma separated
+    /// `[meter:]topic=json.path`), optional are `QUM_MQTT_PORT`, `QUM_MQTT_USERNAME`,
+    /// `QUM_MQTT_PASSWORD` and `QUM_MQTT_INTERVAL_SECONDS`.
+    pub fn from_env() -> Result<Option<MqttConfig>, String> {
+        let (host, subscribe) = match (env::var("QUM_MQTT_HOST"), env::var("QUM_MQTT_SUBSCRIBE")) {
+            (Ok(host), Ok(subscribe)) => (host, subscribe),
+            _ => return Ok(None),
+        };
+
+        let subscriptions = subscribe
+            .split(',')
+            .filter(|value| !value.trim().is_empty())
+            .map(MqttSubscription::parse)
+            .collect::<Result<Vec<MqttSubscription>, String>>()?;
+        let port = match env::var("QUM_MQTT_PORT") {
+            Ok(port) => port
+                .parse()
+                .map_err(|_| format!("invalid QUM_MQTT_PORT '{}'", port))?,
+            Err(_) => DEFAULT_PORT,
+        };
+        let interval_seconds = match env::var("QUM_MQTT_INTERVAL_SECONDS") {
+            Ok(seconds) => seconds
+                .parse()
+                .map_err(|_| format!("invalid QUM_MQTT_INTERVAL_SECONDS '{}'", seconds))?,
+            Err(_) => 15 * 60,
+        };
+
+        Ok(Some(MqttConfig {
+            host,
+            port,
+            client_id: "qum".to_string(),
+            username: env::var("QUM_MQTT_USERNAME").ok(),
+            password: env::var("QUM_MQTT_PASSWORD").ok(),
+            subscriptions,
+            interval_seconds,
+        }))
+    }
+}
+
+/// Matches a topic against a subscription filter with `+` and `#` wildcards.
+pub fn topic_matches(filter: &str, topic: &str) -> bool {
+    let mut topic_levels = topic.split('/');
+    for level in filter.split('/') {
+        if level == "#" {
+            return true;
+        }
+        match topic_levels.next() {
+            Some(topic_level) if level == "+" || level == topic_level => {}
+            _ => return false,
+        }
+    }
+    topic_levels.next().is_none()
+}
+
+/// Reads the value at `json_path`, numbers sent as strings are accepted as well.
+pub fn extract_value(payload: &[u8], json_path: &str) -> Result<f64, String> {
+    let text = std::str::from_utf8(payload).map_err(|err| err.to_string())?;
+    let mut value: Value = match serde_json::from_str(text) {
+        Ok(value) => value,
+        Err(_) if json_path.is_empty() => Value::String(text.trim().to_string()),
+        Err(err) => return Err(format!("invalid payload: {}", err)),
+    };
+
+    for key in json_path.split('.').filter(|key| !key.is_empty()) {
+        value = match value {
+            Value::Object(mut object) => object.remove(key),
+            Value::Array(mut array) => key
+                .parse::<usize>()
+                .ok()
+                .filter(|index| *index < array.len())
+                .map(|index| array.swap_remove(index)),
+            _ => None,
+        }
+        .ok_or_else(|| format!("payload has no '{}'", json_path))?;
+    }
+
+    match value {
+        Value::Number(number) => number
+            .as_f64()
+            .ok_or_else(|| format!("invalid number {}", number)),
+        Value::String(text) => text
+            .trim()
+            .parse()
+            .map_err(|_| format!("'{}' is not a number", text)),
+        other => Err(format!("'{}' is not a number", other)),
+    }
+}
+
+/// Turns messages into meter readings, every subscription has its own meter and interval.
+pub struct MqttIngest {
+    subscriptions: Vec<(MqttSubscription, IntervalFilter)>,
+    pub result: IngestResult,
+}
+
+impl MqttIngest {
+    /// Two subscriptions of one meter would interleave their values into one series, so
+    /// they are refused.
+    pub fn new<R: Repository>(
+        repository: &mut R,
+        config: &MqttConfig,
+    ) -> Result<MqttIngest, String> {
+        for (index, subscription) in config.subscriptions.iter().enumerate() {
+            if let Some(other) = config.subscriptions[..index]
+                .iter()
+                .find(|other| other.meter_id == subscription.meter_id)
+            {
+                return Err(format!(
+                    "subscriptions {} and {} both store readings of meter {}",
+                    other.topic, subscription.topic, subscription.meter_id
+                ));
+            }
+            repository.get_meter(subscription.meter_id)?;
+        }
+
+        let subscriptions = config
+            .subscriptions
+            .iter()
+            .map(|subscription| {
+                let filter = IntervalFilter::new(config.interval_seconds)
+                    .resume(repository, subscription.meter_id)?;
+                Ok((subscription.clone(), filter))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(MqttIngest {
+            subscriptions,
+            result: IngestResult::default(),
+        })
+    }
+
+    /// Messages are stored with the time they were received.
+    pub fn process<R: Repository>(&mut self, repository: &mut R, topic: &str, payload: &[u8]) {
+        let date = chrono::Utc::now().naive_utc();
+        for (subscription, filter) in self.subscriptions.iter_mut() {
+            if !topic_matches(subscription.topic.as_str(), topic) {
+                continue;
+            }
+
+            self.result.received += 1;
+            match extract_value(payload, subscription.json_path.as_str()) {
+                Ok(value) => {
+                    self.result
+                        .store(repository, subscription.meter_id, filter, value, &date)
+                }
+                Err(err) => self.result.errors.push(format!("{}: {}", topic, err)),
+            }
+        }
+    }
+}
+
+/// Subscribes and stores readings until the process is stopped. Lost connections are
+/// reestablished with an increasing delay, the subscriptions are renewed on every connect.
+pub fn run(config: MqttConfig, connection: &Mutex<Connection>) -> Result<(), String> {
+    if config.subscriptions.is_empty() {
+        return Err("no topics to subscribe".to_string());
+    }
+
+    let mut ingest = {
+        let mut connection = connection.lock().unwrap();
+        let mut repository = SqliteRepository::new(&mut connection);
+        MqttIngest::new(&mut repository, &config)?
+    };
+
+    let mut options =
+        MqttOptions::new(config.client_id.as_str(), config.host.as_str(), config.port);
+    options.set_keep_alive(Duration::from_secs(30));
+    if let Some(username) = &config.username {
+        options.set_credentials(
+            username.as_str(),
+            config.password.clone().unwrap_or_default(),
+        );
+    }
+
+    let (client, mut events) = Client::new(options, 10);
+    let mut delay = Duration::from_secs(1);
+    for event in events.iter() {
+        match event {
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                println!("mqtt: connected to {}:{}", config.host, config.port);
+                delay = Duration::from_secs(1);
+                for subscription in &config.subscriptions {
+                    if let Err(err) =
+                        client.try_subscribe(subscription.topic.as_str(), QoS::AtLeastOnce)
+                    {
+                        println!("mqtt: failed to subscribe {}: {}", subscription.topic, err);
+                    }
+                }
+            }
+            Ok(Event::Incoming(Packet::Publish(publish))) => {
+                let mut connection = connection.lock().unwrap();
+                let mut repository = SqliteRepository::new(&mut connection);
+                let stored = ingest.result.stored;
+                ingest.process(&mut repository, publish.topic.as_str(), &publish.payload);
+
+                for error in ingest.result.errors.drain(..) {
+                    println!("mqtt: {}", error);
+                }
+                if ingest.result.stored > stored {
+                    println!("mqtt: stored reading from {}", publish.topic);
+                }
+            }
+            Ok(_) => {}
+            Err(err) => {
+                println!("mqtt: {}, reconnecting in {}s", err, delay.as_secs());
+                thread::sleep(delay);
+                delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs the subscriber on its own thread.
+pub fn start(config: MqttConfig, connection: Arc<Mutex<Connection>>) -> JoinHandle<()> {
+    thread::spawn(move || {
+        if let Err(err) = run(config, &connection) {
+            println!("mqtt: {}", err);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::fees::CreateFeeParams;
+    use crate::models::meter::{CreateMeterParams, MeterKind};
+    use crate::repository::{
+        FeeRepository, InMemoryRepository, MeterReadingRepository, MeterRepository,
+    };
+
+    use super::{extract_value, topic_matches, MqttConfig, MqttIngest, MqttSubscription};
+
+    #[test]
+    fn matches_topics() {
+        assert!(topic_matches("tele/meter/SENSOR", "tele/meter/SENSOR"));
+        assert!(topic_matches("tele/+/SENSOR", "tele/meter/SENSOR"));
+        assert!(topic_matches("tele/#", "tele/meter/SENSOR"));
+        assert!(!topic_matches("tele/+", "tele/meter/SENSOR"));
+        assert!(!topic_matches("tele/meter/SENSOR/x", "tele/meter/SENSOR"));
+    }
+
+    #[test]
+    fn extracts_values() {
+        let tasmota = br#"{"Time":"2023-01-15T12:00:00","SML":{"Total_in":1234.5,"Power":230}}"#;
+        assert_eq!(extract_value(tasmota, "SML.Total_in"), Ok(1234.5));
+        assert!(extract_value(tasmota, "SML.Total_out").is_err());
+        assert_eq!(
+            extract_value(br#"{"values":[1,"2.5"]}"#, "values.1"),
+            Ok(2.5)
+        );
+        assert_eq!(extract_value(b"1234.5", ""), Ok(1234.5));
+        assert_eq!(extract_value(b" 42 \n", ""), Ok(42.0));
+        assert!(extract_value(b"unavailable", "").is_err());
+    }
+
+    #[test]
+    fn stores_messages_of_subscribed_topics() {
+        let mut repository = InMemoryRepository::new();
+        let today = chrono::Utc::now().date_naive();
+        repository
+            .create_fee(CreateFeeParams {
+                meter_id: None,
+                base_fee: 10.0,
+                price_per_unit: 0.3,
+                monthly_discount: 50.0,
+                spot_surcharge: None,
+                blocks: vec![],
+                net_prices: false,
+                price_brake: None,
+                date_start: format!("{}T00:00:00.000Z", today.pred_opt().unwrap()),
+                date_end: format!("{}T00:00:00.000Z", today.succ_opt().unwrap()),
+            })
+            .unwrap();
+
+        let config = MqttConfig {
+            host: "localhost".to_string(),
+            port: 1883,
+            client_id: "qum-test".to_string(),
+            username: None,
+            password: None,
+            subscriptions: vec![MqttSubscription::parse("tele/+/SENSOR=SML.Total_in").unwrap()],
+            interval_seconds: 900,
+        };
+        let mut ingest = MqttIngest::new(&mut repository, &config).unwrap();
+
+        ingest.process(
+            &mut repository,
+            "tele/meter/SENSOR",
+            br#"{"SML":{"Total_in":100.5}}"#,
+        );
+        ingest.process(
+            &mut repository,
+            "tele/meter/STATE",
+            br#"{"Uptime":"1T00:00:00"}"#,
+        );
+        ingest.process(&mut repository, "tele/meter/SENSOR", br#"{"SML":{}}"#);
+
+        assert_eq!(ingest.result.received, 2);
+        assert_eq!(ingest.result.stored, 1);
+        assert_eq!(ingest.result.errors.len(), 1);
+        assert_eq!(repository.list_meter_readings().unwrap()[0].value, 100.5);
+    }
+
+    #[test]
+    fn stores_subscriptions_per_meter() {
+        let mut repository = InMemoryRepository::new();
+        let gas = repository
+            .create_meter(CreateMeterParams {
+                name: "Gas".to_string(),
+                kind: MeterKind::Gas,
+                external_id: None,
+            })
+            .unwrap();
+        let today = chrono::Utc::now().date_naive();
+        for meter_id in [1, gas.id] {
+            repository
+                .create_fee(CreateFeeParams {
+                    meter_id: Some(meter_id),
+                    base_fee: 10.0,
+                    price_per_unit: 0.3,
+                    monthly_discount: 50.0,
+                    spot_surcharge: None,
+                    blocks: vec![],
+                    net_prices: false,
+                    price_brake: None,
+                    date_start: format!("{}T00:00:00.000Z", today.pred_opt().unwrap()),
+                    date_end: format!("{}T00:00:00.000Z", today.succ_opt().unwrap()),
+                })
+                .unwrap();
+        }
+
+        let subscription = MqttSubscription::parse("2:tele/gas/SENSOR=Gas").unwrap();
+        assert_eq!(subscription.meter_id, gas.id);
+        assert_eq!(subscription.topic, "tele/gas/SENSOR");
+        let mut config = MqttConfig {
+            host: "localhost".to_string(),
+            port: 1883,
+            client_id: "qum-test".to_string(),
+            username: None,
+            password: None,
+            subscriptions: vec![
+                MqttSubscription::parse("tele/power/SENSOR=Total_in").unwrap(),
+                subscription,
+            ],
+            interval_seconds: 900,
+        };
+        let mut ingest = MqttIngest::new(&mut repository, &config).unwrap();
+        ingest.process(
+            &mut repository,
+            "tele/power/SENSOR",
+            br#"{"Total_in":100.5}"#,
+        );
+        ingest.process(&mut repository, "tele/gas/SENSOR", br#"{"Gas":20.25}"#);
+
+        assert_eq!(ingest.result.stored, 2);
+        let readings = repository.list_meter_readings().unwrap();
+        let value_of = |meter_id: i32| {
+            readings
+                .iter()
+                .find(|reading| reading.meter_id == meter_id)
+                .map(|reading| reading.value)
+        };
+        assert_eq!(value_of(1), Some(100.5));
+        assert_eq!(value_of(gas.id), Some(20.25));
+
+        config
+            .subscriptions
+            .push(MqttSubscription::parse("tele/other/SENSOR").unwrap());
+        assert!(MqttIngest::new(&mut repository, &config).is_err());
+    }
+
+    /// Needs a broker, run with
+    /// `QUM_MQTT_TEST_BROKER=localhost:1883 cargo test -p qum-core --features mqtt -- --ignored`.
+    #[test]
+    #[ignore]
+    fn local_broker() {
+        use std::sync::{Arc, Mutex};
+        use std::time::Duration;
+
+        use rumqttc::{Client, MqttOptions, QoS};
+
+        use crate::db::connection::run_migrations;
+        use crate::repository::SqliteRepository;
+
+        let broker = std::env::var("QUM_MQTT_TEST_BROKER").unwrap_or("localhost:1883".to_string());
+        let (host, port) = broker.split_once(':').unwrap();
+        let port: u16 = port.parse().unwrap();
+
+        let mut connection = rusqlite::Connection::open_in_memory().unwrap();
+        run_migrations(&mut connection).expect("failed to run migrations");
+        let today = chrono::Utc::now().date_naive();
+        SqliteRepository::new(&mut connection)
+            .create_fee(CreateFeeParams {
+                meter_id: None,
+                base_fee: 10.0,
+                price_per_unit: 0.3,
+                monthly_discount: 50.0,
+                spot_surcharge: None,
+                blocks: vec![],
+                net_prices: false,
+                price_brake: None,
+                date_start: format!("{}T00:00:00.000Z", today.pred_opt().unwrap()),
+                date_end: format!("{}T00:00:00.000Z", today.succ_opt().unwrap()),
+            })
+            .unwrap();
+        let connection = Arc::new(Mutex::new(connection));
+
+        let config = MqttConfig {
+            host: host.to_string(),
+            port,
+            client_id: "qum-test-subscriber".to_string(),
+            username: None,
+            password: None,
+            subscriptions: vec![MqttSubscription::parse("qum-test/meter/state").unwrap()],
+            interval_seconds: 900,
+        };
+        super::start(config, connection.clone());
+        std::thread::sleep(Duration::from_secs(1));
+
+        let (client, mut events) =
+            Client::new(MqttOptions::new("qum-test-publisher", host, port), 10);
+        client
+            .publish("qum-test/meter/state", QoS::AtLeastOnce, false, "4321.5")
+            .unwrap();
+        for event in events.iter().take(3) {
+            event.unwrap();
+        }
+        std::thread::sleep(Duration::from_secs(1));
+
+        let mut connection = connection.lock().unwrap();
+        let readings = SqliteRepository::new(&mut connection)
+            .list_meter_readings()
+            .unwrap();
+        assert_eq!(readings.len(), 1);
+        assert_eq!(readings[0].value, 4321.5);
+    }
+}
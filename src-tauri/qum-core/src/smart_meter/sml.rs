@@ -0,0 +1,592 @@
+//! SML (Smart Message Language) as pushed by German modern meters over the optical
+//! interface.
+//!
+//! A transport frame starts with `1B1B1B1B 01010101` and ends with `1B1B1B1B 1A`, the
+//! number of fill bytes and the CRC16 (X.25) of the frame. The messages in between are
+//! TLV encoded; the counters are entries of a `GetListResponse` with their OBIS code.
+
+use std::fs::File;
+use std::io::{BufReader, ErrorKind, Read};
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use super::{IngestResult, IntervalFilter};
+use crate::models::meter::{default_meter_id, MeterKind};
+use crate::repository::Repository;
+
+const ESCAPE: [u8; 4] = [0x1B; 4];
+const START: [u8; 8] = [0x1B, 0x1B, 0x1B, 0x1B, 0x01, 0x01, 0x01, 0x01];
+
+/// SML unit code of Wh
+const UNIT_WH: u64 = 30;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmlRegister {
+    /// OBIS 1.8.0, energy delivered to the customer
+    #[serde(rename = "import")]
+    Import,
+    /// OBIS 2.8.0, energy delivered by the customer
+    #[serde(rename = "export")]
+    Export,
+}
+
+impl SmlRegister {
+    fn obis(&self) -> [u8; 5] {
+        match self {
+            SmlRegister::Import => [1, 0, 1, 8, 0],
+            SmlRegister::Export => [1, 0, 2, 8, 0],
+        }
+    }
+
+    /// What the register counts, it's only stored for meters of this kind.
+    pub fn kind(&self) -> MeterKind {
+        match self {
+            SmlRegister::Import => MeterKind::Consumption,
+            SmlRegister::Export => MeterKind::FeedIn,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SmlOptions {
+    pub register: SmlRegister,
+    /// Meter the register is stored for, it must count the same as the register
+    #[serde(rename = "meterId", default = "default_meter_id")]
+    pub meter_id: i32,
+    /// Only the first frame of every interval is stored
+    #[serde(rename = "intervalSeconds")]
+    pub interval_seconds: i64,
+    #[serde(rename = "verifyCrc")]
+    pub verify_crc: bool,
+}
+
+impl Default for SmlOptions {
+    fn default() -> Self {
+        SmlOptions {
+            register: SmlRegister::Import,
+            meter_id: default_meter_id(),
+            interval_seconds: 15 * 60,
+            verify_crc: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SmlValue {
+    Bytes(Vec<u8>),
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    List(Vec<SmlValue>),
+    EndOfMessage,
+}
+
+impl SmlValue {
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            SmlValue::Int(value) => Some(*value as f64),
+            SmlValue::UInt(value) => Some(*value as f64),
+            _ => None,
+        }
+    }
+
+    /// `SML_Time`, only the timestamp choice carries a date.
+    fn as_date(&self) -> Option<NaiveDateTime> {
+        match self {
+            SmlValue::List(items) => match items.as_slice() {
+                [SmlValue::UInt(2), SmlValue::UInt(seconds)] => {
+                    NaiveDateTime::from_timestamp_opt(*seconds as i64, 0)
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// One entry of a `GetListResponse`, energy is converted to kWh.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmlEntry {
+    pub obis: [u8; 6],
+    pub value: f64,
+    pub unit: Option<u64>,
+    pub date: Option<NaiveDateTime>,
+}
+
+impl SmlEntry {
+    pub fn obis_code(&self) -> String {
+        let [a, b, c, d, e, f] = self.obis;
+        format!("{}-{}:{}.{}.{}*{}", a, b, c, d, e, f)
+    }
+}
+
+pub fn find_register(entries: &[SmlEntry], register: SmlRegister) -> Option<&SmlEntry> {
+    entries
+        .iter()
+        .find(|entry| entry.obis[..5] == register.obis())
+}
+
+/// CRC-16/X-25 as used by the SML transport.
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for byte in data {
+        crc ^= *byte as u16;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0x8408
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+pub fn verify_crc(frame: &[u8]) -> Result<(), String> {
+    if frame.len() < 16 {
+        return Err("frame is too short".to_string());
+    }
+    let (data, crc) = frame.split_at(frame.len() - 2);
+    let given = u16::from_le_bytes([crc[0], crc[1]]);
+    let expected = crc16(data);
+    if given != expected {
+        return Err(format!(
+            "crc mismatch: frame has {:04X}, computed {:04X}",
+            given, expected
+        ));
+    }
+    Ok(())
+}
+
+/// Removes start, end, escaping and fill bytes of a transport frame.
+fn payload(frame: &[u8]) -> Result<Vec<u8>, String> {
+    if frame.len() < 16 || frame[..8] != START || frame[frame.len() - 8..frame.len() - 4] != ESCAPE
+    {
+        return Err("invalid frame".to_string());
+    }
+
+    let data = &frame[8..frame.len() - 8];
+    let mut payload = Vec::with_capacity(data.len());
+    let mut pos = 0;
+    while pos < data.len() {
+        if data[pos..].starts_with(&ESCAPE) && data[pos + 4..].starts_with(&ESCAPE) {
+            payload.extend_from_slice(&ESCAPE);
+            pos += 8;
+        } else {
+            payload.push(data[pos]);
+            pos += 1;
+        }
+    }
+
+    let fill = frame[frame.len() - 3] as usize;
+    if fill > payload.len() {
+        return Err("invalid fill bytes".to_string());
+    }
+    payload.truncate(payload.len() - fill);
+    Ok(payload)
+}
+
+fn decode_value(data: &[u8], pos: &mut usize) -> Result<SmlValue, String> {
+    let error = || format!("unexpected end of data at byte {}", *pos);
+
+    let mut tl = *data.get(*pos).ok_or_else(error)?;
+    if tl == 0x00 {
+        *pos += 1;
+        return Ok(SmlValue::EndOfMessage);
+    }
+
+    let kind = (tl >> 4) & 0x07;
+    let mut length = (tl & 0x0F) as usize;
+    let mut header = 1;
+    while tl & 0x80 != 0 {
+        tl = *data.get(*pos + header).ok_or_else(error)?;
+        length = (length << 4) | (tl & 0x0F) as usize;
+        header += 1;
+    }
+
+    if kind == 0x07 {
+        *pos += header;
+        let items = (0..length)
+            .map(|_| decode_value(data, pos))
+            .collect::<Result<Vec<SmlValue>, String>>()?;
+        return Ok(SmlValue::List(items));
+    }
+
+    if length < header || *pos + length > data.len() {
+        return Err(error());
+    }
+    let bytes = &data[*pos + header..*pos + length];
+    *pos += length;
+
+    let unsigned = bytes
+        .iter()
+        .fold(0u64, |value, byte| (value << 8) | *byte as u64);
+    match kind {
+        0x00 => Ok(SmlValue::Bytes(bytes.to_vec())),
+        0x04 => Ok(SmlValue::Bool(bytes.iter().any(|byte| *byte != 0))),
+        0x05 if bytes.is_empty() || bytes.len() > 8 => {
+            Err(format!("invalid integer at byte {}", *pos))
+        }
+        0x05 => {
+            let shift = 64 - 8 * bytes.len() as u32;
+            Ok(SmlValue::Int(((unsigned << shift) as i64) >> shift))
+        }
+        0x06 if bytes.len() > 8 => Err(format!("invalid integer at byte {}", *pos)),
+        0x06 => Ok(SmlValue::UInt(unsigned)),
+        _ => Err(format!("unknown type {:#04x} at byte {}", kind, *pos)),
+    }
+}
+
+/// Decodes all messages of a frame.
+pub fn decode(frame: &[u8]) -> Result<Vec<SmlValue>, String> {
+    let payload = payload(frame)?;
+    let mut pos = 0;
+    let mut values = Vec::new();
+    while pos < payload.len() {
+        values.push(decode_value(&payload, &mut pos)?);
+    }
+    Ok(values)
+}
+
+/// Walks the decoded messages for list entries (`SML_ListEntry`), entries without a
+/// time of their own get the sensor time of their `GetListResponse`.
+fn collect_entries(value: &SmlValue, date: Option<NaiveDateTime>, entries: &mut Vec<SmlEntry>) {
+    let items = match value {
+        SmlValue::List(items) => items,
+        _ => return,
+    };
+
+    if let [SmlValue::Bytes(obis), _status, time, unit, scaler, value, _signature] =
+        items.as_slice()
+    {
+        if let (Ok(obis), Some(value)) = (<[u8; 6]>::try_from(obis.as_slice()), value.as_f64()) {
+            let scaler = match scaler {
+                SmlValue::Int(scaler) => *scaler as i32,
+                _ => 0,
+            };
+            let unit = match unit {
+                SmlValue::UInt(unit) => Some(*unit),
+                _ => None,
+            };
+            let mut value = value * 10f64.powi(scaler);
+            if unit == Some(UNIT_WH) {
+                value /= 1000.0;
+            }
+            entries.push(SmlEntry {
+                obis,
+                value,
+                unit,
+                date: time.as_date().or(date),
+            });
+            return;
+        }
+    }
+
+    let date = match items.as_slice() {
+        [_client, _server, _list, time, SmlValue::List(_), _signature, _gateway] => {
+            time.as_date().or(date)
+        }
+        _ => date,
+    };
+    for item in items {
+        collect_entries(item, date, entries);
+    }
+}
+
+pub fn parse(frame: &[u8]) -> Result<Vec<SmlEntry>, String> {
+    let mut entries = Vec::new();
+    for message in decode(frame)? {
+        collect_entries(&message, None, &mut entries);
+    }
+    Ok(entries)
+}
+
+/// Splits a byte stream into transport frames, data before the first start sequence is
+/// skipped. Read timeouts of serial ports are retried.
+pub struct FrameReader<S: Read> {
+    source: S,
+    buffer: Vec<u8>,
+    eof: bool,
+}
+
+impl<S: Read> FrameReader<S> {
+    pub fn new(source: S) -> FrameReader<S> {
+        FrameReader {
+            source,
+            buffer: Vec::new(),
+            eof: false,
+        }
+    }
+
+    fn take_frame(&mut self) -> Option<Vec<u8>> {
+        loop {
+            let start = match self
+                .buffer
+                .windows(START.len())
+                .position(|window| window == START)
+            {
+                Some(start) => start,
+                None => {
+                    let keep = self.buffer.len().min(START.len() - 1);
+                    self.buffer.drain(..self.buffer.len() - keep);
+                    return None;
+                }
+            };
+            self.buffer.drain(..start);
+
+            // escape sequences are aligned to 4 bytes within a frame
+            let mut pos = START.len();
+            let mut restart = false;
+            while pos + 8 <= self.buffer.len() {
+                if self.buffer[pos..pos + 4] == ESCAPE {
+                    let next = &self.buffer[pos + 4..pos + 8];
+                    if next == ESCAPE {
+                        pos += 8;
+                        continue;
+                    }
+                    if next[0] == 0x1A {
+                        return Some(self.buffer.drain(..pos + 8).collect());
+                    }
+                    if next == [0x01; 4] {
+                        self.buffer.drain(..pos);
+                        restart = true;
+                        break;
+                    }
+                }
+                pos += 4;
+            }
+            if !restart {
+                return None;
+            }
+        }
+    }
+}
+
+impl<S: Read> Iterator for FrameReader<S> {
+    type Item = Result<Vec<u8>, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chunk = [0u8; 512];
+        loop {
+            if let Some(frame) = self.take_frame() {
+                return Some(Ok(frame));
+            }
+            if self.eof {
+                return None;
+            }
+            match self.source.read(&mut chunk) {
+                Ok(0) => self.eof = true,
+                Ok(read) => self.buffer.extend_from_slice(&chunk[..read]),
+                Err(err)
+                    if err.kind() == ErrorKind::TimedOut
+                        || err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Some(Err(err.to_string())),
+            }
+        }
+    }
+}
+
+/// Turns frames into meter readings of the configured register.
+pub struct SmlIngest {
+    options: SmlOptions,
+    filter: IntervalFilter,
+    pub result: IngestResult,
+}
+
+impl SmlIngest {
+    /// Fails if the meter doesn't count what the register counts.
+    pub fn new<R: Repository>(
+        repository: &mut R,
+        options: SmlOptions,
+    ) -> Result<SmlIngest, String> {
+        repository
+            .get_meter(options.meter_id)?
+            .expect_kind(options.register.kind())?;
+        let filter =
+            IntervalFilter::new(options.interval_seconds).resume(repository, options.meter_id)?;
+        Ok(SmlIngest {
+            options,
+            filter,
+            result: IngestResult::default(),
+        })
+    }
+
+    /// Most meters send no time, their readings are stored for now.
+    pub fn process<R: Repository>(&mut self, repository: &mut R, frame: &[u8]) {
+        self.result.received += 1;
+
+        let entries = match self.check(frame) {
+            Ok(entries) => entries,
+            Err(err) => {
+                self.result.errors.push(err);
+                return;
+            }
+        };
+
+        let entry = match find_register(&entries, self.options.register) {
+            Some(entry) => entry,
+            None => {
+                self.result
+                    .errors
+                    .push(format!("frame has no {:?} register", self.options.register));
+                return;
+            }
+        };
+        let date = entry.date.unwrap_or_else(|| chrono::Utc::now().naive_utc());
+
+        self.result.store(
+            repository,
+            self.options.meter_id,
+            &mut self.filter,
+            entry.value,
+            &date,
+        );
+    }
+
+    fn check(&self, frame: &[u8]) -> Result<Vec<SmlEntry>, String> {
+        if self.options.verify_crc {
+            verify_crc(frame)?;
+        }
+        parse(frame)
+    }
+}
+
+pub fn ingest<R: Repository, S: Read>(
+    repository: &mut R,
+    source: S,
+    options: SmlOptions,
+) -> Result<IngestResult, String> {
+    let mut ingest = SmlIngest::new(repository, options)?;
+    for frame in FrameReader::new(source) {
+        ingest.process(repository, frame?.as_slice());
+    }
+    Ok(ingest.result)
+}
+
+/// Imports a captured binary file, e.g. written with `cat /dev/ttyUSB0 > sml.bin`.
+pub fn ingest_file<R: Repository>(
+    repository: &mut R,
+    path: &str,
+    options: SmlOptions,
+) -> Result<IngestResult, String> {
+    let file = File::open(path).map_err(|err| err.to_string())?;
+    ingest(repository, BufReader::new(file), options)
+}
+
+/// Opens the IR head, SML meters send with 9600 baud 8N1.
+#[cfg(feature = "serial")]
+pub fn open_serial(device: &str) -> Result<Box<dyn serialport::SerialPort>, String> {
+    serialport::new(device, 9600)
+        .timeout(std::time::Duration::from_secs(60))
+        .open()
+        .map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::fees::CreateFeeParams;
+    use crate::repository::{FeeRepository, InMemoryRepository, MeterReadingRepository};
+
+    use super::{crc16, find_register, ingest, parse, verify_crc, SmlOptions, SmlRegister};
+
+    /// A `GetListResponse` with 1.8.0 and 2.8.0 in Wh with scaler -1.
+    fn frame(timestamp: u32, import: u32) -> Vec<u8> {
+        let mut message = vec![
+            0x76, 0x05, 0x01, 0x02, 0x03, 0x04, 0x62, 0x00, 0x62, 0x00, 0x72, 0x63, 0x07, 0x01,
+            0x77, 0x01, 0x07, 0x0A, 0x01, 0x45, 0x4D, 0x48, 0x00, 0x01, 0x72, 0x62, 0x01, 0x65,
+            0x00, 0x00, 0x12, 0x34, 0x72,
+        ];
+        message.extend([
+            0x77, 0x07, 0x01, 0x00, 0x01, 0x08, 0x00, 0xFF, 0x01, 0x72, 0x62, 0x02, 0x65,
+        ]);
+        message.extend(timestamp.to_be_bytes());
+        message.extend([0x62, 0x1E, 0x52, 0xFF, 0x65]);
+        message.extend(import.to_be_bytes());
+        message.push(0x01);
+        message.extend([
+            0x77, 0x07, 0x01, 0x00, 0x02, 0x08, 0x00, 0xFF, 0x01, 0x01, 0x62, 0x1E, 0x52, 0xFF,
+            0x56, 0x00, 0x00, 0x00, 0x03, 0xE8, 0x01,
+        ]);
+        message.extend([0x01, 0x01, 0x63, 0x00, 0x00, 0x00]);
+
+        let fill = (4 - message.len() % 4) % 4;
+        let mut frame = vec![0x1B, 0x1B, 0x1B, 0x1B, 0x01, 0x01, 0x01, 0x01];
+        frame.extend(&message);
+        frame.extend(vec![0x00; fill]);
+        frame.extend([0x1B, 0x1B, 0x1B, 0x1B, 0x1A, fill as u8]);
+        let crc = crc16(&frame);
+        frame.extend(crc.to_le_bytes());
+        frame
+    }
+
+    #[test]
+    fn crc() {
+        assert_eq!(crc16(b"123456789"), 0x906E);
+
+        let mut frame = frame(1677721600, 123456);
+        assert!(verify_crc(&frame).is_ok());
+        frame[40] ^= 0xFF;
+        assert!(verify_crc(&frame).is_err());
+    }
+
+    #[test]
+    fn parses_counters() {
+        let entries = parse(&frame(1677721600, 123456)).unwrap();
+        assert_eq!(entries.len(), 2);
+
+        let import = find_register(&entries, SmlRegister::Import).unwrap();
+        assert_eq!(import.obis_code(), "1-0:1.8.0*255");
+        assert!((import.value - 12.3456).abs() < 1e-9);
+        assert_eq!(import.date.unwrap().to_string(), "2023-03-02 01:46:40");
+
+        let export = find_register(&entries, SmlRegister::Export).unwrap();
+        assert!((export.value - 0.1).abs() < 1e-9);
+        assert_eq!(export.date, None);
+    }
+
+    #[test]
+    fn stores_one_reading_per_interval() {
+        let mut repository = InMemoryRepository::new();
+        repository
+            .create_fee(CreateFeeParams {
+                meter_id: None,
+                base_fee: 10.0,
+                price_per_unit: 0.3,
+                monthly_discount: 50.0,
+                spot_surcharge: None,
+                blocks: vec![],
+                net_prices: false,
+                price_brake: None,
+                date_start: "2023-01-01T00:00:00.000Z".to_string(),
+                date_end: "2023-12-31T00:00:00.000Z".to_string(),
+            })
+            .unwrap();
+
+        // 2023-03-02 01:45:00 UTC
+        let start = 1677721500;
+        let mut capture = vec![0x00, 0x1B, 0x1B, 0x1A];
+        for (offset, value) in [(0, 10_000), (300, 10_100), (900, 10_200), (1000, 10_300)] {
+            capture.extend(frame(start + offset, value));
+        }
+
+        // fed in energy is not stored for the meter of the drawn energy
+        let export = SmlOptions {
+            register: SmlRegister::Export,
+            ..SmlOptions::default()
+        };
+        assert!(ingest(&mut repository, capture.as_slice(), export).is_err());
+
+        let result = ingest(&mut repository, capture.as_slice(), SmlOptions::default()).unwrap();
+        assert_eq!(result.received, 4);
+        assert_eq!(result.stored, 2);
+        assert_eq!(result.skipped, 2);
+        assert!(result.errors.is_empty());
+
+        let mut readings = repository.list_meter_readings().unwrap();
+        readings.sort_by_key(|reading| reading.date);
+        assert_eq!(readings.len(), 2);
+        assert_eq!(readings[0].value, 1.0);
+        assert_eq!(readings[1].value, 1.02);
+    }
+}
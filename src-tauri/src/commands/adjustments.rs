@@ -0,0 +1,34 @@
+use qum_core::models::adjustment::{Adjustment, CreateAdjustmentParams};
+use qum_core::repository::{AdjustmentRepository, SqliteRepository};
+
+use crate::DbConnection;
+
+#[tauri::command]
+pub fn list_adjustments(
+    conn: tauri::State<DbConnection>,
+    fee_id: i32,
+) -> Result<Vec<Adjustment>, String> {
+    println!("command: list adjustments of fee {}", fee_id);
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.list_adjustments(fee_id)
+}
+
+#[tauri::command]
+pub fn create_adjustment(
+    conn: tauri::State<DbConnection>,
+    params: CreateAdjustmentParams,
+) -> Result<Adjustment, String> {
+    println!("received: {:?}", params);
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.create_adjustment(params)
+}
+
+#[tauri::command]
+pub fn delete_adjustment(conn: tauri::State<DbConnection>, id: i32) -> Result<(), String> {
+    println!("command: delete adjustment {}", id);
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.delete_adjustment(id)
+}
@@ -1,28 +1,21 @@
-use serde::{Deserialize, Serialize};
+use qum_core::calculation::intervals::{load_bucket_consumption, BucketConsumption};
+use qum_core::models::interval::Resolution;
+use qum_core::models::meter::DEFAULT_METER_ID;
+use qum_core::models::meter_reading::{CreateMeterReadingParams, MeterReading};
+use qum_core::models::parse_datetime;
+use qum_core::repository::{MeterReadingRepository, SqliteRepository};
 
-use crate::{models::meter_reading::MeterReading, DbConnection};
-
-#[derive(Serialize, Deserialize)]
-pub struct CreateMeterReadingParams {
-    pub value: f32,
-    #[serde(rename = "feeId")]
-    pub fee_id: i32,
-    #[serde(rename = "readingDate")]
-    pub reading_date: String,
-}
+use crate::DbConnection;
 
 #[tauri::command]
-pub fn get_meter_readings(conn: tauri::State<DbConnection>) -> Vec<MeterReading> {
+pub fn get_meter_readings(conn: tauri::State<DbConnection>) -> Result<Vec<MeterReading>, String> {
     println!("command: load meter readings");
     let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
 
-    let list = MeterReading::list(&mut connection);
-    if let Ok(measurements) = list {
-        println!("found {} measurements", measurements.len());
-        return measurements;
-    }
-
-    vec![]
+    let measurements = repository.list_meter_readings()?;
+    println!("found {} measurements", measurements.len());
+    Ok(measurements)
 }
 
 #[tauri::command]
@@ -31,5 +24,29 @@ pub fn create_meter_reading(
     params: CreateMeterReadingParams,
 ) -> Result<MeterReading, String> {
     let mut connection = conn.connection.lock().unwrap();
-    MeterReading::create(&mut connection, params)
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.create_meter_reading(params)
+}
+
+/// Consumption of smart meter interval data for the chart.
+#[tauri::command]
+pub fn get_interval_consumption(
+    conn: tauri::State<DbConnection>,
+    meter_id: Option<i32>,
+    resolution: Resolution,
+    date_start: String,
+    date_end: String,
+) -> Result<Vec<BucketConsumption>, String> {
+    let date_start = parse_datetime(date_start.as_str())?;
+    let date_end = parse_datetime(date_end.as_str())?;
+
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    load_bucket_consumption(
+        &mut repository,
+        meter_id.unwrap_or(DEFAULT_METER_ID),
+        resolution,
+        &date_start,
+        &date_end,
+    )
 }
@@ -0,0 +1,33 @@
+use qum_core::calculation::advance::{
+    load_advance_payment_proposal, AdvancePaymentProposal, DEFAULT_SAFETY_MARGIN,
+};
+use qum_core::calculation::costs::{load_cost_breakdowns, CostBreakdown};
+use qum_core::repository::SqliteRepository;
+
+use crate::DbConnection;
+
+#[tauri::command]
+pub fn get_cost_breakdowns(conn: tauri::State<DbConnection>) -> Result<Vec<CostBreakdown>, String> {
+    println!("command: get cost breakdowns");
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    load_cost_breakdowns(&mut repository)
+}
+
+#[tauri::command]
+pub fn propose_advance_payment(
+    conn: tauri::State<DbConnection>,
+    fee_id: i32,
+    safety_margin: Option<f32>,
+    consumption: Option<f32>,
+) -> Result<AdvancePaymentProposal, String> {
+    println!("command: propose advance payment of fee {}", fee_id);
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    load_advance_payment_proposal(
+        &mut repository,
+        fee_id,
+        safety_margin.unwrap_or(DEFAULT_SAFETY_MARGIN),
+        consumption,
+    )
+}
@@ -0,0 +1,34 @@
+use std::fs::File;
+
+use qum_core::export::{csv_export, xlsx_export, ExportData, ExportKind, ExportLocale};
+use qum_core::repository::SqliteRepository;
+
+use crate::DbConnection;
+
+#[tauri::command]
+pub fn export_csv(
+    conn: tauri::State<DbConnection>,
+    path: String,
+    kind: ExportKind,
+    locale: ExportLocale,
+) -> Result<(), String> {
+    println!("command: export {:?} as csv to {}", kind, path);
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    let data = ExportData::load(&mut repository)?;
+    let file = File::create(path).map_err(|err| err.to_string())?;
+    csv_export::write(file, &data, kind, locale)
+}
+
+#[tauri::command]
+pub fn export_xlsx(
+    conn: tauri::State<DbConnection>,
+    path: String,
+    locale: ExportLocale,
+) -> Result<(), String> {
+    println!("command: export xlsx to {}", path);
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    let data = ExportData::load(&mut repository)?;
+    xlsx_export::save(&data, locale, path.as_str())
+}
@@ -1,31 +1,17 @@
-use serde::{Deserialize, Serialize};
+use qum_core::models::fees::{CreateFeeParams, Fee};
+use qum_core::models::meter::DEFAULT_METER_ID;
+use qum_core::models::parse_datetime;
+use qum_core::models::price_component::{CreatePriceComponentParams, PriceComponent};
+use qum_core::repository::{FeeRepository, PriceComponentRepository, SqliteRepository};
 
-use crate::models::fees::Fee;
 use crate::DbConnection;
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct CreateFeeParams {
-    #[serde(rename = "baseFee")]
-    pub base_fee: f32,
-    #[serde(rename = "pricePerUnit")]
-    pub price_per_unit: f32,
-    #[serde(rename = "monthlyDiscount")]
-    pub monthly_discount: f32,
-    #[serde(rename = "dateStart")]
-    pub date_start: String,
-    #[serde(rename = "dateEnd")]
-    pub date_end: String,
-}
-
 #[tauri::command]
-pub fn get_fees_list(conn: tauri::State<DbConnection>) -> Vec<Fee> {
+pub fn get_fees_list(conn: tauri::State<DbConnection>) -> Result<Vec<Fee>, String> {
     println!("get_fees_list called");
     let mut connection = conn.connection.lock().unwrap();
-    if let Ok(fees) = Fee::list(&mut connection) {
-        return fees;
-    }
-
-    return vec![];
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.list_fees()
 }
 
 #[tauri::command]
@@ -35,25 +21,67 @@ pub fn create_fee(
 ) -> Result<Fee, String> {
     println!("received: {:?}", params);
     let mut connection = conn.connection.lock().unwrap();
-    Fee::create(&mut connection, params)
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.create_fee(params)
 }
 
 #[tauri::command]
-pub fn delete_fee(conn: tauri::State<DbConnection>, id: i32) {
+pub fn delete_fee(conn: tauri::State<DbConnection>, id: i32) -> Result<(), String> {
     let mut connection = conn.connection.lock().unwrap();
-    match Fee::delete(&mut connection, id) {
+    let mut repository = SqliteRepository::new(&mut connection);
+    match repository.delete_fee(id) {
         Ok(()) => println!("deleted!"),
-        Err(err) => println!("delete-error: {}", err),
+        Err(err) => {
+            println!("delete-error: {}", err);
+            return Err(err);
+        }
     }
+    Ok(())
 }
 
 #[tauri::command]
 pub fn find_in_time_range(
     conn: tauri::State<DbConnection>,
+    meter_id: Option<i32>,
     date_start: String,
     date_end: String,
 ) -> Option<Fee> {
     println!("find_in_time_range called");
     let mut connection = conn.connection.lock().unwrap();
-    Fee::find_in_time_range(&mut connection, date_start, date_end)
+    let mut repository = SqliteRepository::new(&mut connection);
+    let date_start = parse_datetime(date_start.as_str()).ok()?;
+    let date_end = parse_datetime(date_end.as_str()).ok()?;
+    repository
+        .find_fee_in_time_range(meter_id.unwrap_or(DEFAULT_METER_ID), &date_start, &date_end)
+        .unwrap_or(None)
+}
+
+#[tauri::command]
+pub fn list_price_components(
+    conn: tauri::State<DbConnection>,
+    fee_id: i32,
+) -> Result<Vec<PriceComponent>, String> {
+    println!("command: list price components of fee {}", fee_id);
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.list_price_components(fee_id)
+}
+
+#[tauri::command]
+pub fn create_price_component(
+    conn: tauri::State<DbConnection>,
+    params: CreatePriceComponentParams,
+) -> Result<PriceComponent, String> {
+    println!("received: {:?}", params);
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.create_price_component(params)
+}
+
+#[tauri::command]
+pub fn delete_price_component(conn: tauri::State<DbConnection>, id: i32) -> Result<(), String> {
+    println!("command: delete price component {}", id);
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.delete_price_component(id)
 }
@@ -0,0 +1,173 @@
+use serde::{Deserialize, Serialize};
+
+use qum_core::import::bank_statement::{self, BankCsvOptions, TransactionMatch};
+use qum_core::import::e_invoice::{self, EInvoiceImportResult, EInvoicePrefill};
+use qum_core::import::green_button::{self, GreenButtonImportOptions, GreenButtonUsagePoint};
+use qum_core::import::meter_readings_csv::{
+    self, CsvImportOptions, CsvImportResult, CsvPreviewRow,
+};
+use qum_core::import::mscons::{self, MsconsDocument, MsconsImportOptions};
+use qum_core::import::spot_prices::{self, SpotPriceImportOptions};
+use qum_core::models::fees::CreateFeeParams;
+use qum_core::models::invoice::CreateInvoiceParams;
+use qum_core::repository::SqliteRepository;
+use qum_core::smart_meter::dsmr::{self, DsmrOptions};
+use qum_core::smart_meter::sml::{self, SmlOptions};
+use qum_core::smart_meter::IngestResult;
+
+use crate::DbConnection;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ImportMeterReadingsCsvParams {
+    pub path: String,
+    pub options: CsvImportOptions,
+    #[serde(rename = "skipInvalid")]
+    pub skip_invalid: bool,
+}
+
+#[tauri::command]
+pub fn preview_meter_readings_csv(
+    conn: tauri::State<DbConnection>,
+    path: String,
+    options: CsvImportOptions,
+) -> Result<Vec<CsvPreviewRow>, String> {
+    println!("command: preview meter readings csv {}", path);
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    meter_readings_csv::preview(&mut repository, path, &options)
+}
+
+#[tauri::command]
+pub fn import_meter_readings_csv(
+    conn: tauri::State<DbConnection>,
+    params: ImportMeterReadingsCsvParams,
+) -> Result<CsvImportResult, String> {
+    println!("command: import meter readings csv {}", params.path);
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    let rows = meter_readings_csv::preview(&mut repository, params.path, &params.options)?;
+    meter_readings_csv::import(&mut repository, rows, params.skip_invalid)
+}
+
+#[tauri::command]
+pub fn import_dsmr_telegrams(
+    conn: tauri::State<DbConnection>,
+    path: String,
+    options: DsmrOptions,
+) -> Result<IngestResult, String> {
+    println!("command: import dsmr telegrams {}", path);
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    dsmr::ingest_file(&mut repository, path.as_str(), options)
+}
+
+#[tauri::command]
+pub fn import_sml_capture(
+    conn: tauri::State<DbConnection>,
+    path: String,
+    options: SmlOptions,
+) -> Result<IngestResult, String> {
+    println!("command: import sml capture {}", path);
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    sml::ingest_file(&mut repository, path.as_str(), options)
+}
+
+/// Usage points and meter readings of a Green Button file to choose from.
+#[tauri::command]
+pub fn preview_green_button(path: String) -> Result<Vec<GreenButtonUsagePoint>, String> {
+    println!("command: preview green button {}", path);
+    Ok(green_button::load(path.as_str())?.usage_points)
+}
+
+#[tauri::command]
+pub fn import_green_button(
+    conn: tauri::State<DbConnection>,
+    path: String,
+    options: GreenButtonImportOptions,
+) -> Result<IngestResult, String> {
+    println!("command: import green button {}", path);
+    let document = green_button::load(path.as_str())?;
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    green_button::import(&mut repository, &document, &options)
+}
+
+#[tauri::command]
+pub fn preview_mscons(path: String) -> Result<MsconsDocument, String> {
+    println!("command: preview mscons {}", path);
+    mscons::load(path.as_str())
+}
+
+#[tauri::command]
+pub fn import_mscons(
+    conn: tauri::State<DbConnection>,
+    path: String,
+    options: MsconsImportOptions,
+) -> Result<IngestResult, String> {
+    println!("command: import mscons {}", path);
+    let document = mscons::load(path.as_str())?;
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    mscons::import(&mut repository, &document, &options)
+}
+
+/// Returns the number of stored hourly prices.
+#[tauri::command]
+pub fn import_spot_prices(
+    conn: tauri::State<DbConnection>,
+    path: String,
+    options: SpotPriceImportOptions,
+) -> Result<usize, String> {
+    println!("command: import spot prices {}", path);
+    let prices = spot_prices::load(path.as_str(), &options)?;
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    spot_prices::import(&mut repository, &prices)
+}
+
+/// Transactions of the statement with the payments that would be recorded.
+#[tauri::command]
+pub fn preview_bank_statement(
+    conn: tauri::State<DbConnection>,
+    path: String,
+    csv: Option<BankCsvOptions>,
+) -> Result<Vec<TransactionMatch>, String> {
+    println!("command: preview bank statement {}", path);
+    let transactions = bank_statement::load(path.as_str(), csv.as_ref())?;
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    bank_statement::match_transactions(&mut repository, &transactions)
+}
+
+#[tauri::command]
+pub fn import_bank_statement(
+    conn: tauri::State<DbConnection>,
+    path: String,
+    csv: Option<BankCsvOptions>,
+) -> Result<Vec<TransactionMatch>, String> {
+    println!("command: import bank statement {}", path);
+    let transactions = bank_statement::load(path.as_str(), csv.as_ref())?;
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    bank_statement::import(&mut repository, &transactions)
+}
+
+/// Invoice and fee prefilled from an XRechnung or ZUGFeRD file.
+#[tauri::command]
+pub fn preview_e_invoice(path: String) -> Result<EInvoicePrefill, String> {
+    println!("command: preview e-invoice {}", path);
+    e_invoice::prefill(e_invoice::load(path.as_str())?)
+}
+
+#[tauri::command]
+pub fn import_e_invoice(
+    conn: tauri::State<DbConnection>,
+    fee: CreateFeeParams,
+    invoice: CreateInvoiceParams,
+) -> Result<EInvoiceImportResult, String> {
+    println!("command: import e-invoice {}", invoice.number);
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    e_invoice::import(&mut repository, fee, invoice)
+}
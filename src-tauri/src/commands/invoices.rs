@@ -0,0 +1,43 @@
+use qum_core::calculation::reconciliation::{load_reconciliation, InvoiceReconciliation};
+use qum_core::models::invoice::{CreateInvoiceParams, Invoice};
+use qum_core::repository::{InvoiceRepository, SqliteRepository};
+
+use crate::DbConnection;
+
+#[tauri::command]
+pub fn list_invoices(conn: tauri::State<DbConnection>) -> Result<Vec<Invoice>, String> {
+    println!("command: list invoices");
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.list_invoices()
+}
+
+#[tauri::command]
+pub fn create_invoice(
+    conn: tauri::State<DbConnection>,
+    params: CreateInvoiceParams,
+) -> Result<Invoice, String> {
+    println!("received: {:?}", params);
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.create_invoice(params)
+}
+
+#[tauri::command]
+pub fn delete_invoice(conn: tauri::State<DbConnection>, id: i32) -> Result<(), String> {
+    println!("command: delete invoice {}", id);
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.delete_invoice(id)
+}
+
+#[tauri::command]
+pub fn reconcile_invoice(
+    conn: tauri::State<DbConnection>,
+    id: i32,
+) -> Result<InvoiceReconciliation, String> {
+    println!("command: reconcile invoice {}", id);
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    load_reconciliation(&mut repository, id)
+}
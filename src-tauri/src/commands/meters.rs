@@ -0,0 +1,31 @@
+use qum_core::models::meter::{CreateMeterParams, Meter};
+use qum_core::repository::{MeterRepository, SqliteRepository};
+
+use crate::DbConnection;
+
+#[tauri::command]
+pub fn list_meters(conn: tauri::State<DbConnection>) -> Result<Vec<Meter>, String> {
+    println!("command: list meters");
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.list_meters()
+}
+
+#[tauri::command]
+pub fn create_meter(
+    conn: tauri::State<DbConnection>,
+    params: CreateMeterParams,
+) -> Result<Meter, String> {
+    println!("received: {:?}", params);
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.create_meter(params)
+}
+
+#[tauri::command]
+pub fn delete_meter(conn: tauri::State<DbConnection>, id: i32) -> Result<(), String> {
+    println!("command: delete meter {}", id);
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.delete_meter(id)
+}
@@ -1,2 +1,12 @@
+pub mod adjustments;
 pub mod consumption;
+pub mod costs;
+pub mod export;
 pub mod fees;
+pub mod import;
+pub mod invoices;
+pub mod meters;
+pub mod payments;
+pub mod report;
+pub mod tariffs;
+pub mod taxes;
@@ -0,0 +1,75 @@
+use qum_core::models::payment::{
+    CreatePaymentParams, CreatePaymentRuleParams, Payment, PaymentRule,
+};
+use qum_core::repository::{PaymentRepository, SqliteRepository};
+
+use crate::DbConnection;
+
+#[tauri::command]
+pub fn list_payments(
+    conn: tauri::State<DbConnection>,
+    fee_id: Option<i32>,
+) -> Result<Vec<Payment>, String> {
+    println!("command: list payments");
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.list_payments(fee_id)
+}
+
+#[tauri::command]
+pub fn create_payment(
+    conn: tauri::State<DbConnection>,
+    params: CreatePaymentParams,
+) -> Result<Payment, String> {
+    println!("received: {:?}", params);
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.create_payment(params)
+}
+
+#[tauri::command]
+pub fn update_payment(
+    conn: tauri::State<DbConnection>,
+    id: i32,
+    params: CreatePaymentParams,
+) -> Result<Payment, String> {
+    println!("command: update payment {}: {:?}", id, params);
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.edit_payment(id, params)
+}
+
+#[tauri::command]
+pub fn delete_payment(conn: tauri::State<DbConnection>, id: i32) -> Result<(), String> {
+    println!("command: delete payment {}", id);
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.delete_payment(id)
+}
+
+#[tauri::command]
+pub fn list_payment_rules(conn: tauri::State<DbConnection>) -> Result<Vec<PaymentRule>, String> {
+    println!("command: list payment rules");
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.list_payment_rules()
+}
+
+#[tauri::command]
+pub fn create_payment_rule(
+    conn: tauri::State<DbConnection>,
+    params: CreatePaymentRuleParams,
+) -> Result<PaymentRule, String> {
+    println!("received: {:?}", params);
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.create_payment_rule(params)
+}
+
+#[tauri::command]
+pub fn delete_payment_rule(conn: tauri::State<DbConnection>, id: i32) -> Result<(), String> {
+    println!("command: delete payment rule {}", id);
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.delete_payment_rule(id)
+}
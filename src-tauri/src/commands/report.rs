@@ -0,0 +1,20 @@
+use qum_core::report::{pdf_report, AnnualReport};
+use qum_core::repository::SqliteRepository;
+
+use crate::DbConnection;
+
+#[tauri::command]
+pub fn generate_annual_report(
+    conn: tauri::State<DbConnection>,
+    fee_id: i32,
+    path: String,
+) -> Result<(), String> {
+    println!(
+        "command: generate annual report for fee {} to {}",
+        fee_id, path
+    );
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    let report = AnnualReport::load(&mut repository, fee_id)?;
+    pdf_report::save(&report, path.as_str())
+}
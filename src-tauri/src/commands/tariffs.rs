@@ -0,0 +1,60 @@
+use qum_core::models::parse_date;
+use qum_core::models::tariff::{CreateTariffRuleParams, Holiday, TariffRule};
+use qum_core::repository::{SqliteRepository, TariffRepository};
+
+use crate::DbConnection;
+
+#[tauri::command]
+pub fn list_tariff_rules(
+    conn: tauri::State<DbConnection>,
+    fee_id: i32,
+) -> Result<Vec<TariffRule>, String> {
+    println!("command: list tariff rules of fee {}", fee_id);
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.list_tariff_rules(fee_id)
+}
+
+#[tauri::command]
+pub fn create_tariff_rule(
+    conn: tauri::State<DbConnection>,
+    params: CreateTariffRuleParams,
+) -> Result<TariffRule, String> {
+    println!("received: {:?}", params);
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.create_tariff_rule(params)
+}
+
+#[tauri::command]
+pub fn delete_tariff_rule(conn: tauri::State<DbConnection>, id: i32) -> Result<(), String> {
+    println!("command: delete tariff rule {}", id);
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.delete_tariff_rule(id)
+}
+
+#[tauri::command]
+pub fn list_holidays(conn: tauri::State<DbConnection>) -> Result<Vec<Holiday>, String> {
+    println!("command: list holidays");
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.list_holidays()
+}
+
+#[tauri::command]
+pub fn save_holiday(conn: tauri::State<DbConnection>, holiday: Holiday) -> Result<(), String> {
+    println!("command: save holiday {}", holiday.date);
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.upsert_holiday(holiday)
+}
+
+#[tauri::command]
+pub fn delete_holiday(conn: tauri::State<DbConnection>, date: String) -> Result<(), String> {
+    println!("command: delete holiday {}", date);
+    let date = parse_date(date.as_str())?;
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.delete_holiday(&date)
+}
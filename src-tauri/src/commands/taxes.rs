@@ -0,0 +1,31 @@
+use qum_core::models::vat::{CreateVatRateParams, VatRate};
+use qum_core::repository::{SqliteRepository, VatRepository};
+
+use crate::DbConnection;
+
+#[tauri::command]
+pub fn list_vat_rates(conn: tauri::State<DbConnection>) -> Result<Vec<VatRate>, String> {
+    println!("command: list VAT rates");
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.list_vat_rates()
+}
+
+#[tauri::command]
+pub fn create_vat_rate(
+    conn: tauri::State<DbConnection>,
+    params: CreateVatRateParams,
+) -> Result<VatRate, String> {
+    println!("received: {:?}", params);
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.create_vat_rate(params)
+}
+
+#[tauri::command]
+pub fn delete_vat_rate(conn: tauri::State<DbConnection>, id: i32) -> Result<(), String> {
+    println!("command: delete VAT rate {}", id);
+    let mut connection = conn.connection.lock().unwrap();
+    let mut repository = SqliteRepository::new(&mut connection);
+    repository.delete_vat_rate(id)
+}
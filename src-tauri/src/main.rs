@@ -3,54 +3,51 @@
     windows_subsystem = "windows"
 )]
 
-use std::fs;
-use std::path::Path;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
+use qum_core::api::{self, ApiConfig};
+use qum_core::db::connection::{database_file, establish_connection, run_migrations};
+use qum_core::smart_meter::mqtt::{self, MqttConfig};
 use rusqlite::Connection;
 use tauri::generate_handler;
 
-use crate::commands::consumption::{create_meter_reading, get_meter_readings};
-use crate::commands::fees::{create_fee, delete_fee, find_in_time_range, get_fees_list};
-use crate::db::connection::{establish_connection, run_migrations};
+use crate::commands::adjustments::{create_adjustment, delete_adjustment, list_adjustments};
+use crate::commands::consumption::{
+    create_meter_reading, get_interval_consumption, get_meter_readings,
+};
+use crate::commands::costs::{get_cost_breakdowns, propose_advance_payment};
+use crate::commands::export::{export_csv, export_xlsx};
+use crate::commands::fees::{
+    create_fee, create_price_component, delete_fee, delete_price_component, find_in_time_range,
+    get_fees_list, list_price_components,
+};
+use crate::commands::import::{
+    import_bank_statement, import_dsmr_telegrams, import_e_invoice, import_green_button,
+    import_meter_readings_csv, import_mscons, import_sml_capture, import_spot_prices,
+    preview_bank_statement, preview_e_invoice, preview_green_button, preview_meter_readings_csv,
+    preview_mscons,
+};
+use crate::commands::invoices::{create_invoice, delete_invoice, list_invoices, reconcile_invoice};
+use crate::commands::meters::{create_meter, delete_meter, list_meters};
+use crate::commands::payments::{
+    create_payment, create_payment_rule, delete_payment, delete_payment_rule, list_payment_rules,
+    list_payments, update_payment,
+};
+use crate::commands::report::generate_annual_report;
+use crate::commands::tariffs::{
+    create_tariff_rule, delete_holiday, delete_tariff_rule, list_holidays, list_tariff_rules,
+    save_holiday,
+};
+use crate::commands::taxes::{create_vat_rate, delete_vat_rate, list_vat_rates};
 
 pub mod commands;
-pub mod db;
-pub mod models;
 
 pub struct DbConnection {
-    connection: Mutex<Connection>,
+    connection: Arc<Mutex<Connection>>,
 }
 
 fn main() {
-    let home_dir = dirs::home_dir();
-    let home_dir = match home_dir {
-        Some(dir) => dir,
-        None => panic!("Can not read home dir"),
-    };
-
-    let home_dir_str = home_dir.into_os_string().into_string();
-    let home_dir_str = match home_dir_str {
-        Ok(str) => str,
-        Err(_) => panic!("Can not read home dir"),
-    };
-
-    let database_folder = Path::new(home_dir_str.as_str()).join(".qum");
-    fs::create_dir_all(&database_folder).expect("Can not create .qum folder");
-
-    let database_folder = match database_folder.into_os_string().into_string() {
-        Ok(path) => path,
-        Err(_) => panic!("Can not read database_folder"),
-    };
-
-    let database_file = Path::new(database_folder.as_str())
-        .join("qum")
-        .with_extension("db");
-    let database_file = match database_file.into_os_string().into_string() {
-        Ok(path) => path,
-        Err(_) => panic!("Can not read database file"),
-    };
-
+    let database_file = database_file();
     println!("Connecting to {}", database_file);
 
     let connection = establish_connection(database_file);
@@ -59,19 +56,83 @@ fn main() {
         Err(_) => panic!("failed to connect to database"),
     };
 
-    run_migrations(&mut connection);
+    match run_migrations(&mut connection) {
+        Ok(()) => println!("migrations runs!"),
+        Err(err) => panic!("failed to run migrations: {}", err),
+    }
+    let connection = Arc::new(Mutex::new(connection));
+
+    dotenvy::dotenv().ok();
+    if let Some(config) = ApiConfig::from_env() {
+        if let Err(err) = api::start(config, connection.clone()) {
+            println!("failed to start api: {}", err);
+        }
+    }
+    match MqttConfig::from_env() {
+        Ok(Some(config)) => {
+            mqtt::start(config, connection.clone());
+        }
+        Ok(None) => {}
+        Err(err) => println!("failed to start mqtt: {}", err),
+    }
 
     tauri::Builder::default()
-        .manage(DbConnection {
-            connection: Mutex::new(connection),
-        })
+        .manage(DbConnection { connection })
         .invoke_handler(generate_handler![
+            list_meters,
+            create_meter,
+            delete_meter,
             get_fees_list,
             create_fee,
             delete_fee,
             find_in_time_range,
+            list_price_components,
+            create_price_component,
+            delete_price_component,
+            list_adjustments,
+            create_adjustment,
+            delete_adjustment,
+            list_payments,
+            create_payment,
+            update_payment,
+            delete_payment,
+            list_payment_rules,
+            create_payment_rule,
+            delete_payment_rule,
+            list_invoices,
+            create_invoice,
+            delete_invoice,
+            reconcile_invoice,
+            list_vat_rates,
+            create_vat_rate,
+            delete_vat_rate,
+            list_tariff_rules,
+            create_tariff_rule,
+            delete_tariff_rule,
+            list_holidays,
+            save_holiday,
+            delete_holiday,
             get_meter_readings,
-            create_meter_reading
+            create_meter_reading,
+            get_interval_consumption,
+            preview_meter_readings_csv,
+            import_meter_readings_csv,
+            import_dsmr_telegrams,
+            import_sml_capture,
+            preview_green_button,
+            import_green_button,
+            preview_mscons,
+            import_mscons,
+            import_spot_prices,
+            preview_bank_statement,
+            import_bank_statement,
+            preview_e_invoice,
+            import_e_invoice,
+            get_cost_breakdowns,
+            propose_advance_payment,
+            export_csv,
+            export_xlsx,
+            generate_annual_report
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
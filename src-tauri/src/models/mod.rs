@@ -1,2 +0,0 @@
-pub mod fees;
-pub mod meter_reading;
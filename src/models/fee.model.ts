@@ -1,8 +1,62 @@
+export interface PriceBlock {
+  upTo: number
+  pricePerUnit: number
+}
+
+export interface PriceBrake {
+  forecast: number
+  contingent?: number
+  capPrice: number
+  dateStart?: string
+  dateEnd?: string
+}
+
 export interface Fee {
   id?: number
+  meterId?: number
   baseFee: number
   pricePerUnit: number
   monthlyDiscount: number
+  spotSurcharge?: number | null
+  blocks?: PriceBlock[]
+  netPrices?: boolean
+  priceBrake?: PriceBrake | null
   dateStart: string
   dateEnd: string
 }
+
+export type ComponentKind =
+  | 'energy'
+  | 'gridFee'
+  | 'meteringPoint'
+  | 'concessionLevy'
+  | 'surcharge'
+  | 'electricityTax'
+  | 'vat'
+  | 'adjustment'
+
+export interface PriceComponent {
+  id?: number
+  feeId: number
+  kind: ComponentKind
+  name: string
+  baseFee: number
+  pricePerUnit: number
+  rate: number
+}
+
+export interface VatRate {
+  id?: number
+  dateStart: string
+  dateEnd?: string | null
+  rate: number
+}
+
+export interface Adjustment {
+  id?: number
+  feeId: number
+  amount: number
+  date: string
+  description: string
+  taxable: boolean
+}
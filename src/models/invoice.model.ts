@@ -0,0 +1,67 @@
+export interface Invoice {
+  id?: number
+  feeId?: number | null
+  meterId?: number | null
+  number: string
+  dateStart: string
+  dateEnd: string
+  consumption: number
+  readingStart?: number | null
+  readingEnd?: number | null
+  estimated: boolean
+  netAmount: number
+  taxAmount: number
+  grossAmount: number
+  advancePayments: number
+  balance: number
+}
+
+export type FindingKind = 'period' | 'consumption' | 'price' | 'payments'
+
+export interface Finding {
+  kind: FindingKind
+  message: string
+}
+
+export interface InvoiceReconciliation {
+  invoice: Invoice
+  readingStart?: number | null
+  readingEnd?: number | null
+  consumption: number
+  unitPrice: number
+  billedUnitPrice?: number | null
+  costs: number
+  advancePayments: number
+  balance: number
+  consumptionDifference: number
+  costDifference: number
+  consumptionEffect: number
+  priceEffect: number
+  advanceDifference: number
+  balanceDifference: number
+  findings: Finding[]
+}
+
+export interface EInvoiceLine {
+  name: string
+  quantity: number
+  unitCode: string
+  netAmount: number
+  vatRate?: number | null
+  dateStart?: string | null
+  dateEnd?: string | null
+}
+
+export interface EInvoice {
+  syntax: 'cii' | 'ubl'
+  number: string
+  issueDate?: string | null
+  seller: string
+  dateStart?: string | null
+  dateEnd?: string | null
+  lines: EInvoiceLine[]
+  netAmount: number
+  taxAmount: number
+  grossAmount: number
+  prepaidAmount: number
+}
@@ -0,0 +1,8 @@
+export type MeterKind = 'consumption' | 'feedIn' | 'gas'
+
+export interface Meter {
+  id?: number
+  name: string
+  kind: MeterKind
+  externalId?: string | null
+}
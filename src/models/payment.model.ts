@@ -0,0 +1,30 @@
+export interface Payment {
+  id?: number
+  feeId?: number | null
+  meterId?: number | null
+  date: string
+  amount: number
+}
+
+export interface PaymentRule {
+  id?: number
+  creditorId?: string | null
+  text?: string | null
+}
+
+export interface BankTransaction {
+  date: string
+  amount: number
+  counterparty: string
+  text: string
+  creditorId?: string | null
+}
+
+export type MatchStatus = 'new' | 'matched' | 'unmatched'
+
+export interface TransactionMatch {
+  transaction: BankTransaction
+  status: MatchStatus
+  payment?: Payment | null
+  reason?: string | null
+}
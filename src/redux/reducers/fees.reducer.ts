@@ -51,6 +51,12 @@ export const feesSlice = createSlice({
       .addCase(fetchFees.fulfilled, (state, action) => {
         state.fees = action.payload
       })
+      .addCase(fetchFees.rejected, (state, action) => {
+        state.error = {
+          summary: 'Gebührendaten konnten nicht geladen werden',
+          detail: action.error.message,
+        }
+      })
       .addCase(createFee.fulfilled, (state) => {
         state.mode = 'list'
       })